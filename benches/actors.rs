@@ -0,0 +1,94 @@
+// Throughput of the account actors against the engine applying every
+// transaction on one thread, and against account maps sharded by client
+// behind mutexes shared by worker threads, over the same generated input:
+// `cargo bench --features actors --bench actors`
+
+use rust_decimal::Decimal;
+use std::sync::Mutex;
+use std::time::Instant;
+use transactions::actors::Actors;
+use transactions::engine::{process_tx, AccountMap, Engine, Transaction, TxType};
+
+const CLIENTS: u16 = 1000;
+const ROUNDS: u32 = 500;
+const SHARDS: usize = 16;
+
+/// deposits and withdrawals interleaved across the clients, with a dispute
+/// and a resolve now and then
+fn input() -> Vec<Transaction> {
+    let mut txs = Vec::with_capacity(CLIENTS as usize * ROUNDS as usize);
+    let mut id = 0;
+    for round in 0..ROUNDS {
+        for client in 1..=CLIENTS {
+            id += 1;
+            let (tx_type, tx, amount) = match round % 10 {
+                7 => (TxType::Dispute, id - CLIENTS as u32, None),
+                8 => (TxType::Resolve, id - 2 * CLIENTS as u32, None),
+                odd if odd % 2 == 1 => (TxType::Withdrawal, id, Some(Decimal::new(25, 2))),
+                _ => (TxType::Deposit, id, Some(Decimal::new(1050, 2))),
+            };
+            txs.push(Transaction { tx_type, client, tx, amount, ..Default::default() });
+        }
+    }
+    txs
+}
+
+/// Each worker applies the transactions of its clients, so those of a client
+/// stay in order, to the accounts of the client's shard with `process_tx`,
+/// as the actors do, locking the shard for every transaction; workers contend
+/// on the shards their clients share. An `Engine` cannot be shared, its
+/// observers are not `Send`.
+fn sharded(txs: &[Transaction], workers: usize) -> usize {
+    let shards: Vec<Mutex<AccountMap>> = (0..SHARDS).map(|_| Mutex::new(AccountMap::default())).collect();
+    let mut queues = vec![Vec::new(); workers];
+    for tx in txs {
+        queues[tx.client as usize % workers].push(tx.clone());
+    }
+    std::thread::scope(|scope| {
+        for queue in queues {
+            let shards = &shards;
+            scope.spawn(move || {
+                for tx in queue {
+                    let shard = &shards[tx.client as usize % SHARDS];
+                    let _ = process_tx(&mut shard.lock().unwrap(), tx);
+                }
+            });
+        }
+    });
+    shards.into_iter().map(|shard| shard.into_inner().unwrap().len()).sum()
+}
+
+fn report(name: &str, txs: usize, start: Instant) {
+    let secs = start.elapsed().as_secs_f64();
+    println!("{:<12} {:>8.1} ms {:>12.0} tx/s", name, secs * 1000.0, txs as f64 / secs);
+}
+
+fn main() {
+    let txs = input();
+    let start = Instant::now();
+    let mut engine = Engine::new();
+    for tx in txs.iter().cloned() {
+        engine.apply(tx);
+    }
+    report("engine", txs.len(), start);
+    assert_eq!(engine.into_accounts().count(), CLIENTS as usize);
+
+    let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+    let mut workers = 1;
+    while workers <= cores {
+        let start = Instant::now();
+        let mut actors = Actors::new(workers).unwrap();
+        for tx in txs.iter().cloned() {
+            actors.send(tx);
+        }
+        let accounts = actors.into_accounts();
+        report(&format!("actors x{}", workers), txs.len(), start);
+        assert_eq!(accounts.len(), CLIENTS as usize);
+
+        let start = Instant::now();
+        let accounts = sharded(&txs, workers);
+        report(&format!("sharded x{}", workers), txs.len(), start);
+        assert_eq!(accounts, CLIENTS as usize);
+        workers *= 2;
+    }
+}
@@ -0,0 +1,10 @@
+fn main() {
+    #[cfg(feature = "protobuf")]
+    {
+        println!("cargo:rerun-if-changed=schemas/transaction.proto");
+        let fds = protox::compile(["schemas/transaction.proto"], ["schemas"]);
+        assert!(fds.is_ok(), "invalid protobuf schema: {:?}", fds.err());
+        let res = prost_build::compile_fds(fds.unwrap());
+        assert!(res.is_ok(), "protobuf code generation failed: {:?}", res.err());
+    }
+}
@@ -0,0 +1,19 @@
+//! Arbitrary bytes read as a csv of transactions, with and without a header
+//! line. A header line that does not match is an error and malformed records
+//! are rejections, the reader must never panic.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::io::Cursor;
+use transactions::reader::{self, Dialect};
+
+fuzz_target!(|data: &[u8]| {
+    for headers in [true, false] {
+        let dialect = Dialect { headers, ..Dialect::default() };
+        let Ok(txs) = reader::read("fuzz.csv", reader::Format::Csv, &dialect, Cursor::new(data.to_vec())) else {
+            continue;
+        };
+        let rows = txs.count();
+        assert!(rows <= data.len(), "more records than bytes");
+    }
+});
@@ -0,0 +1,78 @@
+//! Arbitrary sequences of transactions applied to an engine with the ledger
+//! enabled. Whatever is rejected, the engine must not panic, every account
+//! must keep `total = available + held + escrow`, a locked account must
+//! stay locked, and the ledger must still balance with the accounts.
+#![no_main]
+
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+use rust_decimal::Decimal;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+use transactions::engine::{Engine, Transaction, TxType};
+
+#[derive(Arbitrary, Debug)]
+struct Op {
+    kind: u8,
+    // few clients and ids, so that transactions refer to each other
+    client: u8,
+    tx: u8,
+    amount: (i64, u8),
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    pending_withdrawals: bool,
+    ops: Vec<Op>,
+}
+
+impl Op {
+    fn transaction(&self) -> Transaction {
+        let tx_type = match self.kind % 10 {
+            0 => TxType::Deposit,
+            1 => TxType::Withdrawal,
+            2 => TxType::Dispute,
+            3 => TxType::Resolve,
+            4 => TxType::ChargeBack,
+            5 => TxType::Settle,
+            6 => TxType::Escrow,
+            7 => TxType::Release,
+            8 => TxType::Reversal,
+            _ => TxType::Move,
+        };
+        // the readers leave amounts to these types only, the engine asserts on them
+        let amount = matches!(tx_type, TxType::Deposit | TxType::Withdrawal | TxType::Escrow | TxType::Move)
+            .then(|| Decimal::new(self.amount.0, u32::from(self.amount.1 % 5)));
+        Transaction {
+            tx_type,
+            client: u16::from(self.client % 4),
+            tx: u32::from(self.tx % 16),
+            amount,
+            ..Default::default()
+        }
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let mut engine = Engine::new();
+    engine.enable_ledger();
+    if input.pending_withdrawals {
+        engine.enable_pending_withdrawals(None);
+    }
+    let locked = Rc::new(RefCell::new(HashSet::new()));
+    let seen = locked.clone();
+    engine.add_observer(move |tx, account, _| {
+        assert_eq!(account.total, account.available + account.held + account.escrow, "after {:?}", tx);
+        let mut locked = seen.borrow_mut();
+        assert!(account.locked || !locked.contains(&account.client), "account unlocked by {:?}", tx);
+        if account.locked {
+            locked.insert(account.client);
+        }
+    });
+    for op in &input.ops {
+        let _ = engine.process(op.transaction());
+    }
+    let trial_balance = engine.trial_balance();
+    assert!(trial_balance.is_balanced(), "{}", trial_balance);
+});
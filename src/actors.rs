@@ -0,0 +1,107 @@
+use crate::engine::{process_tx, Account, AccountMap, Transaction};
+use std::collections::HashMap;
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::task::JoinHandle;
+
+/// Accounts run as actors: each client has a task owning its account and a
+/// mailbox of its transactions, applied one at a time in the order they were
+/// sent, while the tasks of different clients run in parallel on the worker
+/// threads. A client's transactions are serialized by its mailbox alone,
+/// no lock is shared between accounts.
+///
+/// Transactions follow the rules of `process_tx`: a dispute of the tx of
+/// another client is unknown to the account, and there are no observers,
+/// ledger or pending withdrawals as with an `Engine`.
+pub struct Actors {
+    runtime: Runtime,
+    mailboxes: HashMap<u16, UnboundedSender<Transaction>>,
+    actors: Vec<JoinHandle<Option<Account>>>,
+}
+
+impl Actors {
+    /// actors run by `workers` threads
+    pub fn new(workers: usize) -> Result<Actors, String> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(workers.max(1))
+            .build()
+            .map_err(|err| format!("cannot start the actor runtime: {}", err))?;
+        Ok(Actors { runtime, mailboxes: HashMap::new(), actors: Vec::new() })
+    }
+
+    /// queue `tx` in the mailbox of its client, starting its actor on the
+    /// first transaction of the client
+    pub fn send(&mut self, tx: Transaction) {
+        let client = tx.client;
+        let mailbox = self.mailboxes.entry(client).or_insert_with(|| {
+            let (mailbox, mut txs) = mpsc::unbounded_channel();
+            self.actors.push(self.runtime.spawn(async move {
+                let mut accounts = AccountMap::default();
+                while let Some(tx) = txs.recv().await {
+                    let _ = process_tx(&mut accounts, tx);
+                }
+                accounts.remove(&client)
+            }));
+            mailbox
+        });
+        let res = mailbox.send(tx);
+        assert!(res.is_ok(), "actor of client {} has stopped", client);
+    }
+
+    /// the accounts once every mailbox has been drained, in no particular order
+    pub fn into_accounts(self) -> Vec<Account> {
+        let Actors { runtime, mailboxes, actors } = self;
+        // closing the mailboxes ends each actor after its last transaction
+        drop(mailboxes);
+        runtime.block_on(async {
+            let mut accounts = Vec::with_capacity(actors.len());
+            for actor in actors {
+                let res = actor.await;
+                assert!(res.is_ok(), "account actor failed: {:?}", res.err());
+                accounts.extend(res.unwrap());
+            }
+            accounts
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount;
+    use crate::engine::TxType;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_actors_match_process_tx() {
+        let tx = |tx_type, client, tx, amount: Option<Decimal>| Transaction { tx_type, client, tx, amount, ..Default::default() };
+        let mut txs = Vec::new();
+        for client in 1..=20u16 {
+            let base = client as u32 * 10;
+            txs.push(tx(TxType::Deposit, client, base, Some(dec!(10.5))));
+            txs.push(tx(TxType::Withdrawal, client, base + 1, Some(dec!(2.25))));
+            txs.push(tx(TxType::Deposit, client, base + 2, Some(dec!(1.0))));
+            txs.push(tx(TxType::Dispute, client, base, None));
+            if client % 2 == 0 {
+                txs.push(tx(TxType::ChargeBack, client, base, None));
+            }
+        }
+        let mut expected = AccountMap::default();
+        let mut actors = Actors::new(2).unwrap();
+        for tx in txs {
+            let _ = process_tx(&mut expected, tx.clone());
+            actors.send(tx);
+        }
+        let accounts = actors.into_accounts();
+        assert_eq!(accounts.len(), 20);
+        for account in accounts {
+            let other = &expected[&account.client];
+            assert_eq!(amount::to_decimal(account.available), amount::to_decimal(other.available));
+            assert_eq!(amount::to_decimal(account.held), amount::to_decimal(other.held));
+            assert_eq!(amount::to_decimal(account.total), amount::to_decimal(other.total));
+            assert_eq!(account.locked, other.locked);
+            assert_eq!(account.locked, account.client % 2 == 0);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use crate::amount::Amount;
+use crate::engine::{Account, Decision, Transaction, TxType};
+use crate::timestamp::Timestamp;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Age bracket of held funds
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Bucket {
+    #[serde(rename = "0-7d")]
+    Week,
+    #[serde(rename = "7-30d")]
+    Month,
+    #[serde(rename = "30d+")]
+    Older,
+}
+
+impl Bucket {
+    pub fn of(days: i64) -> Bucket {
+        match days {
+            ..7 => Bucket::Week,
+            7..30 => Bucket::Month,
+            _ => Bucket::Older,
+        }
+    }
+}
+
+/// Open timestamped disputes, see `record`
+#[derive(Debug, Default)]
+pub struct Holds {
+    // (client, txID) --> time of the dispute, funds held
+    open: BTreeMap<(u16, u32), (Timestamp, Amount)>,
+    // latest time of the input, ages are measured up to it
+    now: Option<Timestamp>,
+}
+
+/// Funds held by a dispute still open at the end of the input
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Held {
+    pub client: u16,
+    pub tx: u32,
+    pub amount: Amount,
+    pub held_since: Timestamp,
+    pub age_days: i64,
+    pub bucket: Bucket,
+}
+
+impl Holds {
+    /// observer entry point, disputes without a timestamp cannot be aged and are left out
+    pub fn record(&mut self, tx: &Transaction, account: &Account, decision: Decision) {
+        if let Some(time) = tx.timestamp {
+            self.now = Some(self.now.map_or(time, |now| now.max(time)));
+        }
+        if decision != Decision::Applied {
+            return;
+        }
+        match tx.tx_type {
+            TxType::Dispute => {
+                if let (Some(time), Some(amount)) = (tx.timestamp, account.amount_of(tx.tx)) {
+                    self.open.insert((tx.client, tx.tx), (time, amount));
+                }
+            }
+            TxType::Resolve | TxType::ChargeBack | TxType::AutoResolve | TxType::AutoChargeBack => {
+                self.open.remove(&(tx.client, tx.tx));
+            }
+            // the disputes of the merged client, whose id is the tx, move to the target
+            TxType::Merge => {
+                let from = tx.tx as u16;
+                let moved: Vec<(u16, u32)> = self.open.range((from, 0)..=(from, u32::MAX)).map(|(key, _)| *key).collect();
+                for key in moved {
+                    let hold = self.open.remove(&key).unwrap();
+                    self.open.insert((tx.client, key.1), hold);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// open disputes aged up to the latest time of the input, oldest first
+    pub fn aged(&self) -> Vec<Held> {
+        let Some(now) = self.now else {
+            return Vec::new();
+        };
+        let mut held: Vec<Held> = self
+            .open
+            .iter()
+            .map(|(&(client, tx), &(held_since, amount))| {
+                let age_days = (now - held_since).num_days();
+                Held { client, tx, amount, held_since, age_days, bucket: Bucket::of(age_days) }
+            })
+            .collect();
+        held.sort_by_key(|held| held.held_since);
+        held
+    }
+}
+
+pub fn write_csv<W: Write>(held: &[Held], wrt: W) {
+    let mut wrt = csv::Writer::from_writer(wrt);
+    for row in held {
+        let res = wrt.serialize(row);
+        assert!(res.is_ok(), "error in writing the held funds aging report");
+    }
+    let res = wrt.flush();
+    assert!(res.is_ok(), "error in writing the held funds aging report");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::timestamp::parse;
+    use rust_decimal_macros::dec;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_aged_disputes() {
+        let holds = Rc::new(RefCell::new(Holds::default()));
+        let mut engine = Engine::new();
+        let observed = holds.clone();
+        engine.add_observer(move |tx, account, decision| observed.borrow_mut().record(tx, account, decision));
+        let tx = |client, tx_type, tx, amount, time: &str| Transaction { client, tx_type, tx, amount, timestamp: Some(parse(time).unwrap()), ..Default::default() };
+        engine.apply(tx(1, TxType::Deposit, 1, Some(dec!(5.0)), "2024-01-01"));
+        engine.apply(tx(1, TxType::Deposit, 2, Some(dec!(2.5)), "2024-01-01"));
+        engine.apply(tx(2, TxType::Deposit, 3, Some(dec!(1.0)), "2024-01-01"));
+        engine.apply(tx(1, TxType::Dispute, 1, None, "2024-01-02"));
+        engine.apply(tx(1, TxType::Dispute, 2, None, "2024-02-10"));
+        engine.apply(tx(2, TxType::Dispute, 3, None, "2024-02-20"));
+        engine.apply(tx(1, TxType::Dispute, 9, None, "2024-02-21"));
+        engine.apply(tx(1, TxType::Resolve, 2, None, "2024-02-22"));
+        engine.apply(tx(2, TxType::Deposit, 4, Some(dec!(1.0)), "2024-02-25"));
+        let held: Vec<(u32, i64, Bucket)> = holds.borrow().aged().iter().map(|held| (held.tx, held.age_days, held.bucket)).collect();
+        assert_eq!(held, vec![(1, 54, Bucket::Older), (3, 5, Bucket::Week)]);
+        assert_eq!(holds.borrow().aged()[0].amount, dec!(5.0));
+        assert_eq!(Bucket::of(7), Bucket::Month);
+    }
+}
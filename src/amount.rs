@@ -0,0 +1,166 @@
+// Representation of balances and stored transaction amounts inside the engine.
+// Amounts are read and written as `Decimal`, with the `fixed-point` feature
+// they are kept as i64 ten-thousandths in between, as inputs have at most
+// four decimal places.
+
+use rust_decimal::Decimal;
+
+#[cfg(not(feature = "fixed-point"))]
+pub type Amount = Decimal;
+
+#[cfg(not(feature = "fixed-point"))]
+pub fn from_decimal(value: Decimal) -> Amount {
+    value
+}
+
+#[cfg(not(feature = "fixed-point"))]
+pub fn to_decimal(value: Amount) -> Decimal {
+    value
+}
+
+/// an input amount can always be kept as a `Decimal`
+#[cfg(not(feature = "fixed-point"))]
+pub fn representable(_value: Decimal) -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(feature = "fixed-point")]
+pub use fixed::Amount;
+
+/// why an input amount cannot be kept as i64 ten-thousandths, checked by the
+/// readers so that `from_decimal` only sees amounts that fit
+#[cfg(feature = "fixed-point")]
+pub fn representable(value: Decimal) -> Result<(), String> {
+    Amount::try_from(value).map(|_| ())
+}
+
+#[cfg(feature = "fixed-point")]
+pub fn from_decimal(value: Decimal) -> Amount {
+    let res = Amount::try_from(value);
+    assert!(res.is_ok(), "{}", res.err().unwrap_or_default());
+    res.unwrap()
+}
+
+#[cfg(feature = "fixed-point")]
+pub fn to_decimal(value: Amount) -> Decimal {
+    value.into()
+}
+
+#[cfg(feature = "fixed-point")]
+mod fixed {
+    use rust_decimal::Decimal;
+    use std::fmt;
+    use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+    const SCALE: u32 = 4;
+
+    /// Amount in ten-thousandths, overflowing arithmetic panics
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct Amount(i64);
+
+    impl Amount {
+        pub const ZERO: Amount = Amount(0);
+
+        pub fn is_zero(&self) -> bool {
+            self.0 == 0
+        }
+    }
+
+    impl TryFrom<Decimal> for Amount {
+        type Error = String;
+
+        fn try_from(value: Decimal) -> Result<Amount, String> {
+            if value.normalize().scale() > SCALE {
+                return Err(format!("amount {} has more than {} decimal places", value, SCALE));
+            }
+            let mut scaled = value;
+            scaled.rescale(SCALE);
+            i64::try_from(scaled.mantissa())
+                .map(Amount)
+                .map_err(|_| format!("amount {} out of range", value))
+        }
+    }
+
+    impl From<Amount> for Decimal {
+        fn from(value: Amount) -> Decimal {
+            Decimal::new(value.0, SCALE)
+        }
+    }
+
+    impl PartialEq<Decimal> for Amount {
+        fn eq(&self, other: &Decimal) -> bool {
+            Decimal::from(*self) == *other
+        }
+    }
+
+    impl fmt::Display for Amount {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            Decimal::from(*self).normalize().fmt(f)
+        }
+    }
+
+    impl Add for Amount {
+        type Output = Amount;
+
+        fn add(self, rhs: Amount) -> Amount {
+            Amount(self.0.checked_add(rhs.0).expect("amount overflow"))
+        }
+    }
+
+    impl Sub for Amount {
+        type Output = Amount;
+
+        fn sub(self, rhs: Amount) -> Amount {
+            Amount(self.0.checked_sub(rhs.0).expect("amount overflow"))
+        }
+    }
+
+    impl Neg for Amount {
+        type Output = Amount;
+
+        fn neg(self) -> Amount {
+            Amount(self.0.checked_neg().expect("amount overflow"))
+        }
+    }
+
+    impl AddAssign for Amount {
+        fn add_assign(&mut self, rhs: Amount) {
+            *self = *self + rhs;
+        }
+    }
+
+    impl SubAssign for Amount {
+        fn sub_assign(&mut self, rhs: Amount) {
+            *self = *self - rhs;
+        }
+    }
+
+    impl serde::Serialize for Amount {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serde::Serialize::serialize(&Decimal::from(*self).normalize(), serializer)
+        }
+    }
+
+    impl<'de> serde::Deserialize<'de> for Amount {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Amount, D::Error> {
+            let value = <Decimal as serde::Deserialize>::deserialize(deserializer)?;
+            Amount::try_from(value).map_err(serde::de::Error::custom)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use rust_decimal_macros::dec;
+
+        #[test]
+        fn test_conversions() {
+            let amount = Amount::try_from(dec!(1.2345)).unwrap();
+            assert_eq!(Decimal::from(amount), dec!(1.2345));
+            assert_eq!(amount - Amount::try_from(dec!(2)).unwrap(), dec!(-0.7655));
+            assert_eq!(Amount::try_from(dec!(1.50000)).unwrap(), dec!(1.5));
+            assert!(Amount::try_from(dec!(0.00001)).is_err());
+            assert!(Amount::try_from(dec!(1e20)).is_err());
+        }
+    }
+}
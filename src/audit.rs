@@ -0,0 +1,561 @@
+use crate::amount::Amount;
+use crate::crypto::{self, Key};
+use crate::amount;
+use crate::engine::{Account, Decision, Engine, Transaction, TxType};
+use crate::fx::{self, Conversion};
+use crate::pseudonym::Pseudonymizer;
+use rust_decimal::Decimal;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+
+/// hash the first entry chains to
+pub const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// type of the entries of a forgotten client, see `forget`
+const ERASED: &str = "erased";
+/// type of the entry recording that a client was forgotten
+const ERASURE: &str = "erasure";
+
+/// Client of a journal entry, its pseudonym in a pseudonymized journal
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+enum Client {
+    Id(u16),
+    Pseudonym(String),
+}
+
+/// One line of the audit journal, without its hash
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+struct Body<'a> {
+    seq: u64,
+    client: Client,
+    tx: u32,
+    #[serde(rename = "type")]
+    tx_type: String,
+    amount: Option<Decimal>,
+    decision: &'a str,
+    available: Amount,
+    held: Amount,
+    total: Amount,
+    locked: bool,
+    /// of the opening entry of an account set up with one, see `Engine::open_account`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    credit_limit: Option<Amount>,
+    /// balance the entry applies to, when they are kept per currency, see `Currencies`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    currency: Option<String>,
+    /// of both legs of a conversion, the exchange rate applied
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rate: Option<Decimal>,
+    /// of both legs of a conversion, the fraction of the amount kept as the fee
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spread: Option<Decimal>,
+    /// pseudonym of the client merged, which takes the place of the tx id of a merge
+    #[serde(skip_serializing_if = "Option::is_none")]
+    merged: Option<String>,
+    /// of an erasure entry, the number of entries of its client redacted, see `forget`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    erased: Option<u64>,
+    /// of an erasure entry, hash of the last entry of the journal before it was rewritten
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original: Option<String>,
+    /// hash of the previous entry
+    prev: &'a str,
+}
+
+fn sha256(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Position of the next entry of a journal, and the hash it links to
+struct Chain {
+    seq: u64,
+    prev: String,
+}
+
+impl Chain {
+    fn new() -> Chain {
+        Chain { seq: 0, prev: GENESIS.to_string() }
+    }
+
+    /// line of the next entry, with its seq, the previous hash and its own hash
+    fn link(&mut self, body: Body<'_>) -> String {
+        self.seq += 1;
+        let body = Body { seq: self.seq, prev: &self.prev, ..body };
+        let body = serde_json::to_string(&body).expect("audit entries serialize");
+        let hash = sha256(body.as_bytes());
+        let line = format!("{},\"hash\":\"{}\"}}", &body[..body.len() - 1], hash);
+        self.prev = hash;
+        line
+    }
+}
+
+/// Tamper-evident journal of every processed transaction, one JSON object
+/// per line. Each entry carries the hash of the previous one and its own
+/// hash, computed over the line without the trailing `hash` field.
+pub struct Journal {
+    wrt: BufWriter<Box<dyn Write>>,
+    chain: Chain,
+    pseudonyms: Option<Pseudonymizer>,
+    /// bytes of the lines written
+    written: u64,
+}
+
+impl Journal {
+    /// with a `key` the journal is written encrypted once dropped
+    pub fn create(path: &str, key: Option<&Key>) -> Journal {
+        let res = File::create(path);
+        assert!(res.is_ok(), "cannot create audit journal {}: {:?}", path, res.err());
+        let wrt = BufWriter::new(crypto::writer(key, res.unwrap()));
+        Journal { wrt, chain: Chain::new(), pseudonyms: None, written: 0 }
+    }
+
+    /// write client pseudonyms in place of their ids, such a journal can be
+    /// verified but not replayed
+    pub fn pseudonymize(mut self, pseudonyms: Pseudonymizer) -> Journal {
+        self.pseudonyms = Some(pseudonyms);
+        self
+    }
+
+    /// observer entry point, see `Engine::add_observer`
+    pub fn record(&mut self, tx: &Transaction, account: &Account, decision: Decision) {
+        self.write(tx, account, decision, None, None)
+    }
+
+    /// observer entry point of balances kept per currency, see `Currencies::add_observer`
+    pub fn record_fx(&mut self, event: &fx::Event) {
+        self.write(event.tx, event.account, event.decision, Some(event.currency), event.conversion)
+    }
+
+    fn write(&mut self, tx: &Transaction, account: &Account, decision: Decision, currency: Option<&str>, conversion: Option<Conversion>) {
+        let pseudonym = |client| self.pseudonyms.as_ref().map(|pseudonyms| pseudonyms.client(client));
+        let merged = (tx.tx_type == TxType::Merge).then(|| pseudonym(tx.tx as u16)).flatten();
+        let body = Body {
+            seq: 0,
+            client: pseudonym(tx.client).map_or(Client::Id(tx.client), Client::Pseudonym),
+            tx: if merged.is_some() { 0 } else { tx.tx },
+            tx_type: format!("{:?}", tx.tx_type).to_lowercase(),
+            amount: tx.amount,
+            decision: match decision {
+                Decision::Applied => "applied",
+                Decision::Ignored => "ignored",
+            },
+            available: account.available,
+            held: account.held,
+            total: account.total,
+            locked: account.locked,
+            credit_limit: (tx.tx_type == TxType::Opening && !account.credit_limit.is_zero()).then_some(account.credit_limit),
+            currency: currency.map(str::to_string),
+            rate: conversion.map(|conversion| conversion.rate),
+            spread: conversion.map(|conversion| conversion.spread),
+            merged,
+            erased: None,
+            original: None,
+            prev: "",
+        };
+        let line = self.chain.link(body);
+        let res = writeln!(self.wrt, "{}", line);
+        assert!(res.is_ok(), "error in writing the audit journal");
+        self.written += line.len() as u64 + 1;
+    }
+
+    /// bytes of the entries written so far, all in the file once flushed
+    /// unless it is encrypted
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    pub fn flush(&mut self) {
+        let res = self.wrt.flush();
+        assert!(res.is_ok(), "error in writing the audit journal");
+    }
+}
+
+/// First entry of a journal that breaks the chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainError {
+    /// 1-based line in the journal
+    pub line: u64,
+    pub message: String,
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Check every hash and link of a journal, returning the number of entries
+pub fn verify<R: BufRead>(input: R) -> Result<u64, ChainError> {
+    let mut prev = GENESIS.to_string();
+    let mut entries = 0;
+    for (idx, line) in input.lines().enumerate() {
+        let line_no = idx as u64 + 1;
+        let error = |message: String| ChainError { line: line_no, message };
+        let line = line.map_err(|err| error(err.to_string()))?;
+        let value: serde_json::Value = serde_json::from_str(&line).map_err(|err| error(err.to_string()))?;
+        let field = |name: &str| value.get(name).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        let hash = field("hash");
+        let suffix = format!(",\"hash\":\"{}\"}}", hash);
+        let Some(body) = line.strip_suffix(&suffix) else {
+            return Err(error("hash is not the last field".to_string()));
+        };
+        if field("prev") != prev {
+            return Err(error(format!("previous hash {} does not match {}", field("prev"), prev)));
+        }
+        if value.get("seq").and_then(|v| v.as_u64()) != Some(line_no) {
+            return Err(error("entries are missing or out of order".to_string()));
+        }
+        let computed = sha256(format!("{}}}", body).as_bytes());
+        if computed != hash {
+            return Err(error(format!("entry was modified, hash {} expected {}", hash, computed)));
+        }
+        prev = hash;
+        entries += 1;
+    }
+    Ok(entries)
+}
+
+/// A journal entry read back: the transaction, its decision and the account after it
+#[derive(serde::Deserialize)]
+struct Entry {
+    client: Client,
+    tx: u32,
+    #[serde(rename = "type")]
+    tx_type: String,
+    amount: Option<Decimal>,
+    decision: String,
+    available: Amount,
+    held: Amount,
+    total: Amount,
+    locked: bool,
+    #[serde(default)]
+    credit_limit: Option<Amount>,
+    #[serde(default)]
+    currency: Option<String>,
+    #[serde(default)]
+    rate: Option<Decimal>,
+    #[serde(default)]
+    spread: Option<Decimal>,
+    #[serde(default)]
+    merged: Option<String>,
+    #[serde(default)]
+    erased: Option<u64>,
+    #[serde(default)]
+    original: Option<String>,
+}
+
+impl Entry {
+    /// account of `client` with the balances of the entry
+    fn account(&self, client: u16) -> Account {
+        let mut account = Account::new(client);
+        account.available = self.available;
+        account.held = self.held;
+        account.total = self.total;
+        account.locked = self.locked;
+        account
+    }
+
+    /// the entry as written, to link it again
+    fn body(&self) -> Body<'_> {
+        Body {
+            seq: 0,
+            client: self.client.clone(),
+            tx: self.tx,
+            tx_type: self.tx_type.clone(),
+            amount: self.amount,
+            decision: &self.decision,
+            available: self.available,
+            held: self.held,
+            total: self.total,
+            locked: self.locked,
+            credit_limit: self.credit_limit,
+            currency: self.currency.clone(),
+            rate: self.rate,
+            spread: self.spread,
+            merged: self.merged.clone(),
+            erased: self.erased,
+            original: self.original.clone(),
+            prev: "",
+        }
+    }
+}
+
+/// transaction type as written in the journal
+fn tx_type(name: &str) -> Option<TxType> {
+    Some(match name {
+        "deposit" => TxType::Deposit,
+        "withdrawal" => TxType::Withdrawal,
+        "dispute" => TxType::Dispute,
+        "resolve" => TxType::Resolve,
+        "chargeback" => TxType::ChargeBack,
+        "settle" => TxType::Settle,
+        "escrow" => TxType::Escrow,
+        "release" => TxType::Release,
+        "reversal" => TxType::Reversal,
+        "autoresolve" => TxType::AutoResolve,
+        "autochargeback" => TxType::AutoChargeBack,
+        "merge" => TxType::Merge,
+        "opening" => TxType::Opening,
+        "convert" => TxType::Convert,
+        _ => return None,
+    })
+}
+
+/// Accounts rebuilt by applying the journaled transactions to a fresh
+/// engine, once the chain is verified. Every entry must get the decision and
+/// leave the account with the balances the journal records, the first one
+/// that does not is the error. Opening balances are taken as recorded, and
+/// `pending_withdrawals` must be set as in the run that wrote the journal.
+pub fn replay<R: BufRead>(input: R, pending_withdrawals: bool) -> Result<Vec<Account>, ChainError> {
+    let lines: Vec<String> = input.lines().collect::<Result<_, _>>().map_err(|err| ChainError { line: 0, message: err.to_string() })?;
+    verify(lines.join("\n").as_bytes())?;
+    let mut engine = Engine::new();
+    if pending_withdrawals {
+        engine.enable_pending_withdrawals(None);
+    }
+    for (idx, line) in lines.iter().enumerate() {
+        let error = |message: String| ChainError { line: idx as u64 + 1, message };
+        let entry: Entry = serde_json::from_str(line).map_err(|err| error(err.to_string()))?;
+        let Client::Id(client) = entry.client else {
+            return Err(error("the journal is pseudonymized, its clients cannot be replayed".to_string()));
+        };
+        if entry.currency.is_some() {
+            return Err(error("the journal keeps balances per currency, its accounts cannot be replayed".to_string()));
+        }
+        if entry.tx_type == ERASED {
+            continue;
+        }
+        let applied = match tx_type(&entry.tx_type) {
+            // the balances of a forgotten client are only known from its erasure
+            None if entry.tx_type == ERASURE => {
+                if engine.account(client).is_none() {
+                    engine.carry_forward(&entry.account(client));
+                }
+                true
+            }
+            None => return Err(error(format!("unknown transaction type `{}`", entry.tx_type))),
+            Some(TxType::Opening) if entry.held.is_zero() && entry.total == entry.available => {
+                let credit_limit = entry.credit_limit.map(amount::to_decimal).unwrap_or_default();
+                engine.open_account(client, amount::to_decimal(entry.available), credit_limit, None);
+                true
+            }
+            Some(TxType::Opening) => {
+                engine.carry_forward(&entry.account(client));
+                true
+            }
+            // the tx id of a merge is the client merged
+            Some(TxType::Merge) => engine.merge(entry.tx as u16, client).is_ok(),
+            Some(tx_type) => {
+                let tx = Transaction { tx_type, client, tx: entry.tx, amount: entry.amount, ..Default::default() };
+                engine.process(tx).is_ok()
+            }
+        };
+        let decision = if applied { "applied" } else { "ignored" };
+        if decision != entry.decision {
+            return Err(error(format!("{} {} of client {} is {} in the journal, {} when replayed", entry.tx_type, entry.tx, client, entry.decision, decision)));
+        }
+        let recorded = (entry.available, entry.held, entry.total, entry.locked);
+        let replayed = engine.account(client).map(|account| (account.available, account.held, account.total, account.locked));
+        if replayed != Some(recorded) {
+            return Err(error(format!("client {} has {:?} in the journal, {:?} when replayed", client, recorded, replayed)));
+        }
+    }
+    let mut accounts: Vec<Account> = engine.into_accounts().collect();
+    accounts.sort_by_key(|account| account.client);
+    Ok(accounts)
+}
+
+/// Journal of `input` with the history of `client` erased, once its chain is
+/// verified: the entries of the client are typed `erased` and keep neither
+/// their tx id, amount nor the balances after them, which would tell the
+/// transactions apart. The entries are linked again, and an `erasure` entry
+/// with the last balances of the client, the number of entries erased and
+/// the hash of the last entry of the journal as it was records the erasure. A
+/// pseudonymized journal needs the `pseudonyms` it was written with. Returns
+/// the new journal and the number of entries erased.
+pub fn forget<R: BufRead>(input: R, client: u16, pseudonyms: Option<&Pseudonymizer>) -> Result<(String, u64), ChainError> {
+    let lines: Vec<String> = input.lines().collect::<Result<_, _>>().map_err(|err| ChainError { line: 0, message: err.to_string() })?;
+    verify(lines.join("\n").as_bytes())?;
+    let entries = lines
+        .iter()
+        .enumerate()
+        .map(|(idx, line)| serde_json::from_str::<Entry>(line).map_err(|err| ChainError { line: idx as u64 + 1, message: err.to_string() }))
+        .collect::<Result<Vec<_>, _>>()?;
+    let pseudonymized = entries.first().is_some_and(|entry| matches!(entry.client, Client::Pseudonym(_)));
+    let target = match (pseudonymized, pseudonyms) {
+        (false, _) => Client::Id(client),
+        (true, Some(pseudonyms)) => Client::Pseudonym(pseudonyms.client(client)),
+        (true, None) => {
+            let message = "the journal is pseudonymized, its pseudonym key is needed to find the client".to_string();
+            return Err(ChainError { line: 1, message });
+        }
+    };
+    let original = match lines.last() {
+        Some(line) => serde_json::from_str::<serde_json::Value>(line)
+            .ok()
+            .and_then(|value| value.get("hash").and_then(|hash| hash.as_str()).map(str::to_string))
+            .unwrap_or_default(),
+        None => GENESIS.to_string(),
+    };
+    let mut chain = Chain::new();
+    let mut journal = String::new();
+    let mut erased = 0;
+    let mut last = Account::new(client);
+    for entry in &entries {
+        // entries erased before hold no balances, the erasure after them does and is kept as it is
+        if entry.client == target && entry.tx_type != ERASED {
+            last = entry.account(client);
+        }
+        let forgotten = entry.client == target && entry.tx_type != ERASURE;
+        let body = if forgotten {
+            erased += u64::from(entry.tx_type != ERASED);
+            let zero = Amount::default();
+            let redacted = Body { tx: 0, tx_type: ERASED.to_string(), amount: None, available: zero, held: zero, total: zero, locked: false, ..entry.body() };
+            Body { credit_limit: None, currency: None, rate: None, spread: None, merged: None, ..redacted }
+        } else {
+            entry.body()
+        };
+        journal.push_str(&chain.link(body));
+        journal.push('\n');
+    }
+    let erasure = Body {
+        seq: 0,
+        client: target,
+        tx: 0,
+        tx_type: ERASURE.to_string(),
+        amount: None,
+        decision: "applied",
+        available: last.available,
+        held: last.held,
+        total: last.total,
+        locked: last.locked,
+        credit_limit: None,
+        currency: None,
+        rate: None,
+        spread: None,
+        merged: None,
+        erased: Some(erased),
+        original: Some(original),
+        prev: "",
+    };
+    journal.push_str(&chain.link(erasure));
+    journal.push('\n');
+    Ok((journal, erased))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn journal() -> String {
+        let path = std::env::temp_dir().join(format!("audit-{}.ndjson", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let journal = Rc::new(RefCell::new(Journal::create(&path, None)));
+        let mut engine = Engine::new();
+        let log = journal.clone();
+        engine.add_observer(move |tx, account, decision| log.borrow_mut().record(tx, account, decision));
+        let tx = |tx_type, tx, amount| Transaction{client: 1, tx_type, tx, amount, ..Default::default()};
+        engine.apply(tx(TxType::Deposit, 1, Some(dec!(1.50))));
+        engine.apply(tx(TxType::Withdrawal, 2, Some(dec!(9.0))));
+        engine.apply(tx(TxType::Dispute, 1, None));
+        journal.borrow_mut().flush();
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        content
+    }
+
+    #[test]
+    fn test_verify_chain() {
+        let content = journal();
+        assert_eq!(verify(content.as_bytes()), Ok(3));
+        assert!(content.lines().next().unwrap().contains(&format!("\"prev\":\"{}\"", GENESIS)));
+    }
+
+    #[test]
+    fn test_replay() {
+        let accounts = replay(journal().as_bytes(), false).unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].available, dec!(0));
+        assert_eq!(accounts[0].held, dec!(1.50));
+        let modified = journal().replacen("\"9.0\"", "\"0.9\"", 1);
+        assert_eq!(replay(modified.as_bytes(), false).unwrap_err().line, 2);
+    }
+
+    #[test]
+    fn test_replay_detects_forged_entries() {
+        let path = std::env::temp_dir().join(format!("audit-forged-{}.ndjson", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let mut journal = Journal::create(&path, None);
+        let mut account = Account::new(1);
+        let deposit = Transaction { client: 1, tx_type: TxType::Deposit, tx: 1, amount: Some(dec!(2.0)), ..Default::default() };
+        // the balances recorded do not follow from the deposit, the hashes are intact
+        account.available = amount::from_decimal(dec!(20.0));
+        account.total = account.available;
+        journal.record(&deposit, &account, Decision::Applied);
+        journal.flush();
+        drop(journal);
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(verify(content.as_bytes()), Ok(1));
+        assert_eq!(replay(content.as_bytes(), false).unwrap_err().line, 1);
+    }
+
+    #[test]
+    fn test_pseudonymized_journal() {
+        let path = std::env::temp_dir().join(format!("audit-pseudonymized-{}.ndjson", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let pseudonyms = Pseudonymizer::new("secret");
+        let mut journal = Journal::create(&path, None).pseudonymize(pseudonyms.clone());
+        let mut account = Account::new(7);
+        let deposit = Transaction { client: 7, tx_type: TxType::Deposit, tx: 1, amount: Some(dec!(2.0)), ..Default::default() };
+        account.available = amount::from_decimal(dec!(2.0));
+        account.total = account.available;
+        journal.record(&deposit, &account, Decision::Applied);
+        let merge = Transaction { client: 7, tx_type: TxType::Merge, tx: 8, ..Default::default() };
+        journal.record(&merge, &account, Decision::Applied);
+        journal.flush();
+        drop(journal);
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(verify(content.as_bytes()), Ok(2));
+        assert!(content.contains(&format!("\"client\":\"{}\"", pseudonyms.client(7))));
+        let merged = content.lines().nth(1).unwrap();
+        assert!(merged.contains("\"tx\":0") && merged.contains(&format!("\"merged\":\"{}\"", pseudonyms.client(8))));
+        assert_eq!(replay(content.as_bytes(), false).unwrap_err().line, 1);
+    }
+
+    #[test]
+    fn test_forget_keeps_the_balances() {
+        let content = journal();
+        let (forgotten, erased) = forget(content.as_bytes(), 1, None).unwrap();
+        assert_eq!(erased, 3);
+        assert_eq!(verify(forgotten.as_bytes()), Ok(4));
+        assert!(!forgotten.contains("\"9.0\"") && !forgotten.contains("deposit"));
+        let erasure = forgotten.lines().last().unwrap();
+        let head = content.lines().last().unwrap().rsplit_once("\"hash\":").unwrap().1.trim_end_matches('}');
+        assert!(erasure.contains("\"type\":\"erasure\"") && erasure.contains(&format!("\"original\":{}", head)));
+        let accounts = replay(forgotten.as_bytes(), false).unwrap();
+        assert_eq!(accounts[0].available, dec!(0));
+        assert_eq!(accounts[0].held, dec!(1.50));
+        // forgetting twice erases nothing more and keeps the first erasure
+        let (again, erased) = forget(forgotten.as_bytes(), 1, None).unwrap();
+        assert_eq!((erased, again.matches("erasure").count()), (0, 2));
+        assert_eq!(replay(again.as_bytes(), false).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let content = journal();
+        let modified = content.replacen("\"9.0\"", "\"0.9\"", 1);
+        assert_eq!(verify(modified.as_bytes()).unwrap_err().line, 2);
+        let lines: Vec<&str> = content.lines().collect();
+        let dropped = format!("{}\n{}\n", lines[0], lines[2]);
+        assert_eq!(verify(dropped.as_bytes()).unwrap_err().line, 2);
+    }
+}
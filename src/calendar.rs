@@ -0,0 +1,54 @@
+use crate::timestamp::Timestamp;
+use chrono::{Datelike, NaiveDate, NaiveTime, Weekday};
+
+/// Business days and daily cut-off, the `[calendar]` table of the config file.
+/// Without one every day is a business day closing at midnight.
+#[derive(serde::Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Calendar {
+    /// days of the week that are never business days, e.g. `["Sat", "Sun"]`
+    pub weekend: Vec<Weekday>,
+    /// dates that are not business days, e.g. `["2024-12-25"]`
+    pub holidays: Vec<NaiveDate>,
+    /// time of day from which transactions count for the next business day, e.g. `"17:00:00"`
+    pub cutoff: Option<NaiveTime>,
+}
+
+impl Calendar {
+    pub fn is_business_day(&self, date: NaiveDate) -> bool {
+        !self.weekend.contains(&date.weekday()) && !self.holidays.contains(&date)
+    }
+
+    /// the business day a transaction made at `timestamp` is attributed to
+    pub fn business_day(&self, timestamp: Timestamp) -> NaiveDate {
+        let mut date = timestamp.date();
+        if self.cutoff.is_some_and(|cutoff| timestamp.time() >= cutoff) {
+            date = date.succ_opt().expect("date in range");
+        }
+        // a calendar closing every day of the week would loop forever
+        assert!(self.weekend.len() < 7, "calendar has no business day in the week");
+        while !self.is_business_day(date) {
+            date = date.succ_opt().expect("date in range");
+        }
+        date
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timestamp;
+
+    #[test]
+    fn test_business_day() {
+        let config = "weekend = [\"Sat\", \"Sun\"]\nholidays = [\"2024-03-04\"]\ncutoff = \"17:00:00\"\n";
+        let calendar: Calendar = toml::from_str(config).unwrap();
+        let day = |time| calendar.business_day(timestamp::parse(time).unwrap()).to_string();
+        assert_eq!(day("2024-02-28 16:59:59"), "2024-02-28");
+        assert_eq!(day("2024-02-28 17:00:00"), "2024-02-29");
+        // friday evening, weekend and a holiday monday
+        assert_eq!(day("2024-03-01 18:00:00"), "2024-03-05");
+        assert_eq!(day("2024-03-02 09:00:00"), "2024-03-05");
+        assert_eq!(Calendar::default().business_day(timestamp::parse("2024-03-02 23:00:00").unwrap()).to_string(), "2024-03-02");
+    }
+}
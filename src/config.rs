@@ -0,0 +1,84 @@
+use crate::calendar::Calendar;
+use crate::dormancy::Dormancy;
+use crate::engine::DisputeExpiry;
+use crate::fx::FxConfig;
+use crate::joint::Joint;
+use crate::kyc::KycConfig;
+use crate::precision::Precision;
+use crate::reader::{BankAccounts, Columns};
+use crate::recurring::Recurring;
+use crate::simulate::Simulation;
+
+/// Settings read from the toml file given with `--config`
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// input column names, e.g. `[columns]` with `type = "kind"`
+    pub columns: Columns,
+    /// HMAC key of the client pseudonyms written with `--pseudonymize`
+    pub pseudonym_key: Option<String>,
+    /// policy for amounts with too many decimal places, e.g. `[precision]` with `policy = "round"`
+    pub precision: Option<Precision>,
+    /// business days and cut-off of settlements and statements
+    pub calendar: Calendar,
+    /// accounts shared by several clients, `[[joint]]` tables with `account` and `owners`
+    pub joint: Vec<Joint>,
+    /// idle days making an account dormant and its fee, `[dormancy]` with `days` and `fee`
+    pub dormancy: Option<Dormancy>,
+    /// clients' KYC levels and the deposit and withdrawal caps of each level
+    pub kyc: Option<KycConfig>,
+    /// repeated deposits and withdrawals, `[[recurring]]` tables
+    pub recurring: Vec<Recurring>,
+    /// disputes closed after a number of days, `[dispute_expiry]` with `days` and `action`
+    pub dispute_expiry: Option<DisputeExpiry>,
+    /// clients of the accounts of OFX and QIF statements, `[bank]` with `client`, `accounts` and `first_tx`
+    pub bank: BankAccounts,
+    /// client behavior of the `simulate` subcommand
+    pub simulation: Option<Simulation>,
+    /// currency of the rows without one, spread and exchange rates of `--currencies`, `[fx]` with `base`, `spread` and `rates`
+    pub fx: Option<FxConfig>,
+    /// API tokens of the server router, `[[server.tokens]]` tables with `token` and `role`
+    #[cfg(feature = "server")]
+    pub server: crate::server::ServerConfig,
+}
+
+impl Config {
+    pub fn load(path: &str) -> Config {
+        let res = std::fs::read_to_string(path);
+        assert!(res.is_ok(), "cannot read config file {}: {:?}", path, res.err());
+        let res = toml::from_str(&res.unwrap());
+        assert!(res.is_ok(), "invalid config file {}: {}", path, res.err().unwrap());
+        res.unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::precision::{Policy, Rounding};
+
+    #[test]
+    fn test_parse_columns() {
+        let config: Config = toml::from_str("[columns]\ntype = \"kind\"\namount = \"value\"\n").unwrap();
+        assert_eq!(config.columns.tx_type.as_deref(), Some("kind"));
+        assert_eq!(config.columns.amount.as_deref(), Some("value"));
+        assert_eq!(config.columns.client, None);
+        assert_eq!(config.pseudonym_key, None);
+        assert!(toml::from_str::<Config>("[columns]\ncurrency = \"ccy\"\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_precision() {
+        let config: Config = toml::from_str("[precision]\npolicy = \"round\"\nrounding = \"half-up\"\n").unwrap();
+        let precision = config.precision.unwrap();
+        assert_eq!((precision.policy, precision.rounding, precision.scale), (Policy::Round, Rounding::HalfUp, 4));
+        assert!(toml::from_str::<Config>("[precision]\npolicy = \"ceil\"\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_dormancy() {
+        let config: Config = toml::from_str("[dormancy]\ndays = 365\nfee = \"5.00\"\n").unwrap();
+        let dormancy = config.dormancy.unwrap();
+        assert_eq!((dormancy.days, dormancy.fee), (365, Some(rust_decimal_macros::dec!(5.00))));
+    }
+}
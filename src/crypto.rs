@@ -0,0 +1,120 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use std::io::Write;
+
+/// first bytes of an encrypted file, followed by the nonce and the ciphertext
+const MAGIC: &[u8] = b"txenc1";
+const NONCE_LEN: usize = 12;
+
+/// AES-256-GCM key of the files written with `--encrypt-key`
+#[derive(Clone)]
+pub struct Key(Aes256Gcm);
+
+impl Key {
+    /// key given as 64 hex digits
+    pub fn from_hex(hex: &str) -> Result<Key, String> {
+        let hex = hex.trim();
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err("key must be 64 hex digits".to_string());
+        }
+        let bytes: Result<Vec<u8>, _> = (0..hex.len())
+            .step_by(2)
+            .map(|idx| u8::from_str_radix(&hex[idx..idx + 2], 16))
+            .collect();
+        let bytes = bytes.map_err(|err| format!("key must be 64 hex digits: {}", err))?;
+        Ok(Key(Aes256Gcm::new_from_slice(&bytes).expect("key is 32 bytes")))
+    }
+
+    /// key read from a file holding its hex digits
+    pub fn load(path: &str) -> Key {
+        let res = std::fs::read_to_string(path);
+        assert!(res.is_ok(), "cannot read key file {}: {:?}", path, res.err());
+        let res = Key::from_hex(&res.unwrap());
+        assert!(res.is_ok(), "invalid key file {}: {}", path, res.err().unwrap());
+        res.unwrap()
+    }
+
+    pub fn seal(&self, plaintext: &[u8]) -> Vec<u8> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self.0.encrypt(&nonce, plaintext).expect("encryption of a buffer does not fail");
+        [MAGIC, nonce.as_slice(), &ciphertext].concat()
+    }
+
+    /// decrypt what `seal` wrote, failing on a wrong key or modified content
+    pub fn open(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let Some(data) = data.strip_prefix(MAGIC) else {
+            return Err("not an encrypted file".to_string());
+        };
+        if data.len() < NONCE_LEN {
+            return Err("encrypted file is truncated".to_string());
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        self.0
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "wrong key or modified file".to_string())
+    }
+}
+
+/// Writer that collects its content and writes it encrypted once dropped,
+/// GCM authenticates the content as a whole so nothing is written before
+pub struct Sealed<W: Write> {
+    key: Key,
+    buf: Vec<u8>,
+    wrt: W,
+}
+
+impl<W: Write> Sealed<W> {
+    pub fn new(key: Key, wrt: W) -> Sealed<W> {
+        Sealed { key, buf: Vec::new(), wrt }
+    }
+}
+
+impl<W: Write> Write for Sealed<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for Sealed<W> {
+    fn drop(&mut self) {
+        let res = self.wrt.write_all(&self.key.seal(&self.buf)).and_then(|_| self.wrt.flush());
+        assert!(res.is_ok(), "error in writing encrypted output: {:?}", res.err());
+    }
+}
+
+/// plain writer, or one encrypting with `key` when given
+pub fn writer<'a, W: Write + 'a>(key: Option<&Key>, wrt: W) -> Box<dyn Write + 'a> {
+    match key {
+        Some(key) => Box::new(Sealed::new(key.clone(), wrt)),
+        None => Box::new(wrt),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEX: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    #[test]
+    fn test_sealed_round_trip() {
+        let key = Key::from_hex(HEX).unwrap();
+        let mut out = Vec::new();
+        {
+            let mut wrt = Sealed::new(key.clone(), &mut out);
+            wrt.write_all(b"client,available\n").unwrap();
+            wrt.write_all(b"1,2.0\n").unwrap();
+        }
+        assert!(out.starts_with(MAGIC));
+        assert_eq!(key.open(&out).unwrap(), b"client,available\n1,2.0\n");
+        let last = out.len() - 1;
+        out[last] ^= 1;
+        assert!(key.open(&out).is_err());
+        assert!(Key::from_hex(&HEX[2..]).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use crate::amount::Amount;
+use crate::engine::{Account, Transaction};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// One line of the comparison between two account reports.
+/// Balances are deltas (new - old), a client missing from one report counts as all zeros.
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Delta {
+    pub client: u16,
+    pub status: Status,
+    pub available: Amount,
+    pub held: Amount,
+    pub total: Amount,
+    /// lock state in the old and new report, when it differs
+    #[serde(serialize_with = "lock_change")]
+    pub locked: Option<(bool, bool)>,
+}
+
+fn lock_change<S: serde::Serializer>(value: &Option<(bool, bool)>, s: S) -> Result<S::Ok, S::Error> {
+    match value {
+        Some((old, new)) => s.serialize_str(&format!("{}->{}", old, new)),
+        None => s.serialize_str(""),
+    }
+}
+
+pub fn read_report<R: std::io::Read>(mut rdr: csv::Reader<R>) -> Vec<Account> {
+    rdr.deserialize()
+        .map(|res| {
+            assert!(res.is_ok(), "error in parsing an account record: {:?}", res.err());
+            res.unwrap()
+        })
+        .collect()
+}
+
+/// clients whose balances or lock state differ, sorted by client id
+pub fn diff(old: Vec<Account>, new: Vec<Account>) -> Vec<Delta> {
+    let mut pairs: BTreeMap<u16, (Option<Account>, Option<Account>)> = BTreeMap::new();
+    for account in old {
+        let client = account.client;
+        pairs.entry(client).or_default().0 = Some(account);
+    }
+    for account in new {
+        let client = account.client;
+        pairs.entry(client).or_default().1 = Some(account);
+    }
+    pairs
+        .into_iter()
+        .filter_map(|(client, pair)| {
+            let status = match &pair {
+                (None, Some(_)) => Status::Added,
+                (Some(_), None) => Status::Removed,
+                _ => Status::Changed,
+            };
+            let old = pair.0.unwrap_or_else(|| Account::new(client));
+            let new = pair.1.unwrap_or_else(|| Account::new(client));
+            let delta = Delta {
+                client,
+                status,
+                available: new.available - old.available,
+                held: new.held - old.held,
+                total: new.total - old.total,
+                locked: (old.locked != new.locked).then_some((old.locked, new.locked)),
+            };
+            let unchanged = delta.available.is_zero()
+                && delta.held.is_zero()
+                && delta.total.is_zero()
+                && delta.locked.is_none();
+            (status != Status::Changed || !unchanged).then_some(delta)
+        })
+        .collect()
+}
+
+/// Delta a transaction made to its account, published to a message broker
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct TxDelta {
+    pub tx: u32,
+    #[serde(flatten)]
+    pub delta: Delta,
+}
+
+/// Accounts as last seen by an engine observer, turning the account each
+/// transaction reaches into the delta it made; an account is added by its
+/// first transaction, a transaction without effect on a known one makes none
+#[derive(Default)]
+pub struct Tracker {
+    accounts: HashMap<u16, Account>,
+}
+
+impl Tracker {
+    pub fn delta(&mut self, tx: &Transaction, account: &Account) -> Option<TxDelta> {
+        let old = self.accounts.insert(account.client, account.clone());
+        let delta = diff(old.into_iter().collect(), vec![account.clone()]).pop()?;
+        Some(TxDelta { tx: tx.tx, delta })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Engine, TxType};
+    use crate::reader;
+    use rust_decimal_macros::dec;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn report(input: &str) -> Vec<Account> {
+        read_report(reader::builder().from_reader(input.as_bytes()))
+    }
+
+    #[test]
+    fn test_diff_reports() {
+        let old = report("client,available,held,total,locked\n1,1.5,0,1.5,false\n2,2,0,2,false\n3,1,0,1,false\n");
+        let new = report("client,available,held,total,locked\n1,1.5,0,1.5,false\n2,0,0,0,true\n4,3,1,4,false\n");
+        let res = diff(old, new);
+        assert_eq!(res.len(), 3);
+        assert_eq!(res[0].client, 2);
+        assert_eq!(res[0].status, Status::Changed);
+        assert_eq!(res[0].total, dec!(-2));
+        assert_eq!(res[0].locked, Some((false, true)));
+        assert_eq!(res[1].client, 3);
+        assert_eq!(res[1].status, Status::Removed);
+        assert_eq!(res[1].available, dec!(-1));
+        assert_eq!(res[2].client, 4);
+        assert_eq!(res[2].status, Status::Added);
+        assert_eq!(res[2].held, dec!(1));
+        assert_eq!(res[2].locked, None);
+    }
+
+    #[test]
+    fn test_delta_of_each_transaction() {
+        let deltas = Rc::new(RefCell::new(Vec::new()));
+        let mut tracker = Tracker::default();
+        let mut engine = Engine::new();
+        let log = deltas.clone();
+        engine.add_observer(move |tx, account, _| log.borrow_mut().extend(tracker.delta(tx, account)));
+        engine.apply(Transaction{client: 1, tx_type: TxType::Deposit, tx: 1, amount: Some(dec!(2.0)), ..Default::default()});
+        engine.apply(Transaction{client: 1, tx_type: TxType::Withdrawal, tx: 2, amount: Some(dec!(5.0)), ..Default::default()});
+        engine.apply(Transaction{client: 1, tx_type: TxType::Dispute, tx: 1, amount: None, ..Default::default()});
+        let deltas = deltas.borrow();
+        assert_eq!(deltas.len(), 2);
+        assert_eq!((deltas[0].tx, deltas[0].delta.status), (1, Status::Added));
+        assert_eq!(deltas[0].delta.available, dec!(2.0));
+        assert_eq!((deltas[1].tx, deltas[1].delta.status), (1, Status::Changed));
+        assert_eq!(deltas[1].delta.available, dec!(-2.0));
+        assert_eq!(deltas[1].delta.held, dec!(2.0));
+        assert_eq!(deltas[1].delta.total, dec!(0));
+        let json = serde_json::to_value(&deltas[1]).unwrap();
+        assert_eq!((json["tx"].as_u64(), json["client"].as_u64(), json["status"].as_str()), (Some(1), Some(1), Some("changed")));
+    }
+}
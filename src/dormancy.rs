@@ -0,0 +1,123 @@
+use crate::engine::{Engine, Transaction, TxType};
+use crate::timestamp::Timestamp;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Accounts idle for `days` are dormant, charged `fee` when set; the `[dormancy]` config table
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Dormancy {
+    pub days: i64,
+    pub fee: Option<Decimal>,
+}
+
+/// Last timestamped transaction of every client, see `record`
+#[derive(Debug, Default)]
+pub struct Activity {
+    last: BTreeMap<u16, Timestamp>,
+    // latest time of the input, dormancy is measured up to it
+    now: Option<Timestamp>,
+    // fee transactions get ids after the largest one of the input
+    max_tx: u32,
+}
+
+impl Activity {
+    /// observer entry point, rejected transactions count as activity too
+    pub fn record(&mut self, tx: &Transaction) {
+        self.max_tx = self.max_tx.max(tx.tx);
+        let Some(time) = tx.timestamp else {
+            return;
+        };
+        let last = self.last.entry(tx.client).or_insert(time);
+        *last = time.max(*last);
+        self.now = Some(self.now.map_or(time, |now| now.max(time)));
+    }
+}
+
+/// A dormant account, with the fee charged to it if any
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Dormant {
+    pub client: u16,
+    pub last_activity: Timestamp,
+    pub idle_days: i64,
+    pub fee: Option<Decimal>,
+}
+
+/// Accounts without activity for the configured days before the latest time
+/// of the input. The fee is charged as a withdrawal at that time, an account
+/// that cannot afford it is reported without a fee. Fees go through the engine
+/// observers, so `activity` cannot be borrowed from the one recording it.
+pub fn charge(engine: &mut Engine, activity: &Activity, dormancy: &Dormancy) -> Vec<Dormant> {
+    let Some(now) = activity.now else {
+        return Vec::new();
+    };
+    let mut next_tx = activity.max_tx;
+    let mut dormant = Vec::new();
+    for (&client, &last_activity) in &activity.last {
+        let idle_days = (now - last_activity).num_days();
+        if idle_days < dormancy.days {
+            continue;
+        }
+        let fee = dormancy.fee.filter(|fee| {
+            assert!(next_tx < u32::MAX, "no tx id left for dormancy fees");
+            next_tx += 1;
+            let tx = Transaction {
+                client,
+                tx_type: TxType::Withdrawal,
+                tx: next_tx,
+                amount: Some(*fee),
+                timestamp: Some(now),
+                ..Default::default()
+            };
+            engine.process(tx).is_ok()
+        });
+        dormant.push(Dormant { client, last_activity, idle_days, fee });
+    }
+    dormant
+}
+
+pub fn write_csv<W: Write>(dormant: &[Dormant], wrt: W) {
+    let mut wrt = csv::Writer::from_writer(wrt);
+    for row in dormant {
+        let res = wrt.serialize(row);
+        assert!(res.is_ok(), "error in writing the dormant account report");
+    }
+    let res = wrt.flush();
+    assert!(res.is_ok(), "error in writing the dormant account report");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_dormant_accounts_charged() {
+        let activity = Rc::new(RefCell::new(Activity::default()));
+        let mut engine = Engine::new();
+        let observed = activity.clone();
+        engine.add_observer(move |tx, _, _| observed.borrow_mut().record(tx));
+        let tx = |client, tx, amount, day: &str| Transaction {
+            client,
+            tx_type: TxType::Deposit,
+            tx,
+            amount: Some(amount),
+            timestamp: Some(crate::timestamp::parse(day).unwrap()),
+            ..Default::default()
+        };
+        engine.apply(tx(1, 1, dec!(10.0), "2024-01-10"));
+        engine.apply(tx(2, 2, dec!(1.0), "2024-01-20"));
+        engine.apply(tx(3, 3, dec!(5.0), "2024-06-01"));
+        let dormancy = Dormancy { days: 90, fee: Some(dec!(2.5)) };
+        let dormant = charge(&mut engine, &activity.take(), &dormancy);
+        let rows: Vec<_> = dormant.iter().map(|row| (row.client, row.idle_days, row.fee)).collect();
+        assert_eq!(rows, vec![(1, 143, Some(dec!(2.5))), (2, 133, None)]);
+        let mut accounts: Vec<_> = engine.into_accounts().map(|account| (account.client, account.available)).collect();
+        accounts.sort_by_key(|(client, _)| *client);
+        assert_eq!(accounts[0].1, dec!(7.5));
+        assert_eq!(accounts[1].1, dec!(1.0));
+    }
+}
@@ -0,0 +1,1568 @@
+use crate::amount::{self, Amount};
+use crate::ledger::{self, Ledger, LedgerAccount, Posting, TrialBalance};
+use crate::timestamp::{self, Timestamp};
+use chrono::TimeDelta;
+use rust_decimal::Decimal;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+mod saved;
+mod store;
+pub use store::TxStore;
+
+/// hashing dominates dispute heavy workloads, std's SipHash is not needed for u16/u32 keys
+#[cfg(feature = "ahash")]
+type Hasher = ahash::RandomState;
+#[cfg(not(feature = "ahash"))]
+type Hasher = std::collections::hash_map::RandomState;
+
+// most accounts see more than a handful of deposits, avoid the first reallocations
+const TX_CAPACITY: usize = 32;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TxType {
+    Deposit,
+    Withdrawal,
+    Dispute,
+    Resolve,
+    ChargeBack,
+    /// clearing of a pending withdrawal, see `Engine::enable_pending_withdrawals`
+    Settle,
+    /// deposit into the escrow funds of the account, not withdrawable until released
+    Escrow,
+    /// escrow deposit made available
+    Release,
+    /// transfer between two wallets of the client, see `Wallets`
+    Move,
+    /// correction undoing a deposit or withdrawal, without locking the account
+    Reversal,
+    /// exchange between two currency balances of the client, see `Currencies`
+    Convert,
+    /// open dispute resolved by the expiry policy, see `Engine::enable_dispute_expiry`; not an input row
+    #[serde(skip_deserializing)]
+    AutoResolve,
+    /// open dispute charged back by the expiry policy; not an input row
+    #[serde(skip_deserializing)]
+    AutoChargeBack,
+    /// account merged into another one, see `Engine::merge`; not an input row
+    #[serde(skip_deserializing)]
+    Merge,
+    /// opening balance of an account set up before its transactions, see `Engine::open_account`; not an input row
+    #[serde(skip_deserializing)]
+    Opening,
+    /// any other value of the type column, rejected by the readers
+    #[serde(other)]
+    #[default]
+    Unknown,
+}
+
+/// A row of the input; the optional columns default to empty, so
+/// `Transaction { client, tx_type, tx, amount, ..Default::default() }`
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct Transaction {
+    #[serde(alias = "type")]
+    pub tx_type: TxType,
+    pub client: u16,
+    pub tx: u32,
+    pub amount: Option<Decimal>,
+    /// optional column, needed by time based reports only
+    #[serde(default, deserialize_with = "timestamp::deserialize")]
+    pub timestamp: Option<Timestamp>,
+    /// optional column, sub-balance of the client, see `Wallets`
+    #[serde(default)]
+    pub wallet: Option<String>,
+    /// optional column, wallet receiving the amount of a move
+    #[serde(default)]
+    pub to_wallet: Option<String>,
+    /// optional column, currency of the amount, see `Currencies`
+    #[serde(default)]
+    pub currency: Option<String>,
+    /// optional column, currency a conversion is made into
+    #[serde(default)]
+    pub to_currency: Option<String>,
+    /// optional column, business unit owning the account, see `Tenants`
+    #[serde(default)]
+    pub tenant: Option<String>,
+    /// merchant or other party the funds come from or go to
+    #[serde(default)]
+    pub counterparty: Option<String>,
+    /// consecutive rows with the same batch id are applied atomically
+    #[serde(default)]
+    pub batch: Option<String>,
+    /// per client sequence number given upstream, see `sequence::ordered`
+    #[serde(default)]
+    pub seq: Option<u64>,
+    /// optional column, time a future-dated transaction is applied at, see `Scheduler`
+    #[serde(default, deserialize_with = "timestamp::deserialize")]
+    pub due: Option<Timestamp>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Default, Clone, Debug)]
+pub struct Account {
+    pub client: u16,
+    // available funds
+    pub available: Amount,
+    // held funds
+    pub held: Amount,
+    // funds deposited in escrow
+    #[serde(skip)]
+    pub escrow: Amount,
+    //total = held+available+escrow
+    pub total: Amount,
+    // account been frozen
+    pub locked: bool,
+    // available funds may go this far below zero
+    #[serde(skip)]
+    pub credit_limit: Amount,
+    // currency the account is kept in, informational
+    #[serde(skip)]
+    pub currency: Option<String>,
+    // funds charged back so far, written off
+    #[serde(skip)]
+    pub loss: Amount,
+    //transactions that include an amount --> (txID, amount)
+    #[serde(skip)]
+    transactions: TxStore,
+    // IDs of tx that are or have been under dispute
+    #[serde(skip)]
+    disputes: HashMap<u32, Dispute, Hasher>,
+    // withdrawals held until settled --> (txID, amount)
+    #[serde(skip)]
+    pending: HashMap<u32, Amount, Hasher>,
+    // escrow deposits not released yet --> (txID, amount)
+    #[serde(skip)]
+    escrowed: HashMap<u32, Amount, Hasher>,
+    // IDs of the stored transactions that are withdrawals
+    #[serde(skip)]
+    withdrawals: HashSet<u32, Hasher>,
+}
+
+/// Dispute state of a transaction, a charged back one is final
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dispute {
+    Open,
+    ChargedBack,
+}
+impl Account {
+    pub fn new(id: u16) -> Account {
+        Account {
+            client: id,
+            transactions: TxStore::with_capacity(TX_CAPACITY),
+            ..Default::default()
+        }
+    }
+
+    /// amount of a deposit or withdrawal of the account
+    pub fn amount_of(&self, tx: u32) -> Option<Amount> {
+        self.transactions.get(&tx).copied()
+    }
+
+    /// ids of the transactions under dispute, in increasing order
+    pub fn open_disputes(&self) -> Vec<u32> {
+        self.disputes_in(Dispute::Open)
+    }
+
+    /// ids of the transactions charged back, in increasing order
+    pub fn charged_back(&self) -> Vec<u32> {
+        self.disputes_in(Dispute::ChargedBack)
+    }
+
+    fn disputes_in(&self, state: Dispute) -> Vec<u32> {
+        let mut ids: Vec<u32> = self.disputes.iter().filter(|(_, dispute)| **dispute == state).map(|(tx, _)| *tx).collect();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+/// What happens to a dispute left open too long
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpiryAction {
+    Resolve,
+    ChargeBack,
+}
+
+/// Disputes open for `days` are closed with `action`, the `[dispute_expiry]` config table
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct DisputeExpiry {
+    pub days: i64,
+    pub action: ExpiryAction,
+}
+
+pub type AccountMap = HashMap<u16, Account, Hasher>;
+
+/// Balance movement of a transaction that changed an account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Applied {
+    /// funds added to available
+    Deposited(Amount),
+    /// funds removed from available
+    Withdrawn(Amount),
+    /// disputed funds moved from available to held
+    Held(Amount),
+    /// resolved funds moved back from held to available
+    Released(Amount),
+    /// held funds removed, the account is locked
+    ChargedBack(Amount),
+    /// withdrawn funds moved from available to held until settled
+    Pending(Amount),
+    /// held funds of a pending withdrawal removed
+    Settled(Amount),
+    /// funds added to escrow
+    Escrowed(Amount),
+    /// funds moved from escrow to available
+    EscrowReleased(Amount),
+    /// funds of a reversed deposit removed from available
+    DepositReversed(Amount),
+    /// funds of a reversed withdrawal added back to available
+    WithdrawalReversed(Amount),
+    /// opening balance added to available, see `Engine::open_account`
+    Opened(Amount),
+}
+
+/// Why a transaction left its account untouched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// withdrawal larger than the available funds
+    InsufficientFunds,
+    /// deposit, withdrawal or escrow of a negative amount
+    NegativeAmount,
+    /// dispute of a tx the account does not know
+    UnknownTx,
+    /// resolve or chargeback of a tx not under dispute
+    NotDisputed,
+    /// dispute of a tx already under dispute
+    AlreadyDisputed,
+    /// dispute, resolve or chargeback of a tx already charged back
+    ChargedBack,
+    /// transaction type the engine does not process
+    UnsupportedType,
+    /// dispute, resolve, chargeback, settle, release or reversal of a tx that belongs to another client
+    ForeignTx { owner: u16 },
+    /// settle of a tx that is not a pending withdrawal
+    NotPending,
+    /// release of a tx that is not an escrow deposit, or already released
+    NotEscrowed,
+    /// transaction of an account closed by a merge
+    AccountClosed,
+    /// reversal of a tx under or after dispute, or of a pending withdrawal
+    NotReversible,
+    /// dispute, resolve, chargeback or reversal of the receiving leg of a wallet move or
+    /// a conversion, which belongs to the wallet or currency it withdrew from
+    MoveLeg,
+    /// conversion between currencies without an exchange rate
+    NoRate,
+}
+
+/// Outcome of a transaction once it reaches an account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// account balances or state changed
+    Applied,
+    /// transaction had no effect (e.g. overdraft, unknown tx reference)
+    Ignored,
+}
+
+impl From<&Result<Applied, Rejection>> for Decision {
+    fn from(res: &Result<Applied, Rejection>) -> Decision {
+        match res {
+            Ok(_) => Decision::Applied,
+            Err(_) => Decision::Ignored,
+        }
+    }
+}
+
+pub fn process_tx(accounts: &mut AccountMap, tx: Transaction) -> Result<Applied, Rejection> {
+    let account = accounts
+        .entry(tx.client)
+        .or_insert_with(|| Account::new(tx.client));
+    apply_to(account, &tx, false)
+}
+
+/// With `pending_withdrawals` a withdrawal only holds its funds, they leave on `Settle`
+fn apply_to(account: &mut Account, tx: &Transaction, pending_withdrawals: bool) -> Result<Applied, Rejection> {
+    let amount = tx.amount.map(amount::from_decimal);
+    match tx.tx_type {
+        TxType::Deposit | TxType::Withdrawal | TxType::Escrow if tx.amount.is_some_and(|amount| amount < Decimal::ZERO) => {
+            Err(Rejection::NegativeAmount)
+        }
+        TxType::Deposit => {
+            assert!(amount.is_some(), "deposit without amount");
+            // new available funds added
+            account.transactions.insert(tx.tx, amount.unwrap());
+            account.available += amount.unwrap();
+            account.total += amount.unwrap();
+            Ok(Applied::Deposited(amount.unwrap()))
+        }
+        TxType::Withdrawal => {
+            assert!(amount.is_some(), "withdrawal without amount");
+            // available funds decreased only if present, or covered by the credit limit
+            let covered = account.available + account.credit_limit >= amount.unwrap();
+            if covered && pending_withdrawals {
+                account.available -= amount.unwrap();
+                account.held += amount.unwrap();
+                account.transactions.insert(tx.tx, amount.unwrap());
+                account.withdrawals.insert(tx.tx);
+                account.pending.insert(tx.tx, amount.unwrap());
+                return Ok(Applied::Pending(amount.unwrap()));
+            }
+            if covered {
+                account.available -= amount.unwrap();
+                account.total -= amount.unwrap();
+                account.transactions.insert(tx.tx, amount.unwrap());
+                account.withdrawals.insert(tx.tx);
+                return Ok(Applied::Withdrawn(amount.unwrap()));
+            }
+            Err(Rejection::InsufficientFunds)
+        }
+        TxType::Settle => {
+            // held funds of the withdrawal leave the account
+            if let Some(amount) = account.pending.remove(&tx.tx) {
+                account.held -= amount;
+                account.total -= amount;
+                return Ok(Applied::Settled(amount));
+            }
+            Err(Rejection::NotPending)
+        }
+        TxType::Escrow => {
+            assert!(amount.is_some(), "escrow without amount");
+            account.escrowed.insert(tx.tx, amount.unwrap());
+            account.escrow += amount.unwrap();
+            account.total += amount.unwrap();
+            Ok(Applied::Escrowed(amount.unwrap()))
+        }
+        TxType::Release => {
+            // escrow funds decreased, available funds increased
+            if let Some(amount) = account.escrowed.remove(&tx.tx) {
+                account.escrow -= amount;
+                account.available += amount;
+                return Ok(Applied::EscrowReleased(amount));
+            }
+            Err(Rejection::NotEscrowed)
+        }
+        // a charged back tx cannot be disputed again, nor resolved to mint its amount back
+        TxType::Dispute | TxType::Resolve | TxType::ChargeBack | TxType::AutoResolve | TxType::AutoChargeBack
+            if account.disputes.get(&tx.tx) == Some(&Dispute::ChargedBack) =>
+        {
+            Err(Rejection::ChargedBack)
+        }
+        TxType::Dispute if account.disputes.get(&tx.tx) == Some(&Dispute::Open) => Err(Rejection::AlreadyDisputed),
+        TxType::Dispute => {
+            // available funds decreased, held funds increased
+            if let Some(&amount) = account.transactions.get(&tx.tx) {
+                account.available -= amount;
+                account.held += amount;
+                account.disputes.insert(tx.tx, Dispute::Open);
+                return Ok(Applied::Held(amount));
+            }
+            Err(Rejection::UnknownTx)
+        }
+        TxType::Resolve | TxType::AutoResolve => {
+            // held funds decreased, available funds increased
+            if account.disputes.get(&tx.tx) == Some(&Dispute::Open) {
+                // if found in account.disputes, it must be in account.transactions
+                let orig_amount = account.transactions[&tx.tx];
+                account.available += orig_amount;
+                account.held -= orig_amount;
+                account.disputes.remove(&tx.tx);
+                return Ok(Applied::Released(orig_amount));
+            }
+            Err(Rejection::NotDisputed)
+        }
+        TxType::ChargeBack | TxType::AutoChargeBack => {
+            if account.disputes.get(&tx.tx) == Some(&Dispute::Open) {
+                // if found in account.disputes, it must be in account.transactions
+                let orig_amount = account.transactions[&tx.tx];
+                account.held -= orig_amount;
+                account.total -= orig_amount;
+                account.loss += orig_amount;
+                account.locked = true;
+                account.disputes.insert(tx.tx, Dispute::ChargedBack);
+                return Ok(Applied::ChargedBack(orig_amount));
+            }
+            Err(Rejection::NotDisputed)
+        }
+        TxType::Reversal => {
+            let Some(&amount) = account.transactions.get(&tx.tx) else {
+                return Err(Rejection::UnknownTx);
+            };
+            if account.disputes.contains_key(&tx.tx) || account.pending.contains_key(&tx.tx) {
+                return Err(Rejection::NotReversible);
+            }
+            if account.withdrawals.remove(&tx.tx) {
+                account.available += amount;
+                account.total += amount;
+                account.transactions.remove(&tx.tx);
+                return Ok(Applied::WithdrawalReversed(amount));
+            }
+            // the deposited funds must still be there
+            if account.available >= amount {
+                account.available -= amount;
+                account.total -= amount;
+                account.transactions.remove(&tx.tx);
+                return Ok(Applied::DepositReversed(amount));
+            }
+            Err(Rejection::InsufficientFunds)
+        }
+        TxType::Opening => {
+            assert!(amount.is_some(), "opening without amount");
+            // not a stored transaction, an opening balance cannot be disputed
+            account.available += amount.unwrap();
+            account.total += amount.unwrap();
+            Ok(Applied::Opened(amount.unwrap()))
+        }
+        // moves and conversions are split into a withdrawal and a deposit by `Wallets` and `Currencies`
+        TxType::Unknown | TxType::Move | TxType::Convert | TxType::Merge => Err(Rejection::UnsupportedType),
+    }
+}
+
+/// Rejection of a tx referencing a transaction of another client, None if it may go on
+fn foreign(owners: &HashMap<u32, u16, Hasher>, tx: &Transaction) -> Option<Rejection> {
+    if !matches!(tx.tx_type, TxType::Dispute | TxType::Resolve | TxType::ChargeBack | TxType::Settle | TxType::Release | TxType::Reversal) {
+        return None;
+    }
+    owners
+        .get(&tx.tx)
+        .filter(|owner| **owner != tx.client)
+        .map(|owner| Rejection::ForeignTx { owner: *owner })
+}
+
+/// opening balance of an account, see `Engine::open_account`
+fn opening(client: u16, amount: Decimal) -> Transaction {
+    Transaction { client, tx_type: TxType::Opening, tx: 0, amount: Some(amount), ..Default::default() }
+}
+
+/// First transaction of a batch that was rejected, the whole batch was discarded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchError {
+    /// position of the transaction in the batch
+    pub index: usize,
+    pub tx: u32,
+    pub rejection: Rejection,
+}
+
+impl std::fmt::Display for BatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "batch rejected at record {} (tx {}): {:?}", self.index + 1, self.tx, self.rejection)
+    }
+}
+
+type Observer = Box<dyn FnMut(&Transaction, &Account, Decision)>;
+
+/// Why `Engine::merge` left the accounts untouched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeError {
+    /// an account cannot be merged into itself
+    SameAccount,
+    /// the source client has no account
+    UnknownAccount(u16),
+    /// the client's account was already closed by a merge
+    Closed(u16),
+}
+
+impl std::fmt::Display for MergeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergeError::SameAccount => write!(f, "an account cannot be merged into itself"),
+            MergeError::UnknownAccount(client) => write!(f, "client {} has no account", client),
+            MergeError::Closed(client) => write!(f, "account of client {} is closed", client),
+        }
+    }
+}
+
+/// Position in the engine history that can be rolled back to, see `Engine::savepoint`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Savepoint(usize);
+
+/// State of an account before a transaction, as much as a transaction can change
+#[derive(Debug, Clone)]
+struct Prior {
+    available: Amount,
+    held: Amount,
+    total: Amount,
+    locked: bool,
+    loss: Amount,
+    stored: Option<Amount>,
+    dispute: Option<Dispute>,
+    pending: Option<Amount>,
+    escrow: Amount,
+    escrowed: Option<Amount>,
+    withdrawal: bool,
+}
+
+/// Undo log entry of one transaction, `prior` is None if it created the account
+#[derive(Debug, Clone)]
+struct Undo {
+    client: u16,
+    tx: u32,
+    prior: Option<Prior>,
+}
+
+impl Undo {
+    fn before(account: Option<&Account>, tx: &Transaction) -> Undo {
+        let prior = account.map(|account| Prior {
+            available: account.available,
+            held: account.held,
+            total: account.total,
+            locked: account.locked,
+            loss: account.loss,
+            stored: account.transactions.get(&tx.tx).copied(),
+            dispute: account.disputes.get(&tx.tx).copied(),
+            pending: account.pending.get(&tx.tx).copied(),
+            escrow: account.escrow,
+            escrowed: account.escrowed.get(&tx.tx).copied(),
+            withdrawal: account.withdrawals.contains(&tx.tx),
+        });
+        Undo { client: tx.client, tx: tx.tx, prior }
+    }
+
+    fn revert(self, accounts: &mut AccountMap) {
+        let Some(prior) = self.prior else {
+            accounts.remove(&self.client);
+            return;
+        };
+        let account = accounts.get_mut(&self.client).expect("undo of a missing account");
+        account.available = prior.available;
+        account.held = prior.held;
+        account.total = prior.total;
+        account.locked = prior.locked;
+        account.loss = prior.loss;
+        match prior.stored {
+            Some(amount) => account.transactions.insert(self.tx, amount),
+            None => account.transactions.remove(&self.tx),
+        };
+        match prior.dispute {
+            Some(dispute) => account.disputes.insert(self.tx, dispute),
+            None => account.disputes.remove(&self.tx),
+        };
+        match prior.pending {
+            Some(amount) => account.pending.insert(self.tx, amount),
+            None => account.pending.remove(&self.tx),
+        };
+        account.escrow = prior.escrow;
+        match prior.escrowed {
+            Some(amount) => account.escrowed.insert(self.tx, amount),
+            None => account.escrowed.remove(&self.tx),
+        };
+        match prior.withdrawal {
+            true => account.withdrawals.insert(self.tx),
+            false => account.withdrawals.remove(&self.tx),
+        };
+    }
+}
+
+type EventLog = HashMap<u16, Vec<(u64, Transaction)>, Hasher>;
+
+/// Estimated heap memory of a part of the engine, see `Engine::footprint`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Usage {
+    pub part: &'static str,
+    pub entries: usize,
+    pub bytes: usize,
+}
+
+/// bytes of a hash map table, with its control byte per slot
+fn table_bytes<K, V, S>(map: &HashMap<K, V, S>) -> usize {
+    map.capacity() * (size_of::<K>() + size_of::<V>() + 1)
+}
+
+/// Point in the history of an account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsOf {
+    /// after the transaction with this sequence number, counted from 1 in apply order
+    Seq(u64),
+    /// just before the first transaction of the client with this id
+    BeforeTx(u32),
+}
+
+/// Owns the client accounts and applies transactions to them.
+/// Optional observers are called after every transaction, so notifications
+/// or metrics can be hooked in without touching the processing loop.
+#[derive(Default)]
+pub struct Engine {
+    accounts: AccountMap,
+    observers: Vec<Observer>,
+    // recorded only while a savepoint is held
+    undo: Option<Vec<Undo>>,
+    // sequence number of the last applied transaction
+    seq: u64,
+    // transactions of each client with their sequence number, when enabled
+    events: Option<EventLog>,
+    // client owning each deposit and withdrawal, to reject disputes from other clients
+    owners: HashMap<u32, u16, Hasher>,
+    // double-entry postings of every balance change, when enabled
+    ledger: Option<Ledger>,
+    // withdrawals are held until settled, when enabled
+    pending_withdrawals: bool,
+    // time after which a pending withdrawal settles by itself
+    settle_delay: Option<TimeDelta>,
+    // pending withdrawals to settle once the input reaches their time --> (time, client, txID)
+    due: VecDeque<(Timestamp, u16, u32)>,
+    // clients whose account was merged into another one
+    closed: HashSet<u16, Hasher>,
+    // open disputes are closed after a while, when enabled
+    dispute_expiry: Option<DisputeExpiry>,
+    // timestamped disputes to close once the input reaches their expiry --> (time, client, txID)
+    expiring: VecDeque<(Timestamp, u16, u32)>,
+    // accounts opened or carried forward as they were before their opening balance,
+    // where `balance_at` starts replaying from
+    opened: HashMap<u16, Account, Hasher>,
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        Engine::default()
+    }
+
+    /// callback invoked after each transaction, replaces any previous one
+    pub fn set_observer(&mut self, observer: impl FnMut(&Transaction, &Account, Decision) + 'static) {
+        self.observers = vec![Box::new(observer)];
+    }
+
+    /// callback invoked after each transaction, after the ones already set
+    pub fn add_observer(&mut self, observer: impl FnMut(&Transaction, &Account, Decision) + 'static) {
+        self.observers.push(Box::new(observer));
+    }
+
+    /// keep every transaction from now on, needed by `balance_at`
+    pub fn enable_event_log(&mut self) {
+        self.events.get_or_insert_with(HashMap::default);
+    }
+
+    /// record every balance change as ledger postings from now on
+    pub fn enable_ledger(&mut self) {
+        self.ledger.get_or_insert_with(Ledger::default);
+    }
+
+    pub fn ledger(&self) -> Option<&Ledger> {
+        self.ledger.as_ref()
+    }
+
+    /// the ledger checked against the accounts, see `Ledger::trial_balance`
+    pub fn trial_balance(&self) -> TrialBalance {
+        let ledger = self.ledger.as_ref().expect("trial_balance needs the ledger enabled");
+        ledger.trial_balance(self.accounts.values())
+    }
+
+    /// Hold withdrawn funds until a `Settle` transaction of the withdrawal, or
+    /// until an input transaction timestamped at least `delay` after it.
+    pub fn enable_pending_withdrawals(&mut self, delay: Option<TimeDelta>) {
+        self.pending_withdrawals = true;
+        self.settle_delay = delay;
+    }
+
+    /// Close timestamped disputes still open `days` after them, once an input
+    /// transaction reaches that time. They are closed by `AutoResolve` or
+    /// `AutoChargeBack` transactions, told apart from the operator ones.
+    pub fn enable_dispute_expiry(&mut self, expiry: DisputeExpiry) {
+        self.dispute_expiry = Some(expiry);
+    }
+
+    /// sequence number of the last applied transaction
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub fn apply(&mut self, tx: Transaction) -> Decision {
+        Decision::from(&self.process(tx))
+    }
+
+    /// `apply` with the reason a transaction was rejected
+    pub fn process(&mut self, tx: Transaction) -> Result<Applied, Rejection> {
+        if let Some(now) = tx.timestamp {
+            self.settle_due(now);
+            self.expire_due(now);
+        }
+        if self.closed.contains(&tx.client) {
+            return Err(Rejection::AccountClosed);
+        }
+        if let Some(undo) = self.undo.as_mut() {
+            undo.push(Undo::before(self.accounts.get(&tx.client), &tx));
+        }
+        self.seq += 1;
+        if let Some(events) = self.events.as_mut() {
+            events.entry(tx.client).or_default().push((self.seq, tx.clone()));
+        }
+        let account = self
+            .accounts
+            .entry(tx.client)
+            .or_insert_with(|| Account::new(tx.client));
+        let res = match foreign(&self.owners, &tx) {
+            Some(rejection) => Err(rejection),
+            None => apply_to(account, &tx, self.pending_withdrawals),
+        };
+        if res.is_ok() && matches!(tx.tx_type, TxType::Deposit | TxType::Withdrawal | TxType::Escrow) {
+            self.owners.entry(tx.tx).or_insert(tx.client);
+        }
+        if let (Some(ledger), Ok(applied)) = (self.ledger.as_mut(), res) {
+            ledger.post(ledger::posting(self.seq, &tx, applied));
+        }
+        if let (Some(delay), Some(time), Ok(Applied::Pending(_))) = (self.settle_delay, tx.timestamp, res) {
+            self.due.push_back((time + delay, tx.client, tx.tx));
+        }
+        if let (Some(expiry), Some(time), Ok(Applied::Held(_))) = (self.dispute_expiry, tx.timestamp, res) {
+            self.expiring.push_back((time + TimeDelta::days(expiry.days), tx.client, tx.tx));
+        }
+        let decision = Decision::from(&res);
+        for observer in self.observers.iter_mut() {
+            observer(&tx, account, decision);
+        }
+        res
+    }
+
+    /// settle the pending withdrawals due by `now`, as transactions of their own
+    fn settle_due(&mut self, now: Timestamp) {
+        while let Some(&(time, client, tx)) = self.due.front().filter(|(time, _, _)| *time <= now) {
+            self.due.pop_front();
+            let pending = self.accounts.get(&client).is_some_and(|account| account.pending.contains_key(&tx));
+            if pending {
+                let settle = Transaction { client, tx_type: TxType::Settle, tx, amount: None, timestamp: Some(time), ..Default::default() };
+                let _ = self.process(settle);
+            }
+        }
+    }
+
+    /// close the disputes expired by `now` that are still open, as transactions of their own
+    fn expire_due(&mut self, now: Timestamp) {
+        let Some(expiry) = self.dispute_expiry else {
+            return;
+        };
+        while let Some(&(time, client, tx)) = self.expiring.front().filter(|(time, _, _)| *time <= now) {
+            self.expiring.pop_front();
+            let open = self.accounts.get(&client).is_some_and(|account| account.disputes.get(&tx) == Some(&Dispute::Open));
+            if open {
+                let tx_type = match expiry.action {
+                    ExpiryAction::Resolve => TxType::AutoResolve,
+                    ExpiryAction::ChargeBack => TxType::AutoChargeBack,
+                };
+                let close = Transaction { client, tx_type, tx, amount: None, timestamp: Some(time), ..Default::default() };
+                let _ = self.process(close);
+            }
+        }
+    }
+
+    /// Create the account of `client` before any of its transactions, with an
+    /// opening balance and a credit limit the available funds may go below
+    /// zero by. Observers see an `Opening` transaction with tx id 0.
+    pub fn open_account(&mut self, client: u16, opening: Decimal, credit_limit: Decimal, currency: Option<String>) {
+        assert!(!credit_limit.is_sign_negative(), "negative credit limit for client {}", client);
+        let account = Account { credit_limit: amount::from_decimal(credit_limit), currency, ..Account::new(client) };
+        self.open(account, opening);
+    }
+
+    /// Start the account of a client from its row of the report of a previous
+    /// run, for runs chained day after day. The transactions behind the held
+    /// funds are not known, so they cannot be resolved nor charged back;
+    /// funds in the total that are neither available nor held are kept as escrow.
+    pub fn carry_forward(&mut self, previous: &Account) {
+        let escrow = previous.total - previous.available - previous.held;
+        let account = Account {
+            held: previous.held,
+            escrow,
+            total: previous.held + escrow,
+            locked: previous.locked,
+            ..Account::new(previous.client)
+        };
+        self.open(account, amount::to_decimal(previous.available));
+        if let Some(ledger) = self.ledger.as_mut() {
+            let funds = [
+                (LedgerAccount::Held(previous.client), previous.held),
+                (LedgerAccount::Escrow(previous.client), escrow),
+            ];
+            for (account, amount) in funds.into_iter().filter(|(_, amount)| *amount != Amount::default()) {
+                let tx = opening(previous.client, amount::to_decimal(amount));
+                ledger.post(Posting { seq: self.seq, tx, debit: LedgerAccount::OpeningBalance, credit: account, amount });
+            }
+        }
+    }
+
+    /// add a new account and apply its opening balance to it
+    fn open(&mut self, account: Account, opening_balance: Decimal) {
+        let client = account.client;
+        assert!(self.undo.is_none(), "account opened while a savepoint is held");
+        assert!(!self.accounts.contains_key(&client) && !self.closed.contains(&client), "account of client {} already exists", client);
+        self.opened.insert(client, account.clone());
+        self.accounts.insert(client, account);
+        let _ = self.process(opening(client, opening_balance));
+    }
+
+    /// Apply a group of transactions atomically: if any of them is rejected
+    /// the accounts are left as they were before the batch.
+    pub fn apply_batch(&mut self, txs: &[Transaction]) -> Result<(), BatchError> {
+        // time based policies act before the batch as a whole, not between its rows
+        let timed = self.settle_delay.is_some() || self.dispute_expiry.is_some();
+        if let Some(now) = txs.iter().filter_map(|tx| tx.timestamp).max().filter(|_| timed) {
+            self.settle_due(now);
+            self.expire_due(now);
+        }
+        // dry run on copies of the touched accounts, observers only see committed batches
+        let mut scratch: HashMap<u16, Account, Hasher> = HashMap::default();
+        // owners of the transactions made earlier in the batch, not yet in `owners`
+        let mut batch_owners: HashMap<u32, u16, Hasher> = HashMap::default();
+        for (index, tx) in txs.iter().enumerate() {
+            if self.closed.contains(&tx.client) {
+                return Err(BatchError { index, tx: tx.tx, rejection: Rejection::AccountClosed });
+            }
+            let account = scratch.entry(tx.client).or_insert_with(|| {
+                self.accounts
+                    .get(&tx.client)
+                    .cloned()
+                    .unwrap_or_else(|| Account::new(tx.client))
+            });
+            let res = match foreign(&self.owners, tx).or_else(|| foreign(&batch_owners, tx)) {
+                Some(rejection) => Err(rejection),
+                None => apply_to(account, tx, self.pending_withdrawals),
+            };
+            if let Err(rejection) = res {
+                return Err(BatchError { index, tx: tx.tx, rejection });
+            }
+            if matches!(tx.tx_type, TxType::Deposit | TxType::Withdrawal | TxType::Escrow) && !self.owners.contains_key(&tx.tx) {
+                batch_owners.entry(tx.tx).or_insert(tx.client);
+            }
+        }
+        // the fast path skips the bookkeeping done by `apply`, settle and expiry queues included
+        let tracked = !self.observers.is_empty() || self.undo.is_some() || self.events.is_some() || self.ledger.is_some() || timed;
+        if !tracked {
+            self.seq += txs.len() as u64;
+            self.accounts.extend(scratch);
+            for tx in txs.iter().filter(|tx| matches!(tx.tx_type, TxType::Deposit | TxType::Withdrawal | TxType::Escrow)) {
+                self.owners.entry(tx.tx).or_insert(tx.client);
+            }
+        } else {
+            for tx in txs {
+                self.apply(tx.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the balances, transactions and dispute state of client `from` into
+    /// client `into`, for customers registered twice. The source account is
+    /// closed: it is no longer reported and its later transactions are rejected.
+    /// Observers see a `Merge` transaction of the target whose tx is the source
+    /// client id, so merges end up in the audit journal.
+    pub fn merge(&mut self, from: u16, into: u16) -> Result<(), MergeError> {
+        assert!(self.undo.is_none(), "merge while a savepoint is held");
+        if from == into {
+            return Err(MergeError::SameAccount);
+        }
+        if let Some(client) = [from, into].into_iter().find(|client| self.closed.contains(client)) {
+            return Err(MergeError::Closed(client));
+        }
+        let Some(source) = self.accounts.remove(&from) else {
+            return Err(MergeError::UnknownAccount(from));
+        };
+        self.closed.insert(from);
+        self.seq += 1;
+        let merge = Transaction {
+            client: into,
+            tx_type: TxType::Merge,
+            tx: u32::from(from),
+            amount: Some(amount::to_decimal(source.total)),
+            ..Default::default()
+        };
+        if let Some(ledger) = self.ledger.as_mut() {
+            let funds = [
+                (LedgerAccount::Available as fn(u16) -> LedgerAccount, source.available),
+                (LedgerAccount::Held, source.held),
+                (LedgerAccount::Escrow, source.escrow),
+            ];
+            for (account, amount) in funds.into_iter().filter(|(_, amount)| *amount != Amount::default()) {
+                ledger.post(Posting { seq: self.seq, tx: merge.clone(), debit: account(from), credit: account(into), amount });
+            }
+        }
+        for owner in self.owners.values_mut().filter(|owner| **owner == from) {
+            *owner = into;
+        }
+        for due in self.due.iter_mut().chain(self.expiring.iter_mut()).filter(|due| due.1 == from) {
+            due.1 = into;
+        }
+        if let Some(history) = self.events.as_mut().and_then(|events| events.remove(&from)) {
+            let target = self.events.as_mut().unwrap().entry(into).or_default();
+            target.extend(history);
+            target.sort_by_key(|(seq, _)| *seq);
+        }
+        let target = self.accounts.entry(into).or_insert_with(|| Account::new(into));
+        target.available += source.available;
+        target.held += source.held;
+        target.escrow += source.escrow;
+        target.total += source.total;
+        target.locked |= source.locked;
+        target.loss += source.loss;
+        for (id, amount) in source.transactions.iter() {
+            target.transactions.insert(id, amount);
+        }
+        target.disputes.extend(source.disputes);
+        target.pending.extend(source.pending);
+        target.escrowed.extend(source.escrowed);
+        target.withdrawals.extend(source.withdrawals);
+        for observer in self.observers.iter_mut() {
+            observer(&merge, target, Decision::Applied);
+        }
+        Ok(())
+    }
+
+    /// Unlock the account of `client`, locked by a chargeback, once the claim
+    /// is settled; false when there is no such account. No transaction is
+    /// involved, so the observers are not told.
+    pub fn unlock(&mut self, client: u16) -> bool {
+        assert!(self.undo.is_none(), "unlock while a savepoint is held");
+        let Some(account) = self.accounts.get_mut(&client) else {
+            return false;
+        };
+        account.locked = false;
+        true
+    }
+
+    /// Take the account of `client` out of the engine, to keep it elsewhere
+    /// until `reload`; None without one, or while a pending withdrawal or an
+    /// expiring dispute of it is due. A transaction of the client in between
+    /// would open a new account, the index of the tx owners stays.
+    pub fn evict(&mut self, client: u16) -> Option<Account> {
+        assert!(self.undo.is_none(), "evict while a savepoint is held");
+        if self.due.iter().chain(self.expiring.iter()).any(|due| due.1 == client) {
+            return None;
+        }
+        self.accounts.remove(&client)
+    }
+
+    /// put back an account taken out by `evict`
+    pub fn reload(&mut self, account: Account) {
+        assert!(!self.accounts.contains_key(&account.client), "account of client {} already exists", account.client);
+        self.accounts.insert(account.client, account);
+    }
+
+    /// Mark the current state so that the following transactions can be
+    /// reverted with `rollback_to`. Only the changes are recorded, not the accounts.
+    pub fn savepoint(&mut self) -> Savepoint {
+        Savepoint(self.undo.get_or_insert_with(Vec::new).len())
+    }
+
+    /// Revert every transaction applied after the savepoint, which stays valid.
+    /// Observers are not called for reverted transactions.
+    pub fn rollback_to(&mut self, savepoint: Savepoint) {
+        let undo = self.undo.as_mut().expect("rollback without a savepoint");
+        assert!(savepoint.0 <= undo.len(), "savepoint already rolled back");
+        for entry in undo.drain(savepoint.0..).rev() {
+            if let Some(events) = self.events.as_mut() {
+                events.get_mut(&entry.client).and_then(Vec::pop);
+            }
+            self.seq -= 1;
+            let (client, tx) = (entry.client, entry.tx);
+            entry.revert(&mut self.accounts);
+            let stored = self.accounts.get(&client).is_some_and(|account| account.transactions.contains_key(&tx));
+            if !stored && self.owners.get(&tx) == Some(&client) {
+                self.owners.remove(&tx);
+            }
+        }
+        if let Some(ledger) = self.ledger.as_mut() {
+            ledger.truncate(self.seq);
+        }
+    }
+
+    /// State of an account at a point of its history, replayed from the event log.
+    /// None if the client had no transaction before that point.
+    pub fn balance_at(&self, client: u16, as_of: AsOf) -> Option<Account> {
+        let events = self.events.as_ref().expect("balance_at needs the event log enabled");
+        let history = events.get(&client)?;
+        let end = match as_of {
+            AsOf::Seq(seq) => history.partition_point(|(tx_seq, _)| *tx_seq <= seq),
+            AsOf::BeforeTx(id) => history.iter().position(|(_, tx)| tx.tx == id).unwrap_or(history.len()),
+        };
+        if end == 0 {
+            return None;
+        }
+        // the credit limit, and the held and escrowed funds carried forward, are set once when opened
+        let mut account = self.opened.get(&client).cloned().unwrap_or_else(|| Account::new(client));
+        for (_, tx) in &history[..end] {
+            let _ = apply_to(&mut account, tx, self.pending_withdrawals);
+        }
+        Some(account)
+    }
+
+    /// Keep the changes made after the savepoint, recording stops with the outermost one
+    pub fn release(&mut self, savepoint: Savepoint) {
+        if savepoint.0 == 0 {
+            self.undo = None;
+        }
+    }
+
+    /// SHA-256 of the balances and dispute states of every account, in client
+    /// and tx order with amounts normalized, so it does not depend on the
+    /// hash map order nor on the trailing zeros of the input amounts.
+    pub fn state_hash(&self) -> String {
+        let mut clients: Vec<&u16> = self.accounts.keys().collect();
+        clients.sort();
+        let mut hasher = Sha256::new();
+        for client in clients {
+            let account = &self.accounts[client];
+            let amount = |value: Amount| amount::to_decimal(value).normalize();
+            hasher.update(format!(
+                "{},{},{},{},{},{}\n",
+                client,
+                amount(account.available),
+                amount(account.held),
+                amount(account.escrow),
+                amount(account.total),
+                account.locked
+            ));
+            let mut disputes: Vec<(&u32, &Dispute)> = account.disputes.iter().collect();
+            disputes.sort_by_key(|(tx, _)| **tx);
+            for (tx, dispute) in disputes {
+                hasher.update(format!("{}:{:?}\n", tx, dispute));
+            }
+        }
+        hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Heap memory held by the accounts and the optional logs, from the
+    /// capacity of their collections; strings of logged transactions are
+    /// not counted.
+    pub fn footprint(&self) -> Vec<Usage> {
+        let accounts = self.accounts.values();
+        let mut usage = vec![
+            Usage { part: "accounts", entries: self.accounts.len(), bytes: table_bytes(&self.accounts) },
+            Usage {
+                part: "transactions",
+                entries: accounts.clone().map(|account| account.transactions.len()).sum(),
+                bytes: accounts.clone().map(|account| account.transactions.heap_bytes()).sum(),
+            },
+            Usage {
+                part: "disputes",
+                entries: accounts.clone().map(|account| account.disputes.len()).sum(),
+                bytes: accounts.clone().map(|account| table_bytes(&account.disputes)).sum(),
+            },
+            Usage {
+                part: "pending and escrow",
+                entries: accounts.clone().map(|account| account.pending.len() + account.escrowed.len()).sum(),
+                bytes: accounts
+                    .clone()
+                    .map(|account| table_bytes(&account.pending) + table_bytes(&account.escrowed))
+                    .sum(),
+            },
+            Usage {
+                part: "withdrawal ids",
+                entries: accounts.clone().map(|account| account.withdrawals.len()).sum(),
+                bytes: accounts.map(|account| account.withdrawals.capacity() * (size_of::<u32>() + 1)).sum(),
+            },
+            Usage { part: "owners", entries: self.owners.len(), bytes: table_bytes(&self.owners) },
+        ];
+        if let Some(events) = &self.events {
+            usage.push(Usage {
+                part: "event log",
+                entries: events.values().map(Vec::len).sum(),
+                bytes: table_bytes(events) + events.values().map(|log| log.capacity() * size_of::<(u64, Transaction)>()).sum::<usize>(),
+            });
+        }
+        if let Some(ledger) = &self.ledger {
+            let postings = ledger.postings();
+            usage.push(Usage { part: "ledger", entries: postings.len(), bytes: size_of_val(postings) });
+        }
+        if let Some(undo) = &self.undo {
+            usage.push(Usage { part: "undo log", entries: undo.len(), bytes: undo.capacity() * size_of::<Undo>() });
+        }
+        usage
+    }
+
+    pub fn into_accounts(self) -> impl Iterator<Item = Account> {
+        self.accounts.into_values()
+    }
+
+    pub fn account(&self, client: u16) -> Option<&Account> {
+        self.accounts.get(&client)
+    }
+
+    /// all the accounts, in no particular order
+    pub fn accounts(&self) -> impl Iterator<Item = &Account> {
+        self.accounts.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn run(txs: Vec<Transaction>) -> AccountMap {
+        let mut accounts = AccountMap::default();
+        for tx in txs {
+            let _ = process_tx(&mut accounts, tx);
+        }
+        accounts
+    }
+
+    #[test]
+    fn test_dispute_with_missing_deposit() {
+        let txs = vec![
+            Transaction{client: 1, tx_type: TxType::Deposit, tx: 1, amount: Some(dec!(1.0)), ..Default::default()},
+            Transaction{client: 1, tx_type: TxType::Deposit, tx: 2, amount: Some(dec!(1.0)), ..Default::default()},
+            Transaction{client: 1, tx_type: TxType::Deposit, tx: 3, amount: Some(dec!(1.0)), ..Default::default()},
+            Transaction{client: 1, tx_type: TxType::Dispute, tx: 5, amount: None, ..Default::default()},
+        ];
+        let res = run(txs);
+        assert_eq!(res[&1].total, dec!(3.0));
+        assert_eq!(res[&1].available, dec!(3.0));
+        assert_eq!(res[&1].held, dec!(0.0));
+        assert!(!res[&1].locked);
+    }
+
+    #[test]
+    fn test_dispute_deposit() {
+        let txs = vec![
+            Transaction{client: 1, tx_type: TxType::Deposit, tx: 1, amount: Some(dec!(1.0)), ..Default::default()},
+            Transaction{client: 1, tx_type: TxType::Deposit, tx: 2, amount: Some(dec!(1.0)), ..Default::default()},
+            Transaction{client: 1, tx_type: TxType::Deposit, tx: 3, amount: Some(dec!(1.0)), ..Default::default()},
+            Transaction{client: 1, tx_type: TxType::Dispute, tx: 2, amount: None, ..Default::default()},
+        ];
+        let res = run(txs);
+        assert_eq!(res[&1].total, dec!(3.0));
+        assert_eq!(res[&1].available, dec!(2.0));
+        assert_eq!(res[&1].held, dec!(1.0));
+        assert!(!res[&1].locked);
+    }
+
+
+    #[test]
+    fn test_resolve_missing_dispute() {
+        let txs = vec![
+            Transaction{client: 1, tx_type: TxType::Deposit, tx: 1, amount: Some(dec!(1.0)), ..Default::default()},
+            Transaction{client: 1, tx_type: TxType::Deposit, tx: 3, amount: Some(dec!(1.0)), ..Default::default()},
+            Transaction{client: 1, tx_type: TxType::Dispute, tx: 4, amount: None, ..Default::default()},
+            Transaction{client: 1, tx_type: TxType::Resolve, tx: 3, amount: None, ..Default::default()},
+        ];
+        let res = run(txs);
+        assert_eq!(res[&1].total, dec!(2.0));
+        assert_eq!(res[&1].available, dec!(2.0));
+        assert_eq!(res[&1].held, dec!(0.0));
+        assert!(!res[&1].locked);
+    }
+
+    #[test]
+    fn test_resolve_dispute() {
+        let txs = vec![
+            Transaction{client: 1, tx_type: TxType::Deposit, tx: 1, amount: Some(dec!(1.0)), ..Default::default()},
+            Transaction{client: 1, tx_type: TxType::Deposit, tx: 3, amount: Some(dec!(1.0)), ..Default::default()},
+            Transaction{client: 1, tx_type: TxType::Dispute, tx: 1, amount: None, ..Default::default()},
+            Transaction{client: 1, tx_type: TxType::Resolve, tx: 1, amount: None, ..Default::default()},
+        ];
+        let res = run(txs);
+        assert_eq!(res[&1].total, dec!(2.0));
+        assert_eq!(res[&1].available, dec!(2.0));
+        assert_eq!(res[&1].held, dec!(0.0));
+        assert!(!res[&1].locked);
+    }
+
+    #[test]
+    fn test_chargeback_missing_dispute() {
+        let txs = vec![
+            Transaction{client: 1, tx_type: TxType::Deposit, tx: 1, amount: Some(dec!(1.0)), ..Default::default()},
+            Transaction{client: 1, tx_type: TxType::Deposit, tx: 3, amount: Some(dec!(1.0)), ..Default::default()},
+            Transaction{client: 1, tx_type: TxType::Dispute, tx: 4, amount: None, ..Default::default()},
+            Transaction{client: 1, tx_type: TxType::ChargeBack, tx: 3, amount: None, ..Default::default()},
+        ];
+        let res = run(txs);
+        assert_eq!(res[&1].total, dec!(2.0));
+        assert_eq!(res[&1].available, dec!(2.0));
+        assert_eq!(res[&1].held, dec!(0.0));
+        assert!(!res[&1].locked);
+    }
+
+    #[test]
+    fn test_chargeback_dispute() {
+        let txs = vec![
+            Transaction{client: 1, tx_type: TxType::Deposit, tx: 1, amount: Some(dec!(1.0)), ..Default::default()},
+            Transaction{client: 1, tx_type: TxType::Deposit, tx: 3, amount: Some(dec!(1.0)), ..Default::default()},
+            Transaction{client: 1, tx_type: TxType::Dispute, tx: 1, amount: None, ..Default::default()},
+            Transaction{client: 1, tx_type: TxType::ChargeBack, tx: 1, amount: None, ..Default::default()},
+        ];
+        let res = run(txs);
+        assert_eq!(res[&1].total, dec!(1.0));
+        assert_eq!(res[&1].available, dec!(1.0));
+        assert_eq!(res[&1].held, dec!(0.0));
+        assert!(res[&1].locked);
+    }
+
+    #[test]
+    fn test_process_tx_outcome() {
+        let mut accounts = AccountMap::default();
+        let amount = amount::from_decimal;
+        let mut process = |tx_type, tx, value: Option<Decimal>| {
+            process_tx(&mut accounts, Transaction{client: 1, tx_type, tx, amount: value, ..Default::default()})
+        };
+        assert_eq!(process(TxType::Deposit, 1, Some(dec!(2.0))), Ok(Applied::Deposited(amount(dec!(2.0)))));
+        assert_eq!(process(TxType::Withdrawal, 2, Some(dec!(5.0))), Err(Rejection::InsufficientFunds));
+        assert_eq!(process(TxType::Dispute, 3, None), Err(Rejection::UnknownTx));
+        assert_eq!(process(TxType::Resolve, 1, None), Err(Rejection::NotDisputed));
+        assert_eq!(process(TxType::Dispute, 1, None), Ok(Applied::Held(amount(dec!(2.0)))));
+        assert_eq!(process(TxType::ChargeBack, 1, None), Ok(Applied::ChargedBack(amount(dec!(2.0)))));
+        assert_eq!(process(TxType::Unknown, 4, None), Err(Rejection::UnsupportedType));
+        assert_eq!(process(TxType::Deposit, 5, Some(dec!(-1.0))), Err(Rejection::NegativeAmount));
+    }
+
+    #[test]
+    fn test_batch_is_all_or_nothing() {
+        let deposit = |client, tx, amount| Transaction{client, tx_type: TxType::Deposit, tx, amount: Some(amount), ..Default::default()};
+        let withdrawal = |client, tx, amount| Transaction{client, tx_type: TxType::Withdrawal, tx, amount: Some(amount), ..Default::default()};
+        let mut engine = Engine::new();
+        assert_eq!(engine.apply_batch(&[deposit(1, 1, dec!(5.0)), withdrawal(1, 2, dec!(2.0))]), Ok(()));
+        let res = engine.apply_batch(&[deposit(1, 3, dec!(1.0)), deposit(2, 4, dec!(1.0)), withdrawal(1, 5, dec!(10.0))]);
+        assert_eq!(res, Err(BatchError { index: 2, tx: 5, rejection: Rejection::InsufficientFunds }));
+        let accounts: Vec<Account> = engine.into_accounts().collect();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].available, dec!(3.0));
+        assert!(accounts[0].transactions.get(&3).is_none());
+    }
+
+    #[test]
+    fn test_batch_withdrawal_settles() {
+        let tx = |tx_type, tx, amount, time: &str| Transaction{client: 1, tx_type, tx, amount, timestamp: timestamp::parse(time).ok(), batch: Some("b".to_string()), ..Default::default()};
+        let mut engine = Engine::new();
+        engine.enable_pending_withdrawals(Some(TimeDelta::seconds(10)));
+        assert!(engine.apply_batch(&[tx(TxType::Deposit, 1, Some(dec!(11.0)), "2024-03-01 09:00:00"), tx(TxType::Withdrawal, 2, Some(dec!(4.0)), "2024-03-01 09:00:00")]).is_ok());
+        assert!(engine.apply_batch(&[tx(TxType::Deposit, 3, Some(dec!(1.0)), "2024-03-01 09:00:20")]).is_ok());
+        let account = &engine.accounts[&1];
+        assert_eq!(account.held, dec!(0));
+        assert_eq!(account.total, dec!(8.0));
+    }
+
+    #[test]
+    fn test_rollback_to_savepoint() {
+        let tx = |client, tx_type, tx, amount| Transaction{client, tx_type, tx, amount, ..Default::default()};
+        let mut engine = Engine::new();
+        engine.apply(tx(1, TxType::Deposit, 1, Some(dec!(5.0))));
+        let outer = engine.savepoint();
+        engine.apply(tx(1, TxType::Dispute, 1, None));
+        let inner = engine.savepoint();
+        engine.apply(tx(1, TxType::ChargeBack, 1, None));
+        engine.apply(tx(2, TxType::Deposit, 2, Some(dec!(1.0))));
+        engine.rollback_to(inner);
+        engine.apply(tx(1, TxType::Resolve, 1, None));
+        engine.rollback_to(outer);
+        engine.release(outer);
+        engine.apply(tx(1, TxType::Withdrawal, 3, Some(dec!(1.0))));
+        let accounts: Vec<Account> = engine.into_accounts().collect();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].available, dec!(4.0));
+        assert_eq!(accounts[0].held, dec!(0.0));
+        assert!(!accounts[0].locked);
+        assert!(accounts[0].disputes.is_empty());
+    }
+
+    #[test]
+    fn test_chargeback_is_final() {
+        let tx = |tx_type, amount| Transaction{client: 1, tx_type, tx: 1, amount, ..Default::default()};
+        let mut accounts = AccountMap::default();
+        let _ = process_tx(&mut accounts, tx(TxType::Deposit, Some(dec!(3.0))));
+        let _ = process_tx(&mut accounts, tx(TxType::Dispute, None));
+        assert_eq!(process_tx(&mut accounts, tx(TxType::ChargeBack, None)), Ok(Applied::ChargedBack(amount::from_decimal(dec!(3.0)))));
+        assert_eq!(process_tx(&mut accounts, tx(TxType::Resolve, None)), Err(Rejection::ChargedBack));
+        assert_eq!(process_tx(&mut accounts, tx(TxType::Dispute, None)), Err(Rejection::ChargedBack));
+        assert_eq!(process_tx(&mut accounts, tx(TxType::ChargeBack, None)), Err(Rejection::ChargedBack));
+        let account = &accounts[&1];
+        assert_eq!(account.held, dec!(0));
+        assert_eq!(account.total, dec!(0));
+    }
+
+    #[test]
+    fn test_dispute_already_open() {
+        let tx = |tx_type, amount| Transaction{client: 1, tx_type, tx: 1, amount, ..Default::default()};
+        let mut accounts = AccountMap::default();
+        let _ = process_tx(&mut accounts, tx(TxType::Deposit, Some(dec!(10.0))));
+        let _ = process_tx(&mut accounts, tx(TxType::Dispute, None));
+        assert_eq!(process_tx(&mut accounts, tx(TxType::Dispute, None)), Err(Rejection::AlreadyDisputed));
+        assert!(process_tx(&mut accounts, tx(TxType::Resolve, None)).is_ok());
+        let account = &accounts[&1];
+        assert_eq!(account.available, dec!(10.0));
+        assert_eq!(account.held, dec!(0));
+    }
+
+    #[test]
+    fn test_dispute_of_another_client_tx() {
+        let mut engine = Engine::new();
+        engine.apply(Transaction{client: 1, tx_type: TxType::Deposit, tx: 1, amount: Some(dec!(2.0)), ..Default::default()});
+        let res = engine.process(Transaction{client: 2, tx_type: TxType::Dispute, tx: 1, amount: None, ..Default::default()});
+        assert_eq!(res, Err(Rejection::ForeignTx { owner: 1 }));
+        let res = engine.process(Transaction{client: 1, tx_type: TxType::Dispute, tx: 1, amount: None, ..Default::default()});
+        assert!(res.is_ok());
+        let res = engine.apply_batch(&[Transaction{client: 3, tx_type: TxType::Resolve, tx: 1, amount: None, ..Default::default()}]);
+        assert_eq!(res.unwrap_err().rejection, Rejection::ForeignTx { owner: 1 });
+        // the owner of a tx made earlier in the same batch is known as well
+        let res = engine.apply_batch(&[
+            Transaction{client: 1, tx_type: TxType::Deposit, tx: 5, amount: Some(dec!(1.0)), ..Default::default()},
+            Transaction{client: 2, tx_type: TxType::Dispute, tx: 5, amount: None, ..Default::default()},
+        ]);
+        assert_eq!(res, Err(BatchError { index: 1, tx: 5, rejection: Rejection::ForeignTx { owner: 1 } }));
+        assert_eq!(engine.account(1).unwrap().available, dec!(0.0));
+    }
+
+    #[test]
+    fn test_pending_withdrawals() {
+        let tx = |tx_type, tx, amount, time: &str| Transaction{client: 1, tx_type, tx, amount, timestamp: timestamp::parse(time).ok(), ..Default::default()};
+        let mut engine = Engine::new();
+        engine.enable_pending_withdrawals(Some(TimeDelta::hours(1)));
+        engine.apply(tx(TxType::Deposit, 1, Some(dec!(10.0)), "2024-03-01 09:00:00"));
+        engine.apply(tx(TxType::Withdrawal, 2, Some(dec!(3.0)), "2024-03-01 09:10:00"));
+        engine.apply(tx(TxType::Withdrawal, 3, Some(dec!(2.0)), "2024-03-01 09:20:00"));
+        assert_eq!(engine.process(tx(TxType::Settle, 2, None, "2024-03-01 09:30:00")), Ok(Applied::Settled(amount::from_decimal(dec!(3.0)))));
+        assert_eq!(engine.process(tx(TxType::Settle, 2, None, "2024-03-01 09:40:00")), Err(Rejection::NotPending));
+        let account = &engine.accounts[&1];
+        assert_eq!((account.available, account.held, account.total), (amount::from_decimal(dec!(5.0)), amount::from_decimal(dec!(2.0)), amount::from_decimal(dec!(7.0))));
+        // tx 3 settles by itself an hour later
+        engine.apply(tx(TxType::Deposit, 4, Some(dec!(1.0)), "2024-03-01 10:30:00"));
+        let account = &engine.accounts[&1];
+        assert_eq!((account.held, account.total), (amount::from_decimal(dec!(0.0)), amount::from_decimal(dec!(6.0))));
+    }
+
+    #[test]
+    fn test_escrow() {
+        let tx = |tx_type, tx, amount| Transaction{client: 1, tx_type, tx, amount, ..Default::default()};
+        let mut engine = Engine::new();
+        engine.apply(tx(TxType::Escrow, 1, Some(dec!(4.0))));
+        assert_eq!(engine.process(tx(TxType::Withdrawal, 2, Some(dec!(1.0)))), Err(Rejection::InsufficientFunds));
+        assert_eq!(engine.process(tx(TxType::Dispute, 1, None)), Err(Rejection::UnknownTx));
+        assert_eq!(engine.process(tx(TxType::Release, 1, None)), Ok(Applied::EscrowReleased(amount::from_decimal(dec!(4.0)))));
+        assert_eq!(engine.process(tx(TxType::Release, 1, None)), Err(Rejection::NotEscrowed));
+        assert!(engine.process(tx(TxType::Withdrawal, 3, Some(dec!(1.0)))).is_ok());
+        let account = &engine.accounts[&1];
+        assert_eq!(account.escrow, dec!(0));
+        assert_eq!(account.available, dec!(3.0));
+        assert_eq!(account.total, dec!(3.0));
+    }
+
+    #[test]
+    fn test_reversal() {
+        let tx = |tx_type, tx, amount| Transaction{client: 1, tx_type, tx, amount, ..Default::default()};
+        let mut engine = Engine::new();
+        engine.apply(tx(TxType::Deposit, 1, Some(dec!(5.0))));
+        engine.apply(tx(TxType::Withdrawal, 2, Some(dec!(4.0))));
+        engine.apply(tx(TxType::Deposit, 3, Some(dec!(2.0))));
+        engine.apply(tx(TxType::Dispute, 3, None));
+        assert_eq!(engine.process(tx(TxType::Reversal, 1, None)), Err(Rejection::InsufficientFunds));
+        assert_eq!(engine.process(tx(TxType::Reversal, 3, None)), Err(Rejection::NotReversible));
+        assert_eq!(engine.process(tx(TxType::Reversal, 2, None)), Ok(Applied::WithdrawalReversed(amount::from_decimal(dec!(4.0)))));
+        assert_eq!(engine.process(tx(TxType::Reversal, 2, None)), Err(Rejection::UnknownTx));
+        assert_eq!(engine.process(tx(TxType::Reversal, 1, None)), Ok(Applied::DepositReversed(amount::from_decimal(dec!(5.0)))));
+        assert_eq!(engine.process(tx(TxType::Dispute, 1, None)), Err(Rejection::UnknownTx));
+        let account = &engine.accounts[&1];
+        assert!(!account.locked);
+        assert_eq!(account.available, dec!(0.0));
+        assert_eq!(account.total, dec!(2.0));
+    }
+
+    #[test]
+    fn test_dispute_expiry() {
+        let tx = |tx_type, tx, amount, day: &str| Transaction {
+            client: 1,
+            tx_type,
+            tx,
+            amount,
+            timestamp: Some(timestamp::parse(day).unwrap()),
+            ..Default::default()
+        };
+        let mut engine = Engine::new();
+        engine.enable_dispute_expiry(DisputeExpiry { days: 30, action: ExpiryAction::ChargeBack });
+        let seen = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let log = seen.clone();
+        engine.set_observer(move |tx, _, _| log.borrow_mut().push(tx.tx_type));
+        engine.apply(tx(TxType::Deposit, 1, Some(dec!(5.0)), "2024-01-01"));
+        engine.apply(tx(TxType::Deposit, 2, Some(dec!(3.0)), "2024-01-01"));
+        engine.apply(tx(TxType::Dispute, 1, None, "2024-01-02"));
+        engine.apply(tx(TxType::Dispute, 2, None, "2024-01-03"));
+        engine.apply(tx(TxType::Resolve, 2, None, "2024-01-10"));
+        engine.apply(tx(TxType::Deposit, 3, Some(dec!(1.0)), "2024-02-15"));
+        assert_eq!(seen.borrow()[5..], [TxType::AutoChargeBack, TxType::Deposit]);
+        let account = &engine.accounts[&1];
+        assert!(account.locked);
+        assert_eq!(account.total, dec!(4.0));
+    }
+
+    #[test]
+    fn test_merge() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let tx = |client, tx_type, tx, amount| Transaction{client, tx_type, tx, amount, ..Default::default()};
+        let mut engine = Engine::new();
+        engine.enable_ledger();
+        let merges = Rc::new(RefCell::new(Vec::new()));
+        let seen = merges.clone();
+        engine.set_observer(move |tx, account, _| {
+            if tx.tx_type == TxType::Merge {
+                seen.borrow_mut().push((tx.tx, account.client));
+            }
+        });
+        engine.apply(tx(1, TxType::Deposit, 1, Some(dec!(5.0))));
+        engine.apply(tx(2, TxType::Deposit, 2, Some(dec!(3.0))));
+        engine.apply(tx(2, TxType::Dispute, 2, None));
+        assert_eq!(engine.merge(2, 2), Err(MergeError::SameAccount));
+        assert_eq!(engine.merge(3, 1), Err(MergeError::UnknownAccount(3)));
+        assert_eq!(engine.merge(2, 1), Ok(()));
+        assert_eq!(engine.merge(2, 1), Err(MergeError::Closed(2)));
+        assert_eq!(*merges.borrow(), vec![(2, 1)]);
+        // the dispute goes on in the target account
+        assert_eq!(engine.process(tx(1, TxType::Resolve, 2, None)), Ok(Applied::Released(amount::from_decimal(dec!(3.0)))));
+        assert_eq!(engine.process(tx(2, TxType::Deposit, 3, Some(dec!(1.0)))), Err(Rejection::AccountClosed));
+        assert!(engine.trial_balance().is_balanced());
+        let accounts: Vec<Account> = engine.into_accounts().collect();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].available, dec!(8.0));
+        assert_eq!(accounts[0].total, dec!(8.0));
+    }
+
+    #[test]
+    fn test_evict_and_reload() {
+        let tx = |tx_type, tx, amount| Transaction{client: 1, tx_type, tx, amount, ..Default::default()};
+        let mut engine = Engine::new();
+        engine.apply(tx(TxType::Deposit, 1, Some(dec!(5.0))));
+        engine.apply(tx(TxType::Deposit, 2, Some(dec!(2.5))));
+        engine.apply(tx(TxType::Withdrawal, 3, Some(dec!(1.0))));
+        engine.apply(tx(TxType::Dispute, 2, None));
+        let saved = engine.evict(1).unwrap().save();
+        assert!(engine.account(1).is_none() && engine.evict(1).is_none());
+        engine.reload(Account::load(saved.as_bytes()).unwrap());
+        // the disputes and reversals go on as before
+        assert_eq!(engine.process(tx(TxType::Resolve, 2, None)), Ok(Applied::Released(amount::from_decimal(dec!(2.5)))));
+        assert_eq!(engine.process(tx(TxType::Reversal, 3, None)), Ok(Applied::WithdrawalReversed(amount::from_decimal(dec!(1.0)))));
+        let account = engine.account(1).unwrap();
+        assert_eq!((account.available, account.held, account.total), (amount::from_decimal(dec!(7.5)), Amount::default(), amount::from_decimal(dec!(7.5))));
+        assert_eq!(Account::load(account.save().as_bytes()).unwrap().save(), account.save());
+    }
+
+    #[test]
+    fn test_unlock() {
+        let tx = |tx_type, tx, amount| Transaction{client: 1, tx_type, tx, amount, ..Default::default()};
+        let mut engine = Engine::new();
+        engine.apply(tx(TxType::Deposit, 1, Some(dec!(5.0))));
+        engine.apply(tx(TxType::Dispute, 1, None));
+        engine.apply(tx(TxType::ChargeBack, 1, None));
+        assert!(engine.account(1).unwrap().locked);
+        assert!(engine.unlock(1));
+        assert!(!engine.unlock(2));
+        assert!(!engine.account(1).unwrap().locked);
+    }
+
+    #[test]
+    fn test_balance_at() {
+        let tx = |client, tx_type, tx, amount| Transaction{client, tx_type, tx, amount, ..Default::default()};
+        let mut engine = Engine::new();
+        engine.enable_event_log();
+        engine.apply(tx(1, TxType::Deposit, 1, Some(dec!(5.0))));
+        engine.apply(tx(2, TxType::Deposit, 2, Some(dec!(1.0))));
+        engine.apply(tx(1, TxType::Withdrawal, 3, Some(dec!(2.0))));
+        let sp = engine.savepoint();
+        engine.apply(tx(1, TxType::Deposit, 9, Some(dec!(7.0))));
+        engine.rollback_to(sp);
+        engine.apply(tx(1, TxType::Dispute, 1, None));
+        assert_eq!(engine.seq(), 4);
+        assert_eq!(engine.balance_at(1, AsOf::BeforeTx(3)).unwrap().available, dec!(5.0));
+        assert_eq!(engine.balance_at(1, AsOf::Seq(3)).unwrap().available, dec!(3.0));
+        assert_eq!(engine.balance_at(1, AsOf::Seq(4)).unwrap().held, dec!(5.0));
+        assert!(engine.balance_at(2, AsOf::Seq(1)).is_none());
+        assert!(engine.balance_at(3, AsOf::Seq(4)).is_none());
+
+        let previous = Account {
+            available: amount::from_decimal(dec!(1.0)),
+            held: amount::from_decimal(dec!(2.0)),
+            total: amount::from_decimal(dec!(6.0)),
+            locked: true,
+            ..Account::new(4)
+        };
+        // a run chained after another one starts from the carried-forward balances
+        let mut engine = Engine::new();
+        engine.enable_event_log();
+        engine.carry_forward(&previous);
+        engine.apply(tx(4, TxType::Deposit, 10, Some(dec!(0.5))));
+        let opened = engine.balance_at(4, AsOf::BeforeTx(10)).unwrap();
+        let balances = [opened.available, opened.held, opened.escrow, opened.total].map(amount::to_decimal);
+        assert_eq!(balances, [dec!(1.0), dec!(2.0), dec!(3.0), dec!(6.0)]);
+        assert!(opened.locked);
+        let latest = engine.balance_at(4, AsOf::Seq(engine.seq())).unwrap();
+        let account = engine.account(4).unwrap();
+        assert_eq!((latest.available, latest.held, latest.total), (account.available, account.held, account.total));
+    }
+
+    #[test]
+    fn test_state_hash() {
+        let tx = |client, tx_type, tx, amount| Transaction{client, tx_type, tx, amount, ..Default::default()};
+        let mut one = Engine::new();
+        one.apply(tx(1, TxType::Deposit, 1, Some(dec!(5.0))));
+        one.apply(tx(2, TxType::Deposit, 2, Some(dec!(1))));
+        let mut other = Engine::new();
+        other.apply(tx(2, TxType::Deposit, 2, Some(dec!(1.00))));
+        other.apply(tx(1, TxType::Deposit, 1, Some(dec!(5))));
+        assert_eq!(one.state_hash(), other.state_hash());
+        other.apply(tx(1, TxType::Dispute, 1, None));
+        other.apply(tx(1, TxType::Resolve, 1, None));
+        assert_eq!(one.state_hash(), other.state_hash());
+        other.apply(tx(1, TxType::Dispute, 1, None));
+        assert_ne!(one.state_hash(), other.state_hash());
+    }
+
+    #[test]
+    fn test_footprint() {
+        let tx = |client, tx_type, tx, amount| Transaction{client, tx_type, tx, amount, ..Default::default()};
+        let mut engine = Engine::new();
+        engine.enable_ledger();
+        engine.apply(tx(1, TxType::Deposit, 1, Some(dec!(5.0))));
+        engine.apply(tx(2, TxType::Deposit, 2, Some(dec!(1.0))));
+        engine.apply(tx(1, TxType::Dispute, 1, None));
+        let usage = engine.footprint();
+        let part = |name| usage.iter().find(|usage| usage.part == name).unwrap();
+        assert_eq!((part("accounts").entries, part("transactions").entries, part("disputes").entries), (2, 2, 1));
+        assert_eq!(part("transactions").bytes, 2 * TX_CAPACITY * (size_of::<u32>() + size_of::<Amount>()));
+        assert_eq!(part("ledger").entries, 3);
+        assert!(usage.iter().all(|usage| usage.part != "event log"));
+    }
+
+    #[test]
+    fn test_carry_forward() {
+        let tx = |client, tx_type, tx, amount| Transaction{client, tx_type, tx, amount, ..Default::default()};
+        let mut engine = Engine::new();
+        engine.enable_ledger();
+        let previous = |client, available, held, total, locked| Account {
+            available: amount::from_decimal(available),
+            held: amount::from_decimal(held),
+            total: amount::from_decimal(total),
+            locked,
+            ..Account::new(client)
+        };
+        engine.carry_forward(&previous(1, dec!(5.0), dec!(2.0), dec!(10.0), false));
+        engine.carry_forward(&previous(2, dec!(-1.0), dec!(0), dec!(-1.0), true));
+        assert_eq!(engine.process(tx(1, TxType::Withdrawal, 1, Some(dec!(6.0)))), Err(Rejection::InsufficientFunds));
+        assert!(engine.process(tx(1, TxType::Withdrawal, 2, Some(dec!(5.0)))).is_ok());
+        assert!(engine.trial_balance().is_balanced());
+        let mut accounts: Vec<Account> = engine.into_accounts().collect();
+        accounts.sort_by_key(|account| account.client);
+        assert_eq!(accounts[0].held, dec!(2.0));
+        assert_eq!(accounts[0].escrow, dec!(3.0));
+        assert_eq!(accounts[0].total, dec!(5.0));
+        assert!(accounts[1].locked);
+        assert_eq!(accounts[1].total, dec!(-1.0));
+    }
+
+    #[test]
+    fn test_observer_sees_every_tx() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut engine = Engine::new();
+        let log = seen.clone();
+        engine.set_observer(move |tx, account, decision| {
+            log.borrow_mut().push((tx.tx, amount::to_decimal(account.available), decision));
+        });
+        engine.apply(Transaction{client: 1, tx_type: TxType::Deposit, tx: 1, amount: Some(dec!(1.0)), ..Default::default()});
+        engine.apply(Transaction{client: 1, tx_type: TxType::Withdrawal, tx: 2, amount: Some(dec!(5.0)), ..Default::default()});
+        engine.apply(Transaction{client: 1, tx_type: TxType::Dispute, tx: 1, amount: None, ..Default::default()});
+        assert_eq!(
+            *seen.borrow(),
+            vec![
+                (1, dec!(1.0), Decision::Applied),
+                (2, dec!(1.0), Decision::Ignored),
+                (1, dec!(0.0), Decision::Applied),
+            ]
+        );
+    }
+}
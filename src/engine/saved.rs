@@ -0,0 +1,75 @@
+use super::{Account, Dispute, TxStore};
+use crate::amount::Amount;
+
+/// An account with the state of its transactions, as `Account::save` writes it
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Saved {
+    client: u16,
+    available: Amount,
+    held: Amount,
+    escrow: Amount,
+    total: Amount,
+    locked: bool,
+    credit_limit: Amount,
+    currency: Option<String>,
+    loss: Amount,
+    transactions: Vec<(u32, Amount)>,
+    /// disputed transactions, true once charged back
+    disputes: Vec<(u32, bool)>,
+    pending: Vec<(u32, Amount)>,
+    escrowed: Vec<(u32, Amount)>,
+    withdrawals: Vec<u32>,
+}
+
+impl Account {
+    /// JSON of the account with the amounts and the dispute state of its
+    /// transactions, which the report leaves out; read back by `load`
+    pub fn save(&self) -> String {
+        let mut saved = Saved {
+            client: self.client,
+            available: self.available,
+            held: self.held,
+            escrow: self.escrow,
+            total: self.total,
+            locked: self.locked,
+            credit_limit: self.credit_limit,
+            currency: self.currency.clone(),
+            loss: self.loss,
+            transactions: self.transactions.iter().collect(),
+            disputes: self.disputes.iter().map(|(tx, dispute)| (*tx, *dispute == Dispute::ChargedBack)).collect(),
+            pending: self.pending.iter().map(|(tx, amount)| (*tx, *amount)).collect(),
+            escrowed: self.escrowed.iter().map(|(tx, amount)| (*tx, *amount)).collect(),
+            withdrawals: self.withdrawals.iter().copied().collect(),
+        };
+        // the hash maps have no order
+        saved.disputes.sort_unstable();
+        saved.pending.sort_unstable();
+        saved.escrowed.sort_unstable();
+        saved.withdrawals.sort_unstable();
+        serde_json::to_string(&saved).expect("account is always serializable")
+    }
+
+    pub fn load(json: &[u8]) -> Result<Account, serde_json::Error> {
+        let saved: Saved = serde_json::from_slice(json)?;
+        let mut transactions = TxStore::with_capacity(saved.transactions.len());
+        for (tx, amount) in saved.transactions {
+            transactions.insert(tx, amount);
+        }
+        Ok(Account {
+            client: saved.client,
+            available: saved.available,
+            held: saved.held,
+            escrow: saved.escrow,
+            total: saved.total,
+            locked: saved.locked,
+            credit_limit: saved.credit_limit,
+            currency: saved.currency,
+            loss: saved.loss,
+            transactions,
+            disputes: saved.disputes.into_iter().map(|(tx, charged_back)| (tx, if charged_back { Dispute::ChargedBack } else { Dispute::Open })).collect(),
+            pending: saved.pending.into_iter().collect(),
+            escrowed: saved.escrowed.into_iter().collect(),
+            withdrawals: saved.withdrawals.into_iter().collect(),
+        })
+    }
+}
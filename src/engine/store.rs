@@ -0,0 +1,102 @@
+use crate::amount::Amount;
+use std::ops::Index;
+
+/// Amounts of the transactions of an account, keyed by tx id.
+/// Ids and amounts are kept in two sorted columns instead of a hash map,
+/// saving the per-entry overhead on accounts with millions of deposits.
+/// Ids mostly arrive in increasing order, so inserting is usually a push.
+#[derive(Debug, Clone, Default)]
+pub struct TxStore {
+    ids: Vec<u32>,
+    amounts: Vec<Amount>,
+}
+
+impl TxStore {
+    pub fn with_capacity(capacity: usize) -> TxStore {
+        TxStore {
+            ids: Vec::with_capacity(capacity),
+            amounts: Vec::with_capacity(capacity),
+        }
+    }
+
+    fn position(&self, id: u32) -> Result<usize, usize> {
+        match self.ids.last() {
+            Some(&last) if last < id => Err(self.ids.len()),
+            _ => self.ids.binary_search(&id),
+        }
+    }
+
+    /// stores the amount of a tx, returning the one it replaces
+    pub fn insert(&mut self, id: u32, amount: Amount) -> Option<Amount> {
+        match self.position(id) {
+            Ok(idx) => Some(std::mem::replace(&mut self.amounts[idx], amount)),
+            Err(idx) => {
+                self.ids.insert(idx, id);
+                self.amounts.insert(idx, amount);
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: &u32) -> Option<Amount> {
+        let idx = self.position(*id).ok()?;
+        self.ids.remove(idx);
+        Some(self.amounts.remove(idx))
+    }
+
+    pub fn get(&self, id: &u32) -> Option<&Amount> {
+        self.position(*id).ok().map(|idx| &self.amounts[idx])
+    }
+
+    pub fn contains_key(&self, id: &u32) -> bool {
+        self.position(*id).is_ok()
+    }
+
+    /// ids and amounts in increasing id order
+    pub fn iter(&self) -> impl Iterator<Item = (u32, Amount)> + '_ {
+        self.ids.iter().copied().zip(self.amounts.iter().copied())
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// bytes allocated for the two columns
+    pub fn heap_bytes(&self) -> usize {
+        self.ids.capacity() * size_of::<u32>() + self.amounts.capacity() * size_of::<Amount>()
+    }
+}
+
+impl Index<&u32> for TxStore {
+    type Output = Amount;
+
+    fn index(&self, id: &u32) -> &Amount {
+        self.get(id).expect("unknown tx id")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_insert_in_any_order() {
+        let mut store = TxStore::default();
+        let amount = amount::from_decimal;
+        for (id, value) in [(5, dec!(5.0)), (1, dec!(1.0)), (9, dec!(9.0)), (3, dec!(3.0))] {
+            assert_eq!(store.insert(id, amount(value)), None);
+        }
+        assert_eq!(store.insert(3, amount(dec!(0.5))), Some(amount(dec!(3.0))));
+        assert_eq!(store.len(), 4);
+        assert_eq!(store[&9], amount(dec!(9.0)));
+        assert_eq!(store.get(&3), Some(&amount(dec!(0.5))));
+        assert!(store.get(&4).is_none());
+        assert!(!store.contains_key(&10));
+    }
+}
@@ -0,0 +1,126 @@
+use crate::engine::{Transaction, TxType};
+use serde::de::{value::Error, IntoDeserializer};
+use serde::Deserialize;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// Set of ids written as a comma separated list of values and inclusive ranges, e.g. `5,17,100-200`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ranges<T> {
+    ranges: Vec<RangeInclusive<T>>,
+}
+
+impl<T: PartialOrd> Ranges<T> {
+    pub fn contains(&self, id: &T) -> bool {
+        self.ranges.iter().any(|range| range.contains(id))
+    }
+}
+
+impl<T: FromStr + PartialOrd + Copy> FromStr for Ranges<T> {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |value: &str| {
+            value
+                .trim()
+                .parse::<T>()
+                .map_err(|_| format!("invalid id `{}`", value.trim()))
+        };
+        let mut ranges = Vec::new();
+        for item in s.split(',') {
+            let range = match item.split_once('-') {
+                Some((start, end)) => parse(start)?..=parse(end)?,
+                None => {
+                    let id = parse(item)?;
+                    id..=id
+                }
+            };
+            if range.start() > range.end() {
+                return Err(format!("empty range `{}`", item.trim()));
+            }
+            ranges.push(range);
+        }
+        Ok(Ranges { ranges })
+    }
+}
+
+/// Comma separated list of transaction types, spelled as in the `type` column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxTypes(pub Vec<TxType>);
+
+impl FromStr for TxTypes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(|name| {
+                let name = name.trim();
+                match TxType::deserialize(name.into_deserializer()) {
+                    Ok(TxType::Unknown) | Err::<_, Error>(_) => Err(format!("unknown transaction type `{}`", name)),
+                    Ok(tx_type) => Ok(tx_type),
+                }
+            })
+            .collect::<Result<_, _>>()
+            .map(TxTypes)
+    }
+}
+
+/// Selection of transactions applied at the reader stage, before the engine sees them
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    pub clients: Option<Ranges<u16>>,
+    pub types: Option<TxTypes>,
+    pub tx_ids: Option<Ranges<u32>>,
+}
+
+impl Filter {
+    pub fn accepts(&self, tx: &Transaction) -> bool {
+        self.clients.as_ref().is_none_or(|clients| clients.contains(&tx.client))
+            && self.types.as_ref().is_none_or(|types| types.0.contains(&tx.tx_type))
+            && self.tx_ids.as_ref().is_none_or(|ids| ids.contains(&tx.tx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ranges() {
+        let ranges: Ranges<u16> = "5, 17,100-200".parse().unwrap();
+        assert!(ranges.contains(&5));
+        assert!(ranges.contains(&17));
+        assert!(ranges.contains(&100));
+        assert!(ranges.contains(&150));
+        assert!(ranges.contains(&200));
+        assert!(!ranges.contains(&6));
+        assert!(!ranges.contains(&201));
+        assert!("5,abc".parse::<Ranges<u16>>().is_err());
+        assert!("10-5".parse::<Ranges<u16>>().is_err());
+        assert!("70000".parse::<Ranges<u16>>().is_err());
+    }
+
+    #[test]
+    fn test_filter_clients() {
+        let filter = Filter { clients: Some("2-3".parse().unwrap()), ..Default::default() };
+        let tx = |client| Transaction{client, tx_type: TxType::Deposit, tx: 1, amount: None, ..Default::default()};
+        assert!(!filter.accepts(&tx(1)));
+        assert!(filter.accepts(&tx(2)));
+        assert!(Filter::default().accepts(&tx(1)));
+    }
+
+    #[test]
+    fn test_filter_types_and_tx_range() {
+        let filter = Filter {
+            types: Some("deposit, chargeback".parse().unwrap()),
+            tx_ids: Some("1000-2000".parse().unwrap()),
+            ..Default::default()
+        };
+        let tx = |tx_type, tx| Transaction{client: 1, tx_type, tx, amount: None, ..Default::default()};
+        assert!(filter.accepts(&tx(TxType::Deposit, 1000)));
+        assert!(filter.accepts(&tx(TxType::ChargeBack, 2000)));
+        assert!(!filter.accepts(&tx(TxType::Withdrawal, 1500)));
+        assert!(!filter.accepts(&tx(TxType::Deposit, 999)));
+        assert!("deposit,refund".parse::<TxTypes>().is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use crate::amount::{self, Amount};
+use crate::engine::{Account, Engine, Transaction, TxType};
+use polars::prelude::*;
+use rust_decimal::Decimal;
+use serde::de::{value::Error, IntoDeserializer};
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// decimal column of `amounts`, all at the largest scale among them
+fn decimals(name: &str, amounts: Vec<Decimal>) -> PolarsResult<Column> {
+    let scale = amounts.iter().map(|amount| amount.scale()).max().unwrap_or(0);
+    let mantissas = amounts
+        .into_iter()
+        .map(|mut amount| {
+            amount.rescale(scale);
+            amount.mantissa()
+        })
+        .collect();
+    let column = Int128Chunked::from_vec(name.into(), mantissas).into_decimal(38, scale as usize)?;
+    Ok(column.into_series().into_column())
+}
+
+impl Engine {
+    /// The accounts in client order, with the columns of the csv report:
+    /// `client` as u16, the balances as decimals and `locked` as a boolean
+    pub fn accounts_df(&self) -> PolarsResult<DataFrame> {
+        let mut accounts: Vec<&Account> = self.accounts().collect();
+        accounts.sort_by_key(|account| account.client);
+        let balance = |name: &str, field: fn(&Account) -> Amount| {
+            decimals(name, accounts.iter().map(|account| amount::to_decimal(field(account))).collect())
+        };
+        let columns = vec![
+            Column::new("client".into(), accounts.iter().map(|account| account.client).collect::<Vec<_>>()),
+            balance("available", |account| account.available)?,
+            balance("held", |account| account.held)?,
+            balance("total", |account| account.total)?,
+            Column::new("locked".into(), accounts.iter().map(|account| account.locked).collect::<Vec<_>>()),
+        ];
+        DataFrame::new(accounts.len(), columns)
+    }
+
+    /// Engine with the rows of `df` applied in order. `type`, `client` and
+    /// `tx` are required and `amount` is optional; amounts are read from
+    /// decimal, float, integer or string columns. Rows of an unknown type are
+    /// rejected by the engine, the other columns are ignored.
+    pub fn from_transactions_df(df: &DataFrame) -> PolarsResult<Engine> {
+        let types = df.column("type")?.cast(&DataType::String)?;
+        let clients = df.column("client")?.strict_cast(&DataType::UInt16)?;
+        let txs = df.column("tx")?.strict_cast(&DataType::UInt32)?;
+        let amounts = match df.column("amount") {
+            Ok(amounts) => Some(amounts.cast(&DataType::String)?),
+            Err(_) => None,
+        };
+        let (types, clients, txs) = (types.str()?, clients.u16()?, txs.u32()?);
+        let amounts = amounts.as_ref().map(|amounts| amounts.str()).transpose()?;
+        let mut engine = Engine::new();
+        for row in 0..df.height() {
+            let (Some(tx_type), Some(client), Some(tx)) = (types.get(row), clients.get(row), txs.get(row)) else {
+                polars_bail!(ComputeError: "row {} has no type, client or tx", row);
+            };
+            let amount = match amounts.and_then(|amounts| amounts.get(row)) {
+                Some(amount) => match Decimal::from_str(amount).or_else(|_| Decimal::from_scientific(amount)) {
+                    Ok(amount) => match amount::representable(amount) {
+                        Ok(()) => Some(amount),
+                        Err(detail) => polars_bail!(ComputeError: "row {}: {}", row, detail),
+                    },
+                    Err(err) => polars_bail!(ComputeError: "row {} has an invalid amount {}: {}", row, amount, err),
+                },
+                None => None,
+            };
+            let tx_type = TxType::deserialize(IntoDeserializer::<Error>::into_deserializer(tx_type)).unwrap_or_default();
+            if matches!(tx_type, TxType::Deposit | TxType::Withdrawal | TxType::Escrow) && amount.is_none() {
+                polars_bail!(ComputeError: "row {} is a {:?} without amount", row, tx_type);
+            }
+            engine.apply(Transaction { tx_type, client, tx, amount, ..Default::default() });
+        }
+        Ok(engine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_transactions_df_to_accounts_df() {
+        let df = df!(
+            "type" => ["deposit", "deposit", "withdrawal", "dispute", "deposit"],
+            "client" => [1i64, 2, 1, 2, 1],
+            "tx" => [1i64, 2, 3, 2, 4],
+            "amount" => [Some(1.5), Some(2.0), Some(0.25), None, Some(0.0001)],
+        )
+        .unwrap();
+        let accounts = Engine::from_transactions_df(&df).unwrap().accounts_df().unwrap();
+        assert_eq!(accounts.get_column_names(), ["client", "available", "held", "total", "locked"]);
+        assert_eq!(accounts.column("client").unwrap().u16().unwrap().to_vec(), [Some(1), Some(2)]);
+        let available = accounts.column("available").unwrap().cast(&DataType::String).unwrap();
+        assert_eq!(available.str().unwrap().get(0), Some("1.2501"));
+        assert_eq!(available.str().unwrap().get(1), Some("0.0000"));
+        let held = accounts.column("held").unwrap().cast(&DataType::String).unwrap();
+        assert_eq!(Decimal::from_str(held.str().unwrap().get(1).unwrap()).unwrap(), dec!(2.0));
+        assert_eq!(accounts.column("locked").unwrap().bool().unwrap().get(0), Some(false));
+
+        let missing = df!("type" => ["deposit"], "client" => [1i64]).unwrap();
+        assert!(Engine::from_transactions_df(&missing).is_err());
+        let overflow = df!("type" => ["deposit"], "client" => [70000i64], "tx" => [1i64]).unwrap();
+        assert!(Engine::from_transactions_df(&overflow).is_err());
+    }
+}
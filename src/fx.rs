@@ -0,0 +1,246 @@
+use crate::engine::{Account, Applied, Decision, Engine, Rejection, Transaction, TxType};
+use crate::kyc::Kyc;
+use crate::setup::{self, Setup};
+use rust_decimal::Decimal;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
+use std::rc::Rc;
+
+/// decimal places of a converted amount, those of the fixed-point amounts
+const SCALE: u32 = 4;
+
+/// The `[fx]` table of the config file
+#[derive(serde::Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FxConfig {
+    /// currency of the rows without a `currency` column
+    pub base: Option<String>,
+    /// fraction of a converted amount kept as the fee, e.g. `0.002`
+    pub spread: Decimal,
+    /// units of the second currency for one of the first, keyed `"EUR/USD"`
+    pub rates: BTreeMap<String, Decimal>,
+}
+
+/// A rate of the rates file, `from,to,rate`
+#[derive(serde::Deserialize)]
+struct Row {
+    from: String,
+    to: String,
+    rate: Decimal,
+}
+
+/// Exchange rates between pairs of currencies, a pair given one way is
+/// used the other way with the inverse rate
+#[derive(Debug, Clone, Default)]
+pub struct Rates {
+    rates: BTreeMap<(String, String), Decimal>,
+    spread: Decimal,
+}
+
+impl Rates {
+    pub fn new(config: &FxConfig) -> Rates {
+        let mut rates = Rates { rates: BTreeMap::new(), spread: config.spread };
+        for (pair, rate) in &config.rates {
+            let res = pair.split_once('/');
+            assert!(res.is_some(), "exchange rate `{}` is not keyed FROM/TO", pair);
+            let (from, to) = res.unwrap();
+            rates.insert(from, to, *rate);
+        }
+        rates
+    }
+
+    /// add the rates of a csv file with the `from`, `to` and `rate` columns, replacing those of the same pairs
+    pub fn read<R: std::io::Read>(&mut self, mut rdr: csv::Reader<R>) {
+        for res in rdr.deserialize() {
+            let res: Result<Row, _> = res;
+            assert!(res.is_ok(), "error in parsing an exchange rate: {:?}", res.err());
+            let row = res.unwrap();
+            self.insert(&row.from, &row.to, row.rate);
+        }
+    }
+
+    fn insert(&mut self, from: &str, to: &str, rate: Decimal) {
+        assert!(rate > Decimal::ZERO, "exchange rate {}/{} is not positive", from, to);
+        self.rates.insert((from.to_string(), to.to_string()), rate);
+    }
+
+    /// units of `to` for one unit of `from`
+    pub fn rate(&self, from: &str, to: &str) -> Option<Decimal> {
+        let pair = |from: &str, to: &str| self.rates.get(&(from.to_string(), to.to_string())).copied();
+        pair(from, to).or_else(|| pair(to, from).map(|rate| Decimal::ONE / rate))
+    }
+}
+
+/// Rate and fee of a conversion, on both of its legs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conversion {
+    pub rate: Decimal,
+    pub spread: Decimal,
+}
+
+/// A transaction applied to the balance of a client in one currency
+pub struct Event<'a> {
+    pub currency: &'a str,
+    pub tx: &'a Transaction,
+    pub account: &'a Account,
+    pub decision: Decision,
+    /// of both legs of a conversion, typed `Convert`, with the amount of each
+    pub conversion: Option<Conversion>,
+}
+
+type Observer = Rc<RefCell<dyn FnMut(&Event)>>;
+
+/// Balances of the clients kept per currency, each currency an engine of its
+/// own, as `Wallets`. A conversion withdraws its amount from the `currency`
+/// balance and deposits it in the `to_currency` one at the rate of the pair,
+/// less the spread, rounded to 4 decimal places; both legs have the tx id of
+/// the conversion, and only the withdrawal can be disputed or reversed.
+pub struct Currencies {
+    engines: BTreeMap<String, Engine>,
+    rates: Rates,
+    /// currency of the rows without one
+    base: Option<String>,
+    /// (currency, tx) of the deposits made by conversions
+    received: HashSet<(String, u32)>,
+    observers: Vec<Observer>,
+}
+
+impl Currencies {
+    pub fn new(config: &FxConfig, rates: Rates) -> Currencies {
+        Currencies { engines: BTreeMap::new(), rates, base: config.base.clone(), received: HashSet::new(), observers: Vec::new() }
+    }
+
+    /// callback invoked after each transaction and each leg of a conversion
+    pub fn add_observer(&mut self, observer: impl FnMut(&Event) + 'static) {
+        self.observers.push(Rc::new(RefCell::new(observer)));
+    }
+
+    /// currency of `tx`, the base currency when it has none
+    pub fn currency(&self, tx: &Transaction) -> Option<String> {
+        tx.currency.clone().or_else(|| self.base.clone())
+    }
+
+    fn engine(&mut self, currency: &str) -> &mut Engine {
+        if !self.engines.contains_key(currency) {
+            self.engines.insert(currency.to_string(), Engine::new());
+        }
+        self.engines.get_mut(currency).unwrap()
+    }
+
+    /// apply `tx` to the engine of `currency` and tell the observers, as `shown` when given
+    fn apply(&mut self, currency: &str, tx: Transaction, shown: Option<(&Transaction, Conversion)>) -> Result<Applied, Rejection> {
+        let res = self.engine(currency).process(tx.clone());
+        match shown {
+            Some((shown, conversion)) => self.notify(currency, shown, Decision::from(&res), Some(conversion)),
+            None => self.notify(currency, &tx, Decision::from(&res), None),
+        }
+        res
+    }
+
+    fn notify(&mut self, currency: &str, tx: &Transaction, decision: Decision, conversion: Option<Conversion>) {
+        let client = tx.client;
+        let account = self.engine(currency).account(client).cloned().unwrap_or_else(|| Account::new(client));
+        let event = Event { currency, tx, account: &account, decision, conversion };
+        for observer in &self.observers {
+            (observer.borrow_mut())(&event);
+        }
+    }
+
+    /// Apply a transaction to the balance of its currency, a conversion to
+    /// both. A row without a currency is in the base one.
+    pub fn process(&mut self, tx: Transaction) -> Result<Applied, Rejection> {
+        let currency = self.currency(&tx).unwrap_or_default();
+        let referencing = matches!(tx.tx_type, TxType::Dispute | TxType::Resolve | TxType::ChargeBack | TxType::Reversal);
+        if referencing && self.received.contains(&(currency.clone(), tx.tx)) {
+            self.notify(&currency, &tx, Decision::Ignored, None);
+            return Err(Rejection::MoveLeg);
+        }
+        if tx.tx_type != TxType::Convert {
+            return self.apply(&currency, tx, None);
+        }
+        let rate = tx.to_currency.as_deref().and_then(|to_currency| self.rates.rate(&currency, to_currency));
+        let (Some(to_currency), Some(rate)) = (tx.to_currency.clone(), rate) else {
+            self.notify(&currency, &tx, Decision::Ignored, None);
+            return Err(if tx.to_currency.is_none() { Rejection::UnsupportedType } else { Rejection::NoRate });
+        };
+        let conversion = Conversion { rate, spread: self.rates.spread };
+        let withdrawal = Transaction { tx_type: TxType::Withdrawal, ..tx.clone() };
+        let applied = self.apply(&currency, withdrawal, Some((&tx, conversion)))?;
+        let converted = (tx.amount.unwrap_or_default() * rate * (Decimal::ONE - conversion.spread)).round_dp(SCALE);
+        let deposit = Transaction { tx_type: TxType::Deposit, amount: Some(converted), currency: Some(to_currency.clone()), ..tx.clone() };
+        let shown = Transaction { amount: Some(converted), currency: Some(to_currency.clone()), ..tx };
+        let id = deposit.tx;
+        self.apply(&to_currency, deposit, Some((&shown, conversion)))?;
+        self.received.insert((to_currency, id));
+        Ok(applied)
+    }
+
+    /// Open the account of `setup` in its currency, the base one when it has
+    /// none; observers see its `Opening` transaction as with an engine
+    pub fn open(&mut self, kyc: Option<&mut Kyc>, setup: Setup) {
+        let currency = setup.currency.clone().or_else(|| self.base.clone()).unwrap_or_default();
+        let opening = Transaction { client: setup.client, tx_type: TxType::Opening, amount: Some(setup.opening.unwrap_or_default()), ..Default::default() };
+        setup::open(self.engine(&currency), kyc, vec![setup]);
+        self.notify(&currency, &opening, Decision::Applied, None);
+    }
+
+    /// accounts of every currency, with it as their `currency`, sorted by client then currency
+    pub fn into_accounts(self) -> impl Iterator<Item = Account> {
+        let mut accounts: Vec<Account> = self
+            .engines
+            .into_iter()
+            .flat_map(|(currency, engine)| {
+                engine.into_accounts().map(move |mut account| {
+                    account.currency = Some(currency.clone());
+                    account
+                })
+            })
+            .collect();
+        accounts.sort_by(|a, b| (a.client, &a.currency).cmp(&(b.client, &b.currency)));
+        accounts.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_conversions_between_currencies() {
+        let config: FxConfig = toml::from_str("base = \"EUR\"\nspread = 0.01\n[rates]\n\"EUR/USD\" = 1.2\n").unwrap();
+        let mut rates = Rates::new(&config);
+        rates.read(csv::Reader::from_reader("from,to,rate\nGBP,EUR,1.25\n".as_bytes()));
+        assert_eq!(rates.rate("USD", "EUR").map(|rate| rate.round_dp(6)), Some(dec!(0.833333)));
+        let mut currencies = Currencies::new(&config, rates);
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let log = events.clone();
+        currencies.add_observer(move |event: &Event| {
+            log.borrow_mut().push((event.currency.to_string(), event.tx.tx_type, event.tx.amount, event.conversion));
+        });
+        let tx = |tx_type, tx, amount, currency: Option<&str>, to_currency: Option<&str>| Transaction {
+            client: 1,
+            tx_type,
+            tx,
+            amount,
+            currency: currency.map(str::to_string),
+            to_currency: to_currency.map(str::to_string),
+            ..Default::default()
+        };
+        assert!(currencies.process(tx(TxType::Deposit, 1, Some(dec!(100)), None, None)).is_ok());
+        assert!(currencies.process(tx(TxType::Convert, 2, Some(dec!(10)), None, Some("USD"))).is_ok());
+        assert_eq!(currencies.process(tx(TxType::Convert, 3, Some(dec!(1)), None, Some("JPY"))), Err(Rejection::NoRate));
+        assert_eq!(currencies.process(tx(TxType::Convert, 4, Some(dec!(500)), None, Some("USD"))), Err(Rejection::InsufficientFunds));
+        assert_eq!(currencies.process(tx(TxType::Dispute, 2, None, Some("USD"), None)), Err(Rejection::MoveLeg));
+        assert!(currencies.process(tx(TxType::Withdrawal, 5, Some(dec!(1.88)), Some("USD"), None)).is_ok());
+
+        let conversion = Some(Conversion { rate: dec!(1.2), spread: dec!(0.01) });
+        let events = events.borrow();
+        assert_eq!(events[1], ("EUR".to_string(), TxType::Convert, Some(dec!(10)), conversion));
+        assert_eq!(events[2], ("USD".to_string(), TxType::Convert, Some(dec!(11.88)), conversion));
+        let accounts: Vec<(Option<String>, Decimal)> =
+            currencies.into_accounts().map(|account| (account.currency, amount::to_decimal(account.available))).collect();
+        assert_eq!(accounts, [(Some("EUR".to_string()), dec!(90)), (Some("USD".to_string()), dec!(10.00))]);
+    }
+}
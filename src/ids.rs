@@ -0,0 +1,103 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// Internal client ids of external customer ids, e.g. UUIDs. Unknown
+/// external ids get the next free client id as they are read.
+#[derive(Debug, Default)]
+pub struct ExternalIds {
+    clients: HashMap<String, u16>,
+    external: BTreeMap<u16, String>,
+}
+
+/// mapping shared by the readers of all the input files
+pub type SharedIds = Arc<Mutex<ExternalIds>>;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Row {
+    external: String,
+    client: u16,
+}
+
+impl ExternalIds {
+    /// mapping of a csv file with `external` and `client` columns, empty if the file does not exist yet
+    pub fn load(path: &str) -> ExternalIds {
+        let mut ids = ExternalIds::default();
+        if !std::path::Path::new(path).exists() {
+            return ids;
+        }
+        let res = csv::Reader::from_path(path);
+        assert!(res.is_ok(), "cannot read client id mapping {}: {:?}", path, res.err());
+        for res in res.unwrap().deserialize::<Row>() {
+            assert!(res.is_ok(), "invalid client id mapping {}: {:?}", path, res.err());
+            let row = res.unwrap();
+            ids.insert(row.external, row.client);
+        }
+        ids
+    }
+
+    fn insert(&mut self, external: String, client: u16) {
+        assert!(!self.external.contains_key(&client), "client {} is mapped twice", client);
+        self.external.insert(client, external.clone());
+        // a forgotten client keeps its id, for it not to be given to another one
+        if !external.is_empty() {
+            self.clients.insert(external, client);
+        }
+    }
+
+    /// drop the external id of `client`, returning it; the client id stays taken
+    pub fn forget(&mut self, client: u16) -> Option<String> {
+        let external = self.external.get_mut(&client).filter(|external| !external.is_empty())?;
+        let external = std::mem::take(external);
+        self.clients.remove(&external);
+        Some(external)
+    }
+
+    /// client id of an external id, a new one for an id not seen before
+    pub fn client(&mut self, external: &str) -> u16 {
+        if let Some(client) = self.clients.get(external) {
+            return *client;
+        }
+        let next = self.external.last_key_value().map_or(Some(1), |(client, _)| client.checked_add(1));
+        assert!(next.is_some(), "no client id left for external id {}", external);
+        self.insert(external.to_string(), next.unwrap());
+        next.unwrap()
+    }
+
+    pub fn external(&self, client: u16) -> Option<&str> {
+        self.external.get(&client).map(String::as_str).filter(|external| !external.is_empty())
+    }
+
+    /// write the mapping, with the ids created by this run, back to `path`
+    pub fn save(&self, path: &str) {
+        let res = csv::Writer::from_path(path);
+        assert!(res.is_ok(), "cannot write client id mapping {}: {:?}", path, res.err());
+        let mut wrt = res.unwrap();
+        for (client, external) in &self.external {
+            let res = wrt.serialize(Row { external: external.clone(), client: *client });
+            assert!(res.is_ok(), "error in writing the client id mapping");
+        }
+        let res = wrt.flush();
+        assert!(res.is_ok(), "error in writing the client id mapping");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_created_on_the_fly() {
+        let mut ids = ExternalIds::default();
+        ids.insert("2f6c2b0e-aaaa".to_string(), 7);
+        assert_eq!(ids.client("2f6c2b0e-aaaa"), 7);
+        assert_eq!(ids.client("91d0c4a2-bbbb"), 8);
+        assert_eq!(ids.client("91d0c4a2-bbbb"), 8);
+        assert_eq!(ids.external(8), Some("91d0c4a2-bbbb"));
+        assert_eq!(ids.external(9), None);
+        assert_eq!(ids.forget(7).as_deref(), Some("2f6c2b0e-aaaa"));
+        assert_eq!(ids.external(7), None);
+        assert_eq!(ids.forget(7), None);
+        assert_eq!(ids.client("2f6c2b0e-aaaa"), 9);
+        assert_eq!(ids.client("4b1e9d3c-cccc"), 10);
+    }
+}
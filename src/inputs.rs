@@ -0,0 +1,235 @@
+use glob::Pattern;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+use std::fs::File;
+use std::io::{self, Read};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring;
+
+// items are handed over from the workers in batches to keep channel overhead low
+const BATCH: usize = 1024;
+/// batches buffered per source by default, bounding memory when workers run ahead of the consumer
+pub const DEPTH: usize = 16;
+
+/// Input paths in the given order, glob patterns expanded in alphabetical order
+/// so that daily splits like `txs-*.csv` are read oldest first.
+pub fn expand(args: &[String]) -> Vec<String> {
+    let mut paths = Vec::new();
+    for arg in args {
+        // plain paths are kept as they are, a missing file is reported when opened
+        if Pattern::escape(arg) == *arg {
+            paths.push(arg.clone());
+            continue;
+        }
+        let res = glob::glob(arg);
+        assert!(res.is_ok(), "invalid input pattern {}: {:?}", arg, res.err());
+        let matches: Vec<String> = res
+            .unwrap()
+            .filter_map(Result::ok)
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        assert!(!matches.is_empty(), "no input file matches {}", arg);
+        paths.extend(matches);
+    }
+    paths
+}
+
+/// Items of every source in source order, then item order, as if read one after
+/// the other, while up to `jobs` worker threads read the following sources ahead.
+/// Workers take sources in increasing order, so the one being consumed is always
+/// being read. Up to `depth` batches of a source wait for the consumer, a
+/// worker blocks once they are full. A panic in a worker is propagated to the
+/// consumer.
+pub fn ordered<S, T, I, F>(sources: Vec<S>, jobs: usize, depth: usize, read: F) -> impl Iterator<Item = T>
+where
+    S: Send + 'static,
+    T: Send + 'static,
+    I: Iterator<Item = T>,
+    F: Fn(S) -> I + Send + Sync + 'static,
+{
+    let mut receivers: Vec<Receiver<Option<Vec<T>>>> = Vec::new();
+    let mut queue: Vec<(S, SyncSender<Option<Vec<T>>>)> = Vec::new();
+    for source in sources {
+        let (tx, rx) = mpsc::sync_channel(depth);
+        receivers.push(rx);
+        queue.push((source, tx));
+    }
+    let queue = Arc::new(Mutex::new(queue.into_iter()));
+    let read = Arc::new(read);
+    for _ in 0..jobs.max(1) {
+        let queue = Arc::clone(&queue);
+        let read = Arc::clone(&read);
+        thread::spawn(move || loop {
+            let next = queue.lock().map(|mut queue| queue.next());
+            let Ok(Some((source, tx))) = next else {
+                return;
+            };
+            let mut items = read(source);
+            loop {
+                let batch: Vec<T> = items.by_ref().take(BATCH).collect();
+                if batch.is_empty() || tx.send(Some(batch)).is_err() {
+                    break;
+                }
+            }
+            // the end marker tells a finished source apart from a panicked worker
+            let _ = tx.send(None);
+        });
+    }
+    receivers.into_iter().enumerate().flat_map(|(idx, rx)| {
+        std::iter::from_fn(move || {
+            let res = rx.recv();
+            assert!(res.is_ok(), "worker reading input {} failed", idx + 1);
+            res.unwrap()
+        })
+        .flatten()
+    })
+}
+
+/// size of the reads of `ReadAhead`, large enough for sequential disk reads
+pub const BLOCK: usize = 1 << 20;
+
+/// Reader of a file read ahead in `BLOCK`s by a thread of its own, up to a
+/// number of blocks, so that the parser does not wait on slow storage. An
+/// IO error is returned where it happened in the stream.
+pub struct ReadAhead {
+    rx: Receiver<io::Result<Vec<u8>>>,
+    block: Vec<u8>,
+    pos: usize,
+}
+
+impl ReadAhead {
+    pub fn new<R: Read + Send + 'static>(mut input: R, blocks: usize) -> ReadAhead {
+        let (tx, rx) = mpsc::sync_channel(blocks);
+        thread::spawn(move || loop {
+            let mut block = vec![0; BLOCK];
+            let res = input.read(&mut block).map(|len| {
+                block.truncate(len);
+                block
+            });
+            let end = res.as_ref().map_or(true, Vec::is_empty);
+            // a dropped reader stops the thread
+            if tx.send(res).is_err() || end {
+                return;
+            }
+        });
+        ReadAhead { rx, block: Vec::new(), pos: 0 }
+    }
+
+    /// Reader of a whole file read ahead through io_uring, the reads of the
+    /// `blocks` ahead submitted at once so that storage serving several
+    /// requests together, as disk arrays and network volumes do, is kept
+    /// busy. The file is read as long as it was when opened. Without
+    /// io_uring, in a container forbidding it, or for a pipe, the file is
+    /// read as by `new`.
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    pub fn file(file: File, blocks: usize) -> ReadAhead {
+        let depth = blocks.clamp(1, uring::MAX_DEPTH);
+        let size = match file.metadata() {
+            Ok(meta) if meta.is_file() => meta.len(),
+            _ => return ReadAhead::new(file, blocks),
+        };
+        let Ok(ring) = io_uring::IoUring::new(depth.next_power_of_two() as u32) else {
+            return ReadAhead::new(file, blocks);
+        };
+        let (tx, rx) = mpsc::sync_channel(1);
+        thread::spawn(move || uring::read(ring, file, size, depth, tx));
+        ReadAhead { rx, block: Vec::new(), pos: 0 }
+    }
+}
+
+impl Read for ReadAhead {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.block.len() {
+            // a closed channel is the end of the input
+            match self.rx.recv() {
+                Ok(block) => self.block = block?,
+                Err(_) => return Ok(0),
+            }
+            self.pos = 0;
+        }
+        let len = buf.len().min(self.block.len() - self.pos);
+        buf[..len].copy_from_slice(&self.block[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_expand_keeps_order() {
+        let dir = std::env::temp_dir().join(format!("inputs-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["txs-02.csv", "txs-01.csv", "other.csv"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let dir = dir.to_string_lossy();
+        let args = vec![format!("{}/other.csv", dir), format!("{}/txs-*.csv", dir), "missing.csv".to_string()];
+        let paths = expand(&args);
+        fs::remove_dir_all(&*dir).unwrap();
+        assert_eq!(
+            paths,
+            vec![
+                format!("{}/other.csv", dir),
+                format!("{}/txs-01.csv", dir),
+                format!("{}/txs-02.csv", dir),
+                "missing.csv".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ordered_merge() {
+        let sources: Vec<u32> = vec![3000, 0, 5, 2500, 1];
+        let expected: Vec<(u32, u32)> = sources.iter().flat_map(|&n| (0..n).map(move |i| (n, i))).collect();
+        let merged: Vec<(u32, u32)> = ordered(sources, 3, DEPTH, |n| (0..n).map(move |i| (n, i))).collect();
+        assert_eq!(merged, expected);
+    }
+
+    #[test]
+    fn test_read_ahead() {
+        let data: Vec<u8> = (0..3 * BLOCK + 17).map(|i| (i % 251) as u8).collect();
+        let mut read = Vec::new();
+        ReadAhead::new(io::Cursor::new(data.clone()), 2).read_to_end(&mut read).unwrap();
+        assert_eq!(read, data);
+    }
+
+    #[test]
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    fn test_read_ahead_through_io_uring() {
+        let path = std::env::temp_dir().join(format!("uring-{}", std::process::id()));
+        let data: Vec<u8> = (0..5 * BLOCK + 17).map(|i| (i % 251) as u8).collect();
+        for (len, blocks) in [(data.len(), 2), (data.len(), 64), (BLOCK, 1), (0, 4)] {
+            fs::write(&path, &data[..len]).unwrap();
+            let mut read = Vec::new();
+            ReadAhead::file(fs::File::open(&path).unwrap(), blocks).read_to_end(&mut read).unwrap();
+            assert_eq!(read, data[..len]);
+        }
+        // a reader dropped before the end stops the reads
+        fs::write(&path, &data).unwrap();
+        let mut first = [0; 10];
+        ReadAhead::file(fs::File::open(&path).unwrap(), 2).read_exact(&mut first).unwrap();
+        assert_eq!(first, data[..10]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_single_worker_pipeline() {
+        // one source read on a worker while the consumer holds a single batch
+        let merged: Vec<u32> = ordered(vec![5000], 1, 1, |n| 0..n).collect();
+        assert_eq!(merged, (0..5000).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    #[should_panic(expected = "worker reading input 2 failed")]
+    fn test_ordered_merge_propagates_panic() {
+        let read = |n: u32| (0..3).map(move |i| if n == 1 && i == 2 { panic!("bad record") } else { i });
+        ordered(vec![0, 1, 2], 2, DEPTH, read).for_each(drop);
+    }
+}
@@ -0,0 +1,95 @@
+use super::BLOCK;
+use io_uring::{opcode, types, IoUring};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::os::fd::AsRawFd;
+use std::sync::mpsc::SyncSender;
+
+/// most reads in flight on a ring
+pub(super) const MAX_DEPTH: usize = 4096;
+
+/// A block of the file, filled by one read or more
+struct Block {
+    buf: Vec<u8>,
+    filled: usize,
+    res: Option<io::Result<()>>,
+}
+
+/// Read the `size` bytes of `file` on `ring` in `BLOCK`s, with the reads of
+/// up to `depth` of them in flight at once, and send the blocks in order. A
+/// read returning less than asked is completed by another; a file truncated
+/// meanwhile ends at its new size.
+pub(super) fn read(mut ring: IoUring, file: File, size: u64, depth: usize, tx: SyncSender<io::Result<Vec<u8>>>) {
+    let fd = types::Fd(file.as_raw_fd());
+    let mut blocks = size.div_ceil(BLOCK as u64);
+    let mut pending: HashMap<u64, Block> = HashMap::new();
+    // next block to send, and to read
+    let (mut next, mut submitted) = (0, 0);
+    let mut in_flight = 0;
+    let submit = |ring: &mut IoUring, idx: u64, block: &mut Block| {
+        let rest = &mut block.buf[block.filled..];
+        let read = opcode::Read::new(fd, rest.as_mut_ptr(), rest.len() as u32).offset(idx * BLOCK as u64 + block.filled as u64);
+        // safety: the buffer stays in `pending` until its read completes, the
+        // reads in flight are waited for before returning
+        let res = unsafe { ring.submission().push(&read.build().user_data(idx)) };
+        assert!(res.is_ok(), "io_uring submission queue full");
+    };
+    'run: while next < blocks {
+        while submitted < blocks && pending.len() < depth {
+            let len = (size - submitted * BLOCK as u64).min(BLOCK as u64) as usize;
+            let block = pending.entry(submitted).or_insert(Block { buf: vec![0; len], filled: 0, res: None });
+            submit(&mut ring, submitted, block);
+            submitted += 1;
+            in_flight += 1;
+        }
+        if let Err(err) = ring.submit_and_wait(1) {
+            let _ = tx.send(Err(err));
+            break;
+        }
+        let completed: Vec<(u64, i32)> = ring.completion().map(|entry| (entry.user_data(), entry.result())).collect();
+        for (idx, res) in completed {
+            in_flight -= 1;
+            let block = pending.get_mut(&idx).expect("read of a pending block");
+            if res < 0 {
+                let err = io::Error::from_raw_os_error(-res);
+                if matches!(err.kind(), ErrorKind::Interrupted | ErrorKind::WouldBlock) {
+                    submit(&mut ring, idx, block);
+                    in_flight += 1;
+                } else {
+                    block.res = Some(Err(err));
+                }
+            } else if res == 0 {
+                // the end of a truncated file
+                block.buf.truncate(block.filled);
+                block.res = Some(Ok(()));
+                blocks = blocks.min(idx + 1);
+            } else {
+                block.filled += res as usize;
+                if block.filled < block.buf.len() {
+                    submit(&mut ring, idx, block);
+                    in_flight += 1;
+                } else {
+                    block.res = Some(Ok(()));
+                }
+            }
+        }
+        while next < blocks && pending.get(&next).is_some_and(|block| block.res.is_some()) {
+            let block = pending.remove(&next).unwrap();
+            let failed = block.res.as_ref().is_some_and(Result::is_err);
+            // a dropped reader stops the reads
+            if tx.send(block.res.unwrap().map(|()| block.buf)).is_err() || failed {
+                break 'run;
+            }
+            next += 1;
+        }
+    }
+    while in_flight > 0 {
+        if ring.submit_and_wait(in_flight).is_err() {
+            // the buffers of reads that may still complete are leaked rather than freed
+            std::mem::forget(pending);
+            return;
+        }
+        in_flight -= ring.completion().count();
+    }
+}
@@ -0,0 +1,77 @@
+use crate::engine::Transaction;
+use std::collections::{BTreeMap, HashMap};
+
+/// Several clients sharing one account, a `[[joint]]` table of the config file
+#[derive(serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Joint {
+    /// client id the shared account is reported under
+    pub account: u16,
+    pub owners: Vec<u16>,
+}
+
+/// Maps the transactions of the owners of joint accounts to the shared account
+#[derive(Debug, Clone, Default)]
+pub struct JointAccounts {
+    // owner --> account
+    accounts: HashMap<u16, u16>,
+    // account --> owners
+    owners: BTreeMap<u16, Vec<u16>>,
+}
+
+impl JointAccounts {
+    pub fn new(joints: &[Joint]) -> JointAccounts {
+        let mut joint_accounts = JointAccounts::default();
+        for joint in joints {
+            for owner in &joint.owners {
+                let previous = joint_accounts.accounts.insert(*owner, joint.account);
+                assert!(previous.is_none(), "client {} owns more than one joint account", owner);
+            }
+            let mut owners = joint.owners.clone();
+            owners.sort_unstable();
+            joint_accounts.owners.insert(joint.account, owners);
+        }
+        joint_accounts
+    }
+
+    /// account the transactions of `client` apply to
+    pub fn account(&self, client: u16) -> u16 {
+        self.accounts.get(&client).copied().unwrap_or(client)
+    }
+
+    /// `tx` made on the account of its client
+    pub fn map(&self, tx: Transaction) -> Transaction {
+        Transaction { client: self.account(tx.client), ..tx }
+    }
+
+    /// owners of an account, the client alone for an account that is not joint
+    pub fn owners(&self, account: u16) -> Vec<u16> {
+        self.owners.get(&account).cloned().unwrap_or_else(|| vec![account])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Engine, TxType};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_owners_share_the_balance() {
+        let joint = JointAccounts::new(&[Joint { account: 100, owners: vec![2, 1] }]);
+        let tx = |client, tx_type, tx, amount| Transaction{client, tx_type, tx, amount, ..Default::default()};
+        let mut engine = Engine::new();
+        engine.apply(joint.map(tx(1, TxType::Deposit, 1, Some(dec!(5.0)))));
+        engine.apply(joint.map(tx(2, TxType::Withdrawal, 2, Some(dec!(2.0)))));
+        engine.apply(joint.map(tx(2, TxType::Dispute, 1, None)));
+        engine.apply(joint.map(tx(3, TxType::Deposit, 3, Some(dec!(1.0)))));
+        let mut accounts: Vec<_> = engine.into_accounts().collect();
+        accounts.sort_by_key(|account| account.client);
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[1].client, 100);
+        assert_eq!(accounts[1].held, dec!(5.0));
+        assert_eq!(accounts[1].total, dec!(3.0));
+        assert_eq!(joint.owners(100), vec![1, 2]);
+        assert_eq!(joint.owners(3), vec![3]);
+    }
+}
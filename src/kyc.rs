@@ -0,0 +1,114 @@
+use crate::engine::{Transaction, TxType};
+use crate::rejection::{Reason, Rejected};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+/// Verification level of a client, unlisted clients are unverified
+#[derive(serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    #[default]
+    Unverified,
+    Basic,
+    Verified,
+}
+
+/// Largest deposit and withdrawal amounts allowed at a level, no cap when unset;
+/// the deposit cap applies to every transaction bringing funds in, escrows included
+#[derive(serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Caps {
+    pub deposit: Option<Decimal>,
+    pub withdrawal: Option<Decimal>,
+}
+
+/// The `[kyc]` table of the config file, with `[kyc.unverified]` and the like for the caps
+#[derive(serde::Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct KycConfig {
+    /// csv file with the `client` and `level` columns
+    pub levels: Option<String>,
+    pub unverified: Caps,
+    pub basic: Caps,
+    pub verified: Caps,
+}
+
+#[derive(serde::Deserialize)]
+struct Row {
+    client: u16,
+    level: Level,
+}
+
+/// Rejects deposits, escrows and withdrawals above the cap of the client's KYC level
+#[derive(Debug, Clone, Default)]
+pub struct Kyc {
+    levels: HashMap<u16, Level>,
+    config: KycConfig,
+}
+
+impl Kyc {
+    pub fn new(config: &KycConfig) -> Kyc {
+        let mut levels = HashMap::new();
+        if let Some(path) = &config.levels {
+            let res = csv::Reader::from_path(path);
+            assert!(res.is_ok(), "cannot read kyc levels {}: {:?}", path, res.err());
+            for res in res.unwrap().deserialize::<Row>() {
+                assert!(res.is_ok(), "invalid kyc levels {}: {:?}", path, res.err());
+                let row = res.unwrap();
+                levels.insert(row.client, row.level);
+            }
+        }
+        Kyc { levels, config: config.clone() }
+    }
+
+    /// level of a client set up with the account, see `setup::open`
+    pub fn set_level(&mut self, client: u16, level: Level) {
+        self.levels.insert(client, level);
+    }
+
+    pub fn level(&self, client: u16) -> Level {
+        self.levels.get(&client).copied().unwrap_or_default()
+    }
+
+    /// the transaction or its rejection when it exceeds the cap
+    pub fn check(&self, tx: Transaction) -> Result<Transaction, Rejected> {
+        let level = self.level(tx.client);
+        let caps = match level {
+            Level::Unverified => self.config.unverified,
+            Level::Basic => self.config.basic,
+            Level::Verified => self.config.verified,
+        };
+        let cap = match tx.tx_type {
+            TxType::Deposit | TxType::Escrow => caps.deposit,
+            TxType::Withdrawal => caps.withdrawal,
+            _ => None,
+        };
+        match (cap, tx.amount) {
+            (Some(cap), Some(amount)) if amount > cap => {
+                let detail = format!("{:?} of {} above the cap of {} for {:?} clients", tx.tx_type, amount, cap, level);
+                Err(Rejected::new(Reason::Kyc, Some(tx.client), Some(tx.tx), detail.to_lowercase()))
+            }
+            _ => Ok(tx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_caps_by_level() {
+        let config: KycConfig = toml::from_str("[unverified]\ndeposit = \"100\"\nwithdrawal = \"50\"\n").unwrap();
+        let mut kyc = Kyc::new(&config);
+        kyc.levels.insert(2, Level::Verified);
+        let tx = |client, tx_type, amount| Transaction { client, tx_type, tx: 1, amount, ..Default::default() };
+        assert!(kyc.check(tx(1, TxType::Deposit, Some(dec!(100.0)))).is_ok());
+        assert!(kyc.check(tx(1, TxType::Dispute, None)).is_ok());
+        assert!(kyc.check(tx(1, TxType::Escrow, Some(dec!(100.01)))).is_err());
+        let rejected = kyc.check(tx(1, TxType::Withdrawal, Some(dec!(50.01)))).unwrap_err();
+        assert_eq!((rejected.reason, rejected.client), (Reason::Kyc, Some(1)));
+        assert!(kyc.check(tx(2, TxType::Deposit, Some(dec!(1000.0)))).is_ok());
+    }
+}
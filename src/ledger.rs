@@ -0,0 +1,238 @@
+use crate::amount::Amount;
+use crate::engine::{Account, Applied, Transaction};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+
+/// Account of the double-entry ledger, client funds are split in available and held
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LedgerAccount {
+    /// funds of a client that can be withdrawn
+    Available(u16),
+    /// funds of a client under dispute
+    Held(u16),
+    /// funds of a client in escrow
+    Escrow(u16),
+    /// money received by deposits and paid out by withdrawals
+    Cash,
+    /// disputed funds returned to the payer by chargebacks
+    ChargebackLoss,
+    /// balances brought in by accounts opened with one
+    OpeningBalance,
+}
+
+impl fmt::Display for LedgerAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedgerAccount::Available(client) => write!(f, "client:{}:available", client),
+            LedgerAccount::Held(client) => write!(f, "client:{}:held", client),
+            LedgerAccount::Escrow(client) => write!(f, "client:{}:escrow", client),
+            LedgerAccount::Cash => write!(f, "system:cash"),
+            LedgerAccount::ChargebackLoss => write!(f, "system:chargeback-loss"),
+            LedgerAccount::OpeningBalance => write!(f, "system:opening-balance"),
+        }
+    }
+}
+
+impl LedgerAccount {
+    /// code in the chart of accounts of the general ledger export
+    pub fn code(&self) -> String {
+        match self {
+            LedgerAccount::Cash => "1000".to_string(),
+            LedgerAccount::Available(client) => format!("2000-{:05}", client),
+            LedgerAccount::Held(client) => format!("2100-{:05}", client),
+            LedgerAccount::Escrow(client) => format!("2200-{:05}", client),
+            LedgerAccount::ChargebackLoss => "6000".to_string(),
+            LedgerAccount::OpeningBalance => "3000".to_string(),
+        }
+    }
+}
+
+/// Movement of `amount` from the `credit` account to the `debit` one
+#[derive(Debug, Clone)]
+pub struct Posting {
+    /// engine sequence number of the transaction
+    pub seq: u64,
+    pub tx: Transaction,
+    pub debit: LedgerAccount,
+    pub credit: LedgerAccount,
+    pub amount: Amount,
+}
+
+/// the posting of a balance change of `tx.client`
+pub fn posting(seq: u64, tx: &Transaction, applied: Applied) -> Posting {
+    let client = tx.client;
+    let (debit, credit, amount) = match applied {
+        Applied::Deposited(amount) => (LedgerAccount::Cash, LedgerAccount::Available(client), amount),
+        Applied::Withdrawn(amount) => (LedgerAccount::Available(client), LedgerAccount::Cash, amount),
+        Applied::Held(amount) => (LedgerAccount::Available(client), LedgerAccount::Held(client), amount),
+        Applied::Released(amount) => (LedgerAccount::Held(client), LedgerAccount::Available(client), amount),
+        Applied::ChargedBack(amount) => (LedgerAccount::Held(client), LedgerAccount::ChargebackLoss, amount),
+        Applied::Pending(amount) => (LedgerAccount::Available(client), LedgerAccount::Held(client), amount),
+        Applied::Settled(amount) => (LedgerAccount::Held(client), LedgerAccount::Cash, amount),
+        Applied::Escrowed(amount) => (LedgerAccount::Cash, LedgerAccount::Escrow(client), amount),
+        Applied::EscrowReleased(amount) => (LedgerAccount::Escrow(client), LedgerAccount::Available(client), amount),
+        Applied::DepositReversed(amount) => (LedgerAccount::Available(client), LedgerAccount::Cash, amount),
+        Applied::WithdrawalReversed(amount) => (LedgerAccount::Cash, LedgerAccount::Available(client), amount),
+        Applied::Opened(amount) => (LedgerAccount::OpeningBalance, LedgerAccount::Available(client), amount),
+    };
+    Posting { seq, tx: tx.clone(), debit, credit, amount }
+}
+
+/// Postings of every applied transaction, in apply order
+#[derive(Debug, Clone, Default)]
+pub struct Ledger {
+    postings: Vec<Posting>,
+}
+
+impl Ledger {
+    pub fn post(&mut self, posting: Posting) {
+        self.postings.push(posting);
+    }
+
+    pub fn postings(&self) -> &[Posting] {
+        &self.postings
+    }
+
+    /// drop the postings after sequence number `seq`, for rollbacks
+    pub fn truncate(&mut self, seq: u64) {
+        let end = self.postings.partition_point(|posting| posting.seq <= seq);
+        self.postings.truncate(end);
+    }
+
+    /// debits minus credits of every account
+    pub fn balances(&self) -> BTreeMap<LedgerAccount, Amount> {
+        let mut balances: BTreeMap<LedgerAccount, Amount> = BTreeMap::new();
+        for posting in &self.postings {
+            *balances.entry(posting.debit).or_default() += posting.amount;
+            *balances.entry(posting.credit).or_default() -= posting.amount;
+        }
+        balances
+    }
+
+    /// Sum of every account and the client accounts whose funds differ
+    /// from the ledger, the sum is always zero for a sound ledger.
+    pub fn trial_balance<'a>(&self, accounts: impl Iterator<Item = &'a Account>) -> TrialBalance {
+        let balances = self.balances();
+        let net = balances.values().fold(Amount::default(), |net, balance| net + *balance);
+        let funds = |account| balances.get(&account).map_or(Amount::default(), |balance| -*balance);
+        let mut mismatches: Vec<u16> = accounts
+            .filter(|account| {
+                funds(LedgerAccount::Available(account.client)) != account.available
+                    || funds(LedgerAccount::Held(account.client)) != account.held
+                    || funds(LedgerAccount::Escrow(account.client)) != account.escrow
+            })
+            .map(|account| account.client)
+            .collect();
+        mismatches.sort_unstable();
+        TrialBalance { balances, net, mismatches }
+    }
+}
+
+/// One line of a general ledger journal entry, a posting makes a debit and a credit line
+#[derive(serde::Serialize)]
+struct GlLine {
+    /// journal entry number, the engine sequence number
+    entry: u64,
+    date: String,
+    account: String,
+    debit: Option<Amount>,
+    credit: Option<Amount>,
+    memo: String,
+}
+
+/// Journal entries of the postings as csv for the accounting system,
+/// dated with the transaction timestamp when the input has one
+pub fn write_gl<W: Write>(postings: &[Posting], wrt: W) {
+    let mut wrt = csv::Writer::from_writer(wrt);
+    for posting in postings {
+        let tx = &posting.tx;
+        let date = tx.timestamp.map(|timestamp| timestamp.date().to_string()).unwrap_or_default();
+        let memo = format!("{:?} tx {} client {}", tx.tx_type, tx.tx, tx.client).to_lowercase();
+        let line = |account: LedgerAccount, debit, credit| GlLine {
+            entry: posting.seq,
+            date: date.clone(),
+            account: account.code(),
+            debit,
+            credit,
+            memo: memo.clone(),
+        };
+        let res = wrt
+            .serialize(line(posting.debit, Some(posting.amount), None))
+            .and_then(|_| wrt.serialize(line(posting.credit, None, Some(posting.amount))));
+        assert!(res.is_ok(), "error in writing output to stdout");
+    }
+}
+
+/// Result of `Ledger::trial_balance`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrialBalance {
+    /// debits minus credits, client funds are credit balances
+    pub balances: BTreeMap<LedgerAccount, Amount>,
+    pub net: Amount,
+    /// clients whose account funds do not match their ledger accounts
+    pub mismatches: Vec<u16>,
+}
+
+impl TrialBalance {
+    pub fn is_balanced(&self) -> bool {
+        self.net == Amount::default() && self.mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for TrialBalance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (account, balance) in &self.balances {
+            writeln!(f, "{:<28} {:>20}", account.to_string(), balance.to_string())?;
+        }
+        writeln!(f, "{:<28} {:>20}", "net", self.net.to_string())?;
+        for client in &self.mismatches {
+            writeln!(f, "client {} does not match its ledger accounts", client)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Engine, TxType};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_trial_balance_nets_to_zero() {
+        let tx = |client, tx_type, tx, amount| Transaction{client, tx_type, tx, amount, ..Default::default()};
+        let mut engine = Engine::new();
+        engine.enable_ledger();
+        engine.apply(tx(1, TxType::Deposit, 1, Some(dec!(5.0))));
+        engine.apply(tx(1, TxType::Withdrawal, 2, Some(dec!(1.5))));
+        engine.apply(tx(2, TxType::Deposit, 3, Some(dec!(2.0))));
+        engine.apply(tx(2, TxType::Dispute, 3, None));
+        engine.apply(tx(1, TxType::Dispute, 1, None));
+        engine.apply(tx(1, TxType::Resolve, 1, None));
+        engine.apply(tx(2, TxType::ChargeBack, 3, None));
+        engine.apply(tx(1, TxType::Withdrawal, 4, Some(dec!(100.0))));
+        let trial = engine.trial_balance();
+        assert!(trial.is_balanced(), "{}", trial);
+        assert_eq!(engine.ledger().unwrap().postings().len(), 7);
+        assert_eq!(trial.balances[&LedgerAccount::Cash], dec!(5.5));
+        assert_eq!(trial.balances[&LedgerAccount::ChargebackLoss], dec!(-2.0));
+        assert_eq!(trial.balances[&LedgerAccount::Available(1)], dec!(-3.5));
+    }
+
+    #[test]
+    fn test_gl_export() {
+        let mut engine = Engine::new();
+        engine.enable_ledger();
+        let timestamp = crate::timestamp::parse("2024-03-01 10:00:00").ok();
+        engine.apply(Transaction{client: 7, tx_type: TxType::Deposit, tx: 1, amount: Some(dec!(5.0)), timestamp, ..Default::default()});
+        let mut out = Vec::new();
+        write_gl(engine.ledger().unwrap().postings(), &mut out);
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "entry,date,account,debit,credit,memo");
+        assert!(lines[1].starts_with("1,2024-03-01,1000,5"));
+        assert!(lines[2].starts_with("1,2024-03-01,2000-00007,,5"));
+        assert!(lines[2].ends_with(",deposit tx 1 client 7"));
+    }
+}
@@ -0,0 +1,59 @@
+#[cfg(feature = "actors")]
+pub mod actors;
+pub mod aging;
+#[cfg(feature = "amqp")]
+pub mod amqp;
+pub mod amount;
+pub mod audit;
+pub mod calendar;
+pub mod config;
+pub mod crypto;
+pub mod diff;
+pub mod dormancy;
+pub mod engine;
+pub mod filter;
+#[cfg(feature = "polars")]
+pub mod frame;
+pub mod fx;
+pub mod ids;
+pub mod inputs;
+pub mod joint;
+pub mod kyc;
+pub mod ledger;
+#[cfg(feature = "server")]
+pub mod loadtest;
+pub mod loss;
+pub mod memory;
+pub mod merchants;
+#[cfg(feature = "nats")]
+pub mod nats;
+pub mod notifier;
+pub mod output;
+pub mod parking;
+pub mod precision;
+pub mod progress;
+pub mod pseudonym;
+pub mod query;
+pub mod reader;
+pub mod reconcile;
+pub mod recurring;
+pub mod rejection;
+pub mod report;
+pub mod schedule;
+pub mod sequence;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod settle;
+pub mod setup;
+pub mod simulate;
+#[cfg(feature = "datafusion")]
+pub mod sql;
+pub mod statement;
+pub mod stats;
+pub mod store;
+pub mod suspicious;
+pub mod tenant;
+pub mod timestamp;
+pub mod validate;
+pub mod wal;
+pub mod wallet;
@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+use ureq::tls::{Certificate, ClientCert, PrivateKey, RootCerts, TlsConfig};
+
+/// Deposits submitted to the `POST /transactions` endpoint of a server
+#[derive(Debug, Clone)]
+pub struct Load {
+    /// base url of the server, `http://host:8080`
+    pub url: String,
+    /// connections sending at once, one thread each
+    pub connections: usize,
+    /// requests a second over all the connections
+    pub rate: f64,
+    pub requests: u64,
+    /// clients the deposits are spread over, 1 to `clients`
+    pub clients: u16,
+    /// tx id of the first deposit, the others follow
+    pub first_tx: u32,
+    /// bearer token of the requests
+    pub token: Option<String>,
+}
+
+/// Latencies of the requests answered and the count of the others by error
+#[derive(Debug, Default)]
+pub struct Report {
+    pub sent: u64,
+    /// sorted
+    pub latencies: Vec<Duration>,
+    /// keyed `status 429`, `timeout`, `io` or `transport`
+    pub errors: BTreeMap<String, u64>,
+    pub elapsed: Duration,
+}
+
+impl Report {
+    /// latency at the `p` quantile, 0.99 for the 99th percentile
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        let last = self.latencies.len().checked_sub(1)?;
+        Some(self.latencies[((last as f64 * p).round() as usize).min(last)])
+    }
+
+    pub fn failed(&self) -> u64 {
+        self.errors.values().sum()
+    }
+
+    pub fn write<W: Write>(&self, mut wrt: W) -> io::Result<()> {
+        let rate = if self.sent > 0 { self.failed() as f64 * 100.0 / self.sent as f64 } else { 0.0 };
+        let errors: String = self.errors.iter().map(|(error, count)| format!(", {} {}", count, error)).collect();
+        writeln!(wrt, "requests {}, {} ok, {} failed ({:.2}%){}", self.sent, self.latencies.len(), self.failed(), rate, errors)?;
+        let secs = self.elapsed.as_secs_f64();
+        writeln!(wrt, "throughput {:.1}/s over {:.2}s", if secs > 0.0 { self.latencies.len() as f64 / secs } else { 0.0 }, secs)?;
+        let ms = |p: f64| self.percentile(p).map_or("-".to_string(), |latency| format!("{:.2}", latency.as_secs_f64() * 1000.0));
+        writeln!(wrt, "latency ms p50 {} p90 {} p99 {} p99.9 {} max {}", ms(0.5), ms(0.9), ms(0.99), ms(0.999), ms(1.0))
+    }
+}
+
+/// Agent configuration trusting the PEM certificate `ca` of a server over
+/// TLS, presenting the client certificate and key of `client` when given
+pub fn config(ca: Option<&str>, client: Option<(&str, &str)>) -> Result<ureq::config::Config, String> {
+    let read = |path: &str| std::fs::read(path).map_err(|err| format!("{}: {}", path, err));
+    let mut tls = TlsConfig::builder();
+    if let Some(ca) = ca {
+        let cert = Certificate::from_pem(&read(ca)?).map_err(|err| format!("{}: {}", ca, err))?;
+        tls = tls.root_certs(RootCerts::new_with_certs(&[cert]));
+    }
+    if let Some((cert, key)) = client {
+        let chain = Certificate::from_pem(&read(cert)?).map_err(|err| format!("{}: {}", cert, err))?;
+        let key = PrivateKey::from_pem(&read(key)?).map_err(|err| format!("{}: {}", key, err))?;
+        tls = tls.client_cert(Some(ClientCert::new_with_certs(&[chain], key)));
+    }
+    Ok(ureq::Agent::config_builder().http_status_as_error(false).timeout_global(Some(Duration::from_secs(30))).tls_config(tls.build()).build())
+}
+
+fn error(err: &ureq::Error) -> String {
+    match err {
+        ureq::Error::Timeout(_) => "timeout",
+        ureq::Error::Io(_) => "io",
+        _ => "transport",
+    }
+    .to_string()
+}
+
+/// Send the deposits of `load` at its rate, request `i` at `i / rate`
+/// seconds from the start on connection `i % connections`. A latency runs
+/// from the time a request was due, so a server slower than the rate shows in
+/// the latencies rather than in fewer requests sent.
+pub fn run(load: &Load, config: ureq::config::Config) -> Report {
+    assert!(load.connections > 0 && load.rate > 0.0 && load.clients > 0, "load test without connections, rate or clients");
+    let start = Instant::now();
+    let url = format!("{}/transactions", load.url.trim_end_matches('/'));
+    let threads: Vec<_> = (0..load.connections as u64)
+        .map(|connection| {
+            let (load, config, url) = (load.clone(), config.clone(), url.clone());
+            std::thread::spawn(move || {
+                let agent = ureq::Agent::new_with_config(config);
+                let mut report = Report::default();
+                for i in (connection..load.requests).step_by(load.connections) {
+                    let due = start + Duration::from_secs_f64(i as f64 / load.rate);
+                    std::thread::sleep(due.saturating_duration_since(Instant::now()));
+                    let tx = load.first_tx.wrapping_add(i as u32);
+                    let client = (i % u64::from(load.clients)) as u16 + 1;
+                    let body = format!(r#"{{"type": "deposit", "client": {}, "tx": {}, "amount": "1.0"}}"#, client, tx);
+                    let mut req = agent.post(&url).header("content-type", "application/json");
+                    if let Some(token) = &load.token {
+                        req = req.header("authorization", format!("Bearer {}", token));
+                    }
+                    report.sent += 1;
+                    match req.send(body) {
+                        Ok(res) if res.status().is_success() => report.latencies.push(due.elapsed()),
+                        Ok(res) => *report.errors.entry(format!("status {}", res.status().as_u16())).or_default() += 1,
+                        Err(err) => *report.errors.entry(error(&err)).or_default() += 1,
+                    }
+                }
+                report
+            })
+        })
+        .collect();
+    let mut report = Report::default();
+    for thread in threads {
+        let part = thread.join().expect("load test connection panicked");
+        report.sent += part.sent;
+        report.latencies.extend(part.latencies);
+        for (error, count) in part.errors {
+            *report.errors.entry(error).or_default() += count;
+        }
+    }
+    report.elapsed = start.elapsed();
+    report.latencies.sort();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::engine::Engine;
+    use crate::server;
+
+    #[test]
+    fn test_load_against_a_router() {
+        let settings: Config = toml::from_str("[server.rate_limit]\nrate = 0.01\nburst = 30\n").unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || runtime.block_on(server::listen(listener, server::router(Engine::new, &settings), None)));
+
+        let load = Load { url, connections: 4, rate: 400.0, requests: 40, clients: 3, first_tx: 1, token: None };
+        let report = run(&load, config(None, None).unwrap());
+        assert_eq!((report.sent, report.latencies.len()), (40, 30));
+        // beyond the burst the server refuses them
+        assert_eq!(report.errors, BTreeMap::from([("status 429".to_string(), 10)]));
+        assert!(report.percentile(0.5) <= report.percentile(0.99));
+        let mut text = Vec::new();
+        report.write(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.starts_with("requests 40, 30 ok, 10 failed (25.00%), 10 status 429\n"), "{}", text);
+        assert!(config(Some("tests/tls/missing.pem"), None).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use crate::amount::Amount;
+use crate::engine::{Account, Decision, Transaction, TxType};
+use crate::output::Labels;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Funds charged back per calendar month and client, see `record`
+#[derive(Debug, Default)]
+pub struct Losses {
+    // (YYYY-MM, client) --> amount charged back, the month is empty for untimed chargebacks
+    losses: BTreeMap<(String, u16), Amount>,
+}
+
+#[derive(serde::Serialize)]
+struct Row {
+    period: String,
+    /// empty on the total of the period
+    client: String,
+    loss: Amount,
+}
+
+impl Losses {
+    /// observer entry point
+    pub fn record(&mut self, tx: &Transaction, account: &Account, decision: Decision) {
+        if decision != Decision::Applied || !matches!(tx.tx_type, TxType::ChargeBack | TxType::AutoChargeBack) {
+            return;
+        }
+        let Some(amount) = account.amount_of(tx.tx) else {
+            return;
+        };
+        let period = tx.timestamp.map(|time| time.format("%Y-%m").to_string()).unwrap_or_default();
+        *self.losses.entry((period, tx.client)).or_default() += amount;
+    }
+
+    /// every loss since the start of the run
+    pub fn total(&self) -> Amount {
+        self.losses.values().fold(Amount::default(), |total, loss| total + *loss)
+    }
+
+    /// the losses of each client of a month, then the total of the month, in month order
+    pub fn write_csv<W: Write>(&self, wrt: W, labels: &Labels) {
+        let mut wrt = csv::Writer::from_writer(wrt);
+        let mut periods: BTreeMap<&str, Amount> = BTreeMap::new();
+        for ((period, _), loss) in &self.losses {
+            *periods.entry(period).or_default() += *loss;
+        }
+        for (period, total) in periods {
+            let clients = self.losses.iter().filter(|((month, _), _)| month == period);
+            let rows = clients.map(|((_, client), loss)| Row { period: period.to_string(), client: labels.client(*client), loss: *loss });
+            for row in rows.chain([Row { period: period.to_string(), client: String::new(), loss: total }]) {
+                let res = wrt.serialize(row);
+                assert!(res.is_ok(), "error in writing the chargeback loss report");
+            }
+        }
+        let res = wrt.flush();
+        assert!(res.is_ok(), "error in writing the chargeback loss report");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::timestamp::parse;
+    use rust_decimal_macros::dec;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_losses_per_month() {
+        let losses = Rc::new(RefCell::new(Losses::default()));
+        let mut engine = Engine::new();
+        let observed = losses.clone();
+        engine.add_observer(move |tx, account, decision| observed.borrow_mut().record(tx, account, decision));
+        let tx = |client, tx_type, tx, amount, time: &str| Transaction { client, tx_type, tx, amount, timestamp: Some(parse(time).unwrap()), ..Default::default() };
+        for (client, id, amount, month) in [(1, 1, dec!(5.5), "2024-01"), (1, 2, dec!(2.25), "2024-02"), (2, 3, dec!(1.5), "2024-02"), (2, 4, dec!(9), "2024-02")] {
+            engine.apply(tx(client, TxType::Deposit, id, Some(amount), &format!("{}-01", month)));
+            engine.apply(tx(client, TxType::Dispute, id, None, &format!("{}-02", month)));
+            if id != 4 {
+                engine.apply(tx(client, TxType::ChargeBack, id, None, &format!("{}-03", month)));
+            }
+        }
+        engine.apply(tx(1, TxType::ChargeBack, 1, None, "2024-03-01"));
+        assert_eq!(losses.borrow().total(), dec!(9.25));
+        let mut out = Vec::new();
+        losses.borrow().write_csv(&mut out, &Labels::default());
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out, "period,client,loss\n2024-01,1,5.5\n2024-01,,5.5\n2024-02,1,2.25\n2024-02,2,1.5\n2024-02,,3.75\n");
+        let mut accounts: Vec<Account> = engine.into_accounts().collect();
+        accounts.sort_by_key(|account| account.client);
+        assert_eq!(accounts[0].loss, dec!(7.75));
+        assert_eq!(accounts[1].loss, dec!(1.5));
+    }
+}
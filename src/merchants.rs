@@ -0,0 +1,109 @@
+use crate::amount::Amount;
+use crate::engine::{process_tx, AccountMap, Applied, Transaction};
+use std::collections::{BTreeMap, HashMap};
+
+/// Figures of the applied transactions with one counterparty
+#[derive(serde::Serialize, Debug, Clone, Default, PartialEq)]
+pub struct MerchantStats {
+    pub counterparty: String,
+    pub deposits: u64,
+    pub withdrawals: u64,
+    /// deposited + withdrawn
+    pub volume: Amount,
+    pub chargebacks: u64,
+    /// amount charged back, the exposure to the merchant
+    pub charged_back: Amount,
+    /// chargebacks per applied deposit
+    pub chargeback_rate: f64,
+}
+
+/// Applied transactions aggregated per `counterparty`, by decreasing amount
+/// charged back. Chargebacks count for the counterparty of the disputed tx.
+pub fn merchants(txs: impl Iterator<Item = Transaction>) -> Vec<MerchantStats> {
+    let mut accounts = AccountMap::default();
+    let mut merchants: BTreeMap<String, MerchantStats> = BTreeMap::new();
+    // tx id --> counterparty of the deposits and withdrawals
+    let mut counterparties: HashMap<u32, String> = HashMap::new();
+    for tx in txs {
+        let (id, counterparty) = (tx.tx, tx.counterparty.clone());
+        let res = process_tx(&mut accounts, tx);
+        let counterparty = match res {
+            Ok(Applied::Deposited(_) | Applied::Withdrawn(_)) => {
+                let Some(counterparty) = counterparty else {
+                    continue;
+                };
+                counterparties.insert(id, counterparty.clone());
+                counterparty
+            }
+            Ok(Applied::ChargedBack(_)) => match counterparties.get(&id) {
+                Some(counterparty) => counterparty.clone(),
+                None => continue,
+            },
+            _ => continue,
+        };
+        let entry = merchants
+            .entry(counterparty.clone())
+            .or_insert_with(|| MerchantStats { counterparty, ..Default::default() });
+        match res {
+            Ok(Applied::Deposited(amount)) => {
+                entry.deposits += 1;
+                entry.volume += amount;
+            }
+            Ok(Applied::Withdrawn(amount)) => {
+                entry.withdrawals += 1;
+                entry.volume += amount;
+            }
+            Ok(Applied::ChargedBack(amount)) => {
+                entry.chargebacks += 1;
+                entry.charged_back += amount;
+            }
+            _ => {}
+        }
+    }
+    let mut merchants: Vec<MerchantStats> = merchants
+        .into_values()
+        .map(|mut merchant| {
+            if merchant.deposits > 0 {
+                merchant.chargeback_rate = merchant.chargebacks as f64 / merchant.deposits as f64;
+            }
+            merchant
+        })
+        .collect();
+    // stable sort, ties stay by name
+    merchants.sort_by_key(|merchant| std::cmp::Reverse(merchant.charged_back));
+    merchants
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TxType;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_merchants_by_exposure() {
+        let tx = |tx_type, tx, amount, counterparty: Option<&str>| Transaction {
+            client: 1,
+            tx_type,
+            tx,
+            amount,
+            counterparty: counterparty.map(str::to_string),
+            ..Default::default()
+        };
+        let txs = vec![
+            tx(TxType::Deposit, 1, Some(dec!(10.0)), Some("acme")),
+            tx(TxType::Deposit, 2, Some(dec!(4.0)), Some("shady")),
+            tx(TxType::Deposit, 3, Some(dec!(6.0)), Some("shady")),
+            tx(TxType::Withdrawal, 4, Some(dec!(1.0)), None),
+            tx(TxType::Dispute, 3, None, None),
+            tx(TxType::ChargeBack, 3, None, None),
+        ];
+        let merchants = merchants(txs.into_iter());
+        let names: Vec<&str> = merchants.iter().map(|merchant| merchant.counterparty.as_str()).collect();
+        assert_eq!(names, vec!["shady", "acme"]);
+        assert_eq!((merchants[0].deposits, merchants[0].chargebacks), (2, 1));
+        assert_eq!(merchants[0].volume, dec!(10.0));
+        assert_eq!(merchants[0].charged_back, dec!(6.0));
+        assert_eq!(merchants[0].chargeback_rate, 0.5);
+    }
+}
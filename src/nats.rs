@@ -0,0 +1,136 @@
+use crate::diff::TxDelta;
+use crate::precision::Precision;
+use crate::reader::{self, Message};
+use async_nats::jetstream::consumer::{pull, AckPolicy};
+use async_nats::jetstream::{self, Context};
+use futures_util::stream::StreamExt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+/// Connection to a NATS server with JetStream enabled, driven by a runtime of
+/// its own so that callers stay synchronous
+pub struct Jetstream {
+    runtime: Arc<Runtime>,
+    context: Context,
+}
+
+impl Jetstream {
+    /// `url` as `nats://host:4222`
+    pub fn connect(url: &str) -> Result<Jetstream, String> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .map_err(|err| format!("cannot start the nats runtime: {}", err))?;
+        let client = runtime
+            .block_on(async_nats::connect(url))
+            .map_err(|err| format!("cannot connect to {}: {}", url, err))?;
+        Ok(Jetstream { runtime: Arc::new(runtime), context: jetstream::new(client) })
+    }
+
+    /// messages of the durable pull consumer `durable` of `stream`, created on
+    /// first use, with at most `max_pending` delivered and not yet acknowledged;
+    /// they end once none arrives for `idle`
+    pub fn source(
+        &self,
+        stream: &str,
+        durable: &str,
+        max_pending: i64,
+        idle: Option<Duration>,
+        precision: Option<Precision>,
+    ) -> Result<Source, String> {
+        let messages = self.runtime.block_on(async {
+            let stream = self.context.get_stream(stream).await.map_err(|err| err.to_string())?;
+            let config = pull::Config {
+                durable_name: Some(durable.to_string()),
+                ack_policy: AckPolicy::Explicit,
+                max_ack_pending: max_pending,
+                ..Default::default()
+            };
+            let consumer = stream.get_or_create_consumer(durable, config).await.map_err(|err| err.to_string())?;
+            consumer.messages().await.map_err(|err| err.to_string())
+        });
+        let messages = messages.map_err(|err| format!("cannot consume stream {} as {}: {}", stream, durable, err))?;
+        Ok(Source {
+            runtime: self.runtime.clone(),
+            stream: stream.to_string(),
+            precision,
+            idle,
+            messages,
+        })
+    }
+
+    /// publisher of account deltas to `subject`, which a stream must capture
+    pub fn sink(&self, subject: &str) -> Sink {
+        Sink {
+            runtime: self.runtime.clone(),
+            context: self.context.clone(),
+            subject: subject.to_string(),
+        }
+    }
+}
+
+/// Transactions of the messages of a stream, one JSON object per message.
+/// A message is only acknowledged with `Delivery::ack`, once its transaction
+/// has been applied: those not acknowledged within the ack wait of the
+/// consumer, for instance when the process stops, are delivered again.
+pub struct Source {
+    runtime: Arc<Runtime>,
+    stream: String,
+    precision: Option<Precision>,
+    idle: Option<Duration>,
+    messages: pull::Stream,
+}
+
+/// transaction of the next message, or its rejection, waiting for one to be delivered
+impl Iterator for Source {
+    type Item = Result<Message<Delivery>, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let message = self.runtime.block_on(async {
+            match self.idle {
+                Some(idle) => tokio::time::timeout(idle, self.messages.next()).await.ok().flatten(),
+                None => self.messages.next().await,
+            }
+        })?;
+        Some(match message {
+            Ok(message) => Ok(Message {
+                tx: reader::message(&self.stream, self.precision, &message.payload),
+                delivery: Delivery { runtime: self.runtime.clone(), message },
+            }),
+            Err(err) => Err(format!("error in consuming stream {}: {}", self.stream, err)),
+        })
+    }
+}
+
+/// Delivery of a message, to acknowledge once it has been processed
+pub struct Delivery {
+    runtime: Arc<Runtime>,
+    message: jetstream::Message,
+}
+
+impl Delivery {
+    /// acknowledgement confirmed by the server, the message is not delivered again
+    pub fn ack(self) -> Result<(), String> {
+        let res = self.runtime.block_on(self.message.double_ack());
+        res.map_err(|err| format!("cannot acknowledge a message: {}", err))
+    }
+}
+
+/// Publisher of account deltas, each one waits for the acknowledgement of the stream
+pub struct Sink {
+    runtime: Arc<Runtime>,
+    context: Context,
+    subject: String,
+}
+
+impl Sink {
+    pub fn publish(&self, delta: &TxDelta) -> Result<(), String> {
+        let payload = serde_json::to_vec(delta).expect("delta is always serializable");
+        let res = self.runtime.block_on(async {
+            self.context.publish(self.subject.clone(), payload.into()).await?.await
+        });
+        res.map(|_| ()).map_err(|err| format!("cannot publish the delta of client {}: {}", delta.delta.client, err))
+    }
+}
@@ -0,0 +1,249 @@
+use crate::amount::Amount;
+use crate::engine::{Account, Decision, Transaction, TxType};
+use std::collections::HashSet;
+use std::sync::mpsc::{self, SyncSender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Kind of risk event pushed to the webhook
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    ChargeBack,
+    Locked,
+}
+
+/// JSON payload POSTed to the webhook
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub event: EventKind,
+    pub client: u16,
+    pub tx: u32,
+    pub available: Amount,
+    pub held: Amount,
+    pub total: Amount,
+    pub locked: bool,
+}
+
+/// JSON payload published to a message broker for every transaction that
+/// reaches an account, with the balances it leaves
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Update {
+    pub client: u16,
+    pub tx: u32,
+    /// false when the transaction had no effect
+    pub applied: bool,
+    pub available: Amount,
+    pub held: Amount,
+    pub total: Amount,
+    pub locked: bool,
+}
+
+impl Update {
+    pub fn new(tx: &Transaction, account: &Account, decision: Decision) -> Update {
+        Update {
+            client: account.client,
+            tx: tx.tx,
+            applied: decision == Decision::Applied,
+            available: account.available,
+            held: account.held,
+            total: account.total,
+            locked: account.locked,
+        }
+    }
+}
+
+// events waiting for the delivery thread, the observer blocks once it is full
+const QUEUE: usize = 1024;
+
+/// Posts chargeback and account-lock events to a configured URL.
+/// Events are delivered in order by a thread of their own, so the engine is
+/// not held up by a slow or failing webhook. Failed deliveries are retried
+/// with exponential backoff, an event is dropped (and reported on stderr)
+/// once all attempts are exhausted. Dropping the notifier waits for the
+/// queued events to be delivered.
+pub struct Notifier {
+    webhook: Webhook,
+    queue: Option<SyncSender<Event>>,
+    delivery: Option<JoinHandle<()>>,
+    // clients already reported as locked, a lock is notified only once
+    locked: HashSet<u16>,
+}
+
+#[derive(Clone)]
+struct Webhook {
+    url: String,
+    retries: u32,
+    backoff: Duration,
+    agent: ureq::Agent,
+}
+
+impl Webhook {
+    fn send(&self, event: &Event) -> Result<(), ureq::Error> {
+        let body = serde_json::to_string(event).expect("event is always serializable");
+        let mut delay = self.backoff;
+        let mut attempt = 0;
+        loop {
+            let res = self
+                .agent
+                .post(&self.url)
+                .header("Content-Type", "application/json")
+                .send(&body);
+            match res {
+                Ok(_) => return Ok(()),
+                Err(err) if attempt >= self.retries => return Err(err),
+                Err(_) => {
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+impl Notifier {
+    pub fn new(url: &str, retries: u32, backoff: Duration) -> Notifier {
+        let config = ureq::Agent::config_builder()
+            .timeout_global(Some(Duration::from_secs(10)))
+            .build();
+        let webhook = Webhook { url: url.to_string(), retries, backoff, agent: config.into() };
+        let (queue, events) = mpsc::sync_channel::<Event>(QUEUE);
+        let delivery = webhook.clone();
+        let delivery = thread::spawn(move || {
+            for event in events {
+                if let Err(err) = delivery.send(&event) {
+                    eprintln!("webhook: dropping {:?} event for client {}: {}", event.event, event.client, err);
+                }
+            }
+        });
+        Notifier {
+            webhook,
+            queue: Some(queue),
+            delivery: Some(delivery),
+            locked: HashSet::new(),
+        }
+    }
+
+    /// events raised by a transaction that has just been processed
+    pub fn events_for(&mut self, tx: &Transaction, account: &Account, decision: Decision) -> Vec<Event> {
+        let mut events = Vec::new();
+        let event = |kind| Event {
+            event: kind,
+            client: account.client,
+            tx: tx.tx,
+            available: account.available,
+            held: account.held,
+            total: account.total,
+            locked: account.locked,
+        };
+        if decision == Decision::Applied && matches!(tx.tx_type, TxType::ChargeBack | TxType::AutoChargeBack) {
+            events.push(event(EventKind::ChargeBack));
+        }
+        if account.locked && self.locked.insert(account.client) {
+            events.push(event(EventKind::Locked));
+        }
+        events
+    }
+
+    /// observer entry point, see `Engine::set_observer`; the events are queued for delivery
+    pub fn observe(&mut self, tx: &Transaction, account: &Account, decision: Decision) {
+        let events = self.events_for(tx, account, decision);
+        let queue = self.queue.as_ref().expect("queue is open until dropped");
+        for event in events {
+            if let Err(mpsc::SendError(event)) = queue.send(event) {
+                eprintln!("webhook: dropping {:?} event for client {}: delivery stopped", event.event, event.client);
+            }
+        }
+    }
+
+    /// deliver an event right away, retrying on the calling thread
+    pub fn send(&self, event: &Event) -> Result<(), ureq::Error> {
+        self.webhook.send(event)
+    }
+}
+
+impl Drop for Notifier {
+    fn drop(&mut self) {
+        // closing the queue ends the delivery thread after its last event
+        self.queue = None;
+        if let Some(delivery) = self.delivery.take() {
+            let _ = delivery.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use rust_decimal_macros::dec;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Instant;
+
+    #[test]
+    fn test_chargeback_raises_chargeback_and_lock_once() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut notifier = Notifier::new("http://localhost", 0, Duration::ZERO);
+        let mut engine = Engine::new();
+        let log = events.clone();
+        engine.set_observer(move |tx, account, decision| {
+            log.borrow_mut().extend(notifier.events_for(tx, account, decision));
+        });
+        engine.apply(Transaction{client: 1, tx_type: TxType::Deposit, tx: 1, amount: Some(dec!(1.0)), ..Default::default()});
+        engine.apply(Transaction{client: 1, tx_type: TxType::Deposit, tx: 2, amount: Some(dec!(2.0)), ..Default::default()});
+        engine.apply(Transaction{client: 1, tx_type: TxType::Dispute, tx: 1, amount: None, ..Default::default()});
+        engine.apply(Transaction{client: 1, tx_type: TxType::ChargeBack, tx: 1, amount: None, ..Default::default()});
+        engine.apply(Transaction{client: 1, tx_type: TxType::Dispute, tx: 2, amount: None, ..Default::default()});
+        engine.apply(Transaction{client: 1, tx_type: TxType::ChargeBack, tx: 3, amount: None, ..Default::default()});
+        let kinds: Vec<(EventKind, u32)> = events.borrow().iter().map(|e| (e.event, e.tx)).collect();
+        assert_eq!(kinds, vec![(EventKind::ChargeBack, 1), (EventKind::Locked, 1)]);
+        assert_eq!(events.borrow()[1].total, dec!(2.0));
+    }
+
+    #[test]
+    fn test_update_of_every_transaction() {
+        let updates = Rc::new(RefCell::new(Vec::new()));
+        let mut engine = Engine::new();
+        let log = updates.clone();
+        engine.add_observer(move |tx, account, decision| log.borrow_mut().push(Update::new(tx, account, decision)));
+        engine.apply(Transaction{client: 1, tx_type: TxType::Deposit, tx: 1, amount: Some(dec!(1.0)), ..Default::default()});
+        engine.apply(Transaction{client: 1, tx_type: TxType::Withdrawal, tx: 2, amount: Some(dec!(2.0)), ..Default::default()});
+        let updates = updates.borrow();
+        assert_eq!(updates.len(), 2);
+        assert!(updates[0].applied && !updates[1].applied);
+        assert_eq!(updates[1].available, dec!(1.0));
+        let json = serde_json::to_value(&updates[0]).unwrap();
+        assert_eq!((json["client"].as_u64(), json["tx"].as_u64(), json["applied"].as_bool()), (Some(1), Some(1), Some(true)));
+    }
+
+    #[test]
+    fn test_send_gives_up_after_retries() {
+        // nothing listens on port 9 (discard) on a test machine
+        let notifier = Notifier::new("http://127.0.0.1:9/hook", 2, Duration::from_millis(1));
+        let event = Event {
+            event: EventKind::Locked,
+            client: 1,
+            tx: 1,
+            available: Amount::default(),
+            held: Amount::default(),
+            total: Amount::default(),
+            locked: true,
+        };
+        assert!(notifier.send(&event).is_err());
+    }
+
+    #[test]
+    fn test_observe_does_not_wait_for_delivery() {
+        // every attempt fails, delivering the lock takes 100 + 200 ms of backoff
+        let mut notifier = Notifier::new("http://127.0.0.1:9/hook", 2, Duration::from_millis(100));
+        let mut account = Account::new(1);
+        account.locked = true;
+        let start = Instant::now();
+        notifier.observe(&Transaction{client: 1, tx_type: TxType::ChargeBack, tx: 1, ..Default::default()}, &account, Decision::Applied);
+        assert!(start.elapsed() < Duration::from_millis(100), "{:?}", start.elapsed());
+        drop(notifier);
+        assert!(start.elapsed() >= Duration::from_millis(300), "{:?}", start.elapsed());
+    }
+}
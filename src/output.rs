@@ -0,0 +1,438 @@
+use crate::amount::Amount;
+use crate::engine::Account;
+use crate::joint::JointAccounts;
+use crate::ids::SharedIds;
+use crate::pseudonym::Pseudonymizer;
+use std::io::Write;
+use std::str::FromStr;
+
+/// How the account report is printed
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Csv,
+    /// aligned table sorted by client, locked accounts highlighted
+    Table,
+}
+
+/// How client ids are written in the reports: pseudonymized, as external ids, or as they are
+#[derive(Clone, Default)]
+pub struct Labels {
+    pub pseudonyms: Option<Pseudonymizer>,
+    pub ids: Option<SharedIds>,
+}
+
+impl Labels {
+    pub fn client(&self, client: u16) -> String {
+        if let Some(pseudonyms) = &self.pseudonyms {
+            return pseudonyms.client(client);
+        }
+        let ids = self.ids.as_ref().map(|ids| ids.lock().expect("id mapping lock"));
+        match ids.as_ref().and_then(|ids| ids.external(client)) {
+            Some(external) => external.to_string(),
+            None => client.to_string(),
+        }
+    }
+}
+
+/// A column of the csv account report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Client,
+    Available,
+    Held,
+    Total,
+    Locked,
+    Currency,
+    Loss,
+    Owners,
+}
+
+const COLUMNS: [(&str, Column); 8] = [
+    ("client", Column::Client),
+    ("available", Column::Available),
+    ("held", Column::Held),
+    ("total", Column::Total),
+    ("locked", Column::Locked),
+    ("currency", Column::Currency),
+    ("loss", Column::Loss),
+    ("owners", Column::Owners),
+];
+
+/// Comma separated columns of the account report, in the order they are
+/// written, each one possibly renamed with `column=name`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Columns(Vec<(Column, String)>);
+
+impl FromStr for Columns {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(|column| {
+                let (column, name) = column.split_once('=').unwrap_or((column, column));
+                let (column, name) = (column.trim(), name.trim());
+                let known = COLUMNS.iter().find(|(known, _)| *known == column);
+                match (known, name.is_empty()) {
+                    (None, _) => {
+                        let names: Vec<&str> = COLUMNS.iter().map(|(name, _)| *name).collect();
+                        Err(format!("unknown column `{}`, expected one of {}", column, names.join(", ")))
+                    }
+                    (Some(_), true) => Err(format!("empty name for column `{}`", column)),
+                    (Some((_, column)), false) => Ok((*column, name.to_string())),
+                }
+            })
+            .collect::<Result<_, _>>()
+            .map(Columns)
+    }
+}
+
+/// a field of an account as written out
+#[derive(serde::Serialize)]
+#[serde(untagged)]
+enum Cell {
+    Text(String),
+    Amount(Amount),
+    Flag(bool),
+}
+
+impl Columns {
+    /// the selected fields of `account`, an account without a currency or
+    /// owners having empty ones
+    fn cells(&self, account: &Account, client: &str, owners: Option<&str>) -> Vec<Cell> {
+        self.0
+            .iter()
+            .map(|(column, _)| match column {
+                Column::Client => Cell::Text(client.to_string()),
+                Column::Available => Cell::Amount(account.available),
+                Column::Held => Cell::Amount(account.held),
+                Column::Total => Cell::Amount(account.total),
+                Column::Locked => Cell::Flag(account.locked),
+                Column::Currency => Cell::Text(account.currency.clone().unwrap_or_default()),
+                Column::Loss => Cell::Amount(account.loss),
+                Column::Owners => Cell::Text(owners.unwrap_or_default().to_string()),
+            })
+            .collect()
+    }
+}
+
+const HEADER: [&str; 5] = ["client", "available", "held", "total", "locked"];
+// bold red, reset
+const LOCKED_STYLE: (&str, &str) = ("\x1b[1;31m", "\x1b[0m");
+
+/// an account as written out, the client possibly pseudonymized
+#[derive(serde::Serialize)]
+struct Row {
+    client: String,
+    available: Amount,
+    held: Amount,
+    total: Amount,
+    locked: bool,
+    /// only when some account was set up with a currency
+    #[serde(skip_serializing_if = "Option::is_none")]
+    currency: Option<String>,
+    /// funds charged back, only when asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    loss: Option<Amount>,
+    /// space separated, only with joint accounts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    owners: Option<String>,
+}
+
+pub fn write_csv<W: Write>(accounts: impl Iterator<Item = Account>, wrt: W, labels: &Labels) {
+    write_csv_with_owners(accounts, wrt, labels, None, false, None)
+}
+
+/// `write_csv` with an `owners` column listing the owners of each account,
+/// with `loss` a column of the funds charged back from it, and with
+/// `columns` only the columns selected
+pub fn write_csv_with_owners<W: Write>(
+    accounts: impl Iterator<Item = Account>,
+    wrt: W,
+    labels: &Labels,
+    joint: Option<&JointAccounts>,
+    loss: bool,
+    columns: Option<&Columns>,
+) {
+    let accounts: Vec<Account> = accounts.collect();
+    let currencies = accounts.iter().any(|account| account.currency.is_some());
+    let mut wrt = csv::Writer::from_writer(wrt);
+    if let Some(columns) = columns {
+        // the header is written even without accounts
+        let res = wrt.write_record(columns.0.iter().map(|(_, name)| name));
+        assert!(res.is_ok(), "error in writing output to stdout");
+    }
+    for account in accounts {
+        let owners = joint.map(|joint| {
+            let owners = joint.owners(account.client).into_iter();
+            owners.map(|owner| labels.client(owner)).collect::<Vec<_>>().join(" ")
+        });
+        if let Some(columns) = columns {
+            let res = wrt.serialize(columns.cells(&account, &labels.client(account.client), owners.as_deref()));
+            assert!(res.is_ok(), "error in writing output to stdout");
+            continue;
+        }
+        let record = Row {
+            client: labels.client(account.client),
+            available: account.available,
+            held: account.held,
+            total: account.total,
+            locked: account.locked,
+            currency: currencies.then(|| account.currency.unwrap_or_default()),
+            loss: loss.then_some(account.loss),
+            owners,
+        };
+        let res = wrt.serialize(record);
+        assert!(res.is_ok(), "error in writing output to stdout");
+    }
+}
+
+/// an account of a wallet as written out
+#[derive(serde::Serialize)]
+struct WalletRow {
+    client: String,
+    wallet: String,
+    available: Amount,
+    held: Amount,
+    total: Amount,
+    locked: bool,
+}
+
+/// one row per client and wallet, sorted by client then wallet
+pub fn write_wallets<W: Write>(accounts: impl Iterator<Item = (String, Account)>, wrt: W, labels: &Labels) {
+    let mut accounts: Vec<(String, Account)> = accounts.collect();
+    accounts.sort_by(|a, b| (a.1.client, &a.0).cmp(&(b.1.client, &b.0)));
+    let mut wrt = csv::Writer::from_writer(wrt);
+    for (wallet, account) in accounts {
+        let row = WalletRow {
+            client: labels.client(account.client),
+            wallet,
+            available: account.available,
+            held: account.held,
+            total: account.total,
+            locked: account.locked,
+        };
+        let res = wrt.serialize(row);
+        assert!(res.is_ok(), "error in writing output to stdout");
+    }
+}
+
+/// an account of a tenant as written out
+#[derive(serde::Serialize)]
+struct TenantRow {
+    tenant: String,
+    client: String,
+    available: Amount,
+    held: Amount,
+    total: Amount,
+    locked: bool,
+}
+
+/// one row per tenant and client, sorted by tenant then client
+pub fn write_tenants<W: Write>(accounts: impl Iterator<Item = (String, Account)>, wrt: W, labels: &Labels) {
+    let mut accounts: Vec<(String, Account)> = accounts.collect();
+    accounts.sort_by(|a, b| (&a.0, a.1.client).cmp(&(&b.0, b.1.client)));
+    let mut wrt = csv::Writer::from_writer(wrt);
+    for (tenant, account) in accounts {
+        let row = TenantRow {
+            tenant,
+            client: labels.client(account.client),
+            available: account.available,
+            held: account.held,
+            total: account.total,
+            locked: account.locked,
+        };
+        let res = wrt.serialize(row);
+        assert!(res.is_ok(), "error in writing output to stdout");
+    }
+}
+
+#[derive(serde::Serialize)]
+struct EscrowRow {
+    client: String,
+    escrow: Amount,
+}
+
+/// escrowed funds of the accounts holding any, sorted by client
+pub fn write_escrow<'a, W: Write>(accounts: impl Iterator<Item = &'a Account>, wrt: W, labels: &Labels) {
+    let mut accounts: Vec<&Account> = accounts.filter(|account| account.escrow != Amount::default()).collect();
+    accounts.sort_by_key(|account| account.client);
+    let mut wrt = csv::Writer::from_writer(wrt);
+    for account in accounts {
+        let row = EscrowRow { client: labels.client(account.client), escrow: account.escrow };
+        let res = wrt.serialize(row);
+        assert!(res.is_ok(), "error in writing the escrow report");
+    }
+    let res = wrt.flush();
+    assert!(res.is_ok(), "error in writing the escrow report");
+}
+
+#[derive(serde::Serialize)]
+struct NegativeRow {
+    client: String,
+    available: Amount,
+    held: Amount,
+    total: Amount,
+    /// funds the client owes, the most negative of available and total
+    exposure: Amount,
+    locked: bool,
+    /// space separated ids of the transactions under dispute
+    disputed: String,
+    /// space separated ids of the transactions charged back
+    charged_back: String,
+}
+
+/// Accounts whose available funds or total are negative, with the disputes
+/// and chargebacks that took them there, sorted by client
+pub fn write_negative<'a, W: Write>(accounts: impl Iterator<Item = &'a Account>, wrt: W, labels: &Labels) {
+    let zero = Amount::default();
+    let mut accounts: Vec<&Account> = accounts.filter(|account| account.available < zero || account.total < zero).collect();
+    accounts.sort_by_key(|account| account.client);
+    let ids = |ids: Vec<u32>| ids.iter().map(u32::to_string).collect::<Vec<_>>().join(" ");
+    let mut wrt = csv::Writer::from_writer(wrt);
+    for account in accounts {
+        let row = NegativeRow {
+            client: labels.client(account.client),
+            available: account.available,
+            held: account.held,
+            total: account.total,
+            exposure: zero - account.available.min(account.total),
+            locked: account.locked,
+            disputed: ids(account.open_disputes()),
+            charged_back: ids(account.charged_back()),
+        };
+        let res = wrt.serialize(row);
+        assert!(res.is_ok(), "error in writing the negative balance report");
+    }
+    let res = wrt.flush();
+    assert!(res.is_ok(), "error in writing the negative balance report");
+}
+
+/// Accounts as an aligned table, numbers right aligned.
+/// With `color` the rows of locked accounts are highlighted with ANSI codes.
+/// Pseudonymized rows are sorted by pseudonym, not to leak the order of the ids.
+pub fn write_table<W: Write>(accounts: impl Iterator<Item = Account>, mut wrt: W, color: bool, labels: &Labels) {
+    let mut accounts: Vec<(String, Account)> = accounts
+        .map(|account| (labels.client(account.client), account))
+        .collect();
+    match labels.pseudonyms {
+        Some(_) => accounts.sort_by(|a, b| a.0.cmp(&b.0)),
+        None => accounts.sort_by_key(|(_, account)| account.client),
+    }
+    let rows: Vec<[String; 5]> = accounts
+        .iter()
+        .map(|(client, account)| {
+            [
+                client.clone(),
+                account.available.to_string(),
+                account.held.to_string(),
+                account.total.to_string(),
+                account.locked.to_string(),
+            ]
+        })
+        .collect();
+    let mut widths = HEADER.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let line = |cells: [&str; 5]| {
+        let cells: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(idx, (cell, width))| match idx {
+                4 => format!("{:<width$}", cell),
+                _ => format!("{:>width$}", cell),
+            })
+            .collect();
+        cells.join("  ").trim_end().to_string()
+    };
+    let mut res = writeln!(wrt, "{}", line(HEADER));
+    let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    res = res.and_then(|_| writeln!(wrt, "{}", rule.join("  ")));
+    for ((_, account), row) in accounts.iter().zip(&rows) {
+        let text = line([&row[0], &row[1], &row[2], &row[3], &row[4]]);
+        res = res.and_then(|_| match (color, account.locked) {
+            (true, true) => writeln!(wrt, "{}{}{}", LOCKED_STYLE.0, text, LOCKED_STYLE.1),
+            _ => writeln!(wrt, "{}", text),
+        });
+    }
+    assert!(res.is_ok(), "error in writing output to stdout");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Engine, Transaction, TxType};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_table_sorted_and_aligned() {
+        let mut engine = Engine::new();
+        engine.apply(Transaction{client: 12, tx_type: TxType::Deposit, tx: 1, amount: Some(dec!(1500.25)), ..Default::default()});
+        engine.apply(Transaction{client: 3, tx_type: TxType::Deposit, tx: 2, amount: Some(dec!(2.0)), ..Default::default()});
+        engine.apply(Transaction{client: 3, tx_type: TxType::Dispute, tx: 2, amount: None, ..Default::default()});
+        engine.apply(Transaction{client: 3, tx_type: TxType::ChargeBack, tx: 2, amount: None, ..Default::default()});
+        let mut out = Vec::new();
+        write_table(engine.into_accounts(), &mut out, true, &Labels::default());
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[2].starts_with("\x1b[1;31m     3"));
+        assert!(lines[3].starts_with("    12"));
+        assert_eq!(lines[0].find("available").map(|idx| idx + "available".len()), lines[3].find("1500.25").map(|idx| idx + 7));
+    }
+
+    #[test]
+    fn test_negative_balances() {
+        let tx = |client, tx_type, tx, amount| Transaction{client, tx_type, tx, amount, ..Default::default()};
+        let mut engine = Engine::new();
+        engine.apply(tx(1, TxType::Deposit, 1, Some(dec!(5.5))));
+        engine.apply(tx(1, TxType::Withdrawal, 2, Some(dec!(4.25))));
+        engine.apply(tx(1, TxType::Dispute, 1, None));
+        engine.apply(tx(2, TxType::Deposit, 3, Some(dec!(2.5))));
+        engine.apply(tx(2, TxType::Deposit, 4, Some(dec!(1.5))));
+        engine.apply(tx(2, TxType::Withdrawal, 5, Some(dec!(3.75))));
+        engine.apply(tx(2, TxType::Dispute, 3, None));
+        engine.apply(tx(2, TxType::ChargeBack, 3, None));
+        engine.apply(tx(2, TxType::Dispute, 4, None));
+        engine.apply(tx(3, TxType::Deposit, 6, Some(dec!(1.0))));
+        let accounts: Vec<Account> = engine.into_accounts().collect();
+        let mut out = Vec::new();
+        write_negative(accounts.iter(), &mut out, &Labels::default());
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "client,available,held,total,exposure,locked,disputed,charged_back");
+        assert_eq!(&lines[1..], ["1,-4.25,5.5,1.25,4.25,false,1,", "2,-3.75,1.5,-2.25,3.75,true,4,3"]);
+    }
+
+    #[test]
+    fn test_csv_pseudonymized_clients() {
+        let mut engine = Engine::new();
+        engine.apply(Transaction{client: 12, tx_type: TxType::Deposit, tx: 1, amount: Some(dec!(1.5)), ..Default::default()});
+        let pseudonyms = Pseudonymizer::new("key");
+        let mut out = Vec::new();
+        let labels = Labels { pseudonyms: Some(pseudonyms.clone()), ..Default::default() };
+        write_csv(engine.into_accounts(), &mut out, &labels);
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out, format!("client,available,held,total,locked\n{},1.5,0,1.5,false\n", pseudonyms.client(12)));
+    }
+
+    #[test]
+    fn test_selected_columns() {
+        let mut engine = Engine::new();
+        engine.apply(Transaction{client: 4, tx_type: TxType::Deposit, tx: 1, amount: Some(dec!(2.75)), ..Default::default()});
+        let columns: Columns = "total=balance, client=customer_id,locked".parse().unwrap();
+        let mut out = Vec::new();
+        write_csv_with_owners(engine.into_accounts(), &mut out, &Labels::default(), None, false, Some(&columns));
+        assert_eq!(String::from_utf8(out).unwrap(), "balance,customer_id,locked\n2.75,4,false\n");
+        assert!("client,balance".parse::<Columns>().is_err());
+        let columns: Columns = "client,loss".parse().unwrap();
+        let mut out = Vec::new();
+        write_csv_with_owners(std::iter::empty(), &mut out, &Labels::default(), None, false, Some(&columns));
+        assert_eq!(String::from_utf8(out).unwrap(), "client,loss\n");
+        assert!("client=".parse::<Columns>().is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::engine::{Applied, Rejection, Transaction, TxType};
+use crate::rejection::{Reason, Rejected};
+use std::collections::VecDeque;
+
+/// Disputes, resolves and chargebacks arriving before the transaction they
+/// reference, parked for a number of rows and retried once it shows up.
+#[derive(Debug, Default)]
+pub struct Parking {
+    window: u64,
+    row: u64,
+    // (row a parked transaction expires at, transaction), in arrival order
+    parked: VecDeque<(u64, Transaction)>,
+}
+
+/// a dispute of an unknown tx, or a resolve or chargeback of a tx not under dispute
+pub fn unmatched(tx_type: TxType, res: &Result<Applied, Rejection>) -> bool {
+    matches!(
+        (tx_type, res),
+        (TxType::Dispute, Err(Rejection::UnknownTx)) | (TxType::Resolve | TxType::ChargeBack, Err(Rejection::NotDisputed))
+    )
+}
+
+impl Parking {
+    pub fn new(window: u64) -> Parking {
+        Parking { window, ..Default::default() }
+    }
+
+    pub fn park(&mut self, tx: Transaction) {
+        self.parked.push_back((self.row + self.window, tx));
+    }
+
+    /// Retry the transactions parked on tx id `tx`, in arrival order, now that
+    /// it was applied; those still unmatched stay parked. A retried dispute
+    /// may in turn let a parked resolve through, so retries go on until none does.
+    pub fn retry(&mut self, tx: u32, mut process: impl FnMut(Transaction) -> Result<Applied, Rejection>) {
+        loop {
+            let (ready, parked): (VecDeque<_>, VecDeque<_>) =
+                std::mem::take(&mut self.parked).into_iter().partition(|(_, parked)| parked.tx == tx);
+            self.parked = parked;
+            let mut progress = false;
+            for (expiry, parked) in ready {
+                let tx_type = parked.tx_type;
+                let res = process(parked.clone());
+                if unmatched(tx_type, &res) {
+                    let idx = self.parked.partition_point(|(other, _)| *other <= expiry);
+                    self.parked.insert(idx, (expiry, parked));
+                } else {
+                    progress = true;
+                }
+            }
+            if !progress {
+                return;
+            }
+        }
+    }
+
+    /// count a row, rejecting the transactions parked for the whole window
+    pub fn tick(&mut self) -> Vec<Rejected> {
+        self.row += 1;
+        let mut expired = Vec::new();
+        while self.parked.front().is_some_and(|(expiry, _)| *expiry < self.row) {
+            expired.push(self.parked.pop_front().unwrap().1);
+        }
+        expired.into_iter().map(|tx| rejected(tx, self.window)).collect()
+    }
+
+    /// rejections of the transactions still parked when the input ends
+    pub fn finish(self) -> Vec<Rejected> {
+        let window = self.window;
+        self.parked.into_iter().map(|(_, tx)| rejected(tx, window)).collect()
+    }
+}
+
+fn rejected(tx: Transaction, window: u64) -> Rejected {
+    let detail = format!("{:?} of tx {} still unmatched after {} rows", tx.tx_type, tx.tx, window);
+    Rejected::new(Reason::Unmatched, Some(tx.client), Some(tx.tx), detail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Engine;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_dispute_before_deposit() {
+        let tx = |tx_type, tx, amount| Transaction { client: 1, tx_type, tx, amount, ..Default::default() };
+        let mut engine = Engine::new();
+        let mut parking = Parking::new(3);
+        let mut expired = Vec::new();
+        let rows = [
+            tx(TxType::Resolve, 1, None),
+            tx(TxType::Dispute, 1, None),
+            tx(TxType::Dispute, 9, None),
+            tx(TxType::Deposit, 1, Some(dec!(5.0))),
+            tx(TxType::Deposit, 2, Some(dec!(1.0))),
+            tx(TxType::ChargeBack, 2, None),
+        ];
+        for row in rows {
+            expired.extend(parking.tick());
+            let (id, tx_type) = (row.tx, row.tx_type);
+            let res = engine.process(row.clone());
+            if unmatched(tx_type, &res) {
+                parking.park(row);
+            } else if res.is_ok() {
+                parking.retry(id, |retry| engine.process(retry));
+            }
+        }
+        expired.extend(parking.finish());
+        assert_eq!(expired.iter().map(|rejected| rejected.tx).collect::<Vec<_>>(), vec![Some(9), Some(2)]);
+        let account = engine.into_accounts().next().unwrap();
+        assert_eq!(account.held, dec!(0.0));
+        assert_eq!(account.available, dec!(6.0));
+    }
+}
@@ -0,0 +1,74 @@
+use rust_decimal::{Decimal, RoundingStrategy};
+
+/// What happens to an input amount with more decimal places than allowed
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Policy {
+    /// the row is rejected
+    Reject,
+    /// extra digits are dropped
+    Truncate,
+    /// the amount is rounded with the configured mode
+    Round,
+}
+
+/// Tie breaking of `Policy::Round`
+#[derive(serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rounding {
+    /// banker's rounding, ties go to the even digit
+    #[default]
+    HalfEven,
+    /// ties go away from zero
+    HalfUp,
+}
+
+/// Input precision policy, the `[precision]` table of the config file
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Precision {
+    pub policy: Policy,
+    #[serde(default)]
+    pub rounding: Rounding,
+    /// decimal places allowed
+    #[serde(default = "default_scale")]
+    pub scale: u32,
+}
+
+fn default_scale() -> u32 {
+    crate::validate::MAX_SCALE
+}
+
+impl Precision {
+    /// the amount to process, None if it must be rejected
+    pub fn apply(&self, amount: Decimal) -> Option<Decimal> {
+        if amount.scale() <= self.scale {
+            return Some(amount);
+        }
+        let strategy = match (self.policy, self.rounding) {
+            (Policy::Reject, _) => return None,
+            (Policy::Truncate, _) => RoundingStrategy::ToZero,
+            (Policy::Round, Rounding::HalfEven) => RoundingStrategy::MidpointNearestEven,
+            (Policy::Round, Rounding::HalfUp) => RoundingStrategy::MidpointAwayFromZero,
+        };
+        Some(amount.round_dp_with_strategy(self.scale, strategy))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_policies() {
+        let precision = |policy, rounding| Precision { policy, rounding, scale: 4 };
+        let half_even = precision(Policy::Round, Rounding::HalfEven);
+        assert_eq!(half_even.apply(dec!(1.00005)), Some(dec!(1.0000)));
+        assert_eq!(half_even.apply(dec!(1.00015)), Some(dec!(1.0002)));
+        assert_eq!(precision(Policy::Round, Rounding::HalfUp).apply(dec!(1.00005)), Some(dec!(1.0001)));
+        assert_eq!(precision(Policy::Truncate, Rounding::HalfUp).apply(dec!(-1.00009)), Some(dec!(-1.0000)));
+        assert_eq!(precision(Policy::Reject, Rounding::HalfEven).apply(dec!(1.00001)), None);
+        assert_eq!(precision(Policy::Reject, Rounding::HalfEven).apply(dec!(1.5)), Some(dec!(1.5)));
+    }
+}
@@ -0,0 +1,51 @@
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::io::IsTerminal;
+
+// refreshing the rows/sec message on every record would dominate the loop
+const REFRESH_ROWS: u64 = 4096;
+
+/// Progress bar on stderr driven by the bytes read from the input file,
+/// with processed rows and rows/sec in the message.
+pub struct Progress {
+    bar: ProgressBar,
+    rows: u64,
+}
+
+impl Progress {
+    /// the bar is hidden unless requested and stdout is an interactive terminal
+    pub fn new(total_bytes: u64, requested: bool) -> Progress {
+        let bar = ProgressBar::with_draw_target(Some(total_bytes), ProgressDrawTarget::stderr());
+        if !requested || !std::io::stdout().is_terminal() {
+            bar.set_draw_target(ProgressDrawTarget::hidden());
+        }
+        let style = ProgressStyle::with_template(
+            "{bar:40} {binary_bytes}/{binary_total_bytes} {msg} ETA {eta}",
+        );
+        bar.set_style(style.expect("progress template is valid"));
+        Progress { bar, rows: 0 }
+    }
+
+    /// handle to the bar advancing it as bytes are read, through
+    /// `wrap_read` of the inputs opened later or on other threads
+    pub fn bytes(&self) -> ProgressBar {
+        self.bar.clone()
+    }
+
+    pub fn row(&mut self) {
+        self.rows += 1;
+        if self.rows.is_multiple_of(REFRESH_ROWS) {
+            self.refresh();
+        }
+    }
+
+    pub fn finish(&mut self) {
+        self.refresh();
+        self.bar.finish();
+    }
+
+    fn refresh(&self) {
+        let secs = self.bar.elapsed().as_secs_f64();
+        let rate = if secs > 0.0 { self.rows as f64 / secs } else { 0.0 };
+        self.bar.set_message(format!("{} rows {:.0} rows/s", self.rows, rate));
+    }
+}
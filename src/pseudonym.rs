@@ -0,0 +1,48 @@
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::Sha256;
+
+/// hex digits of the HMAC kept in a pseudonym
+const LEN: usize = 16;
+
+/// Replaces client ids with keyed HMAC-SHA256 pseudonyms, so that outputs can
+/// be shared without exposing real ids. The same key always gives the same
+/// pseudonym for a client, files produced with it can still be joined.
+#[derive(Clone)]
+pub struct Pseudonymizer {
+    mac: Hmac<Sha256>,
+}
+
+impl Pseudonymizer {
+    pub fn new(key: &str) -> Pseudonymizer {
+        assert!(!key.is_empty(), "pseudonymization key must not be empty");
+        let mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("hmac accepts keys of any length");
+        Pseudonymizer { mac }
+    }
+
+    pub fn client(&self, client: u16) -> String {
+        let mut mac = self.mac.clone();
+        mac.update(&client.to_be_bytes());
+        let digest = mac.finalize().into_bytes();
+        digest.iter().map(|byte| format!("{:02x}", byte)).collect::<String>()[..LEN].to_string()
+    }
+}
+
+/// client id as it appears in an output, pseudonymized when a key is set
+pub fn label(pseudonyms: Option<&Pseudonymizer>, client: u16) -> String {
+    pseudonyms.map_or_else(|| client.to_string(), |pseudonyms| pseudonyms.client(client))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pseudonyms_are_keyed_and_stable() {
+        let one = Pseudonymizer::new("secret");
+        assert_eq!(one.client(7), Pseudonymizer::new("secret").client(7));
+        assert_eq!(one.client(7).len(), LEN);
+        assert_ne!(one.client(7), one.client(8));
+        assert_ne!(one.client(7), Pseudonymizer::new("other").client(7));
+        assert_eq!(label(None, 7), "7");
+    }
+}
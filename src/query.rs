@@ -0,0 +1,194 @@
+use std::io::Read;
+use std::sync::mpsc::Sender;
+use std::thread::JoinHandle;
+
+/// whether `url` names the rows of a database query rather than a file
+pub fn is_query(url: &str) -> bool {
+    url.starts_with("postgres://") || url.starts_with("postgresql://") || url.starts_with("sqlite://")
+}
+
+/// position of the query parameter, the last one of `url`
+fn query_at(url: &str) -> Option<usize> {
+    ["?query=", "&query="].iter().filter_map(|key| url.find(key)).min()
+}
+
+/// database url and query of `url`, the query taken as written up to the end of the url
+fn split(url: &str) -> Result<(&str, &str), String> {
+    let at = query_at(url).ok_or_else(|| format!("{} has no query parameter", name(url)))?;
+    Ok((&url[..at], &url[at + "?query=".len()..]))
+}
+
+/// `url` without its credentials and query, naming the rows in the rejections
+pub fn name(url: &str) -> String {
+    let database = query_at(url).map_or(url, |at| &url[..at]);
+    match (database.split_once("://"), database.rfind('@')) {
+        (Some((scheme, _)), Some(at)) => format!("{}://{}", scheme, &database[at + 1..]),
+        _ => database.to_string(),
+    }
+}
+
+/// Rows of a query as a csv file with a header line of the column names,
+/// written by a thread running the query as they are read. An error of the
+/// database in the middle of the rows ends them with an io error.
+pub struct Rows {
+    pipe: std::io::PipeReader,
+    worker: Option<JoinHandle<Result<(), String>>>,
+}
+
+impl Read for Rows {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.pipe.read(buf)?;
+        if len == 0 && !buf.is_empty() {
+            if let Some(worker) = self.worker.take() {
+                worker.join().expect("query thread does not panic").map_err(std::io::Error::other)?;
+            }
+        }
+        Ok(len)
+    }
+}
+
+/// Query run by a worker thread, writing the rows to the pipe once it has
+/// started them
+type Backend = fn(&str, &str, std::io::PipeWriter, &mut Option<Sender<Result<(), String>>>) -> Result<(), String>;
+
+fn backend(database: &str) -> Result<Backend, String> {
+    if database.starts_with("sqlite://") {
+        #[cfg(feature = "sqlite")]
+        return Ok(sqlite);
+        #[cfg(not(feature = "sqlite"))]
+        return Err("sqlite queries need the sqlite feature".to_string());
+    }
+    #[cfg(feature = "postgres")]
+    return Ok(postgres);
+    #[cfg(not(feature = "postgres"))]
+    Err("postgres queries need the postgres feature".to_string())
+}
+
+/// rows of `postgres://user@host/db?query=SELECT ...` or `sqlite://path?query=SELECT ...`,
+/// once the query has started: a connection error or an invalid query is returned here
+pub fn open(url: &str) -> Result<Rows, String> {
+    let (database, query) = split(url)?;
+    let run = backend(database)?;
+    let (database, query) = (database.to_string(), query.to_string());
+    let (pipe, wrt) = std::io::pipe().map_err(|err| format!("cannot create the pipe of the query: {}", err))?;
+    let (started, start) = std::sync::mpsc::channel();
+    let worker = std::thread::spawn(move || {
+        let mut started = Some(started);
+        let res = run(&database, &query, wrt, &mut started);
+        if let (Err(err), Some(started)) = (&res, started) {
+            let _ = started.send(Err(err.clone()));
+        }
+        res
+    });
+    match start.recv() {
+        Ok(Ok(())) => Ok(Rows { pipe, worker: Some(worker) }),
+        Ok(Err(err)) => Err(format!("{}: {}", name(url), err)),
+        Err(_) => Err(format!("{}: query thread ended without rows", name(url))),
+    }
+}
+
+/// tells `open` that the rows are coming
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+fn start(started: &mut Option<Sender<Result<(), String>>>) {
+    if let Some(started) = started.take() {
+        let _ = started.send(Ok(()));
+    }
+}
+
+/// the server writes the rows as csv itself, numeric amounts keep their digits
+#[cfg(feature = "postgres")]
+fn postgres(
+    database: &str,
+    query: &str,
+    mut wrt: std::io::PipeWriter,
+    started: &mut Option<Sender<Result<(), String>>>,
+) -> Result<(), String> {
+    let mut client = postgres::Client::connect(database, postgres::NoTls).map_err(|err| err.to_string())?;
+    let copy = format!("COPY ({}) TO STDOUT WITH (FORMAT csv, HEADER)", query.trim().trim_end_matches(';'));
+    let mut rows = client.copy_out(copy.as_str()).map_err(|err| err.to_string())?;
+    start(started);
+    match std::io::copy(&mut rows, &mut wrt) {
+        // the reader stopped early, nothing is left to report to
+        Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        res => res.map(|_| ()).map_err(|err| err.to_string()),
+    }
+}
+
+/// values as sqlite gives them: integers and reals in their shortest form, NULL empty
+#[cfg(feature = "sqlite")]
+fn sqlite(
+    database: &str,
+    query: &str,
+    wrt: std::io::PipeWriter,
+    started: &mut Option<Sender<Result<(), String>>>,
+) -> Result<(), String> {
+    use rusqlite::types::ValueRef;
+    let path = database.strip_prefix("sqlite://").expect("sqlite url");
+    let connection = rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|err| err.to_string())?;
+    let mut statement = connection.prepare(query).map_err(|err| err.to_string())?;
+    let names: Vec<String> = statement.column_names().into_iter().map(String::from).collect();
+    let mut rows = statement.query([]).map_err(|err| err.to_string())?;
+    start(started);
+    let mut out = csv::Writer::from_writer(wrt);
+    let mut write = |record: Vec<String>| match out.write_record(record) {
+        Err(err) if matches!(err.kind(), csv::ErrorKind::Io(err) if err.kind() == std::io::ErrorKind::BrokenPipe) => Ok(false),
+        res => res.map(|_| true).map_err(|err| err.to_string()),
+    };
+    if !write(names.clone())? {
+        return Ok(());
+    }
+    while let Some(row) = rows.next().map_err(|err| err.to_string())? {
+        let mut record = Vec::with_capacity(names.len());
+        for idx in 0..names.len() {
+            record.push(match row.get_ref(idx).map_err(|err| err.to_string())? {
+                ValueRef::Null => String::new(),
+                ValueRef::Integer(value) => value.to_string(),
+                ValueRef::Real(value) => value.to_string(),
+                ValueRef::Text(text) | ValueRef::Blob(text) => String::from_utf8_lossy(text).into_owned(),
+            });
+        }
+        if !write(record)? {
+            return Ok(());
+        }
+    }
+    out.flush().map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_url() {
+        let url = "postgres://app:secret@db:5432/ledger?sslmode=disable&query=SELECT type,client,tx,amount FROM txs ORDER BY id";
+        assert_eq!(
+            split(url),
+            Ok(("postgres://app:secret@db:5432/ledger?sslmode=disable", "SELECT type,client,tx,amount FROM txs ORDER BY id"))
+        );
+        assert_eq!(name(url), "postgres://db:5432/ledger?sslmode=disable");
+        assert_eq!(split("sqlite:///tmp/txs.db?query=SELECT * FROM txs"), Ok(("sqlite:///tmp/txs.db", "SELECT * FROM txs")));
+        assert!(split("postgres://db/ledger").is_err());
+        assert!(is_query("sqlite://txs.db?query=SELECT 1") && !is_query("txs.csv"));
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn test_sqlite_rows_as_csv() {
+        let path = std::env::temp_dir().join(format!("query-{}.db", std::process::id()));
+        let connection = rusqlite::Connection::open(&path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE txs (id INTEGER, type TEXT, client INTEGER, tx INTEGER, amount TEXT);
+                 INSERT INTO txs VALUES (2, 'withdrawal', 1, 2, '0.5'), (1, 'deposit', 1, 1, '1.5'), (3, 'dispute', 1, 1, NULL);",
+            )
+            .unwrap();
+        let url = format!("sqlite://{}?query=SELECT type, client, tx, amount FROM txs ORDER BY id", path.display());
+        let mut content = String::new();
+        open(&url).unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "type,client,tx,amount\ndeposit,1,1,1.5\nwithdrawal,1,2,0.5\ndispute,1,1,\n");
+        let err = open(&format!("sqlite://{}?query=SELECT missing FROM txs", path.display())).err().unwrap();
+        assert!(err.contains("no such column"), "{}", err);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
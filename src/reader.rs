@@ -0,0 +1,549 @@
+use crate::amount;
+use crate::engine::{Transaction, TxType};
+use crate::ids::SharedIds;
+use crate::rejection::{Reason, Rejected};
+use crate::precision::Precision;
+use crate::store::{self, Input};
+use std::io::Read;
+
+pub use bank::BankAccounts;
+pub use locale::AmountFormat;
+
+#[cfg(feature = "avro")]
+mod avro;
+#[cfg(feature = "cbor")]
+mod cbor;
+pub mod bank;
+mod camt;
+mod fix;
+#[cfg(feature = "fast-parser")]
+mod fast;
+pub mod header;
+#[cfg(feature = "iso8583")]
+mod iso8583;
+mod locale;
+mod ofx;
+mod qif;
+#[cfg(feature = "mmap")]
+mod mapped;
+#[cfg(feature = "msgpack")]
+mod msgpack;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+
+/// csv reader settings for the transaction input format:
+/// surrounding whitespaces are ignored and the amount column may be missing
+pub fn builder() -> csv::ReaderBuilder {
+    let mut builder = csv::ReaderBuilder::new();
+    builder.trim(csv::Trim::All).flexible(true);
+    builder
+}
+
+pub fn open(path: &str) -> csv::Reader<Input> {
+    let res = store::open(path);
+    assert!(res.is_ok(), "file does not exist");
+    from_reader(res.unwrap())
+}
+
+pub fn from_reader<R: Read>(input: R) -> csv::Reader<R> {
+    let rdr = builder().from_reader(input);
+    assert!(
+        rdr.has_headers(),
+        "please change input file and add an header line"
+        );
+    rdr
+}
+
+/// Names of the input columns when they differ from `type,client,tx,amount`
+#[derive(serde::Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Columns {
+    #[serde(rename = "type")]
+    pub tx_type: Option<String>,
+    pub client: Option<String>,
+    pub tx: Option<String>,
+    pub amount: Option<String>,
+    pub timestamp: Option<String>,
+}
+
+impl Columns {
+    /// header with the mapped column names replaced by the standard ones
+    pub fn rename(&self, headers: &csv::StringRecord) -> csv::StringRecord {
+        let mapping = [
+            (&self.tx_type, "type"),
+            (&self.client, "client"),
+            (&self.tx, "tx"),
+            (&self.amount, "amount"),
+            (&self.timestamp, "timestamp"),
+        ];
+        headers
+            .iter()
+            .map(|header| {
+                mapping
+                    .iter()
+                    .find(|(name, _)| name.as_deref() == Some(header))
+                    .map_or(header, |(_, standard)| standard)
+            })
+            .collect()
+    }
+}
+
+/// Csv layout of partner files differing from the standard one
+#[derive(Debug, Clone)]
+pub struct Dialect {
+    pub delimiter: u8,
+    pub quote: u8,
+    /// quotes escaped with this character instead of being doubled
+    pub escape: Option<u8>,
+    /// without a header line columns are expected in the standard order
+    pub headers: bool,
+    pub columns: Columns,
+    pub amounts: AmountFormat,
+    /// amounts with more decimal places are kept as they are without a policy
+    pub precision: Option<Precision>,
+    /// client column holding external ids, translated into client ids
+    pub ids: Option<SharedIds>,
+    /// clients of the accounts of OFX and QIF statements
+    pub bank: BankAccounts,
+}
+
+impl Default for Dialect {
+    fn default() -> Dialect {
+        Dialect {
+            delimiter: b',',
+            quote: b'"',
+            escape: None,
+            headers: true,
+            columns: Columns::default(),
+            amounts: AmountFormat::default(),
+            precision: None,
+            ids: None,
+            bank: BankAccounts::default(),
+        }
+    }
+}
+
+/// csv records read with the given layout, see `transactions_with` for parsing them,
+/// or why the header line cannot be read or does not match the expected columns
+pub fn from_reader_with<R: Read>(dialect: &Dialect, input: R) -> Result<csv::Reader<R>, String> {
+    let mut rdr = builder()
+        .delimiter(dialect.delimiter)
+        .quote(dialect.quote)
+        .escape(dialect.escape)
+        .double_quote(dialect.escape.is_none())
+        .from_reader(input);
+    let headers = if dialect.headers {
+        let headers = rdr.headers().map_err(|err| format!("error in reading the header line: {}", err))?;
+        let headers = dialect.columns.rename(headers);
+        header::check(&headers).map_err(|err| format!("unexpected header line: {}", err))?;
+        headers
+    } else {
+        // headers set before the first read make the first line a regular record
+        csv::StringRecord::from(vec!["type", "client", "tx", "amount"])
+    };
+    rdr.set_headers(headers);
+    Ok(rdr)
+}
+
+/// Parsed transactions of the `source` file with amounts written in the dialect
+/// format, rows with an unknown type or that do not parse are rejected. The
+/// amount column is parsed here rather than by serde, which would go through a
+/// float and lose precision. The records end at the first read error.
+pub fn transactions_with<R: Read>(
+    source: &str,
+    dialect: &Dialect,
+    rdr: csv::Reader<R>,
+) -> impl Iterator<Item = Result<Transaction, Rejected>> {
+    located_with(source, dialect, rdr).map(|(_, res)| res)
+}
+
+/// the rows of `transactions_with` with the 1-based line each starts on
+pub fn located_with<R: Read>(
+    source: &str,
+    dialect: &Dialect,
+    mut rdr: csv::Reader<R>,
+) -> impl Iterator<Item = (Option<u64>, Result<Transaction, Rejected>)> {
+    // headers are passed explicitly as they may have been set rather than read from the file
+    let source = source.to_string();
+    let res = rdr.headers().cloned();
+    let mut failed = res.as_ref().err().map(|err| {
+        let detail = format!("error in reading the header line: {}", err);
+        Rejected::new(Reason::Malformed, None, None, detail).at(&source, Some(1), String::new())
+    });
+    let mut done = failed.is_some();
+    let mut headers = res.unwrap_or_default();
+    let amount_idx = headers.iter().position(|header| header == "amount");
+    let type_idx = headers.iter().position(|header| header == "type");
+    let client_idx = headers.iter().position(|header| header == "client");
+    // hide the amount column from serde, it is set afterwards
+    headers = headers
+        .iter()
+        .map(|header| if header == "amount" { "" } else { header })
+        .collect();
+    let amounts = dialect.amounts;
+    let precision = dialect.precision;
+    let ids = dialect.ids.clone();
+    let delimiter = char::from(dialect.delimiter).to_string();
+    let records = rdr.into_records().map_while(move |res| {
+        if done {
+            return None;
+        }
+        let record = match res {
+            Ok(record) => record,
+            Err(err) => {
+                let line = err.position().map(|pos| pos.line());
+                // the reader cannot go on after an io error, an invalid utf-8 record is only skipped
+                done = matches!(err.kind(), csv::ErrorKind::Io(_));
+                let detail = format!("error in reading a transaction record: {}", err);
+                return Some((line, Err(Rejected::new(Reason::Malformed, None, None, detail).at(&source, line, String::new()))));
+            }
+        };
+        let line = record.position().map(|pos| pos.line());
+        let raw = record.iter().collect::<Vec<_>>().join(&delimiter);
+        let record = match (&ids, client_idx) {
+            (Some(ids), Some(idx)) if record.get(idx).is_some_and(|external| !external.is_empty()) => {
+                let client = ids.lock().expect("id mapping lock").client(&record[idx]).to_string();
+                record.iter().enumerate().map(|(i, field)| if i == idx { client.as_str() } else { field }).collect()
+            }
+            _ => record,
+        };
+        // rows may omit the trailing amount, serde wants as many headers as fields
+        let short: csv::StringRecord;
+        let row_headers = if record.len() < headers.len() {
+            short = headers.iter().take(record.len()).collect();
+            &short
+        } else {
+            &headers
+        };
+        let res = record
+            .deserialize::<Transaction>(Some(row_headers))
+            .map_err(|err| err.to_string())
+            .and_then(|mut tx| {
+                let raw_amount = amount_idx.and_then(|idx| record.get(idx)).filter(|raw| !raw.is_empty());
+                tx.amount = raw_amount.map(|raw| amounts.parse(raw)).transpose()?;
+                Ok(tx)
+            });
+        let tx = match res {
+            Ok(tx) => tx,
+            Err(err) => {
+                let detail = format!("error in parsing a transaction record: {}", err);
+                return Some((line, Err(Rejected::new(Reason::Malformed, None, None, detail).at(&source, line, raw))));
+            }
+        };
+        let res = accept(tx, precision).map_err(|rejected| match rejected.reason {
+            Reason::UnknownType => {
+                let raw_type = type_idx.and_then(|idx| record.get(idx)).unwrap_or_default();
+                Rejected {
+                    detail: format!("unknown transaction type `{}`", raw_type),
+                    ..rejected.at(&source, line, raw)
+                }
+            }
+            _ => rejected.at(&source, line, raw),
+        });
+        Some((line, res))
+    });
+    failed.take().map(|rejected| (Some(1), Err(rejected))).into_iter().chain(records)
+}
+
+/// transactions the engine can process, the others are turned into rejections
+fn accept(mut tx: Transaction, precision: Option<Precision>) -> Result<Transaction, Rejected> {
+    if tx.tx_type == TxType::Unknown {
+        let detail = "unknown transaction type".to_string();
+        return Err(Rejected::new(Reason::UnknownType, Some(tx.client), Some(tx.tx), detail));
+    }
+    if matches!(tx.tx_type, TxType::Deposit | TxType::Withdrawal | TxType::Escrow) && tx.amount.is_none() {
+        let detail = format!("{:?} without amount", tx.tx_type).to_lowercase();
+        return Err(Rejected::new(Reason::MissingAmount, Some(tx.client), Some(tx.tx), detail));
+    }
+    if let (Some(precision), Some(amount)) = (precision, tx.amount) {
+        tx.amount = precision.apply(amount);
+        if tx.amount.is_none() {
+            let detail = format!("amount {} has more than {} decimal places", amount, precision.scale);
+            return Err(Rejected::new(Reason::Precision, Some(tx.client), Some(tx.tx), detail));
+        }
+    }
+    if let Some(Err(detail)) = tx.amount.map(amount::representable) {
+        return Err(Rejected::new(Reason::Precision, Some(tx.client), Some(tx.tx), detail));
+    }
+    Ok(tx)
+}
+
+/// Records of files other than csv carry no line information, only the file is
+/// reported. A record that cannot be read or decoded is the last one, the
+/// records after it cannot be told apart.
+fn accept_from(
+    source: &str,
+    precision: Option<Precision>,
+    txs: impl Iterator<Item = Result<Transaction, String>>,
+) -> impl Iterator<Item = Result<Transaction, Rejected>> {
+    let source = source.to_string();
+    let mut done = false;
+    txs.map_while(move |res| {
+        if done {
+            return None;
+        }
+        Some(match res {
+            Ok(tx) => accept(tx, precision).map_err(|rejected| rejected.at(&source, None, String::new())),
+            Err(detail) => {
+                done = true;
+                Err(Rejected::new(Reason::Malformed, None, None, detail).at(&source, None, String::new()))
+            }
+        })
+    })
+}
+
+/// Message of a queue or stream, to acknowledge with its `delivery` once processed
+pub struct Message<D> {
+    pub tx: Result<Transaction, Rejected>,
+    pub delivery: D,
+}
+
+/// Transaction of a message of a queue or stream: a JSON object with the
+/// columns of a csv row as keys, the amount a number or a string
+pub fn message(source: &str, precision: Option<Precision>, body: &[u8]) -> Result<Transaction, Rejected> {
+    let raw = || String::from_utf8_lossy(body).into_owned();
+    match serde_json::from_slice(body) {
+        Ok(tx) => accept(tx, precision).map_err(|rejected| rejected.at(source, None, raw())),
+        Err(err) => Err(Rejected::new(Reason::Malformed, None, None, err.to_string()).at(source, None, raw())),
+    }
+}
+
+/// Encoding of the input file
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Csv,
+    /// OFX bank statement, 1.x SGML or 2.x XML, see the `[bank]` config table
+    Ofx,
+    /// QIF bank export, see the `[bank]` config table
+    Qif,
+    /// ISO 20022 camt.053 XML bank statement, see the `[bank]` config table
+    Camt053,
+    /// FIX drop copy of execution reports, see the `[bank]` config table
+    Fix,
+    /// stream of concatenated MessagePack records
+    #[cfg(feature = "msgpack")]
+    Msgpack,
+    /// stream of concatenated CBOR records
+    #[cfg(feature = "cbor")]
+    Cbor,
+    /// Avro object container file, resolved against the bundled schema
+    #[cfg(feature = "avro")]
+    Avro,
+    /// length-prefixed ISO 8583 card messages, a simplified subset
+    #[cfg(feature = "iso8583")]
+    Iso8583,
+    /// length-delimited stream of protobuf `Transaction` messages
+    #[cfg(feature = "protobuf")]
+    Protobuf,
+}
+
+/// Parsed transactions of the `source` file in the given format, or why its
+/// csv header line, or the file as a whole for the formats read at once, does
+/// not allow reading them
+pub fn read<R: Read + 'static>(
+    source: &str,
+    format: Format,
+    dialect: &Dialect,
+    input: R,
+) -> Result<Box<dyn Iterator<Item = Result<Transaction, Rejected>>>, String> {
+    Ok(match format {
+        Format::Csv => Box::new(transactions_with(source, dialect, from_reader_with(dialect, input)?)),
+        Format::Ofx => Box::new(accept_from(source, dialect.precision, ofx::transactions(input, &dialect.bank)?.map(Ok))),
+        Format::Qif => Box::new(accept_from(source, dialect.precision, qif::transactions(input, &dialect.bank)?.map(Ok))),
+        Format::Camt053 => Box::new(accept_from(source, dialect.precision, camt::transactions(input, &dialect.bank)?.map(Ok))),
+        Format::Fix => Box::new(accept_from(source, dialect.precision, fix::transactions(input, &dialect.bank)?.map(Ok))),
+        #[cfg(feature = "msgpack")]
+        Format::Msgpack => Box::new(accept_from(source, dialect.precision, msgpack::transactions(input))),
+        #[cfg(feature = "cbor")]
+        Format::Cbor => Box::new(accept_from(source, dialect.precision, cbor::transactions(input))),
+        #[cfg(feature = "avro")]
+        Format::Avro => Box::new(accept_from(source, dialect.precision, avro::transactions(input)?)),
+        #[cfg(feature = "iso8583")]
+        Format::Iso8583 => Box::new(accept_from(source, dialect.precision, iso8583::transactions(input))),
+        #[cfg(feature = "protobuf")]
+        Format::Protobuf => Box::new(accept_from(source, dialect.precision, protobuf::transactions(input))),
+    })
+}
+
+/// Transactions of a csv file read through a memory map when its layout allows,
+/// with the regular csv reader otherwise.
+#[cfg(feature = "mmap")]
+pub fn read_mapped(source: &str, dialect: &Dialect, file: std::fs::File) -> Result<Box<dyn Iterator<Item = Result<Transaction, Rejected>>>, String> {
+    match mapped::transactions(source, dialect, &file)? {
+        Some(records) => Ok(Box::new(records)),
+        None => read(source, Format::Csv, dialect, file),
+    }
+}
+
+/// Transactions of a csv input split with memchr, see `fast::transactions`,
+/// with the regular csv reader for other layouts.
+#[cfg(feature = "fast-parser")]
+pub fn read_fast<R: Read + 'static>(source: &str, dialect: &Dialect, input: R) -> Result<Box<dyn Iterator<Item = Result<Transaction, Rejected>>>, String> {
+    fast::transactions(source, dialect, input)
+}
+
+/// Records of a self-delimiting binary encoding, decoded one after the other
+/// until the input ends cleanly between two records.
+#[cfg(any(feature = "msgpack", feature = "cbor"))]
+fn records<R: Read, E: std::fmt::Display>(
+    input: R,
+    mut decode: impl FnMut(&mut std::io::BufReader<R>) -> Result<Transaction, E>,
+) -> impl Iterator<Item = Result<Transaction, String>> {
+    use std::io::{BufRead, BufReader};
+
+    let mut input = BufReader::new(input);
+    std::iter::from_fn(move || match input.fill_buf() {
+        Ok([]) => None,
+        Ok(_) => Some(decode(&mut input).map_err(|err| format!("error in parsing a transaction record: {}", err))),
+        Err(err) => Some(Err(format!("error in reading input: {}", err))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TxType;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_semicolon_dialect_with_column_mapping() {
+        let input = "kind;customer;id;value\n'deposit';1;1;'1.5'\nwithdrawal;1;2;0.5\n";
+        let dialect = Dialect {
+            delimiter: b';',
+            quote: b'\'',
+            columns: Columns {
+                tx_type: Some("kind".to_string()),
+                client: Some("customer".to_string()),
+                tx: Some("id".to_string()),
+                amount: Some("value".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let rdr = from_reader_with(&dialect, input.as_bytes()).unwrap();
+        let txs: Vec<Transaction> = transactions_with("in.csv", &dialect, rdr).map(Result::unwrap).collect();
+        assert_eq!(txs.len(), 2);
+        assert_eq!(txs[0].tx_type, TxType::Deposit);
+        assert_eq!(txs[0].amount, Some(dec!(1.5)));
+        assert_eq!(txs[1].tx, 2);
+    }
+
+    #[test]
+    fn test_tab_dialect_without_headers() {
+        let input = "deposit\t7\t1\t2.0\ndispute\t7\t1\t\n";
+        let dialect = Dialect { delimiter: b'\t', headers: false, ..Default::default() };
+        let rdr = from_reader_with(&dialect, input.as_bytes()).unwrap();
+        let txs: Vec<Transaction> = transactions_with("in.csv", &dialect, rdr).map(Result::unwrap).collect();
+        assert_eq!(txs.len(), 2);
+        assert_eq!(txs[0].client, 7);
+        assert_eq!(txs[1].tx_type, TxType::Dispute);
+        assert_eq!(txs[1].amount, None);
+    }
+
+    #[test]
+    fn test_precision_policy() {
+        use crate::precision::{Policy, Rounding};
+        let input = "type,client,tx,amount\ndeposit,1,1,1.00005\ndeposit,1,2,2.5\n";
+        let read = |policy| {
+            let precision = Some(Precision { policy, rounding: Rounding::HalfUp, scale: 4 });
+            let dialect = Dialect { precision, ..Default::default() };
+            transactions_with("in.csv", &dialect, from_reader(input.as_bytes())).collect::<Vec<_>>()
+        };
+        assert_eq!(read(Policy::Round)[0].as_ref().unwrap().amount, Some(dec!(1.0001)));
+        let res = read(Policy::Reject);
+        let rejected = res[0].as_ref().unwrap_err();
+        assert_eq!((rejected.reason, rejected.line), (Reason::Precision, Some(2)));
+        assert_eq!(res[1].as_ref().unwrap().amount, Some(dec!(2.5)));
+    }
+
+    #[test]
+    #[cfg(feature = "fixed-point")]
+    fn test_amount_beyond_fixed_point_is_rejected() {
+        let input = "type,client,tx,amount\ndeposit,1,1,1.00001\ndeposit,1,2,10000000000000000\ndeposit,1,3,1.0001\n";
+        let res: Vec<_> = transactions_with("in.csv", &Dialect::default(), from_reader(input.as_bytes())).collect();
+        let rejected = res[0].as_ref().unwrap_err();
+        assert_eq!((rejected.reason, rejected.line), (Reason::Precision, Some(2)));
+        assert!(rejected.detail.contains("more than 4 decimal places"), "{}", rejected.detail);
+        let rejected = res[1].as_ref().unwrap_err();
+        assert_eq!(rejected.reason, Reason::Precision);
+        assert!(rejected.detail.contains("out of range"), "{}", rejected.detail);
+        assert_eq!(res[2].as_ref().unwrap().amount, Some(dec!(1.0001)));
+        let rejected = message("txs", None, br#"{"type": "deposit", "client": 1, "tx": 4, "amount": "0.00001"}"#).unwrap_err();
+        assert_eq!(rejected.reason, Reason::Precision);
+    }
+
+    #[test]
+    fn test_external_client_ids() {
+        let ids = SharedIds::default();
+        let dialect = Dialect { ids: Some(ids.clone()), ..Default::default() };
+        let input = "type,client,tx,amount\ndeposit,9b2e-41,1,1.0\ndeposit,c07a-12,2,2.0\nwithdrawal,9b2e-41,3,0.5\n";
+        let clients: Vec<_> = transactions_with("in.csv", &dialect, from_reader(input.as_bytes()))
+            .map(|res| res.unwrap().client)
+            .collect();
+        assert_eq!(clients, vec![1, 2, 1]);
+        assert_eq!(ids.lock().unwrap().external(2), Some("c07a-12"));
+    }
+
+    #[test]
+    fn test_unknown_type_is_rejected() {
+        let input = "type,client,tx,amount\ndeposit,1,1,1.0\nrefund, 1,2,1.0\ndispute,1,1\n";
+        let res: Vec<_> = transactions_with("in.csv", &Dialect::default(), from_reader(input.as_bytes())).collect();
+        assert_eq!(res.len(), 3);
+        assert_eq!(res[2].as_ref().unwrap().amount, None);
+        assert_eq!(res[0].as_ref().unwrap().tx, 1);
+        let rejected = res[1].as_ref().unwrap_err();
+        assert_eq!(rejected.reason, Reason::UnknownType);
+        assert_eq!(rejected.tx, Some(2));
+        assert!(rejected.detail.contains("refund"));
+        assert_eq!(rejected.file, "in.csv");
+        assert_eq!(rejected.line, Some(3));
+        assert_eq!(rejected.raw, "refund,1,2,1.0");
+    }
+
+    #[test]
+    fn test_malformed_record_is_rejected() {
+        let input = b"type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,x,2,1.0\ndeposit,1,\xff,1.0\ndeposit,1,4,2.0\nwithdrawal,1,5\n";
+        let res: Vec<_> = transactions_with("in.csv", &Dialect::default(), from_reader(&input[..])).collect();
+        assert_eq!(res.len(), 5);
+        let rejected = res[1].as_ref().unwrap_err();
+        assert_eq!((rejected.reason, rejected.line), (Reason::Malformed, Some(3)));
+        assert!(rejected.detail.starts_with("error in parsing a transaction record"));
+        assert_eq!(rejected.raw, "deposit,x,2,1.0");
+        assert_eq!(res[2].as_ref().unwrap_err().reason, Reason::Malformed);
+        assert_eq!(res[3].as_ref().unwrap().tx, 4);
+        let rejected = res[4].as_ref().unwrap_err();
+        assert_eq!((rejected.reason, rejected.detail.as_str()), (Reason::MissingAmount, "withdrawal without amount"));
+    }
+
+    #[test]
+    #[cfg(feature = "msgpack")]
+    fn test_undecodable_record_ends_the_file() {
+        let mut input = rmp_serde::to_vec_named(&serde_json::json!({"type": "deposit", "client": 1, "tx": 1, "amount": "1.5"})).unwrap();
+        input.extend([0xc1, 0x92, 0x01]);
+        let res: Vec<_> = read("in.mp", Format::Msgpack, &Dialect::default(), std::io::Cursor::new(input)).unwrap().collect();
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].as_ref().unwrap().tx, 1);
+        assert_eq!(res[1].as_ref().unwrap_err().reason, Reason::Malformed);
+    }
+
+    #[test]
+    fn test_message_body() {
+        let tx = message("txs", None, br#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.5"}"#).unwrap();
+        assert_eq!((tx.tx_type, tx.client, tx.tx), (TxType::Deposit, 1, 1));
+        assert_eq!(tx.amount, Some(dec!(1.5)));
+        let rejected = message("txs", None, br#"{"type": "refund", "client": 1, "tx": 2}"#).unwrap_err();
+        assert_eq!(rejected.reason, Reason::UnknownType);
+        let rejected = message("txs", None, b"deposit,1,3,1.0").unwrap_err();
+        assert_eq!((rejected.reason, rejected.file.as_str()), (Reason::Malformed, "txs"));
+        assert_eq!(rejected.raw, "deposit,1,3,1.0");
+    }
+
+    #[test]
+    fn test_unexpected_header_is_an_error() {
+        let err = read("in.csv", Format::Csv, &Dialect::default(), "kind,client,tx\ndeposit,1,1\n".as_bytes()).err();
+        assert!(err.unwrap().starts_with("unexpected header line: missing columns: type, amount"));
+        let err = read("in.csv", Format::Csv, &Dialect::default(), &b"type,\xff\n"[..]).err();
+        assert!(err.unwrap().starts_with("error in reading the header line"));
+    }
+}
@@ -0,0 +1,103 @@
+use crate::engine::Transaction;
+use apache_avro::{Reader, Schema};
+use std::io::Read;
+use std::sync::LazyLock;
+
+/// Bundled reader schema, data written with older or newer schemas is resolved against it
+pub static SCHEMA: LazyLock<Schema> = LazyLock::new(|| {
+    let res = Schema::parse_str(include_str!("../../schemas/transaction.avsc"));
+    assert!(res.is_ok(), "invalid bundled avro schema: {:?}", res.err());
+    res.unwrap()
+});
+
+/// Records of an Avro object container file, or why its header cannot be read
+pub fn transactions<R: Read>(input: R) -> Result<impl Iterator<Item = Result<Transaction, String>>, String> {
+    let rdr = Reader::builder(input)
+        .reader_schema(&SCHEMA)
+        .build()
+        .map_err(|err| format!("error in reading avro header: {}", err))?;
+    Ok(rdr.map(|res| res.and_then(|value| apache_avro::from_value::<Transaction>(&value)).map_err(|err| err.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TxType;
+    use apache_avro::types::{Record, Value};
+    use apache_avro::Writer;
+    use rust_decimal_macros::dec;
+
+    fn write(schema: &str, records: &[Vec<(&str, Value)>]) -> Vec<u8> {
+        let schema = Schema::parse_str(schema).unwrap();
+        let mut writer = Writer::new(&schema, Vec::new()).unwrap();
+        for fields in records {
+            let mut record = Record::new(&schema).unwrap();
+            for (name, value) in fields {
+                record.put(name, value.clone());
+            }
+            writer.append_value(record).unwrap();
+        }
+        writer.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_read_current_schema() {
+        let input = write(
+            include_str!("../../schemas/transaction.avsc"),
+            &[vec![
+                ("type", Value::String("deposit".into())),
+                ("client", Value::Int(1)),
+                ("tx", Value::Long(1)),
+                ("amount", Value::Union(1, Box::new(Value::String("1.2345".into())))),
+            ]],
+        );
+        let txs: Vec<Transaction> = transactions(input.as_slice()).unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(txs.len(), 1);
+        assert_eq!(txs[0].tx_type, TxType::Deposit);
+        assert_eq!(txs[0].amount, Some(dec!(1.2345)));
+    }
+
+    #[test]
+    fn test_read_older_and_newer_schemas() {
+        // older producers did not send amounts for dispute flows
+        let older = r#"{"type": "record", "name": "Transaction", "fields": [
+            {"name": "type", "type": "string"},
+            {"name": "client", "type": "int"},
+            {"name": "tx", "type": "long"}
+        ]}"#;
+        let input = write(
+            older,
+            &[vec![
+                ("type", Value::String("dispute".into())),
+                ("client", Value::Int(2)),
+                ("tx", Value::Long(7)),
+            ]],
+        );
+        let txs: Vec<Transaction> = transactions(input.as_slice()).unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(txs[0].tx_type, TxType::Dispute);
+        assert_eq!(txs[0].amount, None);
+
+        // newer producers add fields the engine does not know about
+        let newer = r#"{"type": "record", "name": "Transaction", "fields": [
+            {"name": "type", "type": "string"},
+            {"name": "client", "type": "int"},
+            {"name": "tx", "type": "long"},
+            {"name": "amount", "type": ["null", "string", "double"]},
+            {"name": "currency", "type": "string"}
+        ]}"#;
+        let input = write(
+            newer,
+            &[vec![
+                ("type", Value::String("withdrawal".into())),
+                ("client", Value::Int(2)),
+                ("tx", Value::Long(8)),
+                ("amount", Value::Union(2, Box::new(Value::Double(0.5)))),
+                ("currency", Value::String("EUR".into())),
+            ]],
+        );
+        let txs: Vec<Transaction> = transactions(input.as_slice()).unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(txs[0].tx_type, TxType::Withdrawal);
+        assert_eq!(txs[0].client, 2);
+        assert_eq!(txs[0].amount, Some(dec!(0.5)));
+    }
+}
@@ -0,0 +1,78 @@
+use crate::engine::{Transaction, TxType};
+use crate::timestamp::Timestamp;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+/// Clients of the accounts of bank statement files, the `[bank]` config table
+#[derive(serde::Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BankAccounts {
+    /// client of the entries of accounts not listed in `accounts`
+    pub client: Option<u16>,
+    /// account number or name --> client
+    pub accounts: BTreeMap<String, u16>,
+    /// tx id of the first entry of a file, the next ones are numbered from it
+    pub first_tx: Option<u32>,
+}
+
+/// Booked entry of a statement, credits are positive
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Entry {
+    pub account: Option<String>,
+    pub amount: Decimal,
+    pub posted: Option<Timestamp>,
+    /// payee or payer named by the bank
+    pub counterparty: Option<String>,
+}
+
+/// Statement entries as deposits and withdrawals of the mapped clients, numbered
+/// in file order, or the first bank account without a client
+pub fn transactions(entries: Vec<Entry>, accounts: &BankAccounts) -> Result<impl Iterator<Item = Transaction>, String> {
+    let first_tx = accounts.first_tx.unwrap_or(1);
+    let client = |account: Option<&str>| account.and_then(|account| accounts.accounts.get(account).copied()).or(accounts.client);
+    if let Some(entry) = entries.iter().find(|entry| client(entry.account.as_deref()).is_none()) {
+        return Err(format!(
+            "no client for bank account {}, map it in the [bank] table of the config",
+            entry.account.as_deref().unwrap_or("without a number")
+        ));
+    }
+    let clients: Vec<u16> = entries.iter().filter_map(|entry| client(entry.account.as_deref())).collect();
+    Ok(entries.into_iter().zip(clients).zip(first_tx..).map(move |((entry, client), tx)| {
+        let tx_type = if entry.amount.is_sign_negative() { TxType::Withdrawal } else { TxType::Deposit };
+        Transaction {
+            tx_type,
+            client,
+            tx,
+            amount: Some(entry.amount.abs()),
+            timestamp: entry.posted,
+            counterparty: entry.counterparty,
+            ..Default::default()
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_entries_to_transactions() {
+        let accounts = BankAccounts {
+            client: Some(9),
+            accounts: BTreeMap::from([("123".to_string(), 4)]),
+            first_tx: Some(100),
+        };
+        let entry = |account: Option<&str>, amount| Entry { account: account.map(str::to_string), amount, ..Entry::default() };
+        let entries = vec![entry(Some("123"), dec!(10.5)), entry(Some("999"), dec!(-2.25)), entry(None, dec!(1))];
+        let txs: Vec<Transaction> = transactions(entries, &accounts).unwrap().collect();
+        let summary: Vec<_> = txs.iter().map(|tx| (tx.client, tx.tx, tx.tx_type, tx.amount.unwrap())).collect();
+        assert_eq!(
+            summary,
+            vec![(4, 100, TxType::Deposit, dec!(10.5)), (9, 101, TxType::Withdrawal, dec!(2.25)), (9, 102, TxType::Deposit, dec!(1))]
+        );
+        let unmapped = BankAccounts { client: None, ..accounts };
+        let err = transactions(vec![entry(Some("999"), dec!(1))], &unmapped).err();
+        assert!(err.unwrap().starts_with("no client for bank account 999"));
+    }
+}
@@ -0,0 +1,216 @@
+use super::bank::{self, BankAccounts, Entry};
+use crate::engine::Transaction;
+use crate::timestamp;
+use rust_decimal::Decimal;
+use std::io::Read;
+use std::str::FromStr;
+
+/// Booked `<Ntry>` entries of an ISO 20022 camt.053 statement. Entries
+/// belong to the IBAN, or other id, of the `<Acct>` of their statement;
+/// pending and informational ones are left out.
+pub fn transactions<R: Read>(mut input: R, accounts: &BankAccounts) -> Result<impl Iterator<Item = Transaction>, String> {
+    let mut text = String::new();
+    input.read_to_string(&mut text).map_err(|err| format!("error in reading a camt.053 file: {}", err))?;
+    let entries = entries(&text).map_err(|err| format!("error in parsing a camt.053 file: {}", err))?;
+    bank::transactions(entries, accounts)
+}
+
+enum Event<'a> {
+    Open,
+    Text(&'a str),
+    Close,
+}
+
+/// text with the predefined entities and character references replaced
+fn unescape(text: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find(';') else {
+            return Err(format!("unterminated entity in `{}`", text));
+        };
+        let entity = &rest[start + 1..start + len];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match entity.strip_prefix("#x") {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => entity.strip_prefix('#').and_then(|dec| dec.parse().ok()).and_then(char::from_u32),
+            },
+        };
+        out.push(decoded.ok_or_else(|| format!("unknown entity `&{};`", entity))?);
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Calls `visit` on every element opened and closed, and on its text, with
+/// the path of element names down to it; namespace prefixes and attributes
+/// are dropped.
+fn walk<'a>(xml: &'a str, mut visit: impl FnMut(&[&'a str], Event<'a>) -> Result<(), String>) -> Result<(), String> {
+    let mut path: Vec<&str> = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        let text = rest[..start].trim();
+        if !text.is_empty() {
+            visit(&path, Event::Text(text))?;
+        }
+        rest = &rest[start..];
+        // declaration, processing instructions and comments
+        if let Some((_, close)) = [("<?", "?>"), ("<!--", "-->"), ("<!", ">")].into_iter().find(|(open, _)| rest.starts_with(open)) {
+            let Some(end) = rest.find(close) else {
+                return Err(format!("unterminated `{}`", &rest[..rest.len().min(10)]));
+            };
+            rest = &rest[end + close.len()..];
+            continue;
+        }
+        let Some(end) = rest.find('>') else {
+            return Err(format!("unterminated tag `{}`", rest.trim()));
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        let local = |name: &'a str| name.rsplit(':').next().unwrap_or(name);
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = local(name.trim());
+            visit(&path, Event::Close)?;
+            if path.pop() != Some(name) {
+                return Err(format!("unexpected closing tag `</{}>`", name));
+            }
+            continue;
+        }
+        path.push(local(tag.trim_end_matches('/').split_whitespace().next().unwrap_or_default()));
+        visit(&path, Event::Open)?;
+        if tag.ends_with('/') {
+            visit(&path, Event::Close)?;
+            path.pop();
+        }
+    }
+    match path.last() {
+        Some(name) => Err(format!("element `<{}>` is not closed", name)),
+        None => Ok(()),
+    }
+}
+
+/// Entry being read with its credit or debit indicator, status, and
+/// related parties
+#[derive(Default)]
+struct Pending {
+    entry: Entry,
+    debit: bool,
+    booked: bool,
+    debtor: Option<String>,
+    creditor: Option<String>,
+}
+
+fn entries(xml: &str) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    let mut account: Option<String> = None;
+    let mut pending: Option<Pending> = None;
+    walk(xml, |path, event| {
+        let Some(stmt) = path.iter().position(|name| *name == "Stmt") else {
+            return Ok(());
+        };
+        match (&path[stmt + 1..], event) {
+            (["Ntry"], Event::Open) => {
+                pending = Some(Pending { entry: Entry { account: account.clone(), ..Entry::default() }, ..Pending::default() })
+            }
+            (["Ntry"], Event::Close) => {
+                let Some(mut done) = pending.take().filter(|done| done.booked) else {
+                    return Ok(());
+                };
+                if done.debit {
+                    done.entry.amount = -done.entry.amount;
+                }
+                // the other party: who paid a credit, who was paid by a debit
+                done.entry.counterparty = if done.debit { done.creditor } else { done.debtor };
+                entries.push(done.entry);
+            }
+            (["Acct", "Id", "IBAN"] | ["Acct", "Id", "Othr", "Id"], Event::Text(value)) => account = Some(unescape(value)?),
+            (["Ntry", field @ ..], Event::Text(value)) => {
+                let Some(pending) = pending.as_mut() else {
+                    return Ok(());
+                };
+                match field {
+                    ["Amt"] => {
+                        pending.entry.amount = Decimal::from_str(value).map_err(|_| format!("invalid amount `{}`", value))?
+                    }
+                    ["CdtDbtInd"] => pending.debit = value == "DBIT",
+                    ["Sts"] | ["Sts", "Cd"] => pending.booked = value == "BOOK",
+                    ["BookgDt", "Dt" | "DtTm"] => pending.entry.posted = Some(timestamp::parse(value)?),
+                    [.., "RltdPties", party @ ("Dbtr" | "Cdtr"), "Nm"] | [.., "RltdPties", party @ ("Dbtr" | "Cdtr"), "Pty", "Nm"] => {
+                        let name = if *party == "Dbtr" { &mut pending.debtor } else { &mut pending.creditor };
+                        if name.is_none() {
+                            *name = Some(unescape(value)?);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    })?;
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TxType;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_booked_entries() {
+        let input = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.08">
+  <BkToCstmrStmt>
+    <Stmt>
+      <Acct><Id><IBAN>DE89370400440532013000</IBAN></Id><Ccy>EUR</Ccy></Acct>
+      <Bal><Amt Ccy="EUR">999.00</Amt></Bal>
+      <!-- a credit -->
+      <Ntry>
+        <Amt Ccy="EUR">100.50</Amt><CdtDbtInd>CRDT</CdtDbtInd>
+        <Sts><Cd>BOOK</Cd></Sts>
+        <BookgDt><Dt>2024-03-05</Dt></BookgDt>
+        <NtryDtls><TxDtls><RltdPties>
+          <Dbtr><Pty><Nm>Smith &amp; Sons</Nm></Pty></Dbtr>
+          <Cdtr><Pty><Nm>Us</Nm></Pty></Cdtr>
+        </RltdPties></TxDtls></NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">20.00</Amt><CdtDbtInd>DBIT</CdtDbtInd><Sts><Cd>PDNG</Cd></Sts>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">7.25</Amt><CdtDbtInd>DBIT</CdtDbtInd><Sts>BOOK</Sts>
+        <BookgDt><DtTm>2024-03-06T10:00:00</DtTm></BookgDt>
+        <NtryDtls><TxDtls><RltdPties><Cdtr><Nm>Grocer</Nm></Cdtr></RltdPties></TxDtls></NtryDtls>
+        <AddtlNtryInf/>
+      </Ntry>
+    </Stmt>
+  </BkToCstmrStmt>
+</Document>"#;
+        let accounts = BankAccounts { accounts: [("DE89370400440532013000".to_string(), 5)].into(), ..BankAccounts::default() };
+        let txs: Vec<Transaction> = transactions(input.as_bytes(), &accounts).unwrap().collect();
+        assert_eq!(txs.len(), 2);
+        assert_eq!((txs[0].client, txs[0].tx, txs[0].tx_type, txs[0].amount), (5, 1, TxType::Deposit, Some(dec!(100.50))));
+        assert_eq!(txs[0].counterparty.as_deref(), Some("Smith & Sons"));
+        assert_eq!(txs[0].timestamp, Some(timestamp::parse("2024-03-05").unwrap()));
+        assert_eq!((txs[1].tx, txs[1].tx_type, txs[1].amount), (2, TxType::Withdrawal, Some(dec!(7.25))));
+        assert_eq!(txs[1].counterparty.as_deref(), Some("Grocer"));
+    }
+
+    #[test]
+    fn test_malformed() {
+        let stmt = |ntry: &str| format!("<Document><BkToCstmrStmt><Stmt>{}</Stmt></BkToCstmrStmt></Document>", ntry);
+        assert!(entries(&stmt("<Ntry><Amt>ten</Amt></Ntry>")).is_err());
+        assert!(entries(&stmt("<Ntry><Amt>1</Amt></Stmt>")).is_err());
+        assert!(entries("<Document><Stmt>").is_err());
+        assert!(entries(&stmt("<Ntry><Sts>BOOK</Sts><BookgDt><Dt>05.03.2024</Dt></BookgDt></Ntry>")).is_err());
+        assert_eq!(entries(&stmt("<Ntry><Amt>1</Amt><Sts>INFO</Sts></Ntry>")).unwrap(), vec![]);
+    }
+}
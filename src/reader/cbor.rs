@@ -0,0 +1,31 @@
+use crate::engine::Transaction;
+use std::io::Read;
+
+/// Records encoded as CBOR maps with `type`, `client`, `tx` and `amount` keys
+pub fn transactions<R: Read>(input: R) -> impl Iterator<Item = Result<Transaction, String>> {
+    super::records(input, |rdr| ciborium::from_reader(rdr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TxType;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_read_stream() {
+        let mut input = Vec::new();
+        let records = [
+            serde_json::json!({"type": "deposit", "client": 1, "tx": 1, "amount": "1.5"}),
+            serde_json::json!({"type": "chargeback", "client": 1, "tx": 1}),
+        ];
+        for record in &records {
+            ciborium::into_writer(record, &mut input).unwrap();
+        }
+        let txs: Vec<Transaction> = transactions(input.as_slice()).collect::<Result<_, _>>().unwrap();
+        assert_eq!(txs.len(), 2);
+        assert_eq!(txs[0].amount, Some(dec!(1.5)));
+        assert_eq!(txs[1].tx_type, TxType::ChargeBack);
+        assert_eq!(txs[1].amount, None);
+    }
+}
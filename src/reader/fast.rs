@@ -0,0 +1,233 @@
+use super::{accept, builder, read, Dialect, Format};
+use crate::engine::{Transaction, TxType};
+use crate::rejection::{Reason, Rejected};
+use memchr::{memchr, memchr_iter};
+use rust_decimal::Decimal;
+use std::io::Read;
+use std::str::FromStr;
+
+// bytes read from the input at once
+const CHUNK: usize = 64 * 1024;
+
+/// Transactions of a csv stream in the plain `type,client,tx,amount` layout,
+/// lines and fields found with memchr instead of the csv state machine.
+/// Lines with quotes or an unexpected number of fields are split by the csv
+/// crate instead, a quoted field cannot span lines. Layouts with other
+/// columns, mapped names or external client ids are left to the csv reader.
+pub fn transactions<R: Read + 'static>(
+    source: &str,
+    dialect: &Dialect,
+    input: R,
+) -> Result<Box<dyn Iterator<Item = Result<Transaction, Rejected>>>, String> {
+    if dialect.ids.is_some() || dialect.columns != Default::default() {
+        return read(source, Format::Csv, dialect, input);
+    }
+    let mut records = Records {
+        input,
+        buf: Vec::with_capacity(CHUNK),
+        pos: 0,
+        eof: false,
+        line: 0,
+        failed: None,
+        source: source.to_string(),
+        dialect: dialect.clone(),
+    };
+    if dialect.headers {
+        let plain = records.next_line().is_some_and(|(_, start, end)| {
+            let Ok(text) = records.text(start, end) else {
+                return false;
+            };
+            let headers: Vec<&str> = text.split(char::from(dialect.delimiter)).map(str::trim).collect();
+            headers == ["type", "client", "tx", "amount"]
+        });
+        if !plain {
+            // the bytes read so far go back in front of the rest of the input
+            let read_so_far = std::io::Cursor::new(std::mem::take(&mut records.buf));
+            return read(source, Format::Csv, dialect, read_so_far.chain(records.input));
+        }
+    }
+    Ok(Box::new(records))
+}
+
+pub struct Records<R> {
+    input: R,
+    buf: Vec<u8>,
+    pos: usize,
+    eof: bool,
+    line: u64,
+    /// read error that ended the input, given as the last rejection
+    failed: Option<String>,
+    source: String,
+    dialect: Dialect,
+}
+
+impl<R: Read> Records<R> {
+    /// reads more of the input after the unconsumed bytes, false at its end
+    fn fill(&mut self) -> bool {
+        if self.eof {
+            return false;
+        }
+        self.buf.drain(..self.pos);
+        self.pos = 0;
+        let len = self.buf.len();
+        self.buf.resize(len + CHUNK, 0);
+        let read = match self.input.read(&mut self.buf[len..]) {
+            Ok(read) => read,
+            Err(err) => {
+                self.failed = Some(format!("error in reading input: {}", err));
+                0
+            }
+        };
+        self.buf.truncate(len + read);
+        self.eof = read == 0;
+        !self.eof
+    }
+
+    /// next non empty line without its terminator, with its 1-based number
+    fn next_line(&mut self) -> Option<(u64, usize, usize)> {
+        loop {
+            let end = match memchr(b'\n', &self.buf[self.pos..]) {
+                Some(len) => self.pos + len,
+                None if self.fill() => continue,
+                None if self.pos < self.buf.len() => self.buf.len(),
+                None => return None,
+            };
+            let start = self.pos;
+            self.pos = (end + 1).min(self.buf.len());
+            self.line += 1;
+            let end = if end > start && self.buf[end - 1] == b'\r' { end - 1 } else { end };
+            if end > start {
+                return Some((self.line, start, end));
+            }
+        }
+    }
+
+    fn text(&self, start: usize, end: usize) -> Result<&str, String> {
+        std::str::from_utf8(&self.buf[start..end]).map_err(|_| "invalid utf-8 in a transaction record".to_string())
+    }
+
+    /// fields of a line, split by the csv crate unless it is a plain one
+    fn fields(&self, text: &str) -> Result<Vec<String>, String> {
+        let bytes = text.as_bytes();
+        let plain = memchr(self.dialect.quote, bytes).is_none()
+            && self.dialect.escape.is_none_or(|escape| memchr(escape, bytes).is_none());
+        let delimiters = memchr_iter(self.dialect.delimiter, bytes).count();
+        if plain && (2..=3).contains(&delimiters) {
+            return Ok(text.split(char::from(self.dialect.delimiter)).map(|field| field.trim().to_string()).collect());
+        }
+        let mut rdr = builder()
+            .has_headers(false)
+            .delimiter(self.dialect.delimiter)
+            .quote(self.dialect.quote)
+            .escape(self.dialect.escape)
+            .double_quote(self.dialect.escape.is_none())
+            .from_reader(bytes);
+        let mut record = csv::StringRecord::new();
+        rdr.read_record(&mut record).map_err(|err| err.to_string())?;
+        Ok(record.iter().map(str::to_string).collect())
+    }
+
+    fn parse(&self, fields: &[String]) -> Result<Transaction, String> {
+        if !(3..=4).contains(&fields.len()) {
+            return Err(format!("expected 3 or 4 fields, found {}", fields.len()));
+        }
+        let tx_type = match fields[0].as_str() {
+            "deposit" => TxType::Deposit,
+            "withdrawal" => TxType::Withdrawal,
+            "dispute" => TxType::Dispute,
+            "resolve" => TxType::Resolve,
+            "chargeback" => TxType::ChargeBack,
+            "settle" => TxType::Settle,
+            "escrow" => TxType::Escrow,
+            "release" => TxType::Release,
+            "move" => TxType::Move,
+            "reversal" => TxType::Reversal,
+            "convert" => TxType::Convert,
+            _ => TxType::Unknown,
+        };
+        let client = fields[1].parse().map_err(|_| format!("invalid client `{}`", fields[1]))?;
+        let tx = fields[2].parse().map_err(|_| format!("invalid tx `{}`", fields[2]))?;
+        let amount = match fields.get(3).map(String::as_str).unwrap_or_default() {
+            "" => None,
+            raw if self.dialect.amounts == Default::default() => {
+                Some(Decimal::from_str(raw).map_err(|_| format!("invalid amount `{}`", raw))?)
+            }
+            raw => Some(self.dialect.amounts.parse(raw)?),
+        };
+        Ok(Transaction { tx_type, client, tx, amount, ..Default::default() })
+    }
+}
+
+impl<R: Read> Iterator for Records<R> {
+    type Item = Result<Transaction, Rejected>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Some((line, start, end)) = self.next_line() else {
+            let detail = self.failed.take()?;
+            return Some(Err(Rejected::new(Reason::Malformed, None, None, detail).at(&self.source, None, String::new())));
+        };
+        let text = match self.text(start, end) {
+            Ok(text) => text.to_string(),
+            Err(detail) => {
+                let raw = String::from_utf8_lossy(&self.buf[start..end]).into_owned();
+                return Some(Err(Rejected::new(Reason::Malformed, None, None, detail).at(&self.source, Some(line), raw)));
+            }
+        };
+        let fields = self.fields(&text);
+        let (tx, fields) = match fields.and_then(|fields| self.parse(&fields).map(|tx| (tx, fields))) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                let detail = format!("error in parsing a transaction record: {}", err);
+                return Some(Err(Rejected::new(Reason::Malformed, None, None, detail).at(&self.source, Some(line), text)));
+            }
+        };
+        Some(accept(tx, self.dialect.precision).map_err(|rejected| match rejected.reason {
+            Reason::UnknownType => Rejected {
+                detail: format!("unknown transaction type `{}`", fields[0]),
+                ..rejected.at(&self.source, Some(line), text.clone())
+            },
+            _ => rejected.at(&self.source, Some(line), text.clone()),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::{from_reader_with, transactions_with};
+    use std::io::Cursor;
+
+    fn both(input: &str, dialect: &Dialect) -> (Vec<String>, Vec<String>) {
+        let fast = transactions("in.csv", dialect, Cursor::new(input.to_string())).unwrap().map(|res| format!("{:?}", res)).collect();
+        let slow = transactions_with("in.csv", dialect, from_reader_with(dialect, input.as_bytes()).unwrap())
+            .map(|res| format!("{:?}", res))
+            .collect();
+        (fast, slow)
+    }
+
+    #[test]
+    fn test_same_as_csv_reader() {
+        let input = "type, client, tx, amount\r\ndeposit, 1, 1, 1.5\n\n\
+                     withdrawal,1,2,0.25\ndispute,1,1,\n\"deposit\",2,4,\"3.0\"\nrefund,2,3,1.0\nresolve,1,1";
+        let (fast, slow) = both(input, &Dialect::default());
+        assert_eq!(fast, slow);
+        let (fast, slow) = both("deposit;1;1;2.0\nwithdrawal;1;2\n", &Dialect { delimiter: b';', headers: false, ..Dialect::default() });
+        assert_eq!(fast, slow);
+    }
+
+    #[test]
+    fn test_lines_across_reads() {
+        let rows: String = (1..=20_000).map(|tx| format!("deposit,{},{},1.0\n", tx % 7, tx)).collect();
+        let input = format!("type,client,tx,amount\n{}", rows);
+        let txs: Vec<_> = transactions("in.csv", &Dialect::default(), Cursor::new(input)).unwrap().collect();
+        assert_eq!(txs.len(), 20_000);
+        assert_eq!(txs[19_999].as_ref().unwrap().tx, 20_000);
+    }
+
+    #[test]
+    fn test_other_layouts_fall_back() {
+        let (fast, slow) = both("type,client,tx,amount,timestamp\ndeposit,1,1,1.0,2024-01-01\n", &Dialect::default());
+        assert_eq!(fast, slow);
+        assert!(fast[0].contains("timestamp: Some"));
+    }
+}
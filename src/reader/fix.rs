@@ -0,0 +1,96 @@
+use super::bank::{self, BankAccounts, Entry};
+use crate::engine::Transaction;
+use crate::timestamp::Timestamp;
+use chrono::NaiveDateTime;
+use rust_decimal::Decimal;
+use std::io::{BufRead, BufReader, Read};
+use std::str::FromStr;
+
+/// Fills of the execution reports (`35=8`) of a FIX drop copy, one message
+/// per line with fields separated by SOH or `|`. Buys pay cash out of the
+/// `Account` (tag 1) and are withdrawals, sells are deposits; other messages,
+/// and cancels or corrections of executions, are skipped.
+pub fn transactions<R: Read>(input: R, accounts: &BankAccounts) -> Result<impl Iterator<Item = Transaction>, String> {
+    let mut entries = Vec::new();
+    for (idx, line) in BufReader::new(input).lines().enumerate() {
+        let line = line.map_err(|err| format!("error in reading a FIX file: {}", err))?;
+        let parsed = entry(&line).map_err(|err| format!("error in parsing line {} of a FIX file: {}", idx + 1, err))?;
+        entries.extend(parsed);
+    }
+    bank::transactions(entries, accounts)
+}
+
+/// `YYYYMMDD-HH:MM:SS[.sss]`, in UTC
+fn datetime(value: &str) -> Result<Timestamp, String> {
+    NaiveDateTime::parse_from_str(value, "%Y%m%d-%H:%M:%S%.f").map_err(|_| format!("invalid time `{}`", value))
+}
+
+fn entry(line: &str) -> Result<Option<Entry>, String> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let mut fields = Vec::new();
+    for field in line.split(['\x01', '|']).filter(|field| !field.is_empty()) {
+        let Some((tag, value)) = field.split_once('=') else {
+            return Err(format!("field `{}` is not tag=value", field));
+        };
+        fields.push((tag.parse::<u32>().map_err(|_| format!("invalid tag `{}`", tag))?, value));
+    }
+    let tag = |tag: u32| fields.iter().find(|(number, _)| *number == tag).map(|(_, value)| *value);
+    let decimal = |number: u32| {
+        tag(number).map(|value| Decimal::from_str(value).map_err(|_| format!("invalid value `{}` of tag {}", value, number))).transpose()
+    };
+    // ExecTransType of FIX 4.2 and earlier, ExecType of every version
+    let fill = matches!(tag(150), Some("1" | "2" | "F")) && matches!(tag(20), None | Some("0"));
+    if tag(35) != Some("8") || !fill {
+        return Ok(None);
+    }
+    // NetMoney, GrossTradeAmt, LastQty times LastPx
+    let cash = match (decimal(118)?, decimal(381)?, decimal(32)?, decimal(31)?) {
+        (Some(net), _, _, _) | (None, Some(net), _, _) => net,
+        (None, None, Some(qty), Some(px)) => qty * px,
+        _ => return Err("execution report without a cash amount".to_string()),
+    };
+    let amount = match tag(54) {
+        Some("1") => -cash,
+        Some("2" | "5" | "6") => cash,
+        side => return Err(format!("unsupported side `{}`", side.unwrap_or_default())),
+    };
+    Ok(Some(Entry {
+        account: tag(1).map(str::to_string),
+        amount,
+        posted: tag(60).map(datetime).transpose()?,
+        counterparty: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TxType;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_drop_copy() {
+        let input = "8=FIX.4.4|9=100|35=8|1=ACC7|17=E1|150=F|39=2|54=1|55=ACME|32=10|31=12.5|60=20240305-14:30:00.125|10=000|\n\
+                     8=FIX.4.4|9=60|35=0|10=000|\n\
+                     8=FIX.4.4\x019=90\x0135=8\x011=ACC7\x0117=E2\x01150=F\x0154=2\x0132=4\x0131=12.5\x01118=49.90\x0110=000\x01\n\
+                     8=FIX.4.2|35=8|1=ACC7|17=E3|20=1|150=2|54=1|32=1|31=1|10=000|\n\
+                     8=FIX.4.4|35=8|1=ACC7|17=E4|150=0|54=1|10=000|\n";
+        let accounts = BankAccounts { accounts: [("ACC7".to_string(), 7)].into(), first_tx: Some(10), ..BankAccounts::default() };
+        let txs: Vec<Transaction> = transactions(input.as_bytes(), &accounts).unwrap().collect();
+        let summary: Vec<_> = txs.iter().map(|tx| (tx.client, tx.tx, tx.tx_type, tx.amount)).collect();
+        assert_eq!(summary, vec![(7, 10, TxType::Withdrawal, Some(dec!(125.0))), (7, 11, TxType::Deposit, Some(dec!(49.90)))]);
+        assert_eq!(txs[0].timestamp, Some(datetime("20240305-14:30:00.125").unwrap()));
+    }
+
+    #[test]
+    fn test_invalid_reports() {
+        assert!(entry("35=8|150=F|54=1|10=000").is_err());
+        assert!(entry("35=8|150=F|54=1|32=ten|31=1").is_err());
+        assert!(entry("35=8|150=F|54=9|118=1").is_err());
+        assert!(entry("35=8|150=F|54=1|118=1|60=2024-03-05").is_err());
+        assert!(entry("35=8|garbage").is_err());
+    }
+}
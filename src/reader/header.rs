@@ -0,0 +1,147 @@
+use std::fmt;
+
+/// columns every input must have, with the alternative names serde accepts
+const EXPECTED: [(&str, &[&str]); 4] = [
+    ("type", &["tx_type"]),
+    ("client", &[]),
+    ("tx", &[]),
+    ("amount", &[]),
+];
+
+/// usual names of the expected columns in partner files
+const SYNONYMS: [(&str, &[&str]); 4] = [
+    ("type", &["kind", "transactiontype", "txtype", "operation"]),
+    ("client", &["clientid", "customer", "customerid", "account"]),
+    ("tx", &["txid", "id", "transaction", "transactionid"]),
+    ("amount", &["value", "amt", "sum"]),
+];
+
+/// Header line that does not match the expected columns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderError {
+    pub missing: Vec<String>,
+    pub extra: Vec<String>,
+    /// (expected column, header column) pairs for the `[columns]` config section
+    pub suggested: Vec<(String, String)>,
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "missing columns: {}", self.missing.join(", "))?;
+        if !self.extra.is_empty() {
+            write!(f, "; unexpected columns: {}", self.extra.join(", "))?;
+        }
+        if !self.suggested.is_empty() {
+            write!(f, "; suggested config mapping:\n[columns]")?;
+            for (expected, found) in &self.suggested {
+                write!(f, "\n{} = \"{}\"", expected, found)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = (prev + usize::from(ca != *cb)).min(row[j] + 1).min(cur + 1);
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
+
+/// best candidate among the unexpected columns for an expected one
+fn suggest<'a>(expected: &str, extra: &'a [String]) -> Option<&'a String> {
+    let synonyms = SYNONYMS.iter().find(|(name, _)| *name == expected).map_or(&[][..], |(_, s)| *s);
+    extra
+        .iter()
+        .map(|column| {
+            let norm = normalize(column);
+            let score = if norm == expected {
+                0
+            } else if synonyms.contains(&norm.as_str()) {
+                1
+            } else {
+                2 + distance(&norm, expected)
+            };
+            (score, column)
+        })
+        .filter(|(score, _)| *score <= 4)
+        .min_by_key(|(score, _)| *score)
+        .map(|(_, column)| column)
+}
+
+/// Check the header line, after the configured column mapping has been applied
+pub fn check(headers: &csv::StringRecord) -> Result<(), HeaderError> {
+    let present = |name: &str, aliases: &[&str]| headers.iter().any(|h| h == name || aliases.contains(&h));
+    let missing: Vec<String> = EXPECTED
+        .iter()
+        .filter(|(name, aliases)| !present(name, aliases))
+        .map(|(name, _)| name.to_string())
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let extra: Vec<String> = headers
+        .iter()
+        .filter(|h| !EXPECTED.iter().any(|(name, aliases)| h == name || aliases.contains(h)))
+        .map(str::to_string)
+        .collect();
+    let mut suggested = Vec::new();
+    let mut candidates = extra.clone();
+    for expected in &missing {
+        if let Some(found) = suggest(expected, &candidates).cloned() {
+            candidates.retain(|c| *c != found);
+            suggested.push((expected.clone(), found));
+        }
+    }
+    Err(HeaderError { missing, extra, suggested })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expected_headers() {
+        assert!(check(&csv::StringRecord::from(vec!["type", "client", "tx", "amount"])).is_ok());
+        assert!(check(&csv::StringRecord::from(vec!["tx_type", "client", "tx", "amount"])).is_ok());
+    }
+
+    #[test]
+    fn test_missing_columns_with_suggestions() {
+        let headers = csv::StringRecord::from(vec!["Kind", "client", "Transaction_ID", "value", "currency"]);
+        let err = check(&headers).unwrap_err();
+        assert_eq!(err.missing, vec!["type", "tx", "amount"]);
+        assert_eq!(err.extra, vec!["Kind", "Transaction_ID", "value", "currency"]);
+        assert_eq!(
+            err.suggested,
+            vec![
+                ("type".to_string(), "Kind".to_string()),
+                ("tx".to_string(), "Transaction_ID".to_string()),
+                ("amount".to_string(), "value".to_string()),
+            ]
+        );
+        assert!(err.to_string().contains("type = \"Kind\""));
+    }
+
+    #[test]
+    fn test_typo_is_suggested() {
+        let headers = csv::StringRecord::from(vec!["type", "cleint", "tx", "amount"]);
+        let err = check(&headers).unwrap_err();
+        assert_eq!(err.suggested, vec![("client".to_string(), "cleint".to_string())]);
+    }
+}
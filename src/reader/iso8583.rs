@@ -0,0 +1,222 @@
+use crate::engine::{Transaction, TxType};
+use rust_decimal::Decimal;
+use std::io::{ErrorKind, Read};
+
+/// Length of a data element: fixed, or given by a 2 or 3 digit prefix
+#[derive(Debug, Clone, Copy)]
+enum Length {
+    Fixed(usize),
+    Llvar,
+    Lllvar,
+}
+
+/// data elements that can be present in a message, any other one is an error
+fn length(field: usize) -> Option<Length> {
+    use Length::*;
+    Some(match field {
+        2 | 32 | 33 | 102 | 103 => Llvar,
+        3 | 11 | 12 => Fixed(6),
+        4..=6 => Fixed(12),
+        7 => Fixed(10),
+        13..=15 | 18 => Fixed(4),
+        22 | 49..=51 => Fixed(3),
+        37 => Fixed(12),
+        38 => Fixed(6),
+        39 => Fixed(2),
+        41 => Fixed(8),
+        42 => Fixed(15),
+        43 => Fixed(40),
+        48 | 54 | 55 | 60 | 63 => Lllvar,
+        90 => Fixed(42),
+        _ => return None,
+    })
+}
+
+/// Message type indicator and data elements of one message
+struct Message {
+    mti: String,
+    fields: Vec<(usize, String)>,
+}
+
+impl Message {
+    fn field(&self, field: usize) -> Option<&str> {
+        self.fields.iter().find(|(number, _)| *number == field).map(|(_, value)| value.as_str())
+    }
+
+    fn number<T: std::str::FromStr>(&self, field: usize, name: &str) -> Result<T, String> {
+        let value = self.field(field).ok_or_else(|| format!("{} without {} (field {})", self.mti, name, field))?;
+        value.trim().parse().map_err(|_| format!("invalid {} `{}` (field {})", name, value, field))
+    }
+}
+
+fn parse(body: &[u8]) -> Result<Message, String> {
+    let text = |bytes: &[u8]| String::from_utf8(bytes.to_vec()).map_err(|_| "non ascii data element".to_string());
+    if body.len() < 12 {
+        return Err("message shorter than its MTI and bitmap".to_string());
+    }
+    let mti = text(&body[..4])?;
+    let mut bitmap = body[4..12].to_vec();
+    let mut pos = 12;
+    if bitmap[0] & 0x80 != 0 {
+        let secondary = body.get(12..20).ok_or("truncated secondary bitmap")?;
+        bitmap.extend_from_slice(secondary);
+        pos = 20;
+    }
+    let mut fields = Vec::new();
+    // bit 1 flags the secondary bitmap
+    for field in 2..=bitmap.len() * 8 {
+        if bitmap[(field - 1) / 8] & (0x80 >> ((field - 1) % 8)) == 0 {
+            continue;
+        }
+        let mut take = |len: usize| {
+            let bytes = body.get(pos..pos + len).ok_or_else(|| format!("truncated field {}", field))?;
+            pos += len;
+            text(bytes)
+        };
+        let len = match length(field).ok_or_else(|| format!("unsupported field {}", field))? {
+            Length::Fixed(len) => len,
+            Length::Llvar => take(2)?.parse().map_err(|_| format!("invalid length of field {}", field))?,
+            Length::Lllvar => take(3)?.parse().map_err(|_| format!("invalid length of field {}", field))?,
+        };
+        fields.push((field, take(len)?));
+    }
+    if pos != body.len() {
+        return Err(format!("{} bytes after the last field", body.len() - pos));
+    }
+    Ok(Message { mti, fields })
+}
+
+/// engine transactions of a request message, none for responses and network messages
+fn translate(message: &Message) -> Result<Vec<Transaction>, String> {
+    let tx = |tx_type, tx, amount| Transaction {
+        tx_type,
+        client: 0,
+        tx,
+        amount,
+        counterparty: message.field(43).or(message.field(42)).map(str::trim).filter(|name| !name.is_empty()).map(str::to_string),
+        ..Default::default()
+    };
+    // original STAN of reversals and chargebacks, in the original data elements
+    let original = || match message.field(90) {
+        Some(original) => original.get(4..10).unwrap_or_default().parse().map_err(|_| format!("invalid original data elements `{}`", original)),
+        None => Err(format!("{} without original data elements (field 90)", message.mti)),
+    };
+    let txs = match &message.mti[..] {
+        "0100" | "0200" | "0220" => {
+            let processing = message.field(3).ok_or_else(|| format!("{} without processing code (field 3)", message.mti))?;
+            let tx_type = match &processing[..2] {
+                "00" | "01" => TxType::Withdrawal,
+                "20" | "21" => TxType::Deposit,
+                code => return Err(format!("unsupported processing code `{}`", code)),
+            };
+            let minor: i64 = message.number(4, "amount")?;
+            vec![tx(tx_type, message.number(11, "STAN")?, Some(Decimal::new(minor, 2)))]
+        }
+        "0400" | "0420" => vec![tx(TxType::Reversal, original()?, None)],
+        // a chargeback is raised and settled by the same message
+        "0422" => vec![tx(TxType::Dispute, original()?, None), tx(TxType::ChargeBack, original()?, None)],
+        _ => vec![],
+    };
+    let client = if txs.is_empty() { 0 } else { message.number(102, "account")? };
+    Ok(txs.into_iter().map(|tx| Transaction { client, ..tx }).collect())
+}
+
+/// Simplified ISO 8583 (1987) stream: each message is prefixed by its length
+/// as 2 big-endian bytes and made of an ASCII MTI, a binary primary and
+/// optional secondary bitmap, and ASCII data elements. Authorization and
+/// financial requests (0100, 0200, 0220) are withdrawals for purchases and
+/// cash (processing code 00, 01) or deposits for refunds and deposits (20,
+/// 21), with the STAN as tx id and the amount in minor units; reversals
+/// (0400, 0420) and chargebacks (0422) name the original STAN in field 90.
+/// The client is account identification 1, field 102; other messages are
+/// skipped.
+pub fn transactions<R: Read>(mut input: R) -> impl Iterator<Item = Result<Transaction, String>> {
+    let mut message = 0;
+    std::iter::from_fn(move || loop {
+        let mut prefix = [0; 2];
+        match input.read_exact(&mut prefix) {
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return None,
+            Err(err) => return Some(vec![Err(format!("error in reading input: {}", err))]),
+            Ok(()) => {}
+        }
+        message += 1;
+        let mut body = vec![0; u16::from_be_bytes(prefix) as usize];
+        if let Err(err) = input.read_exact(&mut body) {
+            return Some(vec![Err(format!("error in reading ISO 8583 message {}: {}", message, err))]);
+        }
+        let txs = match parse(&body).and_then(|parsed| translate(&parsed)) {
+            Ok(txs) => txs,
+            Err(err) => return Some(vec![Err(format!("error in parsing ISO 8583 message {}: {}", message, err))]),
+        };
+        if !txs.is_empty() {
+            return Some(txs.into_iter().map(Ok).collect());
+        }
+    })
+    .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn message(mti: &str, fields: &[(usize, &str)]) -> Vec<u8> {
+        let mut bitmap = [0u8; 16];
+        let mut data = String::new();
+        for (field, value) in fields {
+            bitmap[(field - 1) / 8] |= 0x80 >> ((field - 1) % 8);
+            match length(*field).unwrap() {
+                Length::Llvar => data.push_str(&format!("{:02}", value.len())),
+                Length::Lllvar => data.push_str(&format!("{:03}", value.len())),
+                Length::Fixed(_) => {}
+            }
+            data.push_str(value);
+        }
+        let bitmap = if fields.iter().any(|(field, _)| *field > 64) {
+            bitmap[0] |= 0x80;
+            &bitmap[..]
+        } else {
+            &bitmap[..8]
+        };
+        let body = [mti.as_bytes(), bitmap, data.as_bytes()].concat();
+        [&(body.len() as u16).to_be_bytes()[..], &body].concat()
+    }
+
+    #[test]
+    fn test_read_stream() {
+        let merchant = format!("{:<40}", "CORNER SHOP");
+        let input = [
+            message("0200", &[(3, "000000"), (4, "000000001250"), (11, "000042"), (43, &merchant), (102, "7")]),
+            message("0210", &[(3, "000000"), (11, "000042"), (39, "00")]),
+            message("0100", &[(3, "200000"), (4, "000000000300"), (11, "000043"), (102, "7")]),
+            message("0422", &[(11, "000044"), (90, "020000004201011200000000000000000000000000"), (102, "7")]),
+            message("0400", &[(11, "000045"), (90, "010000004301011200000000000000000000000000"), (102, "7")]),
+        ]
+        .concat();
+        let txs: Vec<Transaction> = transactions(input.as_slice()).collect::<Result<_, _>>().unwrap();
+        let summary: Vec<_> = txs.iter().map(|tx| (tx.tx_type, tx.client, tx.tx, tx.amount)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (TxType::Withdrawal, 7, 42, Some(dec!(12.50))),
+                (TxType::Deposit, 7, 43, Some(dec!(3.00))),
+                (TxType::Dispute, 7, 42, None),
+                (TxType::ChargeBack, 7, 42, None),
+                (TxType::Reversal, 7, 43, None),
+            ]
+        );
+        assert_eq!(txs[0].counterparty.as_deref(), Some("CORNER SHOP"));
+    }
+
+    #[test]
+    fn test_invalid_messages() {
+        let body = |bytes: Vec<u8>| parse(&bytes[2..]).and_then(|message| translate(&message));
+        assert!(body(message("0200", &[(3, "000000"), (4, "000000001250"), (11, "000042")])).is_err());
+        assert!(body(message("0200", &[(3, "300000"), (4, "000000001250"), (11, "000042"), (102, "7")])).is_err());
+        assert!(body(message("0400", &[(11, "000045"), (102, "7")])).is_err());
+        let mut truncated = message("0200", &[(3, "000000"), (4, "000000001250")]);
+        truncated.pop();
+        assert!(body(truncated).is_err());
+        assert!(parse(b"0200\x00\x00\x00\x00\x00\x00\x00\x01X").is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+/// How amounts are written in the input, e.g. `1.234,56` for most of Europe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AmountFormat {
+    pub decimal_separator: char,
+    pub thousands_separator: Option<char>,
+}
+
+impl Default for AmountFormat {
+    fn default() -> AmountFormat {
+        AmountFormat {
+            decimal_separator: '.',
+            thousands_separator: None,
+        }
+    }
+}
+
+impl AmountFormat {
+    /// Exact decimal value of an amount. Thousands separators are only
+    /// accepted between groups of three digits, so that `1.5` is not read
+    /// as `15` when `.` separates thousands.
+    pub fn parse(&self, value: &str) -> Result<Decimal, String> {
+        let invalid = || format!("invalid amount `{}`", value);
+        let (int_part, frac_part) = match value.split_once(self.decimal_separator) {
+            Some((int_part, frac_part)) => (int_part, Some(frac_part)),
+            None => (value, None),
+        };
+        let mut normalized = String::with_capacity(value.len());
+        match self.thousands_separator {
+            Some(sep) if int_part.contains(sep) => {
+                let (sign, digits) = match int_part.strip_prefix('-') {
+                    Some(digits) => ("-", digits),
+                    None => ("", int_part),
+                };
+                let mut groups = digits.split(sep);
+                let first = groups.next().unwrap_or_default();
+                if first.is_empty() || first.len() > 3 {
+                    return Err(invalid());
+                }
+                normalized.push_str(sign);
+                normalized.push_str(first);
+                for group in groups {
+                    if group.len() != 3 {
+                        return Err(invalid());
+                    }
+                    normalized.push_str(group);
+                }
+            }
+            _ => normalized.push_str(int_part),
+        }
+        if let Some(frac_part) = frac_part {
+            normalized.push('.');
+            normalized.push_str(frac_part);
+        }
+        Decimal::from_str(&normalized).map_err(|_| invalid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_default_format() {
+        let format = AmountFormat::default();
+        assert_eq!(format.parse("1.2345"), Ok(dec!(1.2345)));
+        assert_eq!(format.parse("-3"), Ok(dec!(-3)));
+        assert_eq!(format.parse("12345678901234.5678"), Ok(dec!(12345678901234.5678)));
+        assert!(format.parse("1,5").is_err());
+    }
+
+    #[test]
+    fn test_european_format() {
+        let format = AmountFormat {
+            decimal_separator: ',',
+            thousands_separator: Some('.'),
+        };
+        assert_eq!(format.parse("1.234,56"), Ok(dec!(1234.56)));
+        assert_eq!(format.parse("1.234.567"), Ok(dec!(1234567)));
+        assert_eq!(format.parse("-12.000,5"), Ok(dec!(-12000.5)));
+        assert_eq!(format.parse("0,75"), Ok(dec!(0.75)));
+        assert!(format.parse("1.5").is_err());
+        assert!(format.parse("1234.56,0").is_err());
+        assert!(format.parse("1,2,3").is_err());
+    }
+}
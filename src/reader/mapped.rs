@@ -0,0 +1,209 @@
+use super::{accept, header, Dialect};
+use crate::engine::{Transaction, TxType};
+use crate::rejection::{Reason, Rejected};
+use crate::timestamp;
+use memmap2::Mmap;
+use rust_decimal::Decimal;
+use std::fs::File;
+use std::str::FromStr;
+
+/// Transactions of a memory-mapped csv file, split by hand without copying
+/// the fields. Files using quotes or escapes, or external client ids, are
+/// left to the csv reader. The error is a header line that does not match
+/// the expected columns.
+pub fn transactions(source: &str, dialect: &Dialect, file: &File) -> Result<Option<Records>, String> {
+    if dialect.ids.is_some() {
+        return Ok(None);
+    }
+    // safety: the input file is not expected to change while it is processed
+    let Ok(map) = (unsafe { Mmap::map(file) }) else {
+        return Ok(None);
+    };
+    if map.contains(&dialect.quote) || dialect.escape.is_some_and(|escape| map.contains(&escape)) {
+        return Ok(None);
+    }
+    let mut records = Records {
+        map,
+        pos: 0,
+        line: 0,
+        source: source.to_string(),
+        dialect: dialect.clone(),
+        columns: [Some(0), Some(1), Some(2), Some(3), None, None, None, None, None, None, None, None, None, None],
+    };
+    if dialect.headers {
+        records.read_headers()?;
+    }
+    Ok(Some(records))
+}
+
+pub struct Records {
+    map: Mmap,
+    pos: usize,
+    line: u64,
+    source: String,
+    dialect: Dialect,
+    /// position of the type, client, tx, amount, timestamp, wallet, to_wallet, counterparty, batch, seq, due, tenant, currency and to_currency columns
+    columns: [Option<usize>; 14],
+}
+
+impl Records {
+    /// next non empty line without its terminator, with its 1-based number
+    fn next_line(&mut self) -> Option<(u64, usize, usize)> {
+        while self.pos < self.map.len() {
+            let start = self.pos;
+            let end = self.map[start..].iter().position(|&b| b == b'\n').map_or(self.map.len(), |len| start + len);
+            self.pos = end + 1;
+            self.line += 1;
+            let end = if end > start && self.map[end - 1] == b'\r' { end - 1 } else { end };
+            if end > start {
+                return Some((self.line, start, end));
+            }
+        }
+        None
+    }
+
+    fn text(&self, start: usize, end: usize) -> Result<&str, String> {
+        std::str::from_utf8(&self.map[start..end]).map_err(|_| "invalid utf-8 in a transaction record".to_string())
+    }
+
+    fn read_headers(&mut self) -> Result<(), String> {
+        let Some((_, start, end)) = self.next_line() else {
+            return Ok(());
+        };
+        let delimiter = char::from(self.dialect.delimiter);
+        let text = self.text(start, end).map_err(|_| "error in reading the header line: invalid utf-8".to_string())?;
+        let headers: csv::StringRecord = text.split(delimiter).map(str::trim).collect();
+        let headers = self.dialect.columns.rename(&headers);
+        header::check(&headers).map_err(|err| format!("unexpected header line: {}", err))?;
+        let position = |names: &[&str]| headers.iter().position(|header| names.contains(&header));
+        self.columns = [
+            position(&["type", "tx_type"]),
+            position(&["client"]),
+            position(&["tx"]),
+            position(&["amount"]),
+            position(&["timestamp"]),
+            position(&["wallet"]),
+            position(&["to_wallet"]),
+            position(&["counterparty"]),
+            position(&["batch"]),
+            position(&["seq"]),
+            position(&["due"]),
+            position(&["tenant"]),
+            position(&["currency"]),
+            position(&["to_currency"]),
+        ];
+        Ok(())
+    }
+
+    fn parse(&self, text: &str) -> Result<Transaction, String> {
+        let mut fields: [&str; 14] = [""; 14];
+        for (idx, field) in text.split(char::from(self.dialect.delimiter)).enumerate() {
+            if let Some(column) = self.columns.iter().position(|&column| column == Some(idx)) {
+                fields[column] = field.trim();
+            }
+        }
+        let tx_type = match fields[0] {
+            "deposit" => TxType::Deposit,
+            "withdrawal" => TxType::Withdrawal,
+            "dispute" => TxType::Dispute,
+            "resolve" => TxType::Resolve,
+            "chargeback" => TxType::ChargeBack,
+            "settle" => TxType::Settle,
+            "escrow" => TxType::Escrow,
+            "release" => TxType::Release,
+            "move" => TxType::Move,
+            "reversal" => TxType::Reversal,
+            "convert" => TxType::Convert,
+            _ => TxType::Unknown,
+        };
+        let client = fields[1].parse().map_err(|_| format!("invalid client `{}`", fields[1]))?;
+        let tx = fields[2].parse().map_err(|_| format!("invalid tx `{}`", fields[2]))?;
+        let amount = match fields[3] {
+            "" => None,
+            raw if self.dialect.amounts == Default::default() => {
+                Some(Decimal::from_str(raw).map_err(|_| format!("invalid amount `{}`", raw))?)
+            }
+            raw => Some(self.dialect.amounts.parse(raw)?),
+        };
+        let time = |field: &str| match field {
+            "" => Ok(None),
+            raw => timestamp::parse(raw).map(Some),
+        };
+        let (timestamp, due) = (time(fields[4])?, time(fields[10])?);
+        let name = |field: &str| (!field.is_empty()).then(|| field.to_string());
+        let (wallet, to_wallet, counterparty, batch) = (name(fields[5]), name(fields[6]), name(fields[7]), name(fields[8]));
+        let (tenant, currency, to_currency) = (name(fields[11]), name(fields[12]), name(fields[13]));
+        let seq = match fields[9] {
+            "" => None,
+            raw => Some(raw.parse().map_err(|_| format!("invalid seq `{}`", raw))?),
+        };
+        Ok(Transaction { tx_type, client, tx, amount, timestamp, wallet, to_wallet, currency, to_currency, tenant, counterparty, batch, seq, due })
+    }
+}
+
+impl Iterator for Records {
+    type Item = Result<Transaction, Rejected>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (line, start, end) = self.next_line()?;
+        let text = match self.text(start, end) {
+            Ok(text) => text,
+            Err(detail) => {
+                let raw = String::from_utf8_lossy(&self.map[start..end]).into_owned();
+                return Some(Err(Rejected::new(Reason::Malformed, None, None, detail).at(&self.source, Some(line), raw)));
+            }
+        };
+        let tx = match self.parse(text) {
+            Ok(tx) => tx,
+            Err(err) => {
+                let detail = format!("error in parsing a transaction record: {}", err);
+                return Some(Err(Rejected::new(Reason::Malformed, None, None, detail).at(&self.source, Some(line), text.to_string())));
+            }
+        };
+        Some(accept(tx, self.dialect.precision).map_err(|rejected| match rejected.reason {
+            Reason::UnknownType => {
+                let raw_type = self.columns[0].and_then(|idx| text.split(char::from(self.dialect.delimiter)).nth(idx));
+                Rejected {
+                    detail: format!("unknown transaction type `{}`", raw_type.unwrap_or_default().trim()),
+                    ..rejected.at(&self.source, Some(line), text.to_string())
+                }
+            }
+            _ => rejected.at(&self.source, Some(line), text.to_string()),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::{from_reader_with, transactions_with};
+    use std::io::Write;
+
+    fn mapped(name: &str, input: &str, dialect: &Dialect) -> (File, Option<Records>) {
+        let path = std::env::temp_dir().join(format!("mapped-{}-{}", std::process::id(), name));
+        File::create(&path).unwrap().write_all(input.as_bytes()).unwrap();
+        let file = File::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let records = transactions("in.csv", dialect, &file).unwrap();
+        (file, records)
+    }
+
+    #[test]
+    fn test_same_as_csv_reader() {
+        let input = "type, client, tx, amount, timestamp\r\ndeposit, 1, 1, 1.5, 2024-03-05\n\n\
+                     withdrawal,1,2,0.25\ndispute,1,1,\nrefund,2,3,1.0\nresolve,1,1\n";
+        let dialect = Dialect::default();
+        let (_file, records) = mapped("same", input, &dialect);
+        let fast: Vec<_> = records.unwrap().map(|res| format!("{:?}", res)).collect();
+        let slow: Vec<_> = transactions_with("in.csv", &dialect, from_reader_with(&dialect, input.as_bytes()).unwrap())
+            .map(|res| format!("{:?}", res))
+            .collect();
+        assert_eq!(fast, slow);
+    }
+
+    #[test]
+    fn test_quoted_file_is_not_mapped() {
+        let (_file, records) = mapped("quoted", "type,client,tx,amount\n\"deposit\",1,1,1.0\n", &Dialect::default());
+        assert!(records.is_none());
+    }
+}
@@ -0,0 +1,35 @@
+use crate::engine::Transaction;
+use std::io::Read;
+
+/// Records encoded either as maps (`type`, `client`, `tx`, `amount` keys) or
+/// as arrays in that field order; the amount may be a float, an integer or a string.
+pub fn transactions<R: Read>(input: R) -> impl Iterator<Item = Result<Transaction, String>> {
+    super::records(input, |rdr| rmp_serde::from_read(rdr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TxType;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_read_stream() {
+        let mut input = Vec::new();
+        let records = [
+            serde_json::json!({"type": "deposit", "client": 1, "tx": 1, "amount": "1.5"}),
+            serde_json::json!({"type": "withdrawal", "client": 1, "tx": 2, "amount": 0.5}),
+            serde_json::json!({"type": "dispute", "client": 1, "tx": 1, "amount": null}),
+        ];
+        for record in &records {
+            input.extend(rmp_serde::to_vec_named(record).unwrap());
+        }
+        let txs: Vec<Transaction> = transactions(input.as_slice()).collect::<Result<_, _>>().unwrap();
+        assert_eq!(txs.len(), 3);
+        assert_eq!(txs[0].tx_type, TxType::Deposit);
+        assert_eq!(txs[0].amount, Some(dec!(1.5)));
+        assert_eq!(txs[1].amount, Some(dec!(0.5)));
+        assert_eq!(txs[2].tx_type, TxType::Dispute);
+        assert_eq!(txs[2].amount, None);
+    }
+}
@@ -0,0 +1,90 @@
+use super::bank::{self, BankAccounts, Entry};
+use crate::engine::Transaction;
+use crate::timestamp::Timestamp;
+use chrono::NaiveDateTime;
+use rust_decimal::Decimal;
+use std::io::Read;
+use std::str::FromStr;
+
+/// `<STMTTRN>` entries of an OFX file, either the SGML 1.x syntax, where
+/// elements are not closed, or the XML 2.x one. Entries belong to the
+/// `<ACCTID>` of the statement they are in.
+pub fn transactions<R: Read>(mut input: R, accounts: &BankAccounts) -> Result<impl Iterator<Item = Transaction>, String> {
+    let mut text = String::new();
+    input.read_to_string(&mut text).map_err(|err| format!("error in reading an OFX file: {}", err))?;
+    let entries = entries(&text).map_err(|err| format!("error in parsing an OFX file: {}", err))?;
+    bank::transactions(entries, accounts)
+}
+
+/// `YYYYMMDD[HHMMSS[.XXX]][[offset:zone]]`, the time zone is ignored
+fn datetime(value: &str) -> Result<Timestamp, String> {
+    let digits: String = value.chars().take_while(char::is_ascii_digit).collect();
+    let padded = match digits.len() {
+        8 => format!("{}000000", digits),
+        len if len >= 14 => digits[..14].to_string(),
+        _ => return Err(format!("invalid date `{}`", value)),
+    };
+    NaiveDateTime::parse_from_str(&padded, "%Y%m%d%H%M%S").map_err(|_| format!("invalid date `{}`", value))
+}
+
+fn entries(text: &str) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    let mut account: Option<String> = None;
+    let mut entry: Option<Entry> = None;
+    // every tag followed by the text up to the next one
+    for chunk in text.split('<').skip(1) {
+        let Some((tag, value)) = chunk.split_once('>') else {
+            return Err(format!("unterminated tag `<{}`", chunk.trim()));
+        };
+        let value = value.trim();
+        match tag.trim().to_ascii_uppercase().as_str() {
+            "ACCTID" => account = Some(value.to_string()),
+            "STMTTRN" => entry = Some(Entry { account: account.clone(), ..Entry::default() }),
+            "/STMTTRN" => entries.extend(entry.take()),
+            name => {
+                let Some(entry) = entry.as_mut() else {
+                    continue;
+                };
+                match name {
+                    "TRNAMT" => {
+                        let raw = value.trim_start_matches('+').replace(',', ".");
+                        entry.amount = Decimal::from_str(&raw).map_err(|_| format!("invalid amount `{}`", value))?;
+                    }
+                    "DTPOSTED" => entry.posted = Some(datetime(value)?),
+                    "NAME" | "PAYEE" if !value.is_empty() => entry.counterparty = Some(value.to_string()),
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_sgml_statement() {
+        let input = "OFXHEADER:100\nDATA:OFXSGML\n\n<OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS>\
+                     <BANKACCTFROM><BANKID>001<ACCTID>12345<ACCTTYPE>CHECKING</BANKACCTFROM>\
+                     <BANKTRANLIST>\n<STMTTRN>\n<TRNTYPE>CREDIT\n<DTPOSTED>20240305120000.000[-5:EST]\n<TRNAMT>1500.00\n<FITID>A1\n<NAME>ACME PAYROLL\n</STMTTRN>\n\
+                     <STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20240306<TRNAMT>-42.10<FITID>A2</STMTTRN>\
+                     </BANKTRANLIST></STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>";
+        let accounts = BankAccounts { accounts: [("12345".to_string(), 3)].into(), ..BankAccounts::default() };
+        let txs: Vec<Transaction> = transactions(input.as_bytes(), &accounts).unwrap().collect();
+        assert_eq!(txs.len(), 2);
+        assert_eq!((txs[0].client, txs[0].tx, txs[0].amount), (3, 1, Some(dec!(1500.00))));
+        assert_eq!(txs[0].counterparty.as_deref(), Some("ACME PAYROLL"));
+        assert_eq!(txs[0].timestamp, Some(datetime("20240305120000").unwrap()));
+        assert_eq!((txs[1].tx_type, txs[1].amount), (crate::engine::TxType::Withdrawal, Some(dec!(42.10))));
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        assert!(entries("<STMTTRN><TRNAMT>ten</STMTTRN>").is_err());
+        assert!(entries("<STMTTRN><DTPOSTED>2024</STMTTRN>").is_err());
+        assert_eq!(entries("<ACCTID>1<TRNAMT>5").unwrap(), vec![]);
+    }
+}
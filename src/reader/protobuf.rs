@@ -0,0 +1,116 @@
+use crate::engine::{Transaction, TxType};
+use prost::Message;
+use rust_decimal::Decimal;
+use std::io::{BufReader, Read};
+use std::str::FromStr;
+
+/// types generated from `schemas/transaction.proto`
+pub mod proto {
+    include!(concat!(env!("OUT_DIR"), "/transactions.rs"));
+}
+
+impl From<proto::TxType> for TxType {
+    fn from(tx_type: proto::TxType) -> TxType {
+        match tx_type {
+            proto::TxType::Deposit => TxType::Deposit,
+            proto::TxType::Withdrawal => TxType::Withdrawal,
+            proto::TxType::Dispute => TxType::Dispute,
+            proto::TxType::Resolve => TxType::Resolve,
+            proto::TxType::Chargeback => TxType::ChargeBack,
+        }
+    }
+}
+
+impl TryFrom<proto::Transaction> for Transaction {
+    type Error = String;
+
+    fn try_from(msg: proto::Transaction) -> Result<Transaction, String> {
+        // enum values added after this build are treated like unknown csv types
+        let tx_type = proto::TxType::try_from(msg.r#type).map_or(TxType::Unknown, TxType::from);
+        let client = u16::try_from(msg.client).map_err(|_| format!("client id {} out of range", msg.client))?;
+        let amount = msg
+            .amount
+            .map(|amount| Decimal::from_str(&amount).map_err(|err| format!("amount {}: {}", amount, err)))
+            .transpose()?;
+        Ok(Transaction { tx_type, client, tx: msg.tx, amount, ..Default::default() })
+    }
+}
+
+/// varint size prefix, `None` on a clean end of input
+fn read_len<R: Read>(input: &mut R) -> std::io::Result<Option<usize>> {
+    let mut len = 0usize;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8];
+        if input.read(&mut byte)? == 0 {
+            if shift == 0 {
+                return Ok(None);
+            }
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        len |= ((byte[0] & 0x7f) as usize) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(len));
+        }
+    }
+    Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "varint too long"))
+}
+
+/// Length-delimited stream of `Transaction` messages
+pub fn transactions<R: Read>(input: R) -> impl Iterator<Item = Result<Transaction, String>> {
+    let mut input = BufReader::new(input);
+    let mut buf = Vec::new();
+    std::iter::from_fn(move || {
+        let len = match read_len(&mut input) {
+            Ok(len) => len?,
+            Err(err) => return Some(Err(format!("error in reading input: {}", err))),
+        };
+        buf.resize(len, 0);
+        if let Err(err) = input.read_exact(&mut buf) {
+            return Some(Err(format!("error in reading input: {}", err)));
+        }
+        let res = proto::Transaction::decode(buf.as_slice())
+            .map_err(|err| err.to_string())
+            .and_then(Transaction::try_from);
+        Some(res.map_err(|err| format!("error in parsing a transaction record: {}", err)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_read_stream() {
+        let msgs = [
+            proto::Transaction {
+                r#type: proto::TxType::Deposit as i32,
+                client: 1,
+                tx: 1,
+                amount: Some("1.2345".to_string()),
+            },
+            proto::Transaction {
+                r#type: proto::TxType::Chargeback as i32,
+                client: 1,
+                tx: 1,
+                amount: None,
+            },
+        ];
+        let mut input = Vec::new();
+        for msg in &msgs {
+            msg.encode_length_delimited(&mut input).unwrap();
+        }
+        let txs: Vec<Transaction> = transactions(input.as_slice()).collect::<Result<_, _>>().unwrap();
+        assert_eq!(txs.len(), 2);
+        assert_eq!(txs[0].tx_type, TxType::Deposit);
+        assert_eq!(txs[0].amount, Some(dec!(1.2345)));
+        assert_eq!(txs[1].tx_type, TxType::ChargeBack);
+        assert_eq!(txs[1].amount, None);
+    }
+
+    #[test]
+    fn test_reject_out_of_range_client() {
+        let msg = proto::Transaction { r#type: 0, client: 70000, tx: 1, amount: None };
+        assert!(Transaction::try_from(msg).is_err());
+    }
+}
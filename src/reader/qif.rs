@@ -0,0 +1,108 @@
+use super::bank::{self, BankAccounts, Entry};
+use crate::engine::Transaction;
+use crate::timestamp::Timestamp;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::io::Read;
+use std::str::FromStr;
+
+/// Records of a QIF file, one field per line and `^` after each record.
+/// Entries belong to the `N` name of the last `!Account` block, if any.
+pub fn transactions<R: Read>(mut input: R, accounts: &BankAccounts) -> Result<impl Iterator<Item = Transaction>, String> {
+    let mut text = String::new();
+    input.read_to_string(&mut text).map_err(|err| format!("error in reading a QIF file: {}", err))?;
+    let entries = entries(&text).map_err(|err| format!("error in parsing a QIF file: {}", err))?;
+    bank::transactions(entries, accounts)
+}
+
+/// US ordered `M/D/YYYY`, `M/D/YY` or `M/D'YY` (years after 2000), or `YYYY-MM-DD`
+fn date(value: &str) -> Result<Timestamp, String> {
+    let error = || format!("invalid date `{}`", value);
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_time(Default::default()));
+    }
+    let (month_day, year) = value.rsplit_once(['/', '\'']).ok_or_else(error)?;
+    let (month, day) = month_day.split_once('/').ok_or_else(error)?;
+    let number = |part: &str| part.trim().parse::<u32>().map_err(|_| error());
+    let year = match number(year)? {
+        year if year < 100 && value.contains('\'') => 2000 + year,
+        year if year < 100 => 1900 + year + if year < 70 { 100 } else { 0 },
+        year => year,
+    };
+    let date = NaiveDate::from_ymd_opt(year as i32, number(month)?, number(day)?).ok_or_else(error)?;
+    Ok(date.and_time(Default::default()))
+}
+
+fn entries(text: &str) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    let mut account: Option<String> = None;
+    let mut in_account = false;
+    let mut entry = Entry::default();
+    let mut fields = 0;
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let error = |message: String| format!("line {}: {}", idx + 1, message);
+        if line.starts_with('!') {
+            in_account = line.eq_ignore_ascii_case("!Account");
+            continue;
+        }
+        let Some(code) = line.chars().next() else {
+            continue;
+        };
+        let value = line[code.len_utf8()..].trim();
+        if in_account {
+            match code {
+                'N' => account = Some(value.to_string()),
+                '^' => in_account = false,
+                _ => {}
+            }
+            continue;
+        }
+        match code {
+            '^' => {
+                if fields > 0 {
+                    entries.push(std::mem::take(&mut entry));
+                }
+                fields = 0;
+                continue;
+            }
+            'T' | 'U' => {
+                let raw = value.replace(',', "");
+                entry.amount = Decimal::from_str(&raw).map_err(|_| error(format!("invalid amount `{}`", value)))?;
+            }
+            'D' => entry.posted = Some(date(value).map_err(error)?),
+            'P' if !value.is_empty() => entry.counterparty = Some(value.to_string()),
+            _ => {}
+        }
+        entry.account = account.clone();
+        fields += 1;
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TxType;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_bank_file() {
+        let input = "!Account\nNChecking\nTBank\n^\n!Type:Bank\nD03/05/2024\nT1,500.00\nPACME Payroll\n^\nD3/6'24\nT-42.10\nMGroceries\n^\n";
+        let accounts = BankAccounts { accounts: [("Checking".to_string(), 5)].into(), ..BankAccounts::default() };
+        let txs: Vec<Transaction> = transactions(input.as_bytes(), &accounts).unwrap().collect();
+        assert_eq!(txs.len(), 2);
+        assert_eq!((txs[0].client, txs[0].tx_type, txs[0].amount), (5, TxType::Deposit, Some(dec!(1500.00))));
+        assert_eq!(txs[0].counterparty.as_deref(), Some("ACME Payroll"));
+        assert_eq!((txs[1].tx_type, txs[1].amount), (TxType::Withdrawal, Some(dec!(42.10))));
+        assert_eq!(txs[1].timestamp, Some(date("2024-03-06").unwrap()));
+    }
+
+    #[test]
+    fn test_dates() {
+        assert_eq!(date("12/31/99").unwrap(), date("1999-12-31").unwrap());
+        assert_eq!(date("1/2/05").unwrap(), date("2005-01-02").unwrap());
+        assert!(date("13/01/2024").is_err());
+        assert!(entries("!Type:Bank\nTabc\n^\n").unwrap_err().starts_with("line 2"));
+    }
+}
@@ -0,0 +1,110 @@
+use crate::amount;
+use crate::engine::{Account, Decision, Engine, Transaction};
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// One row of the external statement
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct LedgerEntry {
+    pub client: u16,
+    pub expected_total: Decimal,
+}
+
+/// A client whose computed total differs from the ledger.
+/// `None` means the client is missing from that side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub client: u16,
+    pub expected: Option<Decimal>,
+    pub computed: Option<Decimal>,
+}
+
+impl Mismatch {
+    pub fn difference(&self) -> Decimal {
+        self.computed.unwrap_or_default() - self.expected.unwrap_or_default()
+    }
+}
+
+pub fn read_ledger<R: std::io::Read>(mut rdr: csv::Reader<R>) -> Vec<LedgerEntry> {
+    rdr.deserialize()
+        .map(|res| {
+            assert!(res.is_ok(), "error in parsing a ledger record: {:?}", res.err());
+            res.unwrap()
+        })
+        .collect()
+}
+
+/// mismatching clients sorted by client id, clients only known to one side included
+pub fn reconcile(ledger: &[LedgerEntry], accounts: &[Account]) -> Vec<Mismatch> {
+    let mut pairs: BTreeMap<u16, (Option<Decimal>, Option<Decimal>)> = BTreeMap::new();
+    for entry in ledger {
+        pairs.entry(entry.client).or_default().0 = Some(entry.expected_total);
+    }
+    for account in accounts {
+        pairs.entry(account.client).or_default().1 = Some(amount::to_decimal(account.total));
+    }
+    pairs
+        .into_iter()
+        .filter(|(_, (expected, computed))| expected != computed)
+        .map(|(client, (expected, computed))| Mismatch { client, expected, computed })
+        .collect()
+}
+
+/// Replay the transactions of the given clients only, returning each of them
+/// with the decision the engine took; accounts are independent so the outcome
+/// is the same as in the full run.
+pub fn drill_down(
+    txs: impl Iterator<Item = Transaction>,
+    clients: &HashSet<u16>,
+) -> HashMap<u16, Vec<(Transaction, Decision)>> {
+    let mut engine = Engine::new();
+    let mut contributions: HashMap<u16, Vec<(Transaction, Decision)>> = HashMap::new();
+    for tx in txs.filter(|tx| clients.contains(&tx.client)) {
+        let decision = engine.apply(tx.clone());
+        contributions.entry(tx.client).or_default().push((tx, decision));
+    }
+    contributions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TxType;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_reconcile() {
+        let mut engine = Engine::new();
+        let txs = vec![
+            Transaction{client: 1, tx_type: TxType::Deposit, tx: 1, amount: Some(dec!(1.0)), ..Default::default()},
+            Transaction{client: 2, tx_type: TxType::Deposit, tx: 2, amount: Some(dec!(2.0)), ..Default::default()},
+            Transaction{client: 2, tx_type: TxType::Withdrawal, tx: 3, amount: Some(dec!(3.0)), ..Default::default()},
+            Transaction{client: 3, tx_type: TxType::Deposit, tx: 4, amount: Some(dec!(1.0)), ..Default::default()},
+        ];
+        for tx in txs.clone() {
+            engine.apply(tx);
+        }
+        let accounts: Vec<Account> = engine.into_accounts().collect();
+        let ledger = vec![
+            LedgerEntry { client: 1, expected_total: dec!(1) },
+            LedgerEntry { client: 2, expected_total: dec!(-1) },
+            LedgerEntry { client: 5, expected_total: dec!(4) },
+        ];
+        let res = reconcile(&ledger, &accounts);
+        assert_eq!(
+            res,
+            vec![
+                Mismatch { client: 2, expected: Some(dec!(-1)), computed: Some(dec!(2.0)) },
+                Mismatch { client: 3, expected: None, computed: Some(dec!(1.0)) },
+                Mismatch { client: 5, expected: Some(dec!(4)), computed: None },
+            ]
+        );
+        assert_eq!(res[0].difference(), dec!(3));
+
+        let clients = HashSet::from([2]);
+        let details = drill_down(txs.into_iter(), &clients);
+        let decisions: Vec<(u32, Decision)> = details[&2].iter().map(|(tx, d)| (tx.tx, *d)).collect();
+        assert_eq!(decisions, vec![(2, Decision::Applied), (3, Decision::Ignored)]);
+        assert!(!details.contains_key(&1));
+    }
+}
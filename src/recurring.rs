@@ -0,0 +1,98 @@
+use crate::engine::{Transaction, TxType};
+use crate::timestamp::Timestamp;
+use chrono::{NaiveDate, TimeDelta};
+use rust_decimal::Decimal;
+
+/// A deposit or withdrawal repeated every `every_days` from `start`, a
+/// `[[recurring]]` table of the config file. Occurrences get the tx ids
+/// `tx`, `tx + 1` and so on, which the input must leave free.
+#[derive(serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Recurring {
+    pub client: u16,
+    #[serde(rename = "type")]
+    pub tx_type: TxType,
+    pub amount: Decimal,
+    /// day of the first occurrence, at midnight
+    pub start: NaiveDate,
+    pub every_days: u32,
+    /// number of occurrences, unlimited if not set
+    pub count: Option<u32>,
+    pub tx: u32,
+}
+
+/// Expands the recurring definitions into transactions as the input time goes by
+#[derive(Debug, Clone, Default)]
+pub struct Recurrences {
+    definitions: Vec<Recurring>,
+    // index of the next occurrence of each definition
+    next: Vec<u32>,
+}
+
+impl Recurrences {
+    pub fn new(definitions: &[Recurring]) -> Recurrences {
+        for definition in definitions {
+            assert!(
+                matches!(definition.tx_type, TxType::Deposit | TxType::Withdrawal),
+                "recurring transactions of client {} must be deposits or withdrawals",
+                definition.client
+            );
+            assert!(definition.every_days > 0, "recurring transactions of client {} need every_days", definition.client);
+        }
+        Recurrences { definitions: definitions.to_vec(), next: vec![0; definitions.len()] }
+    }
+
+    /// occurrences due by `now` not returned yet, in time order
+    pub fn due(&mut self, now: Timestamp) -> Vec<Transaction> {
+        let mut due = Vec::new();
+        for (definition, next) in self.definitions.iter().zip(self.next.iter_mut()) {
+            while definition.count.is_none_or(|count| *next < count) {
+                let days = i64::from(definition.every_days) * i64::from(*next);
+                let time = definition.start.and_time(Default::default()) + TimeDelta::days(days);
+                if time > now {
+                    break;
+                }
+                let tx = definition.tx.checked_add(*next);
+                assert!(tx.is_some(), "no tx id left for the recurring transactions of client {}", definition.client);
+                due.push(Transaction {
+                    client: definition.client,
+                    tx_type: definition.tx_type,
+                    tx: tx.unwrap(),
+                    amount: Some(definition.amount),
+                    timestamp: Some(time),
+                    ..Default::default()
+                });
+                *next += 1;
+            }
+        }
+        due.sort_by_key(|tx| tx.timestamp);
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timestamp::parse;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_weekly_occurrences() {
+        let definition = Recurring {
+            client: 3,
+            tx_type: TxType::Withdrawal,
+            amount: dec!(9.99),
+            start: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            every_days: 7,
+            count: Some(3),
+            tx: 1000,
+        };
+        let mut recurrences = Recurrences::new(&[definition]);
+        assert!(recurrences.due(parse("2023-12-31").unwrap()).is_empty());
+        let due: Vec<u32> = recurrences.due(parse("2024-01-08").unwrap()).iter().map(|tx| tx.tx).collect();
+        assert_eq!(due, vec![1000, 1001]);
+        let due = recurrences.due(parse("2024-12-31").unwrap());
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].timestamp, Some(parse("2024-01-15").unwrap()));
+    }
+}
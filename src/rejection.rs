@@ -0,0 +1,150 @@
+use crate::pseudonym::{self, Pseudonymizer};
+use std::collections::BTreeMap;
+use std::fs::File;
+
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    /// value of the type column is not a known transaction type
+    UnknownType,
+    /// dispute, resolve, chargeback, settle or release of a tx that belongs to another client
+    ForeignTx,
+    /// amount with more decimal places than the precision policy allows
+    Precision,
+    /// deposit or withdrawal above the cap of the client's KYC level
+    Kyc,
+    /// occurrence of a recurring withdrawal the account could not cover
+    InsufficientFunds,
+    /// record the csv layer cannot split, or whose fields do not parse
+    Malformed,
+    /// deposit, withdrawal or escrow without amount
+    MissingAmount,
+    /// row of a batch discarded as a whole because one of its rows was rejected
+    Batch,
+    /// row whose seq is behind, or the same as, one already seen for the client
+    Sequence,
+    /// seq numbers of a client that never arrived, not a row
+    SequenceGap,
+    /// dispute, resolve or chargeback whose tx did not show up within the retry window
+    Unmatched,
+    /// conversion between two currencies the rates do not cover
+    NoRate,
+}
+
+/// An input row skipped instead of being processed
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Rejected {
+    /// input file the row comes from
+    pub file: String,
+    /// 1-based line of the row, for line oriented inputs
+    pub line: Option<u64>,
+    pub reason: Reason,
+    pub client: Option<u16>,
+    pub tx: Option<u32>,
+    pub detail: String,
+    /// content of the row as read
+    pub raw: String,
+}
+
+impl Rejected {
+    pub fn new(reason: Reason, client: Option<u16>, tx: Option<u32>, detail: String) -> Rejected {
+        Rejected {
+            file: String::new(),
+            line: None,
+            reason,
+            client,
+            tx,
+            detail,
+            raw: String::new(),
+        }
+    }
+
+    /// the same rejection pointing at the row it comes from
+    pub fn at(self, file: &str, line: Option<u64>, raw: String) -> Rejected {
+        Rejected {
+            file: file.to_string(),
+            line,
+            raw,
+            ..self
+        }
+    }
+}
+
+/// `file:line: detail`, as printed by the subcommands that stop on rejected rows
+impl std::fmt::Display for Rejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.file, line, self.detail),
+            None => write!(f, "{}: {}", self.file, self.detail),
+        }
+    }
+}
+
+/// A rejected row as written to the rejection file
+#[derive(serde::Serialize)]
+struct Row<'a> {
+    file: &'a str,
+    line: Option<u64>,
+    reason: Reason,
+    client: Option<String>,
+    tx: Option<u32>,
+    detail: &'a str,
+    raw: &'a str,
+}
+
+/// Counts rejected rows by reason and optionally writes them to a csv file
+#[derive(Default)]
+pub struct RejectionLog {
+    wrt: Option<csv::Writer<File>>,
+    counts: BTreeMap<Reason, u64>,
+    pseudonyms: Option<Pseudonymizer>,
+}
+
+impl RejectionLog {
+    pub fn new(path: Option<&str>) -> RejectionLog {
+        let wrt = path.map(|path| {
+            let res = csv::Writer::from_path(path);
+            assert!(res.is_ok(), "cannot create rejection file {}: {:?}", path, res.err());
+            res.unwrap()
+        });
+        RejectionLog { wrt, counts: BTreeMap::new(), pseudonyms: None }
+    }
+
+    /// write pseudonymized client ids, the raw rows are left out since they contain the real ones
+    pub fn pseudonymize(self, pseudonyms: Pseudonymizer) -> RejectionLog {
+        RejectionLog { pseudonyms: Some(pseudonyms), ..self }
+    }
+
+    pub fn record(&mut self, rejected: Rejected) {
+        *self.counts.entry(rejected.reason).or_default() += 1;
+        if let Some(wrt) = self.wrt.as_mut() {
+            let pseudonyms = self.pseudonyms.as_ref();
+            let row = Row {
+                file: &rejected.file,
+                line: rejected.line,
+                reason: rejected.reason,
+                client: rejected.client.map(|client| pseudonym::label(pseudonyms, client)),
+                tx: rejected.tx,
+                detail: &rejected.detail,
+                raw: if pseudonyms.is_some() { "" } else { &rejected.raw },
+            };
+            let res = wrt.serialize(row);
+            assert!(res.is_ok(), "error in writing the rejection file");
+        }
+    }
+
+    pub fn counts(&self) -> &BTreeMap<Reason, u64> {
+        &self.counts
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    pub fn flush(&mut self) {
+        if let Some(wrt) = self.wrt.as_mut() {
+            let res = wrt.flush();
+            assert!(res.is_ok(), "error in writing the rejection file");
+        }
+    }
+}
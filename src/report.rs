@@ -0,0 +1,210 @@
+use crate::amount::{self, Amount};
+use crate::engine::Account;
+use crate::rejection::Rejected;
+use std::fmt::Write;
+
+// clients shown in the held funds chart
+const TOP_HELD: usize = 10;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub accounts: usize,
+    pub locked: usize,
+    pub available: Amount,
+    pub held: Amount,
+    pub total: Amount,
+    pub rejected: usize,
+}
+
+/// Daily ops report: summary figures, clients holding the most disputed
+/// funds, locked accounts and rejected rows
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub summary: Summary,
+    /// (client, held funds) by decreasing held funds
+    pub top_held: Vec<(u16, Amount)>,
+    pub locked: Vec<Account>,
+    pub rejections: Vec<Rejected>,
+}
+
+impl Report {
+    pub fn new(accounts: impl Iterator<Item = Account>, rejections: Vec<Rejected>) -> Report {
+        let mut accounts: Vec<Account> = accounts.collect();
+        accounts.sort_by_key(|account| account.client);
+        let mut summary = Summary {
+            accounts: accounts.len(),
+            rejected: rejections.len(),
+            ..Default::default()
+        };
+        for account in &accounts {
+            summary.available += account.available;
+            summary.held += account.held;
+            summary.total += account.total;
+            summary.locked += usize::from(account.locked);
+        }
+        let mut top_held: Vec<(u16, Amount)> = accounts
+            .iter()
+            .filter(|account| account.held > Amount::default())
+            .map(|account| (account.client, account.held))
+            .collect();
+        top_held.sort_by_key(|(_, held)| std::cmp::Reverse(*held));
+        top_held.truncate(TOP_HELD);
+        let locked = accounts.into_iter().filter(|account| account.locked).collect();
+        Report { summary, top_held, locked, rejections }
+    }
+
+    fn summary_rows(&self) -> [(&'static str, String); 6] {
+        let summary = &self.summary;
+        [
+            ("accounts", summary.accounts.to_string()),
+            ("locked accounts", summary.locked.to_string()),
+            ("available funds", summary.available.to_string()),
+            ("held funds", summary.held.to_string()),
+            ("total funds", summary.total.to_string()),
+            ("rejected rows", summary.rejected.to_string()),
+        ]
+    }
+
+    /// self-contained page, the chart is inline svg
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Transactions report</title>\n");
+        out.push_str("<style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse;margin-bottom:2em}");
+        out.push_str("td,th{border:1px solid #ccc;padding:4px 8px}td.n{text-align:right}</style>\n</head>\n<body>\n");
+        out.push_str("<h1>Transactions report</h1>\n<h2>Summary</h2>\n<table>\n");
+        for (name, value) in self.summary_rows() {
+            let _ = writeln!(out, "<tr><th>{}</th><td class=\"n\">{}</td></tr>", name, value);
+        }
+        out.push_str("</table>\n<h2>Held funds</h2>\n");
+        out.push_str(&self.held_chart());
+        out.push_str("<h2>Locked accounts</h2>\n<table>\n<tr><th>client</th><th>available</th><th>held</th><th>total</th></tr>\n");
+        for account in &self.locked {
+            let _ = writeln!(
+                out,
+                "<tr><td class=\"n\">{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td></tr>",
+                account.client, account.available, account.held, account.total
+            );
+        }
+        out.push_str("</table>\n<h2>Rejected rows</h2>\n<table>\n<tr><th>file</th><th>line</th><th>reason</th><th>detail</th><th>row</th></tr>\n");
+        for rejected in &self.rejections {
+            let _ = writeln!(
+                out,
+                "<tr><td>{}</td><td class=\"n\">{}</td><td>{:?}</td><td>{}</td><td><code>{}</code></td></tr>",
+                escape(&rejected.file),
+                rejected.line.map_or(String::new(), |line| line.to_string()),
+                rejected.reason,
+                escape(&rejected.detail),
+                escape(&rejected.raw)
+            );
+        }
+        out.push_str("</table>\n</body>\n</html>\n");
+        out
+    }
+
+    /// horizontal bars of the clients holding the most funds
+    fn held_chart(&self) -> String {
+        if self.top_held.is_empty() {
+            return "<p>no funds held</p>\n".to_string();
+        }
+        let (bar_height, width) = (20, 400.0);
+        let max = amount::to_decimal(self.top_held[0].1);
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"600\" height=\"{}\">",
+            self.top_held.len() * bar_height
+        );
+        for (idx, (client, held)) in self.top_held.iter().enumerate() {
+            let ratio = amount::to_decimal(*held) / max;
+            let len = width * f64::try_from(ratio).unwrap_or(0.0);
+            let y = idx * bar_height;
+            let _ = writeln!(
+                svg,
+                "<text x=\"0\" y=\"{}\" font-size=\"12\">client {}</text><rect x=\"90\" y=\"{}\" width=\"{:.1}\" height=\"{}\" fill=\"#d9534f\"/><text x=\"{:.1}\" y=\"{}\" font-size=\"12\">{}</text>",
+                y + 14,
+                client,
+                y + 2,
+                len,
+                bar_height - 4,
+                95.0 + len,
+                y + 14,
+                held
+            );
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("# Transactions report\n\n## Summary\n\n| | |\n|---|---:|\n");
+        for (name, value) in self.summary_rows() {
+            let _ = writeln!(out, "| {} | {} |", name, value);
+        }
+        out.push_str("\n## Held funds\n\n| client | held |\n|---:|---:|\n");
+        for (client, held) in &self.top_held {
+            let _ = writeln!(out, "| {} | {} |", client, held);
+        }
+        out.push_str("\n## Locked accounts\n\n| client | available | held | total |\n|---:|---:|---:|---:|\n");
+        for account in &self.locked {
+            let _ = writeln!(out, "| {} | {} | {} | {} |", account.client, account.available, account.held, account.total);
+        }
+        out.push_str("\n## Rejected rows\n\n| file | line | reason | detail | row |\n|---|---:|---|---|---|\n");
+        for rejected in &self.rejections {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {:?} | {} | `{}` |",
+                rejected.file,
+                rejected.line.map_or(String::new(), |line| line.to_string()),
+                rejected.reason,
+                rejected.detail.replace('|', "\\|"),
+                rejected.raw.replace('|', "\\|")
+            );
+        }
+        out
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Engine, Transaction, TxType};
+    use crate::rejection::Reason;
+    use rust_decimal_macros::dec;
+
+    fn report() -> Report {
+        let mut engine = Engine::new();
+        let tx = |client, tx_type, tx, amount| Transaction{client, tx_type, tx, amount, ..Default::default()};
+        engine.apply(tx(1, TxType::Deposit, 1, Some(dec!(5.0))));
+        engine.apply(tx(1, TxType::Dispute, 1, None));
+        engine.apply(tx(2, TxType::Deposit, 2, Some(dec!(3.0))));
+        engine.apply(tx(2, TxType::Dispute, 2, None));
+        engine.apply(tx(2, TxType::ChargeBack, 2, None));
+        engine.apply(tx(3, TxType::Deposit, 3, Some(dec!(1.0))));
+        let rejected = Rejected::new(Reason::UnknownType, Some(4), Some(4), "unknown transaction type `<refund>`".to_string());
+        Report::new(engine.into_accounts(), vec![rejected.at("in.csv", Some(7), "<refund>,4,4,1.0".to_string())])
+    }
+
+    #[test]
+    fn test_summary() {
+        let report = report();
+        assert_eq!(report.summary.accounts, 3);
+        assert_eq!(report.summary.locked, 1);
+        assert_eq!(report.summary.held, dec!(5.0));
+        assert_eq!(report.summary.total, dec!(6.0));
+        assert_eq!(report.top_held.len(), 1);
+        assert_eq!(report.locked[0].client, 2);
+    }
+
+    #[test]
+    fn test_html_is_escaped() {
+        let html = report().to_html();
+        assert!(html.contains("&lt;refund&gt;,4,4,1.0"));
+        assert!(!html.contains("<refund>"));
+        assert!(html.contains("<svg"));
+        assert!(report().to_markdown().contains("| 2 | 0"));
+    }
+}
@@ -0,0 +1,107 @@
+use crate::engine::Transaction;
+use crate::timestamp::Timestamp;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Future-dated transactions held back until the processing clock reaches
+/// their `due` time. The clock follows the timestamps of the input rows;
+/// transactions without a due time are never held.
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    clock: Timestamp,
+    // due time --> transactions, in arrival order
+    queue: BTreeMap<Timestamp, Vec<Transaction>>,
+}
+
+impl Scheduler {
+    pub fn new(clock: Timestamp) -> Scheduler {
+        Scheduler { clock, queue: BTreeMap::new() }
+    }
+
+    pub fn clock(&self) -> Timestamp {
+        self.clock
+    }
+
+    /// the transaction if it can be applied now, otherwise it is queued
+    pub fn submit(&mut self, tx: Transaction) -> Option<Transaction> {
+        match tx.due {
+            Some(time) if time > self.clock => {
+                self.queue.entry(time).or_default().push(tx);
+                None
+            }
+            _ => Some(tx),
+        }
+    }
+
+    /// move the clock forward, returning the transactions now due in time order
+    pub fn advance(&mut self, now: Timestamp) -> Vec<Transaction> {
+        self.clock = self.clock.max(now);
+        let later = self.queue.split_off(&(self.clock + chrono::TimeDelta::nanoseconds(1)));
+        std::mem::replace(&mut self.queue, later).into_values().flatten().collect()
+    }
+
+    /// queued transactions in due order
+    pub fn pending(&self) -> impl Iterator<Item = &Transaction> {
+        self.queue.values().flatten()
+    }
+}
+
+/// A queued transaction as written to the scheduled report
+#[derive(serde::Serialize)]
+struct Row {
+    #[serde(rename = "type")]
+    tx_type: String,
+    client: u16,
+    tx: u32,
+    amount: Option<rust_decimal::Decimal>,
+    timestamp: Option<Timestamp>,
+    due: Option<Timestamp>,
+}
+
+pub fn write_csv<'a, W: Write>(pending: impl Iterator<Item = &'a Transaction>, wrt: W) {
+    let mut wrt = csv::Writer::from_writer(wrt);
+    for tx in pending {
+        let row = Row {
+            tx_type: format!("{:?}", tx.tx_type).to_lowercase(),
+            client: tx.client,
+            tx: tx.tx,
+            amount: tx.amount,
+            timestamp: tx.timestamp,
+            due: tx.due,
+        };
+        let res = wrt.serialize(row);
+        assert!(res.is_ok(), "error in writing the scheduled transactions");
+    }
+    let res = wrt.flush();
+    assert!(res.is_ok(), "error in writing the scheduled transactions");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TxType;
+    use crate::timestamp::parse;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_future_transactions_wait_for_the_clock() {
+        let tx = |tx, time: Option<&str>| Transaction {
+            client: 1,
+            tx_type: TxType::Deposit,
+            tx,
+            amount: Some(dec!(1.0)),
+            due: time.map(|time| parse(time).unwrap()),
+            ..Default::default()
+        };
+        let mut scheduler = Scheduler::new(parse("2024-03-01").unwrap());
+        assert!(scheduler.submit(tx(1, Some("2024-02-28"))).is_some());
+        assert!(scheduler.submit(tx(2, None)).is_some());
+        assert!(scheduler.submit(tx(3, Some("2024-03-10"))).is_none());
+        assert!(scheduler.submit(tx(4, Some("2024-03-05"))).is_none());
+        let pending: Vec<u32> = scheduler.pending().map(|tx| tx.tx).collect();
+        assert_eq!(pending, vec![4, 3]);
+        let due: Vec<u32> = scheduler.advance(parse("2024-03-05").unwrap()).iter().map(|tx| tx.tx).collect();
+        assert_eq!(due, vec![4]);
+        assert_eq!(scheduler.pending().count(), 1);
+    }
+}
@@ -0,0 +1,179 @@
+use crate::engine::Transaction;
+use crate::rejection::{Reason, Rejected};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// What happens to rows whose `seq` is ahead of the next one expected for the client
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ordering {
+    /// rows behind the client's last seq are rejected, jumps are reported as gaps
+    Reject,
+    /// rows ahead are parked until the missing ones arrive, for a window of rows
+    Reorder,
+}
+
+/// Per client `seq` checks, rows without a seq pass through
+#[derive(Debug, Default)]
+pub struct Sequencer {
+    // rows a parked transaction waits for the missing ones, 0 to never park
+    window: u64,
+    // rows seen so far
+    row: u64,
+    // client --> next seq expected
+    next: HashMap<u16, u64>,
+    // client --> seq --> (row it arrived at, transaction), rows ahead of the next seq
+    parked: BTreeMap<u16, BTreeMap<u64, (u64, Transaction)>>,
+}
+
+impl Sequencer {
+    pub fn new(ordering: Ordering, window: u64) -> Sequencer {
+        let window = if ordering == Ordering::Reorder { window } else { 0 };
+        Sequencer { window, ..Default::default() }
+    }
+
+    /// the transactions that can go on after `tx`, in order, with the rejected ones and the gaps
+    pub fn push(&mut self, tx: Transaction) -> Vec<Result<Transaction, Rejected>> {
+        self.row += 1;
+        let mut out = Vec::new();
+        match tx.seq {
+            None => out.push(Ok(tx)),
+            Some(seq) => {
+                let client = tx.client;
+                let next = self.next.get(&client).copied();
+                let parked = self.parked.get(&client).is_some_and(|parked| parked.contains_key(&seq));
+                match next {
+                    Some(next) if seq < next || parked => {
+                        let detail = format!("seq {} is late or repeated, next expected {}", seq, next);
+                        out.push(Err(Rejected::new(Reason::Sequence, Some(client), Some(tx.tx), detail)));
+                    }
+                    Some(next) if seq > next && self.window > 0 => {
+                        self.parked.entry(client).or_default().insert(seq, (self.row, tx));
+                    }
+                    _ => {
+                        if let Some(next) = next.filter(|next| seq > *next) {
+                            out.push(Err(gap(client, next, seq)));
+                        }
+                        self.next.insert(client, seq + 1);
+                        out.push(Ok(tx));
+                        self.release(client, &mut out);
+                    }
+                }
+            }
+        }
+        // clients whose oldest parked row waited the whole window skip the missing ones
+        let expired: Vec<u16> = self
+            .parked
+            .iter()
+            .filter(|(_, parked)| parked.values().any(|(row, _)| row + self.window <= self.row))
+            .map(|(client, _)| *client)
+            .collect();
+        for client in expired {
+            self.skip(client, &mut out);
+        }
+        out
+    }
+
+    /// every parked transaction once the input ends, skipping the missing ones
+    pub fn finish(&mut self) -> Vec<Result<Transaction, Rejected>> {
+        let mut out = Vec::new();
+        let clients: Vec<u16> = self.parked.keys().copied().collect();
+        for client in clients {
+            while self.parked.contains_key(&client) {
+                self.skip(client, &mut out);
+            }
+        }
+        out
+    }
+
+    /// move the next seq to the first parked transaction of the client, reporting the gap
+    fn skip(&mut self, client: u16, out: &mut Vec<Result<Transaction, Rejected>>) {
+        let Some(&first) = self.parked.get(&client).and_then(|parked| parked.keys().next()) else {
+            return;
+        };
+        let next = self.next.insert(client, first).unwrap_or(first);
+        out.push(Err(gap(client, next, first)));
+        self.release(client, out);
+    }
+
+    /// the parked transactions following the client's next seq
+    fn release(&mut self, client: u16, out: &mut Vec<Result<Transaction, Rejected>>) {
+        let Some(parked) = self.parked.get_mut(&client) else {
+            return;
+        };
+        let next = self.next.get_mut(&client).expect("parked rows of a client without a seq");
+        while let Some((_, tx)) = parked.remove(next) {
+            *next += 1;
+            out.push(Ok(tx));
+        }
+        if parked.is_empty() {
+            self.parked.remove(&client);
+        }
+    }
+}
+
+fn gap(client: u16, from: u64, to: u64) -> Rejected {
+    let detail = format!("seq {} to {} missing", from, to - 1);
+    Rejected::new(Reason::SequenceGap, Some(client), None, detail)
+}
+
+/// The transactions in per client `seq` order, as far as the ordering allows
+pub fn ordered(
+    txs: impl Iterator<Item = Result<Transaction, Rejected>>,
+    mut sequencer: Sequencer,
+) -> impl Iterator<Item = Result<Transaction, Rejected>> {
+    let mut txs = txs.fuse();
+    let mut ready = VecDeque::new();
+    std::iter::from_fn(move || loop {
+        if let Some(res) = ready.pop_front() {
+            return Some(res);
+        }
+        match txs.next() {
+            Some(Ok(tx)) => ready.extend(sequencer.push(tx)),
+            Some(Err(rejected)) => return Some(Err(rejected)),
+            None => {
+                ready.extend(sequencer.finish());
+                if ready.is_empty() {
+                    return None;
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TxType;
+    use rust_decimal_macros::dec;
+
+    fn tx(client: u16, seq: u64) -> Transaction {
+        Transaction {
+            client,
+            tx_type: TxType::Deposit,
+            tx: u32::from(client) * 100 + seq as u32,
+            amount: Some(dec!(1.0)),
+            seq: Some(seq),
+            ..Default::default()
+        }
+    }
+
+    fn run(ordering: Ordering, window: u64, txs: Vec<Transaction>) -> Vec<Result<u32, Reason>> {
+        ordered(txs.into_iter().map(Ok), Sequencer::new(ordering, window))
+            .map(|res| res.map(|tx| tx.tx).map_err(|rejected| rejected.reason))
+            .collect()
+    }
+
+    #[test]
+    fn test_reject_out_of_order() {
+        let res = run(Ordering::Reject, 10, vec![tx(1, 1), tx(1, 3), tx(1, 2), tx(1, 4)]);
+        assert_eq!(res, vec![Ok(101), Err(Reason::SequenceGap), Ok(103), Err(Reason::Sequence), Ok(104)]);
+    }
+
+    #[test]
+    fn test_reorder_within_window() {
+        let res = run(Ordering::Reorder, 2, vec![tx(1, 1), tx(1, 3), tx(2, 1), tx(1, 2), tx(1, 5), tx(2, 2), tx(2, 3)]);
+        assert_eq!(
+            res,
+            vec![Ok(101), Ok(201), Ok(102), Ok(103), Ok(202), Ok(203), Err(Reason::SequenceGap), Ok(105)]
+        );
+    }
+}
@@ -0,0 +1,794 @@
+use crate::audit::Journal;
+use crate::config::Config;
+use crate::engine::{Account, Decision, Engine, MergeError, Transaction, TxType};
+use crate::notifier::Update;
+use crate::precision::Precision;
+use crate::reader;
+use crate::wal::Wal;
+use async_graphql_axum::GraphQL;
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::middleware;
+use axum::{Json, Router};
+use auth::Tokens;
+use evict::Evicted;
+use limit::{Key, Limiter};
+use graphql::DisputeEvent;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Instant, SystemTime};
+use tokio::sync::oneshot;
+
+mod auth;
+mod evict;
+#[cfg(feature = "flight")]
+mod flight;
+mod graphql;
+mod limit;
+mod tls;
+pub use auth::{Role, Token};
+pub use evict::Eviction;
+pub use limit::{LimitKey, RateLimit};
+pub use tls::{listen, Tls};
+
+/// transactions applied between two snapshots of a router with a write-ahead log
+const CHECKPOINT: u64 = 10_000;
+/// requests waiting for the engine beyond which the router is not ready
+const READY_BACKLOG: usize = 1024;
+
+/// The `[server]` table of the config file
+#[derive(serde::Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// bearer tokens of the API, `[[server.tokens]]` with `token` and `role`; the API is open without any
+    pub tokens: Vec<Token>,
+    /// requests of each token or client, `[server.rate_limit]` with `rate` per second, `burst` and `key`
+    pub rate_limit: Option<RateLimit>,
+    /// accounts kept in memory, `[server.eviction]` with `resident` and the `dir` of the others
+    pub eviction: Option<Eviction>,
+    /// audit journal of the transactions applied, see `Journal`
+    pub journal: Option<String>,
+}
+
+/// Request to the thread owning the engine, with the channel of its reply;
+/// accounts are sent without their transactions
+enum Request {
+    /// with the time it was queued
+    Submit(Box<Transaction>, Instant, oneshot::Sender<Option<Update>>),
+    Account(u16, oneshot::Sender<Option<Account>>),
+    Accounts(oneshot::Sender<Vec<Account>>),
+    Disputes(u16, oneshot::Sender<Vec<DisputeEvent>>),
+    Unlock(u16, oneshot::Sender<Option<Account>>),
+    /// of the first client into the second, replied the merged account
+    Merge(u16, u16, oneshot::Sender<Result<Account, MergeError>>),
+    /// bytes of the audit journal once flushed, None without one
+    #[cfg(feature = "flight")]
+    Journal(oneshot::Sender<Option<u64>>),
+}
+
+/// disputes, resolves and chargebacks of each client, in order
+type History = Rc<RefCell<HashMap<u16, Vec<DisputeEvent>>>>;
+
+/// audit journal recorded by an observer of the engine, flushed by the engine thread
+type Audit = Rc<RefCell<Journal>>;
+
+/// Figures of the engine thread reported by the health endpoints
+#[derive(Default)]
+struct Health {
+    /// requests queued for the engine thread and not taken yet
+    backlog: AtomicUsize,
+    /// time the last transaction applied waited in the queue, in microseconds
+    lag: AtomicU64,
+    /// unix time of the last snapshot saved, 0 before any
+    checkpoint: AtomicU64,
+    stopped: AtomicBool,
+}
+
+impl Health {
+    fn checkpointed(&self, time: Option<SystemTime>) {
+        let secs = time.and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok()).map_or(0, |since| since.as_secs());
+        self.checkpoint.store(secs, Ordering::Relaxed);
+    }
+}
+
+/// marks the engine thread stopped when dropped, on a panic as well
+struct Running(Arc<Health>);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.0.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// State of the handlers, the engine is reached through its thread
+#[derive(Clone)]
+struct Handle {
+    engine: mpsc::Sender<Request>,
+    precision: Option<Precision>,
+    health: Arc<Health>,
+    limiter: Option<Arc<Limiter>>,
+}
+
+impl Handle {
+    fn new(engine: mpsc::Sender<Request>, health: Arc<Health>, config: &Config) -> Handle {
+        let limiter = config.server.rate_limit.as_ref().map(|limit| Arc::new(Limiter::new(limit)));
+        Handle { engine, precision: config.precision, health, limiter }
+    }
+
+    /// reply of the engine thread, None once it has stopped
+    async fn ask<T>(&self, request: impl FnOnce(oneshot::Sender<T>) -> Request) -> Option<T> {
+        let (reply, replied) = oneshot::channel();
+        self.health.backlog.fetch_add(1, Ordering::Relaxed);
+        if self.engine.send(request(reply)).is_err() {
+            self.health.backlog.fetch_sub(1, Ordering::Relaxed);
+            return None;
+        }
+        replied.await.ok()
+    }
+}
+
+/// Router of the transaction-submission and account-query endpoints, to
+/// mount in an application's own server:
+/// - `POST /transactions` applies a JSON transaction, as the messages of the
+///   `amqp` subcommand, and replies the account update it made, or 422 with
+///   the rejection when the body is not a transaction
+/// - `GET /accounts` lists the accounts in client order, as in the report
+/// - `GET /accounts/{client}` gives one account, or 404
+/// - `/graphql` answers GraphQL queries of the `accounts`, filtered by
+///   `locked` and `totalAbove`, of one `account` and of the `disputes` of a
+///   client and their outcomes, over POST or GET
+/// - `GET /healthz`, the liveness probe, is 200 while the engine thread runs
+///   and 503 once it has stopped
+/// - `GET /readyz`, the readiness probe, gives the `backlog` of requests
+///   waiting for the engine, the `lag_ms` the last transaction waited and
+///   the unix time of the `last_checkpoint`; it is 503 once the engine has
+///   stopped or with more than 1024 requests waiting
+/// - `POST /accounts/{client}/unlock` unlocks an account and
+///   `POST /accounts/{client}/merge/{into}` merges it into another one, see
+///   `Engine::merge`, 404 for an unknown account and 409 for a closed one
+/// - with the `flight` feature, the gRPC methods of an Arrow Flight service
+///   stream the `accounts` table and the audit `journal`, read as the
+///   accounts, over HTTP/2
+///
+/// With `tokens` in the `[server]` table of `config`, every endpoint but the
+/// probes needs an `Authorization: Bearer` header with one of them, 401
+/// otherwise, and a token of the `submit` role only submits, one of the
+/// `read` role only queries the accounts and GraphQL, other requests are
+/// 403; the `admin` role may use every endpoint, and alone the unlock and
+/// merge ones. With a `rate_limit` the requests of each token, or of each
+/// address for those without one, are taken from a bucket refilled with
+/// `rate` requests a second up to `burst`; once it is empty they get 429 with
+/// the seconds until the next one in `Retry-After`. With the `client` key
+/// the submissions are limited by the client of their transaction instead.
+/// With an `eviction`, beyond `resident` accounts the least recently used
+/// are written to the `dir` with the state of their transactions and read
+/// back by their next request, which then goes on as if they had stayed;
+/// queries read them without bringing them back, and the snapshots have
+/// them. The files of a previous run are deleted when the router starts.
+/// With a `journal` the transactions processed are recorded in that audit
+/// journal, see `Journal`, written anew when the router starts.
+/// The precision policy of the amounts is that of `config`.
+///
+/// The engine is built by `setup` on a thread of its own, which applies the
+/// transactions one at a time in the order they arrive, so its observers
+/// need not be `Send`. The thread stops once the router is dropped. The
+/// router is served over TLS, mutual with a client CA, by `listen`.
+pub fn router(setup: impl FnOnce() -> Engine + Send + 'static, config: &Config) -> Router {
+    let (engine, requests) = mpsc::channel();
+    let health = Arc::new(Health::default());
+    let running = Running(health.clone());
+    let evicted = config.server.eviction.as_ref().map(|eviction| {
+        let res = Evicted::open(eviction);
+        assert!(res.is_ok(), "{}", res.err().unwrap());
+        res.unwrap()
+    });
+    let journal = config.server.journal.clone();
+    std::thread::spawn(move || {
+        let (engine, history) = with_history(setup());
+        let (engine, audit) = with_journal(engine, journal.as_deref());
+        serve(engine, history, audit, requests, None, evicted, running)
+    });
+    routes(Handle::new(engine, health, config), &config.server)
+}
+
+/// `router` whose transactions are appended to the write-ahead log at `wal`
+/// before they are applied, see `Wal`. The engine built by `setup` starts
+/// from the `snapshot` and the transactions logged since; a transaction that
+/// cannot be logged is not applied and gets 503. The accounts are saved as
+/// the new snapshot and the log emptied every 10000 transactions, after an
+/// unlock or a merge, and once the router is dropped.
+pub fn router_with_wal(
+    setup: impl FnOnce() -> Engine + Send + 'static,
+    config: &Config,
+    wal: &str,
+    snapshot: Option<&str>,
+) -> Result<Router, String> {
+    let (engine, requests) = mpsc::channel();
+    let (recovered, recovery) = mpsc::sync_channel(1);
+    let (wal, snapshot) = (wal.to_string(), snapshot.map(str::to_string));
+    let health = Arc::new(Health::default());
+    let running = Running(health.clone());
+    let evicted = config.server.eviction.as_ref().map(Evicted::open).transpose()?;
+    let journal = config.server.journal.clone();
+    std::thread::spawn(move || {
+        let (engine, history) = with_history(setup());
+        let (mut engine, audit) = with_journal(engine, journal.as_deref());
+        match Wal::recover(&wal, snapshot.as_deref(), &mut engine) {
+            Ok((wal, _)) => {
+                running.0.checkpointed(wal.checkpointed());
+                let _ = recovered.send(Ok(()));
+                serve(engine, history, audit, requests, Some(wal), evicted, running);
+            }
+            Err(err) => {
+                let _ = recovered.send(Err(err));
+            }
+        }
+    });
+    recovery.recv().map_err(|_| "engine thread stopped during recovery".to_string())??;
+    Ok(routes(Handle::new(engine, health, config), &config.server))
+}
+
+fn routes(handle: Handle, config: &ServerConfig) -> Router {
+    let tokens = Tokens::new(&config.tokens);
+    // the token is checked before a request is taken from its bucket
+    let allow = |router: Router<Handle>, role, limited: bool| {
+        let router = match handle.limiter.clone().filter(|_| limited) {
+            Some(limiter) => router.route_layer(middleware::from_fn_with_state(limiter, limit::limit)),
+            None => router,
+        };
+        router.route_layer(middleware::from_fn_with_state((tokens.clone(), role), auth::authorize))
+    };
+    let by_token = handle.limiter.as_ref().is_none_or(|limiter| limiter.key == LimitKey::Token);
+    let submitting = allow(Router::new().route("/transactions", post(submit)), Role::Submit, by_token);
+    let reading = Router::new()
+        .route("/accounts", get(accounts))
+        .route("/accounts/{client}", get(account))
+        .route_service("/graphql", GraphQL::new(graphql::schema(handle.clone())));
+    #[cfg(feature = "flight")]
+    let reading = reading.route_service(flight::PATH, flight::service(handle.clone(), config));
+    let reading = allow(reading, Role::Read, true);
+    let administering = Router::new()
+        .route("/accounts/{client}/unlock", post(unlock))
+        .route("/accounts/{client}/merge/{into}", post(merge));
+    let administering = allow(administering, Role::Admin, true);
+    Router::new()
+        .route("/healthz", get(live))
+        .route("/readyz", get(ready))
+        .merge(submitting)
+        .merge(reading)
+        .merge(administering)
+        .with_state(handle)
+}
+
+/// `engine` recording the dispute history of its clients
+fn with_history(mut engine: Engine) -> (Engine, History) {
+    let history = History::default();
+    let observed = history.clone();
+    engine.add_observer(move |tx, _, decision| {
+        if matches!(tx.tx_type, TxType::Dispute | TxType::Resolve | TxType::ChargeBack | TxType::AutoResolve | TxType::AutoChargeBack) {
+            let tx_type = format!("{:?}", tx.tx_type).to_lowercase();
+            let event = DisputeEvent { tx: tx.tx, tx_type, applied: decision == Decision::Applied };
+            observed.borrow_mut().entry(tx.client).or_default().push(event);
+        }
+    });
+    (engine, history)
+}
+
+/// `engine` recording the audit journal at `path`, when there is one
+fn with_journal(mut engine: Engine, path: Option<&str>) -> (Engine, Option<Audit>) {
+    let audit = path.map(|path| Audit::new(RefCell::new(Journal::create(path, None))));
+    if let Some(journal) = audit.clone() {
+        engine.add_observer(move |tx, account, decision| journal.borrow_mut().record(tx, account, decision));
+    }
+    (engine, audit)
+}
+
+/// copy of the balances of `account`, without its transactions
+fn balances(account: &Account) -> Account {
+    let mut copy = Account::new(account.client);
+    copy.available = account.available;
+    copy.held = account.held;
+    copy.total = account.total;
+    copy.locked = account.locked;
+    copy
+}
+
+/// the evicted accounts that can be read
+fn evicted_accounts(evicted: Option<&Evicted>) -> impl Iterator<Item = Account> + '_ {
+    evicted.into_iter().flat_map(Evicted::accounts).filter_map(|res| res.map_err(|err| eprintln!("{}", err)).ok())
+}
+
+/// save the accounts, the evicted ones as well, as the snapshot of `wal`, when there is one
+fn checkpoint(wal: Option<&mut Wal>, engine: &Engine, evicted: Option<&Evicted>, health: &Health) {
+    if let Some(wal) = wal {
+        match wal.checkpoint(engine.accounts().cloned().chain(evicted_accounts(evicted))) {
+            Ok(()) => health.checkpointed(wal.checkpointed()),
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+}
+
+/// bring the account of `client` back before a request changing it, false when it cannot be read
+fn touch(evicted: Option<&mut Evicted>, engine: &mut Engine, client: u16) -> bool {
+    match evicted.map(|evicted| evicted.touch(engine, client)) {
+        Some(Err(err)) => {
+            eprintln!("{}", err);
+            false
+        }
+        _ => true,
+    }
+}
+
+fn evict(evicted: Option<&mut Evicted>, engine: &mut Engine) {
+    if let Some(Err(err)) = evicted.map(|evicted| evicted.evict(engine)) {
+        eprintln!("{}", err);
+    }
+}
+
+/// Apply the requests in their order; a request whose evicted account cannot
+/// be read back is dropped, its reply is 503
+fn serve(
+    mut engine: Engine,
+    history: History,
+    audit: Option<Audit>,
+    requests: mpsc::Receiver<Request>,
+    mut wal: Option<Wal>,
+    mut evicted: Option<Evicted>,
+    running: Running,
+) {
+    let health = running.0.clone();
+    let mut logged = 0;
+    // the accounts of the snapshot count as used in client order
+    let mut clients: Vec<u16> = engine.accounts().map(|account| account.client).collect();
+    clients.sort_unstable();
+    for client in clients {
+        touch(evicted.as_mut(), &mut engine, client);
+    }
+    evict(evicted.as_mut(), &mut engine);
+    for request in requests {
+        health.backlog.fetch_sub(1, Ordering::Relaxed);
+        match request {
+            Request::Submit(tx, queued, reply) => {
+                health.lag.store(queued.elapsed().as_micros() as u64, Ordering::Relaxed);
+                if !touch(evicted.as_mut(), &mut engine, tx.client) {
+                    continue;
+                }
+                if let Some(Err(err)) = wal.as_mut().map(|wal| wal.append(&tx)) {
+                    eprintln!("{}", err);
+                    let _ = reply.send(None);
+                    continue;
+                }
+                let decision = engine.apply((*tx).clone());
+                // the account of a merged client is gone, its transactions are rejected
+                let update = match engine.account(tx.client) {
+                    Some(account) => Update::new(&tx, account, decision),
+                    None => Update::new(&tx, &Account::new(tx.client), decision),
+                };
+                let _ = reply.send(Some(update));
+                logged += 1;
+                if logged % CHECKPOINT == 0 {
+                    checkpoint(wal.as_mut(), &engine, evicted.as_ref(), &health);
+                }
+            }
+            Request::Account(client, reply) => {
+                let account = match engine.account(client) {
+                    Some(account) => Some(balances(account)),
+                    None => evicted.as_ref().and_then(|evicted| evicted.read(client).unwrap_or_else(|err| {
+                        eprintln!("{}", err);
+                        None
+                    })),
+                };
+                let _ = reply.send(account.map(|account| balances(&account)));
+            }
+            Request::Accounts(reply) => {
+                let mut accounts: Vec<Account> = engine.accounts().map(balances).chain(evicted_accounts(evicted.as_ref()).map(|account| balances(&account))).collect();
+                accounts.sort_by_key(|account| account.client);
+                let _ = reply.send(accounts);
+            }
+            Request::Disputes(client, reply) => {
+                let _ = reply.send(history.borrow().get(&client).cloned().unwrap_or_default());
+            }
+            // not transactions of the log, kept by a snapshot saved before replying
+            Request::Unlock(client, reply) => {
+                if !touch(evicted.as_mut(), &mut engine, client) {
+                    continue;
+                }
+                let unlocked = engine.unlock(client);
+                if unlocked {
+                    checkpoint(wal.as_mut(), &engine, evicted.as_ref(), &health);
+                }
+                let _ = reply.send(engine.account(client).filter(|_| unlocked).map(balances));
+            }
+            Request::Merge(from, into, reply) => {
+                if !touch(evicted.as_mut(), &mut engine, from) || !touch(evicted.as_mut(), &mut engine, into) {
+                    continue;
+                }
+                let res = engine.merge(from, into);
+                if res.is_ok() {
+                    checkpoint(wal.as_mut(), &engine, evicted.as_ref(), &health);
+                }
+                let _ = reply.send(res.map(|()| engine.account(into).map(balances).unwrap_or_else(|| Account::new(into))));
+            }
+            #[cfg(feature = "flight")]
+            Request::Journal(reply) => {
+                let _ = reply.send(audit.as_ref().map(|journal| {
+                    journal.borrow_mut().flush();
+                    journal.borrow().written()
+                }));
+            }
+        }
+        evict(evicted.as_mut(), &mut engine);
+    }
+    checkpoint(wal.as_mut(), &engine, evicted.as_ref(), &health);
+    if let Some(journal) = audit {
+        journal.borrow_mut().flush();
+    }
+}
+
+async fn submit(State(handle): State<Handle>, body: Bytes) -> Response {
+    let tx = match reader::message("http", handle.precision, &body) {
+        Ok(tx) => tx,
+        Err(rejected) => return (StatusCode::UNPROCESSABLE_ENTITY, Json(rejected)).into_response(),
+    };
+    if let Some(limiter) = handle.limiter.as_ref().filter(|limiter| limiter.key == LimitKey::Client) {
+        if let Err(wait) = limiter.take(Key::Client(tx.client)) {
+            return limit::too_many(wait);
+        }
+    }
+    match handle.ask(|reply| Request::Submit(Box::new(tx), Instant::now(), reply)).await {
+        Some(Some(update)) => Json(update).into_response(),
+        Some(None) | None => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
+
+async fn live(State(handle): State<Handle>) -> Response {
+    if handle.health.stopped.load(Ordering::Relaxed) {
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({ "status": "stopped" }))).into_response();
+    }
+    Json(serde_json::json!({ "status": "live" })).into_response()
+}
+
+async fn ready(State(handle): State<Handle>) -> Response {
+    let health = &handle.health;
+    let backlog = health.backlog.load(Ordering::Relaxed);
+    let ready = !health.stopped.load(Ordering::Relaxed) && backlog <= READY_BACKLOG;
+    let checkpoint = health.checkpoint.load(Ordering::Relaxed);
+    let body = serde_json::json!({
+        "ready": ready,
+        "backlog": backlog,
+        "lag_ms": health.lag.load(Ordering::Relaxed) as f64 / 1000.0,
+        "last_checkpoint": (checkpoint > 0).then_some(checkpoint),
+    });
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(body)).into_response()
+}
+
+async fn accounts(State(handle): State<Handle>) -> Response {
+    match handle.ask(Request::Accounts).await {
+        Some(accounts) => Json(accounts).into_response(),
+        None => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
+
+async fn account(State(handle): State<Handle>, Path(client): Path<u16>) -> Response {
+    match handle.ask(|reply| Request::Account(client, reply)).await {
+        Some(Some(account)) => Json(account).into_response(),
+        Some(None) => StatusCode::NOT_FOUND.into_response(),
+        None => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
+
+async fn unlock(State(handle): State<Handle>, Path(client): Path<u16>) -> Response {
+    match handle.ask(|reply| Request::Unlock(client, reply)).await {
+        Some(Some(account)) => Json(account).into_response(),
+        Some(None) => StatusCode::NOT_FOUND.into_response(),
+        None => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
+
+async fn merge(State(handle): State<Handle>, Path((client, into)): Path<(u16, u16)>) -> Response {
+    match handle.ask(|reply| Request::Merge(client, into, reply)).await {
+        Some(Ok(account)) => Json(account).into_response(),
+        Some(Err(MergeError::UnknownAccount(_))) => StatusCode::NOT_FOUND.into_response(),
+        Some(Err(err)) => (StatusCode::CONFLICT, err.to_string()).into_response(),
+        None => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_router_endpoints() {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let listener = runtime.block_on(tokio::net::TcpListener::bind("127.0.0.1:0")).unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || runtime.block_on(async { axum::serve(listener, router(Engine::new, &Config::default())).await }));
+
+        let agent: ureq::Agent = ureq::Agent::config_builder().http_status_as_error(false).build().into();
+        let submit = |body: &str| {
+            let mut res = agent.post(format!("{}/transactions", url)).send(body).unwrap();
+            (res.status().as_u16(), res.body_mut().read_to_string().unwrap())
+        };
+        let (status, update) = submit(r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.5"}"#);
+        assert_eq!(status, 200);
+        assert!(update.contains(r#""client":1,"tx":1,"applied":true"#), "{}", update);
+        let (status, update) = submit(r#"{"type": "withdrawal", "client": 1, "tx": 2, "amount": "4.0"}"#);
+        assert_eq!(status, 200);
+        assert!(update.contains(r#""applied":false"#), "{}", update);
+        let (status, rejected) = submit(r#"{"type": "deposit", "client": "one"}"#);
+        assert_eq!(status, 422);
+        assert!(rejected.contains("malformed"), "{}", rejected);
+        submit(r#"{"type": "deposit", "client": 2, "tx": 3, "amount": "2"}"#);
+
+        let get = |path: &str| {
+            let mut res = agent.get(format!("{}{}", url, path)).call().unwrap();
+            (res.status().as_u16(), res.body_mut().read_to_string().unwrap())
+        };
+        let (status, account) = get("/accounts/1");
+        assert_eq!(status, 200);
+        let account: serde_json::Value = serde_json::from_str(&account).unwrap();
+        assert_eq!(account["client"], 1);
+        assert_eq!(account["locked"], false);
+        let (_, accounts) = get("/accounts");
+        let accounts: Vec<serde_json::Value> = serde_json::from_str(&accounts).unwrap();
+        assert_eq!(accounts.iter().map(|account| account["client"].as_u64()).collect::<Vec<_>>(), [Some(1), Some(2)]);
+        assert_eq!(get("/accounts/7").0, 404);
+    }
+
+    #[test]
+    fn test_graphql_queries() {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let listener = runtime.block_on(tokio::net::TcpListener::bind("127.0.0.1:0")).unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || runtime.block_on(async { axum::serve(listener, router(Engine::new, &Config::default())).await }));
+        for tx in [
+            r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.5"}"#,
+            r#"{"type": "deposit", "client": 2, "tx": 2, "amount": "8"}"#,
+            r#"{"type": "deposit", "client": 2, "tx": 3, "amount": "4"}"#,
+            r#"{"type": "dispute", "client": 2, "tx": 2}"#,
+            r#"{"type": "chargeback", "client": 2, "tx": 2}"#,
+            r#"{"type": "resolve", "client": 2, "tx": 3}"#,
+        ] {
+            ureq::post(format!("{}/transactions", url)).send(tx).unwrap();
+        }
+        let query = |query: &str| {
+            let body = serde_json::json!({ "query": query }).to_string();
+            let res = ureq::post(format!("{}/graphql", url)).header("content-type", "application/json").send(body);
+            serde_json::from_str::<serde_json::Value>(&res.unwrap().body_mut().read_to_string().unwrap()).unwrap()
+        };
+        let locked = query("{ accounts(locked: true) { client total } }");
+        assert_eq!(locked["data"]["accounts"], serde_json::json!([{"client": 2, "total": "4"}]));
+        let above = query(r#"{ accounts(totalAbove: "2") { client } }"#);
+        assert_eq!(above["data"]["accounts"], serde_json::json!([{"client": 2}]));
+        let disputes = query("{ disputes(client: 2) { tx type applied } account(client: 7) { client } }");
+        let expected = serde_json::json!([
+            {"tx": 2, "type": "dispute", "applied": true},
+            {"tx": 2, "type": "chargeback", "applied": true},
+            {"tx": 3, "type": "resolve", "applied": false},
+        ]);
+        assert_eq!((&disputes["data"]["disputes"], &disputes["data"]["account"]), (&expected, &serde_json::Value::Null));
+        assert!(query(r#"{ accounts(totalAbove: "x") { client } }"#)["errors"][0]["message"].as_str().unwrap().contains("totalAbove"));
+    }
+
+    #[test]
+    fn test_health_endpoints() {
+        let serve = |router: Router| {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            let listener = runtime.block_on(tokio::net::TcpListener::bind("127.0.0.1:0")).unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            std::thread::spawn(move || runtime.block_on(async { axum::serve(listener, router).await }));
+            url
+        };
+        let agent: ureq::Agent = ureq::Agent::config_builder().http_status_as_error(false).build().into();
+        let get = |url: &str| {
+            let mut res = agent.get(url).call().unwrap();
+            let body: serde_json::Value = serde_json::from_str(&res.body_mut().read_to_string().unwrap()).unwrap();
+            (res.status().as_u16(), body)
+        };
+        let url = serve(router(Engine::new, &Config::default()));
+        ureq::post(format!("{}/transactions", url)).send(r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1"}"#).unwrap();
+        assert_eq!(get(&format!("{}/healthz", url)), (200, serde_json::json!({"status": "live"})));
+        let (status, ready) = get(&format!("{}/readyz", url));
+        assert_eq!((status, &ready["ready"], &ready["backlog"], &ready["last_checkpoint"]), (200, &true.into(), &0.into(), &serde_json::Value::Null));
+        assert!(ready["lag_ms"].as_f64().unwrap() >= 0.0);
+
+        // an engine thread that failed to start is not alive
+        let url = serve(router(|| panic!("engine setup failed"), &Config::default()));
+        let mut stopped = (200, serde_json::Value::Null);
+        for _ in 0..50 {
+            stopped = get(&format!("{}/healthz", url));
+            if stopped.0 == 503 {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        assert_eq!(stopped, (503, serde_json::json!({"status": "stopped"})));
+        assert_eq!(get(&format!("{}/readyz", url)).0, 503);
+    }
+
+    #[test]
+    fn test_tokens_and_roles() {
+        let config: Config = toml::from_str(
+            "[[server.tokens]]\ntoken = \"payments\"\nrole = \"submit\"\n\
+             [[server.tokens]]\ntoken = \"reports\"\nrole = \"read\"\n\
+             [[server.tokens]]\ntoken = \"operator\"\nrole = \"admin\"\n",
+        )
+        .unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let listener = runtime.block_on(tokio::net::TcpListener::bind("127.0.0.1:0")).unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || runtime.block_on(async { axum::serve(listener, router(Engine::new, &config)).await }));
+
+        let agent: ureq::Agent = ureq::Agent::config_builder().http_status_as_error(false).build().into();
+        let post = |path: &str, token: Option<&str>, body: &str| {
+            let mut req = agent.post(format!("{}{}", url, path));
+            if let Some(token) = token {
+                req = req.header("authorization", format!("Bearer {}", token));
+            }
+            req.send(body).unwrap().status().as_u16()
+        };
+        let get = |path: &str, token: &str| {
+            let mut res = agent.get(format!("{}{}", url, path)).header("authorization", format!("Bearer {}", token)).call().unwrap();
+            (res.status().as_u16(), res.body_mut().read_to_string().unwrap())
+        };
+        for tx in [r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "2"}"#, r#"{"type": "dispute", "client": 1, "tx": 1}"#] {
+            assert_eq!(post("/transactions", Some("payments"), tx), 200);
+        }
+        assert_eq!(post("/transactions", None, r#"{"type": "chargeback", "client": 1, "tx": 1}"#), 401);
+        assert_eq!(post("/transactions", Some("reports"), r#"{"type": "chargeback", "client": 1, "tx": 1}"#), 403);
+        assert_eq!(post("/transactions", Some("operator"), r#"{"type": "chargeback", "client": 1, "tx": 1}"#), 200);
+        assert_eq!(get("/accounts/1", "payments").0, 403);
+        assert_eq!(get("/accounts/1", "unknown").0, 401);
+        assert!(get("/accounts/1", "reports").1.contains(r#""locked":true"#));
+        assert_eq!(post("/graphql", None, r#"{"query": "{ accounts { client } }"}"#), 401);
+
+        // unlocking is for admins alone
+        assert_eq!(post("/accounts/1/unlock", Some("reports"), ""), 403);
+        assert_eq!(post("/accounts/1/unlock", Some("operator"), ""), 200);
+        assert_eq!(post("/accounts/9/unlock", Some("operator"), ""), 404);
+        assert!(get("/accounts/1", "reports").1.contains(r#""locked":false"#));
+        assert_eq!(post("/transactions", Some("payments"), r#"{"type": "deposit", "client": 2, "tx": 2, "amount": "1"}"#), 200);
+        assert_eq!(post("/accounts/2/merge/1", Some("operator"), ""), 200);
+        assert_eq!(post("/accounts/2/merge/1", Some("operator"), ""), 409);
+        assert_eq!(get("/healthz", "").0, 200);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let serve = |config: &str| {
+            let config: Config = toml::from_str(config).unwrap();
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            std::thread::spawn(move || runtime.block_on(listen(listener, router(Engine::new, &config), None)));
+            url
+        };
+        let agent: ureq::Agent = ureq::Agent::config_builder().http_status_as_error(false).build().into();
+        let submit = |url: &str, client: u16, token: &str| {
+            let tx = format!(r#"{{"type": "deposit", "client": {}, "tx": 1, "amount": "1"}}"#, client);
+            let res = agent.post(format!("{}/transactions", url)).header("authorization", format!("Bearer {}", token)).send(tx).unwrap();
+            (res.status().as_u16(), res.headers().get("retry-after").map(|value| value.to_str().unwrap().to_string()))
+        };
+        let tokens = "[[server.tokens]]\ntoken = \"a\"\nrole = \"admin\"\n[[server.tokens]]\ntoken = \"b\"\nrole = \"admin\"\n";
+        let url = serve(&format!("{}[server.rate_limit]\nrate = 0.5\nburst = 2\n", tokens));
+        assert_eq!(submit(&url, 1, "a"), (200, None));
+        assert_eq!(submit(&url, 2, "a"), (200, None));
+        assert_eq!(submit(&url, 3, "a"), (429, Some("2".to_string())));
+        // the bucket of another token is full
+        assert_eq!(submit(&url, 3, "b").0, 200);
+        let res = agent.get(format!("{}/accounts", url)).header("authorization", "Bearer a").call().unwrap();
+        assert_eq!(res.status().as_u16(), 429);
+        assert_eq!(agent.get(format!("{}/healthz", url)).call().unwrap().status().as_u16(), 200);
+
+        let url = serve(&format!("{}[server.rate_limit]\nrate = 1\nkey = \"client\"\n", tokens));
+        assert_eq!(submit(&url, 1, "a").0, 200);
+        assert_eq!(submit(&url, 1, "b"), (429, Some("1".to_string())));
+        assert_eq!(submit(&url, 2, "a").0, 200);
+        // without tokens the address is the key
+        let url = serve("[server.rate_limit]\nrate = 1\n");
+        assert_eq!(submit(&url, 1, "").0, 200);
+        assert_eq!(submit(&url, 2, "").0, 429);
+    }
+
+    #[test]
+    fn test_eviction_of_inactive_accounts() {
+        let dir = std::env::temp_dir().join(format!("server-evicted-{}", std::process::id()));
+        let (wal, snapshot) = (dir.join("wal.jsonl"), dir.join("snapshot.csv"));
+        let (wal, snapshot) = (wal.to_str().unwrap(), snapshot.to_str().unwrap());
+        let config: Config = toml::from_str(&format!("[server.eviction]\nresident = 1\ndir = {:?}\n", dir.join("accounts"))).unwrap();
+        let serve = |router: Router| {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            let listener = runtime.block_on(tokio::net::TcpListener::bind("127.0.0.1:0")).unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            std::thread::spawn(move || runtime.block_on(async { axum::serve(listener, router).await }));
+            url
+        };
+        let url = serve(router_with_wal(Engine::new, &config, wal, Some(snapshot)).unwrap());
+        for tx in [
+            r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "5"}"#,
+            r#"{"type": "deposit", "client": 2, "tx": 2, "amount": "3"}"#,
+            r#"{"type": "deposit", "client": 3, "tx": 3, "amount": "1"}"#,
+            r#"{"type": "dispute", "client": 1, "tx": 1}"#,
+        ] {
+            ureq::post(format!("{}/transactions", url)).send(tx).unwrap();
+        }
+        assert!(dir.join("accounts/2.json").exists() && !dir.join("accounts/1.json").exists());
+        let get = |url: &str, path: &str| {
+            let body = ureq::get(format!("{}{}", url, path)).call().unwrap().body_mut().read_to_string().unwrap();
+            serde_json::from_str::<serde_json::Value>(&body).unwrap()
+        };
+        // the dispute found the deposit of the evicted account
+        assert_eq!((&get(&url, "/accounts/1")["held"], &get(&url, "/accounts/2")["total"]), (&"5".into(), &"3".into()));
+        let clients = |accounts: serde_json::Value| accounts.as_array().unwrap().iter().map(|account| account["client"].as_u64().unwrap()).collect::<Vec<_>>();
+        assert_eq!(clients(get(&url, "/accounts")), [1, 2, 3]);
+        ureq::post(format!("{}/accounts/3/unlock", url)).send("").unwrap();
+
+        // the snapshot has the evicted accounts
+        let url = serve(router_with_wal(Engine::new, &config, wal, Some(snapshot)).unwrap());
+        assert_eq!(clients(get(&url, "/accounts")), [1, 2, 3]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_tls_with_client_certificates() {
+        let serve = |tls: Tls| {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("https://localhost:{}", listener.local_addr().unwrap().port());
+            std::thread::spawn(move || runtime.block_on(listen(listener, router(Engine::new, &Config::default()), Some(&tls))));
+            url
+        };
+        let pem = |name: &str| std::fs::read(format!("tests/tls/{}", name)).unwrap();
+        let agent = |client_cert: bool| -> ureq::Agent {
+            let ca = ureq::tls::Certificate::from_pem(&pem("ca.pem")).unwrap();
+            let cert = ureq::tls::Certificate::from_pem(&pem("client.pem")).unwrap();
+            let key = ureq::tls::PrivateKey::from_pem(&pem("client.key")).unwrap();
+            let tls = ureq::tls::TlsConfig::builder()
+                .root_certs(ureq::tls::RootCerts::new_with_certs(&[ca]))
+                .client_cert(client_cert.then(|| ureq::tls::ClientCert::new_with_certs(&[cert], key)))
+                .build();
+            ureq::Agent::config_builder().tls_config(tls).build().into()
+        };
+        let deposit = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1"}"#;
+        let tls = Tls { cert: "tests/tls/server.pem".to_string(), key: "tests/tls/server.key".to_string(), client_ca: None };
+        let url = serve(tls.clone());
+        assert_eq!(agent(false).post(format!("{}/transactions", url)).send(deposit).unwrap().status().as_u16(), 200);
+
+        // with a client CA only the clients with a certificate it signed get through
+        let url = serve(Tls { client_ca: Some("tests/tls/ca.pem".to_string()), ..tls });
+        assert!(agent(false).post(format!("{}/transactions", url)).send(deposit).is_err());
+        assert_eq!(agent(true).post(format!("{}/transactions", url)).send(deposit).unwrap().status().as_u16(), 200);
+        assert!(Tls { cert: "tests/tls/missing.pem".to_string(), key: String::new(), client_ca: None }.config().is_err());
+    }
+
+    #[test]
+    fn test_router_recovers_from_wal() {
+        let wal = std::env::temp_dir().join(format!("server-wal-{}.jsonl", std::process::id()));
+        let wal = wal.to_str().unwrap();
+        let _ = std::fs::remove_file(wal);
+        let serve = |router: Router| {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            let listener = runtime.block_on(tokio::net::TcpListener::bind("127.0.0.1:0")).unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            std::thread::spawn(move || runtime.block_on(async { axum::serve(listener, router).await }));
+            url
+        };
+        let url = serve(router_with_wal(Engine::new, &Config::default(), wal, None).unwrap());
+        let res = ureq::post(format!("{}/transactions", url)).send(r#"{"type": "deposit", "client": 3, "tx": 1, "amount": "2.5"}"#);
+        assert_eq!(res.unwrap().status().as_u16(), 200);
+        // a server started again from the log has the account
+        let url = serve(router_with_wal(Engine::new, &Config::default(), wal, None).unwrap());
+        let account = ureq::get(format!("{}/accounts/3", url)).call().unwrap().body_mut().read_to_string().unwrap();
+        let _ = std::fs::remove_file(wal);
+        let account: serde_json::Value = serde_json::from_str(&account).unwrap();
+        assert_eq!(account["total"], "2.5");
+    }
+}
@@ -0,0 +1,66 @@
+use axum::extract::{Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// What the holder of a token may do
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// submit transactions
+    Submit,
+    /// query the accounts and their disputes
+    Read,
+    /// all of it, and unlock or merge accounts
+    Admin,
+}
+
+/// A `[[server.tokens]]` table, the bearer token of an integrator and its role
+#[derive(serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Token {
+    pub token: String,
+    pub role: Role,
+}
+
+/// Roles of the tokens, keyed by their hash so that looking one up compares
+/// no secret; empty when the API is open
+#[derive(Clone, Default)]
+pub(super) struct Tokens(Arc<HashMap<[u8; 32], Role>>);
+
+fn hash(token: &str) -> [u8; 32] {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+impl Tokens {
+    pub(super) fn new(tokens: &[Token]) -> Tokens {
+        Tokens(Arc::new(tokens.iter().map(|token| (hash(&token.token), token.role)).collect()))
+    }
+
+    /// role of the bearer token of `request`
+    fn role(&self, request: &Request) -> Option<Role> {
+        self.0.get(&bearer(request)?).copied()
+    }
+}
+
+/// hash of the bearer token of `request`
+pub(super) fn bearer(request: &Request) -> Option<[u8; 32]> {
+    let value = request.headers().get(header::AUTHORIZATION)?.to_str().ok()?;
+    Some(hash(value.strip_prefix("Bearer ")?.trim()))
+}
+
+/// Middleware of the routes of `needed`: 401 without a known bearer token,
+/// 403 when its role is neither `needed` nor admin; no check without tokens
+pub(super) async fn authorize(State((tokens, needed)): State<(Tokens, Role)>, request: Request, next: Next) -> Response {
+    if tokens.0.is_empty() {
+        return next.run(request).await;
+    }
+    match tokens.role(&request) {
+        None => (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, "Bearer")]).into_response(),
+        Some(role) if role != needed && role != Role::Admin => StatusCode::FORBIDDEN.into_response(),
+        Some(_) => next.run(request).await,
+    }
+}
@@ -0,0 +1,141 @@
+use crate::engine::{Account, Engine};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+/// The `[server.eviction]` table
+#[derive(serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Eviction {
+    /// accounts kept in memory, the least recently used beyond are evicted
+    pub resident: usize,
+    /// directory of the evicted accounts, a JSON file each
+    pub dir: String,
+}
+
+/// Accounts evicted to files, and the order the resident ones were last used in
+pub(super) struct Evicted {
+    resident: usize,
+    dir: PathBuf,
+    evicted: HashSet<u16>,
+    /// clients by the time they were last used
+    used: BTreeMap<u64, u16>,
+    last: HashMap<u16, u64>,
+    clock: u64,
+}
+
+impl Evicted {
+    /// Store of the accounts in `dir`, emptied of the files of a previous
+    /// run: the router starts from the snapshot, which has every account
+    pub(super) fn open(eviction: &Eviction) -> Result<Evicted, String> {
+        let dir = PathBuf::from(&eviction.dir);
+        let error = |err: std::io::Error| format!("evicted accounts {}: {}", eviction.dir, err);
+        fs::create_dir_all(&dir).map_err(error)?;
+        for entry in fs::read_dir(&dir).map_err(error)? {
+            let path = entry.map_err(error)?.path();
+            if path.extension().is_some_and(|extension| extension == "json") {
+                fs::remove_file(&path).map_err(error)?;
+            }
+        }
+        let (evicted, used, last) = (HashSet::new(), BTreeMap::new(), HashMap::new());
+        Ok(Evicted { resident: eviction.resident.max(1), dir, evicted, used, last, clock: 0 })
+    }
+
+    fn path(&self, client: u16) -> PathBuf {
+        self.dir.join(format!("{}.json", client))
+    }
+
+    fn error(&self, client: u16, err: impl std::fmt::Display) -> String {
+        format!("evicted account {}: {}", self.path(client).display(), err)
+    }
+
+    /// an evicted account, read without bringing it back
+    pub(super) fn read(&self, client: u16) -> Result<Option<Account>, String> {
+        if !self.evicted.contains(&client) {
+            return Ok(None);
+        }
+        let json = fs::read(self.path(client)).map_err(|err| self.error(client, err))?;
+        Account::load(&json).map(Some).map_err(|err| self.error(client, err))
+    }
+
+    /// the evicted accounts, read one at a time
+    pub(super) fn accounts(&self) -> impl Iterator<Item = Result<Account, String>> + '_ {
+        self.evicted.iter().map(|client| self.read(*client).map(Option::unwrap))
+    }
+
+    /// Bring the account of `client` back into `engine` when it was evicted,
+    /// before a transaction of its own, and count it as the last one used
+    pub(super) fn touch(&mut self, engine: &mut Engine, client: u16) -> Result<(), String> {
+        if let Some(account) = self.read(client)? {
+            engine.reload(account);
+            self.evicted.remove(&client);
+            fs::remove_file(self.path(client)).map_err(|err| self.error(client, err))?;
+        }
+        if let Some(used) = self.last.insert(client, self.clock) {
+            self.used.remove(&used);
+        }
+        self.used.insert(self.clock, client);
+        self.clock += 1;
+        Ok(())
+    }
+
+    /// Write the least recently used accounts beyond the resident ones to
+    /// their files; those with a withdrawal or a dispute due stay in memory
+    pub(super) fn evict(&mut self, engine: &mut Engine) -> Result<(), String> {
+        let mut kept = Vec::new();
+        while self.used.len() > self.resident {
+            let (_, client) = self.used.pop_first().unwrap();
+            self.last.remove(&client);
+            let Some(account) = engine.evict(client) else {
+                // a merged client has no account, a rejected first transaction neither
+                if engine.account(client).is_some() {
+                    kept.push(client);
+                }
+                continue;
+            };
+            if let Err(err) = fs::write(self.path(client), account.save()) {
+                engine.reload(account);
+                return Err(self.error(client, err));
+            }
+            self.evicted.insert(client);
+        }
+        for client in kept {
+            self.touch(engine, client)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Transaction, TxType};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_least_recently_used_are_evicted() {
+        let dir = std::env::temp_dir().join(format!("evicted-{}", std::process::id()));
+        let eviction = Eviction { resident: 2, dir: dir.to_str().unwrap().to_string() };
+        let mut evicted = Evicted::open(&eviction).unwrap();
+        let mut engine = Engine::new();
+        let apply = |engine: &mut Engine, evicted: &mut Evicted, client, tx_type, tx| {
+            evicted.touch(engine, client).unwrap();
+            engine.apply(Transaction { client, tx_type, tx, amount: Some(dec!(1)), ..Default::default() });
+            evicted.evict(engine).unwrap();
+        };
+        for client in 1..=3 {
+            apply(&mut engine, &mut evicted, client, TxType::Deposit, u32::from(client));
+        }
+        assert!(engine.account(1).is_none() && dir.join("1.json").exists());
+        assert_eq!(evicted.read(1).unwrap().map(|account| account.client), Some(1));
+        // a dispute of the evicted client brings its account back, and evicts the next one
+        apply(&mut engine, &mut evicted, 1, TxType::Dispute, 1);
+        assert!(engine.account(1).unwrap().held > Default::default());
+        assert!(engine.account(2).is_none() && !dir.join("1.json").exists());
+        assert_eq!(evicted.accounts().map(|account| account.unwrap().client).collect::<Vec<_>>(), [2]);
+        // a new run starts without the files of the previous one
+        let evicted = Evicted::open(&eviction).unwrap();
+        assert!(evicted.read(2).unwrap().is_none() && !dir.join("2.json").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,344 @@
+use super::{Handle, Request, ServerConfig};
+use crate::amount::{self, Amount};
+use crate::engine::Account;
+use crate::validate::MAX_SCALE;
+use arrow_array::{ArrayRef, BooleanArray, Decimal128Array, RecordBatch, StringArray, UInt16Array, UInt32Array, UInt64Array};
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::error::FlightError;
+use arrow_flight::flight_service_server::{FlightService, FlightServiceServer};
+use arrow_flight::{
+    Action, ActionType, Criteria, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo, HandshakeRequest, HandshakeResponse, PollInfo, PutResult,
+    SchemaResult, Ticket,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use futures_util::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use rust_decimal::Decimal;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tonic::{Response, Status, Streaming};
+
+/// route of the gRPC methods of the Flight service
+pub(super) const PATH: &str = "/arrow.flight.protocol.FlightService/{*method}";
+/// rows of each record batch streamed
+const BATCH: usize = 8192;
+
+/// Table of the Flight service, its name is its ticket and its descriptor path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Table {
+    Accounts,
+    Journal,
+}
+
+impl Table {
+    const ALL: [Table; 2] = [Table::Accounts, Table::Journal];
+
+    fn name(self) -> &'static str {
+        match self {
+            Table::Accounts => "accounts",
+            Table::Journal => "journal",
+        }
+    }
+
+    fn named(name: &[u8]) -> Result<Table, Status> {
+        let table = Table::ALL.into_iter().find(|table| table.name().as_bytes() == name);
+        table.ok_or_else(|| Status::not_found(format!("no table {}, only accounts and journal", String::from_utf8_lossy(name))))
+    }
+
+    /// the amounts are decimals at `scale`
+    fn schema(self, scale: i8) -> Schema {
+        let decimal = |name: &str| Field::new(name, DataType::Decimal128(38, scale), false);
+        let locked = Field::new("locked", DataType::Boolean, false);
+        match self {
+            Table::Accounts => Schema::new(vec![Field::new("client", DataType::UInt16, false), decimal("available"), decimal("held"), decimal("total"), locked]),
+            Table::Journal => Schema::new(vec![
+                Field::new("seq", DataType::UInt64, false),
+                Field::new("client", DataType::UInt16, false),
+                Field::new("tx", DataType::UInt32, false),
+                Field::new("type", DataType::Utf8, false),
+                decimal("amount").with_nullable(true),
+                Field::new("decision", DataType::Utf8, false),
+                decimal("available"),
+                decimal("held"),
+                decimal("total"),
+                locked,
+                Field::new("prev", DataType::Utf8, false),
+                Field::new("hash", DataType::Utf8, false),
+            ]),
+        }
+    }
+}
+
+/// The columns of a line of the audit journal, the others are left out
+#[derive(serde::Deserialize)]
+struct Line {
+    seq: u64,
+    client: u16,
+    tx: u32,
+    #[serde(rename = "type")]
+    tx_type: String,
+    amount: Option<Decimal>,
+    decision: String,
+    available: Amount,
+    held: Amount,
+    total: Amount,
+    locked: bool,
+    prev: String,
+    hash: String,
+}
+
+/// decimal column of `amounts` at `scale`
+fn decimals(amounts: impl Iterator<Item = Option<Decimal>>, scale: i8) -> Result<ArrayRef, ArrowError> {
+    let mantissas: Vec<Option<i128>> = amounts
+        .map(|amount| {
+            amount.map(|mut amount| {
+                amount.rescale(scale as u32);
+                amount.mantissa()
+            })
+        })
+        .collect();
+    Ok(Arc::new(Decimal128Array::from(mantissas).with_precision_and_scale(38, scale)?))
+}
+
+fn accounts_batch(accounts: &[Account], scale: i8) -> Result<RecordBatch, ArrowError> {
+    let balance = |field: fn(&Account) -> Amount| decimals(accounts.iter().map(|account| Some(amount::to_decimal(field(account)))), scale);
+    RecordBatch::try_new(
+        Arc::new(Table::Accounts.schema(scale)),
+        vec![
+            Arc::new(UInt16Array::from_iter_values(accounts.iter().map(|account| account.client))),
+            balance(|account| account.available)?,
+            balance(|account| account.held)?,
+            balance(|account| account.total)?,
+            Arc::new(BooleanArray::from_iter(accounts.iter().map(|account| Some(account.locked)))),
+        ],
+    )
+}
+
+fn journal_batch(lines: &[Line], scale: i8) -> Result<RecordBatch, ArrowError> {
+    let balance = |field: fn(&Line) -> Amount| decimals(lines.iter().map(|line| Some(amount::to_decimal(field(line)))), scale);
+    let text = |field: fn(&Line) -> &str| Arc::new(StringArray::from_iter_values(lines.iter().map(field))) as ArrayRef;
+    RecordBatch::try_new(
+        Arc::new(Table::Journal.schema(scale)),
+        vec![
+            Arc::new(UInt64Array::from_iter_values(lines.iter().map(|line| line.seq))),
+            Arc::new(UInt16Array::from_iter_values(lines.iter().map(|line| line.client))),
+            Arc::new(UInt32Array::from_iter_values(lines.iter().map(|line| line.tx))),
+            text(|line| &line.tx_type),
+            decimals(lines.iter().map(|line| line.amount), scale)?,
+            text(|line| &line.decision),
+            balance(|line| line.available)?,
+            balance(|line| line.held)?,
+            balance(|line| line.total)?,
+            Arc::new(BooleanArray::from_iter(lines.iter().map(|line| Some(line.locked)))),
+            text(|line| &line.prev),
+            text(|line| &line.hash),
+        ],
+    )
+}
+
+fn stopped() -> Status {
+    Status::unavailable("the engine has stopped")
+}
+
+/// Flight service of a router, see `service`
+pub(super) struct Flight {
+    handle: Handle,
+    journal: Option<String>,
+    /// of every amount column
+    scale: i8,
+}
+
+/// Flight service streaming the `accounts` table, as `GET /accounts`, and
+/// the `journal` of the router, the audit entries written up to the request
+/// with their hashes, in record batches of 8192 rows. The amounts are
+/// decimals at the scale of the precision policy, 4 places at least.
+pub(super) fn service(handle: Handle, config: &ServerConfig) -> FlightServiceServer<Flight> {
+    let scale = handle.precision.map_or(MAX_SCALE, |precision| precision.scale.max(MAX_SCALE)) as i8;
+    FlightServiceServer::new(Flight { handle, journal: config.journal.clone(), scale })
+}
+
+impl Flight {
+    fn info(&self, table: Table) -> Result<FlightInfo, Status> {
+        let info = FlightInfo::new().try_with_schema(&table.schema(self.scale)).map_err(|err| Status::internal(err.to_string()))?;
+        let descriptor = FlightDescriptor::new_path(vec![table.name().to_string()]);
+        Ok(info.with_descriptor(descriptor).with_endpoint(FlightEndpoint::new().with_ticket(Ticket::new(table.name()))).with_ordered(true))
+    }
+
+    fn described(descriptor: &FlightDescriptor) -> Result<Table, Status> {
+        match descriptor.path.as_slice() {
+            [name] => Table::named(name.as_bytes()),
+            _ => Err(Status::invalid_argument("a descriptor is the path of a table, accounts or journal")),
+        }
+    }
+
+    async fn batches(&self, table: Table) -> Result<BoxStream<'static, Result<RecordBatch, FlightError>>, Status> {
+        let scale = self.scale;
+        match table {
+            Table::Accounts => {
+                let accounts = self.handle.ask(Request::Accounts).await.ok_or_else(stopped)?;
+                let starts = (0..accounts.len()).step_by(BATCH);
+                let batches = starts.map(move |start| accounts_batch(&accounts[start..accounts.len().min(start + BATCH)], scale).map_err(FlightError::from));
+                Ok(stream::iter(batches).boxed())
+            }
+            Table::Journal => {
+                let path = self.journal.clone().ok_or_else(|| Status::not_found("the router has no audit journal, see `journal` of the [server] table"))?;
+                let written = self.handle.ask(Request::Journal).await.ok_or_else(stopped)?.unwrap_or_default();
+                let file = tokio::fs::File::open(&path).await.map_err(|err| Status::internal(format!("{}: {}", path, err)))?;
+                // the entries the engine writes meanwhile are left out, the last one may be partly written
+                let lines = BufReader::new(file.take(written)).lines();
+                let batches = stream::unfold(Some(lines), move |lines| {
+                    let path = path.clone();
+                    async move {
+                        let mut lines = lines?;
+                        let mut rows = Vec::new();
+                        while rows.len() < BATCH {
+                            let line = match lines.next_line().await {
+                                Ok(Some(line)) => serde_json::from_str::<Line>(&line).map_err(|err| err.to_string()),
+                                Ok(None) => break,
+                                Err(err) => Err(err.to_string()),
+                            };
+                            match line {
+                                Ok(line) => rows.push(line),
+                                Err(err) => return Some((Err(FlightError::ExternalError(format!("{}: {}", path, err).into())), None)),
+                            }
+                        }
+                        (!rows.is_empty()).then(|| (journal_batch(&rows, scale).map_err(FlightError::from), Some(lines)))
+                    }
+                });
+                Ok(batches.boxed())
+            }
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl FlightService for Flight {
+    type HandshakeStream = BoxStream<'static, Result<HandshakeResponse, Status>>;
+    type ListFlightsStream = BoxStream<'static, Result<FlightInfo, Status>>;
+    type DoGetStream = BoxStream<'static, Result<FlightData, Status>>;
+    type DoPutStream = BoxStream<'static, Result<PutResult, Status>>;
+    type DoExchangeStream = BoxStream<'static, Result<FlightData, Status>>;
+    type DoActionStream = BoxStream<'static, Result<arrow_flight::Result, Status>>;
+    type ListActionsStream = BoxStream<'static, Result<ActionType, Status>>;
+
+    /// the bearer token is that of the router, in the `authorization` header
+    async fn handshake(&self, _: tonic::Request<Streaming<HandshakeRequest>>) -> Result<Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented("no handshake, the token of a request is its bearer token"))
+    }
+
+    async fn list_flights(&self, _: tonic::Request<Criteria>) -> Result<Response<Self::ListFlightsStream>, Status> {
+        let infos: Vec<_> = Table::ALL.into_iter().map(|table| self.info(table)).collect();
+        Ok(Response::new(stream::iter(infos).boxed()))
+    }
+
+    async fn get_flight_info(&self, request: tonic::Request<FlightDescriptor>) -> Result<Response<FlightInfo>, Status> {
+        self.info(Flight::described(request.get_ref())?).map(Response::new)
+    }
+
+    async fn poll_flight_info(&self, _: tonic::Request<FlightDescriptor>) -> Result<Response<PollInfo>, Status> {
+        Err(Status::unimplemented("the tables are streamed at once, see get_flight_info"))
+    }
+
+    async fn get_schema(&self, request: tonic::Request<FlightDescriptor>) -> Result<Response<SchemaResult>, Status> {
+        let info = self.info(Flight::described(request.get_ref())?)?;
+        Ok(Response::new(SchemaResult { schema: info.schema }))
+    }
+
+    async fn do_get(&self, request: tonic::Request<Ticket>) -> Result<Response<Self::DoGetStream>, Status> {
+        let table = Table::named(&request.get_ref().ticket)?;
+        let batches = self.batches(table).await?;
+        let data = FlightDataEncoderBuilder::new().with_schema(Arc::new(table.schema(self.scale))).build(batches);
+        Ok(Response::new(data.map_err(Status::from).boxed()))
+    }
+
+    async fn do_put(&self, _: tonic::Request<Streaming<FlightData>>) -> Result<Response<Self::DoPutStream>, Status> {
+        Err(Status::unimplemented("transactions are submitted to POST /transactions"))
+    }
+
+    async fn do_exchange(&self, _: tonic::Request<Streaming<FlightData>>) -> Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("the tables are read with do_get"))
+    }
+
+    async fn do_action(&self, _: tonic::Request<Action>) -> Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("no actions"))
+    }
+
+    async fn list_actions(&self, _: tonic::Request<arrow_flight::Empty>) -> Result<Response<Self::ListActionsStream>, Status> {
+        Ok(Response::new(stream::empty().boxed()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{listen, router};
+    use super::*;
+    use crate::audit;
+    use crate::config::Config;
+    use crate::engine::Engine;
+    use arrow_array::cast::AsArray;
+    use arrow_array::Array;
+    use arrow_array::types::{Decimal128Type, UInt16Type, UInt64Type};
+    use arrow_flight::FlightClient;
+
+    /// record batches of a table, the error of a call may come with its stream
+    async fn get(client: &mut FlightClient, ticket: &str) -> Result<Vec<RecordBatch>, FlightError> {
+        client.do_get(Ticket::new(ticket.to_string())).await?.try_collect().await
+    }
+
+    #[test]
+    fn test_tables_over_flight() {
+        let dir = std::env::temp_dir().join(format!("flight-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let journal = dir.join("audit.jsonl");
+        let tokens = "[[server.tokens]]\ntoken = \"payments\"\nrole = \"submit\"\n[[server.tokens]]\ntoken = \"reports\"\nrole = \"read\"\n";
+        let settings: Config = toml::from_str(&format!("[server]\njournal = {:?}\n{}", journal.to_str().unwrap(), tokens)).unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || runtime.block_on(listen(listener, router(Engine::new, &settings), None)));
+        for tx in [
+            r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.5"}"#,
+            r#"{"type": "deposit", "client": 2, "tx": 2, "amount": "2"}"#,
+            r#"{"type": "withdrawal", "client": 2, "tx": 3, "amount": "5"}"#,
+        ] {
+            ureq::post(format!("{}/transactions", url)).header("authorization", "Bearer payments").send(tx).unwrap();
+        }
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let channel = tonic::transport::Channel::from_shared(url).unwrap().connect().await.unwrap();
+            let mut client = FlightClient::new(channel);
+            // the tables are read with a token of the read role
+            match get(&mut client, "accounts").await.err() {
+                Some(FlightError::Tonic(status)) => assert_eq!(status.code(), tonic::Code::Unauthenticated),
+                err => panic!("{:?}", err),
+            }
+            client.add_header("authorization", "Bearer reports").unwrap();
+            let infos: Vec<FlightInfo> = client.list_flights(Vec::new()).await.unwrap().try_collect().await.unwrap();
+            assert_eq!(infos.len(), 2);
+            assert_eq!(Schema::try_from(infos[0].clone()).unwrap(), Table::Accounts.schema(4));
+
+            let accounts = get(&mut client, "accounts").await.unwrap();
+            assert_eq!((accounts.len(), accounts[0].num_rows()), (1, 2));
+            assert_eq!(accounts[0].column(0).as_primitive::<UInt16Type>().values(), &[1, 2]);
+            // amounts at 4 decimal places
+            assert_eq!(accounts[0].column(3).as_primitive::<Decimal128Type>().values(), &[15000, 20000]);
+
+            let entries = get(&mut client, "journal").await.unwrap();
+            let entries = &entries[0];
+            assert_eq!(entries.column(0).as_primitive::<UInt64Type>().values(), &[1, 2, 3]);
+            let decisions: Vec<_> = entries.column(5).as_string::<i32>().iter().flatten().collect();
+            assert_eq!(decisions, ["applied", "applied", "ignored"]);
+            assert!(entries.column(4).as_primitive::<Decimal128Type>().is_valid(2));
+            assert_eq!(entries.column(10).as_string::<i32>().value(0), audit::GENESIS);
+            let content = std::fs::read_to_string(&journal).unwrap();
+            assert_eq!(audit::verify(content.as_bytes()), Ok(3));
+            assert!(content.lines().last().unwrap().contains(entries.column(11).as_string::<i32>().value(2)));
+
+            match get(&mut client, "events").await.err() {
+                Some(FlightError::Tonic(status)) => assert_eq!(status.code(), tonic::Code::NotFound),
+                err => panic!("{:?}", err),
+            }
+        });
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,70 @@
+use super::{Handle, Request};
+use crate::amount;
+use crate::engine::Account;
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Result, Schema, SimpleObject};
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+/// An account as queried, amounts as decimal strings
+#[derive(SimpleObject)]
+struct Balances {
+    client: u16,
+    available: String,
+    held: String,
+    total: String,
+    locked: bool,
+}
+
+impl From<&Account> for Balances {
+    fn from(account: &Account) -> Balances {
+        Balances {
+            client: account.client,
+            available: amount::to_decimal(account.available).normalize().to_string(),
+            held: amount::to_decimal(account.held).normalize().to_string(),
+            total: amount::to_decimal(account.total).normalize().to_string(),
+            locked: account.locked,
+        }
+    }
+}
+
+/// A dispute, resolve or chargeback of a client, `applied` false when the
+/// engine ignored it
+#[derive(SimpleObject, Debug, Clone, PartialEq, Eq)]
+pub(super) struct DisputeEvent {
+    pub tx: u32,
+    /// `dispute`, `resolve`, `chargeback`, or `autoresolve` and `autochargeback` of an expired dispute
+    #[graphql(name = "type")]
+    pub tx_type: String,
+    pub applied: bool,
+}
+
+pub(super) struct Query;
+
+#[Object]
+impl Query {
+    /// accounts in client order, only those in the `locked` state and with a total above `totalAbove` when given
+    async fn accounts(&self, ctx: &Context<'_>, locked: Option<bool>, total_above: Option<String>) -> Result<Vec<Balances>> {
+        let threshold = total_above.map(|above| Decimal::from_str(&above)).transpose().map_err(|err| format!("invalid totalAbove: {}", err))?;
+        let accounts = ctx.data::<Handle>()?.ask(Request::Accounts).await.ok_or("engine has stopped")?;
+        let selected = accounts
+            .iter()
+            .filter(|account| locked.is_none_or(|locked| account.locked == locked))
+            .filter(|account| threshold.is_none_or(|threshold| amount::to_decimal(account.total) > threshold));
+        Ok(selected.map(Balances::from).collect())
+    }
+
+    /// account of `client`, null when it has none
+    async fn account(&self, ctx: &Context<'_>, client: u16) -> Result<Option<Balances>> {
+        let account = ctx.data::<Handle>()?.ask(|reply| Request::Account(client, reply)).await.ok_or("engine has stopped")?;
+        Ok(account.as_ref().map(Balances::from))
+    }
+
+    /// disputes of `client` and their outcomes, in the order they were submitted
+    async fn disputes(&self, ctx: &Context<'_>, client: u16) -> Result<Vec<DisputeEvent>> {
+        Ok(ctx.data::<Handle>()?.ask(|reply| Request::Disputes(client, reply)).await.ok_or("engine has stopped")?)
+    }
+}
+
+pub(super) fn schema(handle: Handle) -> Schema<Query, EmptyMutation, EmptySubscription> {
+    Schema::build(Query, EmptyMutation, EmptySubscription).data(handle).finish()
+}
@@ -0,0 +1,110 @@
+use super::auth;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// buckets kept beyond which the full ones are dropped
+const BUCKETS: usize = 65_536;
+
+/// What each bucket of the rate limit is for
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitKey {
+    /// the bearer token of a request, or the address it comes from without one
+    #[default]
+    Token,
+    /// the client of a submitted transaction, the token for the other requests
+    Client,
+}
+
+/// The `[server.rate_limit]` table, a token bucket per `key`
+#[derive(serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    /// requests per second a bucket refills with
+    pub rate: f64,
+    /// requests a full bucket lets through at once, the rate by default
+    pub burst: Option<u32>,
+    #[serde(default)]
+    pub key: LimitKey,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) enum Key {
+    Token([u8; 32]),
+    Peer(IpAddr),
+    Client(u16),
+    /// a request of an unknown address without a token
+    Anonymous,
+}
+
+struct Bucket {
+    requests: f64,
+    at: Instant,
+}
+
+pub(super) struct Limiter {
+    rate: f64,
+    burst: f64,
+    pub(super) key: LimitKey,
+    buckets: Mutex<HashMap<Key, Bucket>>,
+}
+
+impl Limiter {
+    pub(super) fn new(limit: &RateLimit) -> Limiter {
+        assert!(limit.rate > 0.0, "rate_limit rate {} is not positive", limit.rate);
+        let burst = limit.burst.map_or(limit.rate.max(1.0), f64::from);
+        Limiter { rate: limit.rate, burst, key: limit.key, buckets: Mutex::new(HashMap::new()) }
+    }
+
+    /// requests left in `bucket` at `now`
+    fn level(&self, bucket: &Bucket, now: Instant) -> f64 {
+        (bucket.requests + now.duration_since(bucket.at).as_secs_f64() * self.rate).min(self.burst)
+    }
+
+    /// take a request from the bucket of `key`, or the time until it has one
+    pub(super) fn take(&self, key: Key) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= BUCKETS && !buckets.contains_key(&key) {
+            buckets.retain(|_, bucket| self.level(bucket, now) < self.burst);
+        }
+        let bucket = buckets.entry(key).or_insert(Bucket { requests: self.burst, at: now });
+        bucket.requests = self.level(bucket, now);
+        bucket.at = now;
+        if bucket.requests < 1.0 {
+            return Err(Duration::from_secs_f64((1.0 - bucket.requests) / self.rate));
+        }
+        bucket.requests -= 1.0;
+        Ok(())
+    }
+}
+
+/// 429 with the whole seconds to wait in `Retry-After`
+pub(super) fn too_many(wait: Duration) -> Response {
+    let secs = wait.as_secs_f64().ceil().max(1.0) as u64;
+    (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, secs.to_string())]).into_response()
+}
+
+fn key(request: &Request) -> Key {
+    if let Some(token) = auth::bearer(request) {
+        return Key::Token(token);
+    }
+    match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        Some(ConnectInfo(addr)) => Key::Peer(addr.ip()),
+        None => Key::Anonymous,
+    }
+}
+
+/// Middleware taking each request from the bucket of its token, 429 once it is empty
+pub(super) async fn limit(State(limiter): State<Arc<Limiter>>, request: Request, next: Next) -> Response {
+    match limiter.take(key(&request)) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => too_many(wait),
+    }
+}
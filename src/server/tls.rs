@@ -0,0 +1,72 @@
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// PEM files of the certificate of a server over TLS, and with `client_ca`
+/// of the authorities a client certificate must be signed by, mutual TLS
+#[derive(Debug, Clone)]
+pub struct Tls {
+    /// certificate chain, the server's first
+    pub cert: String,
+    pub key: String,
+    pub client_ca: Option<String>,
+}
+
+fn certificates(path: &str) -> Result<Vec<CertificateDer<'static>>, String> {
+    let certs = CertificateDer::pem_file_iter(path).and_then(|certs| certs.collect::<Result<Vec<_>, _>>());
+    match certs {
+        Ok(certs) if !certs.is_empty() => Ok(certs),
+        Ok(_) => Err(format!("{}: no certificate", path)),
+        Err(err) => Err(format!("{}: {}", path, err)),
+    }
+}
+
+impl Tls {
+    /// rustls configuration of the server, on the ring provider
+    pub fn config(&self) -> Result<ServerConfig, String> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let certs = certificates(&self.cert)?;
+        let key = PrivateKeyDer::from_pem_file(&self.key).map_err(|err| format!("{}: {}", self.key, err))?;
+        let builder = ServerConfig::builder_with_provider(provider.clone()).with_safe_default_protocol_versions().map_err(|err| err.to_string())?;
+        let builder = match &self.client_ca {
+            None => builder.with_no_client_auth(),
+            Some(path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in certificates(path)? {
+                    roots.add(cert).map_err(|err| format!("{}: {}", path, err))?;
+                }
+                let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider).build();
+                builder.with_client_cert_verifier(verifier.map_err(|err| format!("{}: {}", path, err))?)
+            }
+        };
+        let mut config = builder.with_single_cert(certs, key).map_err(|err| format!("{}: {}", self.cert, err))?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        // gRPC of the Flight service
+        #[cfg(feature = "flight")]
+        config.alpn_protocols.insert(0, b"h2".to_vec());
+        Ok(config)
+    }
+}
+
+/// Serve `router` on `listener`, over TLS when `tls` is given; a client
+/// without a certificate of the `client_ca` fails the handshake. The
+/// requests without a token are rate limited by the address they come from.
+pub async fn listen(listener: std::net::TcpListener, router: Router, tls: Option<&Tls>) -> Result<(), String> {
+    listener.set_nonblocking(true).map_err(|err| err.to_string())?;
+    let res = match tls {
+        None => {
+            let listener = tokio::net::TcpListener::from_std(listener).map_err(|err| err.to_string())?;
+            axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>()).await
+        }
+        Some(tls) => {
+            let config = RustlsConfig::from_config(Arc::new(tls.config()?));
+            axum_server::from_tcp_rustls(listener, config).map_err(|err| err.to_string())?.serve(router.into_make_service_with_connect_info::<SocketAddr>()).await
+        }
+    };
+    res.map_err(|err| err.to_string())
+}
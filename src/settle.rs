@@ -0,0 +1,132 @@
+use crate::amount::Amount;
+use crate::calendar::Calendar;
+use crate::engine::{process_tx, Account, AccountMap, Applied, Transaction};
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Movement of a client's total funds over a settlement day
+#[derive(serde::Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Settlement {
+    pub client: u16,
+    /// total at the start of the day
+    pub opening: Amount,
+    pub deposits: Amount,
+    pub withdrawals: Amount,
+    pub chargebacks: Amount,
+    /// deposits less withdrawals and chargebacks, disputes only move funds to held
+    pub net: Amount,
+    /// total at the end of the day
+    pub closing: Amount,
+}
+
+/// Settlement of a day and the accounts at its end, the opening of the next day
+#[derive(Debug, Clone)]
+pub struct Day {
+    pub settlements: Vec<Settlement>,
+    pub closing: Vec<Account>,
+}
+
+/// Settle `day`: transactions of earlier business days make the opening balances,
+/// those of later ones are left out. Every transaction must have a timestamp,
+/// the first one without is the error.
+pub fn settle(txs: impl Iterator<Item = Transaction>, day: NaiveDate, calendar: &Calendar) -> Result<Day, String> {
+    let mut accounts = AccountMap::default();
+    let mut settlements: BTreeMap<u16, Settlement> = BTreeMap::new();
+    for tx in txs {
+        let Some(time) = tx.timestamp else {
+            return Err(format!("settle needs timestamped input, tx {} has no timestamp", tx.tx));
+        };
+        let date = calendar.business_day(time);
+        if date > day {
+            continue;
+        }
+        let client = tx.client;
+        if date < day {
+            let _ = process_tx(&mut accounts, tx);
+            continue;
+        }
+        let settlement = settlements.entry(client).or_insert_with(|| Settlement {
+            client,
+            opening: accounts.get(&client).map(|account| account.total).unwrap_or_default(),
+            ..Default::default()
+        });
+        match process_tx(&mut accounts, tx) {
+            Ok(Applied::Deposited(amount)) => settlement.deposits += amount,
+            Ok(Applied::Withdrawn(amount) | Applied::Settled(amount)) => settlement.withdrawals += amount,
+            Ok(Applied::ChargedBack(amount)) => settlement.chargebacks += amount,
+            _ => {}
+        }
+    }
+    for settlement in settlements.values_mut() {
+        settlement.net = settlement.deposits - settlement.withdrawals - settlement.chargebacks;
+        settlement.closing = accounts[&settlement.client].total;
+    }
+    let mut closing: Vec<Account> = accounts.into_values().collect();
+    closing.sort_by_key(|account| account.client);
+    Ok(Day { settlements: settlements.into_values().collect(), closing })
+}
+
+impl Day {
+    pub fn write_csv<W: Write>(&self, wrt: W) {
+        let mut wrt = csv::Writer::from_writer(wrt);
+        for settlement in &self.settlements {
+            let res = wrt.serialize(settlement);
+            assert!(res.is_ok(), "error in writing output to stdout");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TxType;
+    use crate::timestamp;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_settle_day() {
+        let tx = |client, tx_type, tx, amount, time: &str| Transaction {
+            client,
+            tx_type,
+            tx,
+            amount,
+            timestamp: timestamp::parse(time).ok(),
+            ..Default::default()
+        };
+        let txs = vec![
+            tx(1, TxType::Deposit, 1, Some(dec!(10.0)), "2024-03-01 09:00:00"),
+            tx(1, TxType::Deposit, 2, Some(dec!(5.0)), "2024-03-02 09:00:00"),
+            tx(1, TxType::Withdrawal, 3, Some(dec!(2.0)), "2024-03-02 10:00:00"),
+            tx(1, TxType::Dispute, 1, None, "2024-03-02 11:00:00"),
+            tx(2, TxType::Deposit, 4, Some(dec!(1.0)), "2024-03-03 09:00:00"),
+        ];
+        let day = settle(txs.into_iter(), NaiveDate::from_ymd_opt(2024, 3, 2).unwrap(), &Calendar::default()).unwrap();
+        assert_eq!(day.settlements.len(), 1);
+        let settlement = &day.settlements[0];
+        assert_eq!(settlement.opening, dec!(10.0));
+        assert_eq!(settlement.net, dec!(3.0));
+        assert_eq!(settlement.closing, dec!(13.0));
+        assert_eq!(day.closing.len(), 1);
+        assert_eq!(day.closing[0].held, dec!(10.0));
+    }
+
+    #[test]
+    fn test_settle_after_cutoff() {
+        let tx = |tx, time: &str| Transaction {
+            client: 1,
+            tx_type: TxType::Deposit,
+            tx,
+            amount: Some(dec!(1.0)),
+            timestamp: timestamp::parse(time).ok(),
+            ..Default::default()
+        };
+        let calendar = Calendar { cutoff: "17:00:00".parse().ok(), ..Default::default() };
+        let txs = vec![tx(1, "2024-03-01 16:00:00"), tx(2, "2024-03-01 17:30:00")];
+        let day = settle(txs.clone().into_iter(), NaiveDate::from_ymd_opt(2024, 3, 2).unwrap(), &calendar).unwrap();
+        assert_eq!(day.settlements[0].opening, dec!(1.0));
+        assert_eq!(day.settlements[0].deposits, dec!(1.0));
+        let untimed = Transaction { timestamp: None, ..txs[0].clone() };
+        assert!(settle(vec![untimed].into_iter(), NaiveDate::from_ymd_opt(2024, 3, 2).unwrap(), &calendar).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use crate::engine::Engine;
+use crate::kyc::{Kyc, Level};
+use rust_decimal::Decimal;
+use std::collections::HashSet;
+use std::io::Read;
+
+/// Row of the account setup file given with `--accounts`, every column but
+/// `client` may be left empty
+#[derive(serde::Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Setup {
+    pub client: u16,
+    /// available funds the account starts with
+    #[serde(default)]
+    pub opening: Option<Decimal>,
+    /// how far below zero withdrawals may take the available funds
+    #[serde(default)]
+    pub credit_limit: Option<Decimal>,
+    #[serde(default)]
+    pub currency: Option<String>,
+    /// KYC level of the client, in place of the `[kyc]` levels file
+    #[serde(default)]
+    pub kyc: Option<Level>,
+}
+
+/// setup rows of a csv file, a client listed twice aborts the run
+pub fn read<R: Read>(source: &str, mut rdr: csv::Reader<R>) -> Vec<Setup> {
+    let mut clients = HashSet::new();
+    rdr.deserialize::<Setup>()
+        .map(|res| {
+            assert!(res.is_ok(), "invalid account setup {}: {:?}", source, res.err());
+            let setup = res.unwrap();
+            assert!(clients.insert(setup.client), "client {} is set up twice in {}", setup.client, source);
+            setup
+        })
+        .collect()
+}
+
+/// Open the accounts before the input is processed, their KYC levels going to `kyc`
+pub fn open(engine: &mut Engine, mut kyc: Option<&mut Kyc>, setups: Vec<Setup>) {
+    for setup in setups {
+        if let (Some(kyc), Some(level)) = (kyc.as_mut(), setup.kyc) {
+            kyc.set_level(setup.client, level);
+        }
+        let opening = setup.opening.unwrap_or_default();
+        engine.open_account(setup.client, opening, setup.credit_limit.unwrap_or_default(), setup.currency);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Transaction, TxType};
+    use crate::kyc::KycConfig;
+    use crate::reader::from_reader;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_open_accounts() {
+        let input = "client,opening,credit_limit,currency,kyc\n1,100.00,,EUR,verified\n2,,50,,\n";
+        let setups = read("accounts.csv", from_reader(input.as_bytes()));
+        assert_eq!(setups[1], Setup { client: 2, credit_limit: Some(dec!(50)), ..Setup::default() });
+        let mut kyc = Kyc::new(&KycConfig::default());
+        let mut engine = Engine::new();
+        engine.enable_ledger();
+        open(&mut engine, Some(&mut kyc), setups);
+        assert_eq!(kyc.level(1), Level::Verified);
+        let tx = |client, tx_type, tx, amount| Transaction { client, tx_type, tx, amount, ..Default::default() };
+        assert!(engine.process(tx(2, TxType::Withdrawal, 1, Some(dec!(30)))).is_ok());
+        assert!(engine.process(tx(2, TxType::Withdrawal, 2, Some(dec!(30)))).is_err());
+        assert!(engine.process(tx(1, TxType::Dispute, 0, None)).is_err());
+        assert!(engine.trial_balance().is_balanced());
+        let mut accounts: Vec<_> = engine.into_accounts().collect();
+        accounts.sort_by_key(|account| account.client);
+        assert_eq!(accounts[0].available, dec!(100.00));
+        assert_eq!(accounts[0].total, dec!(100.00));
+        assert_eq!(accounts[0].currency.as_deref(), Some("EUR"));
+        assert_eq!(accounts[1].available, dec!(-30));
+    }
+
+    #[test]
+    #[should_panic(expected = "set up twice")]
+    fn test_duplicate_client() {
+        read("accounts.csv", from_reader("client,opening\n1,1\n1,2\n".as_bytes()));
+    }
+}
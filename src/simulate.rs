@@ -0,0 +1,257 @@
+use crate::amount;
+use crate::engine::{Applied, Engine, Transaction, TxType};
+use rust_decimal::Decimal;
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+/// Daily behavior of the simulated clients, the `[simulation]` config table
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Simulation {
+    pub clients: u16,
+    /// horizon of a run
+    pub days: u32,
+    /// probabilities that a client makes a deposit, a withdrawal, or disputes
+    /// one of its deposits on a given day
+    pub deposit: f64,
+    pub withdrawal: f64,
+    pub dispute: f64,
+    /// probability that a dispute is charged back the next day rather than resolved
+    pub chargeback: f64,
+    /// deposit and withdrawal amounts are drawn uniformly from 0.01 up to this one
+    pub max_amount: Decimal,
+}
+
+/// Distribution of a figure over the runs
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Percentiles {
+    pub mean: Decimal,
+    pub p50: Decimal,
+    pub p95: Decimal,
+    pub p99: Decimal,
+    pub max: Decimal,
+}
+
+impl Percentiles {
+    fn of(mut values: Vec<Decimal>) -> Percentiles {
+        values.sort();
+        // nearest rank
+        let rank = |p: f64| values[((p * values.len() as f64).ceil() as usize).clamp(1, values.len()) - 1];
+        Percentiles {
+            mean: values.iter().sum::<Decimal>() / Decimal::from(values.len()),
+            p50: rank(0.50),
+            p95: rank(0.95),
+            p99: rank(0.99),
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub runs: u32,
+    /// highest funds held by open disputes during a run
+    pub peak_held: Percentiles,
+    /// negative balances left by chargebacks at the end of a run, funds the
+    /// clients had already withdrawn
+    pub losses: Percentiles,
+}
+
+/// xorshift, runs are reproducible from the seed
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// true with probability `p`
+    fn chance(&mut self, p: f64) -> bool {
+        ((self.next() >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound.max(1)
+    }
+}
+
+/// whether the transaction was applied, keeping `held` up to date
+fn process(engine: &mut Engine, held: &mut Decimal, client: u16, tx_type: TxType, tx: u32, amount: Option<Decimal>) -> bool {
+    let tx = Transaction { client, tx_type, tx, amount, ..Default::default() };
+    let res = engine.process(tx);
+    match res {
+        Ok(Applied::Held(amount)) => *held += amount::to_decimal(amount),
+        Ok(Applied::Released(amount) | Applied::ChargedBack(amount)) => *held -= amount::to_decimal(amount),
+        _ => {}
+    }
+    res.is_ok()
+}
+
+/// peak held funds and losses of one run on `engine`
+fn run(simulation: &Simulation, random: &mut Random, mut engine: Engine) -> (Decimal, Decimal) {
+    let mut deposits: Vec<Vec<u32>> = vec![Vec::new(); simulation.clients as usize];
+    // disputes raised the previous day, closed today
+    let mut open: Vec<(u16, u32)> = Vec::new();
+    let (mut next_tx, mut held, mut peak_held) = (1, Decimal::ZERO, Decimal::ZERO);
+    let cents = (simulation.max_amount * Decimal::ONE_HUNDRED).trunc().try_into().unwrap_or(0u64);
+    for _ in 0..simulation.days {
+        for (client, tx) in std::mem::take(&mut open) {
+            let tx_type = if random.chance(simulation.chargeback) { TxType::ChargeBack } else { TxType::Resolve };
+            process(&mut engine, &mut held, client, tx_type, tx, None);
+        }
+        for client in 0..simulation.clients {
+            if random.chance(simulation.deposit) {
+                let amount = Decimal::new(random.below(cents) as i64 + 1, 2);
+                if process(&mut engine, &mut held, client, TxType::Deposit, next_tx, Some(amount)) {
+                    deposits[client as usize].push(next_tx);
+                }
+                next_tx += 1;
+            }
+            if random.chance(simulation.withdrawal) {
+                let amount = Decimal::new(random.below(cents) as i64 + 1, 2);
+                process(&mut engine, &mut held, client, TxType::Withdrawal, next_tx, Some(amount));
+                next_tx += 1;
+            }
+            let own = &deposits[client as usize];
+            if !own.is_empty() && random.chance(simulation.dispute) {
+                let tx = own[random.below(own.len() as u64) as usize];
+                if process(&mut engine, &mut held, client, TxType::Dispute, tx, None) {
+                    open.push((client, tx));
+                }
+            }
+        }
+        peak_held = peak_held.max(held);
+    }
+    let losses = engine
+        .into_accounts()
+        .map(|account| amount::to_decimal(account.total))
+        .filter(|total| total.is_sign_negative())
+        .map(|total| -total)
+        .sum();
+    (peak_held, losses)
+}
+
+/// Monte Carlo runs of the simulated clients, `runs` must not be zero
+pub fn simulate(simulation: &Simulation, runs: u32, seed: u64) -> Outcome {
+    assert!(runs > 0, "at least one run is needed");
+    // xorshift is stuck at zero
+    let mut random = Random(seed.max(1));
+    let (peak_held, losses) = (0..runs).map(|_| run(simulation, &mut random, Engine::new())).unzip();
+    Outcome { runs, peak_held: Percentiles::of(peak_held), losses: Percentiles::of(losses) }
+}
+
+/// Ways `chaos` damages a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Damage {
+    /// cut short at a random byte
+    Truncated,
+    /// with a field too many, or one too few
+    ColumnCount,
+    /// amount with far more decimal places than any precision allows
+    Precision,
+    /// followed by a copy of itself, the same tx id twice
+    Duplicate,
+    /// type not valid UTF-8
+    Utf8,
+}
+
+const DAMAGES: [Damage; 5] = [Damage::Truncated, Damage::ColumnCount, Damage::Precision, Damage::Duplicate, Damage::Utf8];
+
+/// csv line of `tx`, damaged when given
+fn line(tx: &Transaction, damage: Option<Damage>, random: &mut Random) -> Vec<u8> {
+    let tx_type = format!("{:?}", tx.tx_type).to_lowercase();
+    let amount = tx.amount.map(|amount| amount.to_string()).unwrap_or_default();
+    let plain = format!("{},{},{},{}\n", tx_type, tx.client, tx.tx, amount).into_bytes();
+    match damage {
+        None => plain,
+        Some(Damage::Truncated) => {
+            let mut cut = plain[..random.below(plain.len() as u64 - 1) as usize + 1].to_vec();
+            cut.push(b'\n');
+            cut
+        }
+        Some(Damage::ColumnCount) if random.chance(0.5) => format!("{},{},{},{},{}\n", tx_type, tx.client, tx.tx, amount, tx.tx).into_bytes(),
+        Some(Damage::ColumnCount) => format!("{},{}\n", tx_type, tx.client).into_bytes(),
+        Some(Damage::Precision) => format!("{},{},{},{}.{:0>24}\n", tx_type, tx.client, tx.tx, tx.amount.unwrap_or_default().trunc(), random.next()).into_bytes(),
+        Some(Damage::Duplicate) => [plain.clone(), plain].concat(),
+        Some(Damage::Utf8) => [&b"\xff\xfe"[..], &plain[tx_type.len()..]].concat(),
+    }
+}
+
+/// Write the transactions of one run of the simulated clients as a
+/// `type,client,tx,amount` csv, with a `rate` fraction of its lines damaged
+/// in one of the ways of `Damage`, to exercise the rejection of malformed
+/// records. The same seed gives the same file.
+pub fn chaos<W: Write>(simulation: &Simulation, seed: u64, rate: f64, mut wrt: W) -> std::io::Result<()> {
+    let mut engine = Engine::new();
+    let txs = Rc::new(RefCell::new(Vec::new()));
+    let observed = txs.clone();
+    engine.add_observer(move |tx, _, _| observed.borrow_mut().push(tx.clone()));
+    let mut random = Random(seed.max(1));
+    run(simulation, &mut random, engine);
+    wrt.write_all(b"type,client,tx,amount\n")?;
+    for tx in txs.borrow().iter() {
+        let damage = random.chance(rate).then(|| DAMAGES[random.below(DAMAGES.len() as u64) as usize]);
+        wrt.write_all(&line(tx, damage, &mut random))?;
+    }
+    wrt.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn simulation(dispute: f64, chargeback: f64) -> Simulation {
+        Simulation {
+            clients: 20,
+            days: 30,
+            deposit: 0.5,
+            withdrawal: 0.3,
+            dispute,
+            chargeback,
+            max_amount: dec!(100),
+        }
+    }
+
+    #[test]
+    fn test_simulate() {
+        let outcome = simulate(&simulation(0.05, 0.5), 50, 7);
+        assert_eq!(outcome, simulate(&simulation(0.05, 0.5), 50, 7));
+        assert!(outcome.peak_held.p50 > dec!(0));
+        assert!(outcome.peak_held.p50 <= outcome.peak_held.p95 && outcome.peak_held.p95 <= outcome.peak_held.max);
+        let quiet = simulate(&simulation(0.0, 0.5), 50, 7);
+        assert_eq!((quiet.peak_held.max, quiet.losses.max), (dec!(0), dec!(0)));
+        let resolved = simulate(&simulation(0.05, 0.0), 50, 7);
+        assert_eq!(resolved.losses.max, dec!(0));
+    }
+
+    #[test]
+    fn test_chaos() {
+        let generate = |rate| {
+            let mut out = Vec::new();
+            chaos(&simulation(0.05, 0.5), 3, rate, &mut out).unwrap();
+            out
+        };
+        let (clean, damaged) = (generate(0.0), generate(0.3));
+        assert_eq!(clean, generate(0.0));
+        let clean = String::from_utf8(clean).unwrap();
+        assert!(clean.starts_with("type,client,tx,amount\n") && clean.lines().count() > 100);
+        assert!(clean.lines().skip(1).all(|line| line.split(',').count() == 4));
+        assert!(String::from_utf8(damaged.clone()).is_err());
+        let lines: Vec<&[u8]> = damaged.split(|byte| *byte == b'\n').collect();
+        assert!(lines.iter().any(|line| line.split(|byte| *byte == b',').count() != 4));
+        assert!(lines.windows(2).any(|pair| pair[0] == pair[1] && !pair[0].is_empty()));
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let values = (1..=100).map(Decimal::from).collect();
+        let percentiles = Percentiles::of(values);
+        assert_eq!((percentiles.p50, percentiles.p95, percentiles.p99, percentiles.max), (dec!(50), dec!(95), dec!(99), dec!(100)));
+        assert_eq!(percentiles.mean, dec!(50.5));
+    }
+}
@@ -0,0 +1,156 @@
+use crate::amount;
+use crate::engine::{Account, Decision, Engine, Transaction};
+use datafusion::arrow::array::{ArrayRef, BooleanArray, Decimal128Array, StringArray, UInt16Array, UInt32Array, UInt64Array};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::datasource::MemTable;
+use datafusion::error::Result;
+use datafusion::prelude::SessionContext;
+use rust_decimal::Decimal;
+use std::sync::Arc;
+
+/// decimal column of `amounts`, all at the largest scale among them
+fn decimals(amounts: Vec<Option<Decimal>>) -> Result<ArrayRef> {
+    let scale = amounts.iter().flatten().map(|amount| amount.scale()).max().unwrap_or(0);
+    let mantissas: Vec<Option<i128>> = amounts
+        .into_iter()
+        .map(|amount| {
+            amount.map(|mut amount| {
+                amount.rescale(scale);
+                amount.mantissa()
+            })
+        })
+        .collect();
+    Ok(Arc::new(Decimal128Array::from(mantissas).with_precision_and_scale(38, scale as i8)?))
+}
+
+fn table(columns: Vec<(&str, ArrayRef)>) -> Result<MemTable> {
+    let batch = RecordBatch::try_from_iter(columns)?;
+    MemTable::try_new(batch.schema(), vec![vec![batch]])
+}
+
+/// Table of the accounts in client order, with the columns of the csv report
+pub fn accounts_table(engine: &Engine) -> Result<MemTable> {
+    let mut accounts: Vec<&Account> = engine.accounts().collect();
+    accounts.sort_by_key(|account| account.client);
+    let balance = |field: fn(&Account) -> amount::Amount| {
+        decimals(accounts.iter().map(|account| Some(amount::to_decimal(field(account)))).collect())
+    };
+    table(vec![
+        ("client", Arc::new(UInt16Array::from_iter_values(accounts.iter().map(|account| account.client)))),
+        ("available", balance(|account| account.available)?),
+        ("held", balance(|account| account.held)?),
+        ("total", balance(|account| account.total)?),
+        ("locked", Arc::new(BooleanArray::from_iter(accounts.iter().map(|account| Some(account.locked))))),
+    ])
+}
+
+/// An entry of the events table
+struct Event {
+    client: u16,
+    tx: u32,
+    tx_type: String,
+    amount: Option<Decimal>,
+    decision: &'static str,
+    available: Decimal,
+    held: Decimal,
+    total: Decimal,
+    locked: bool,
+}
+
+/// Journal of every processed transaction kept in memory, with the fields of
+/// an audit journal entry, to query as the `events` table
+#[derive(Default)]
+pub struct Events {
+    events: Vec<Event>,
+}
+
+impl Events {
+    /// observer entry point, see `Engine::add_observer`
+    pub fn record(&mut self, tx: &Transaction, account: &Account, decision: Decision) {
+        self.events.push(Event {
+            client: tx.client,
+            tx: tx.tx,
+            tx_type: format!("{:?}", tx.tx_type).to_lowercase(),
+            amount: tx.amount,
+            decision: match decision {
+                Decision::Applied => "applied",
+                Decision::Ignored => "ignored",
+            },
+            available: amount::to_decimal(account.available),
+            held: amount::to_decimal(account.held),
+            total: amount::to_decimal(account.total),
+            locked: account.locked,
+        });
+    }
+
+    /// the entries in the order they were recorded, `seq` counted from 1
+    pub fn table(&self) -> Result<MemTable> {
+        let events = &self.events;
+        let balance = |field: fn(&Event) -> Decimal| decimals(events.iter().map(|event| Some(field(event))).collect());
+        table(vec![
+            ("seq", Arc::new(UInt64Array::from_iter_values(1..=events.len() as u64))),
+            ("client", Arc::new(UInt16Array::from_iter_values(events.iter().map(|event| event.client)))),
+            ("tx", Arc::new(UInt32Array::from_iter_values(events.iter().map(|event| event.tx)))),
+            ("type", Arc::new(StringArray::from_iter_values(events.iter().map(|event| event.tx_type.as_str())))),
+            ("amount", decimals(events.iter().map(|event| event.amount).collect())?),
+            ("decision", Arc::new(StringArray::from_iter_values(events.iter().map(|event| event.decision)))),
+            ("available", balance(|event| event.available)?),
+            ("held", balance(|event| event.held)?),
+            ("total", balance(|event| event.total)?),
+            ("locked", Arc::new(BooleanArray::from_iter(events.iter().map(|event| Some(event.locked))))),
+        ])
+    }
+}
+
+/// Register the `accounts` table of `engine` in `ctx`, and the `events`
+/// table when given. The tables are copies, taken when registered.
+pub fn register(ctx: &SessionContext, engine: &Engine, events: Option<&Events>) -> Result<()> {
+    ctx.register_table("accounts", Arc::new(accounts_table(engine)?))?;
+    if let Some(events) = events {
+        ctx.register_table("events", Arc::new(events.table()?))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TxType;
+    use datafusion::arrow::array::{Array, AsArray};
+    use datafusion::arrow::datatypes::{Decimal128Type, UInt16Type};
+    use rust_decimal_macros::dec;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_sql_over_accounts_and_events() {
+        let mut engine = Engine::new();
+        let events = Rc::new(RefCell::new(Events::default()));
+        let recorder = events.clone();
+        engine.add_observer(move |tx, account, decision| recorder.borrow_mut().record(tx, account, decision));
+        let tx = |tx_type, client, tx, amount| Transaction { tx_type, client, tx, amount, ..Default::default() };
+        engine.apply(tx(TxType::Deposit, 1, 1, Some(dec!(1.5))));
+        engine.apply(tx(TxType::Deposit, 2, 2, Some(dec!(2.25))));
+        engine.apply(tx(TxType::Withdrawal, 1, 3, Some(dec!(4.0))));
+        engine.apply(tx(TxType::Withdrawal, 2, 4, Some(dec!(0.25))));
+
+        let ctx = SessionContext::new();
+        register(&ctx, &engine, Some(&events.borrow())).unwrap();
+        let query = "SELECT a.client, a.total, count(*) AS ignored FROM accounts a \
+                     JOIN events e ON e.client = a.client AND e.decision = 'ignored' GROUP BY a.client, a.total";
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let batches = runtime.block_on(async { ctx.sql(query).await?.collect().await }).unwrap();
+        assert_eq!(batches.iter().map(|batch| batch.num_rows()).sum::<usize>(), 1);
+        let batch = &batches[0];
+        assert_eq!(batch.column(0).as_primitive::<UInt16Type>().value(0), 1);
+        let total = batch.column(1).as_primitive::<Decimal128Type>();
+        assert_eq!(Decimal::from_i128_with_scale(total.value(0), total.scale() as u32), dec!(1.5));
+        assert_eq!(batch.column(2).len(), 1);
+
+        let sums = runtime
+            .block_on(async { ctx.sql("SELECT sum(amount) FROM events WHERE decision = 'applied'").await?.collect().await })
+            .unwrap();
+        let sum = sums[0].column(0).as_primitive::<Decimal128Type>();
+        assert_eq!(Decimal::from_i128_with_scale(sum.value(0), sum.scale() as u32), dec!(4.0));
+    }
+}
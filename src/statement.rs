@@ -0,0 +1,214 @@
+use crate::amount::Amount;
+use crate::calendar::Calendar;
+use crate::engine::{process_tx, Account, AccountMap, Decision, Transaction, TxType};
+use crate::timestamp::{Month, Timestamp};
+use rust_decimal::Decimal;
+use std::fmt;
+
+/// How a statement is rendered
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StatementFormat {
+    #[default]
+    Csv,
+    Text,
+}
+
+#[derive(serde::Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Balances {
+    pub available: Amount,
+    pub held: Amount,
+    pub total: Amount,
+    pub locked: bool,
+}
+
+impl From<&Account> for Balances {
+    fn from(account: &Account) -> Balances {
+        Balances {
+            available: account.available,
+            held: account.held,
+            total: account.total,
+            locked: account.locked,
+        }
+    }
+}
+
+/// A transaction of the month with the balances right after it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub timestamp: Timestamp,
+    pub tx_type: TxType,
+    pub tx: u32,
+    pub amount: Option<Decimal>,
+    pub decision: Decision,
+    pub balances: Balances,
+}
+
+/// Activity of a client over a calendar month
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    pub client: u16,
+    pub month: Month,
+    pub opening: Balances,
+    pub entries: Vec<Entry>,
+    pub closing: Balances,
+}
+
+/// Statement of `client` for `month`. Transactions are applied in input order,
+/// those of business days after the month are left out; every transaction of
+/// the client must have a timestamp.
+pub fn statement(txs: impl Iterator<Item = Transaction>, client: u16, month: Month, calendar: &Calendar) -> Statement {
+    let mut accounts = AccountMap::default();
+    let mut opening = None;
+    let mut entries = Vec::new();
+    for tx in txs.filter(|tx| tx.client == client) {
+        assert!(tx.timestamp.is_some(), "statement needs timestamped input, tx {} has no timestamp", tx.tx);
+        let timestamp = tx.timestamp.unwrap();
+        let day = calendar.business_day(timestamp).and_time(Default::default());
+        if day >= month.end() {
+            continue;
+        }
+        if day < month.start() {
+            let _ = process_tx(&mut accounts, tx);
+            continue;
+        }
+        opening.get_or_insert_with(|| accounts.get(&client).map(Balances::from).unwrap_or_default());
+        let (tx_type, id, amount) = (tx.tx_type, tx.tx, tx.amount);
+        let decision = Decision::from(&process_tx(&mut accounts, tx));
+        entries.push(Entry {
+            timestamp,
+            tx_type,
+            tx: id,
+            amount,
+            decision,
+            balances: Balances::from(&accounts[&client]),
+        });
+    }
+    let closing = accounts.get(&client).map(Balances::from).unwrap_or_default();
+    Statement {
+        client,
+        month,
+        opening: opening.unwrap_or(closing),
+        entries,
+        closing,
+    }
+}
+
+fn type_name(tx_type: TxType) -> String {
+    format!("{:?}", tx_type).to_lowercase()
+}
+
+#[derive(serde::Serialize)]
+struct Row {
+    timestamp: String,
+    entry: String,
+    tx: Option<u32>,
+    amount: Option<Decimal>,
+    status: &'static str,
+    available: Amount,
+    held: Amount,
+    total: Amount,
+    locked: bool,
+}
+
+impl Row {
+    fn new(timestamp: Timestamp, entry: String, balances: Balances) -> Row {
+        Row {
+            timestamp: timestamp.to_string(),
+            entry,
+            tx: None,
+            amount: None,
+            status: "",
+            available: balances.available,
+            held: balances.held,
+            total: balances.total,
+            locked: balances.locked,
+        }
+    }
+}
+
+impl Statement {
+    fn rows(&self) -> Vec<Row> {
+        let mut rows = vec![Row::new(self.month.start(), "opening".to_string(), self.opening)];
+        rows.extend(self.entries.iter().map(|entry| Row {
+            tx: Some(entry.tx),
+            amount: entry.amount,
+            status: match entry.decision {
+                Decision::Applied => "applied",
+                Decision::Ignored => "ignored",
+            },
+            ..Row::new(entry.timestamp, type_name(entry.tx_type), entry.balances)
+        }));
+        rows.push(Row::new(self.month.end(), "closing".to_string(), self.closing));
+        rows
+    }
+
+    pub fn write_csv<W: std::io::Write>(&self, wrt: W) {
+        let mut wrt = csv::Writer::from_writer(wrt);
+        for row in self.rows() {
+            let res = wrt.serialize(row);
+            assert!(res.is_ok(), "error in writing the statement");
+        }
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Statement of client {} for {}", self.client, self.month)?;
+        for row in self.rows() {
+            let tx = row.tx.map_or(String::new(), |tx| tx.to_string());
+            let amount = row.amount.map_or(String::new(), |amount| amount.to_string());
+            writeln!(
+                f,
+                "{:<19}  {:<10} {:>10} {:>12} {:<7}  available {:>12}  held {:>12}  total {:>12}{}",
+                row.timestamp,
+                row.entry,
+                tx,
+                amount,
+                row.status,
+                row.available,
+                row.held,
+                row.total,
+                if row.locked { "  locked" } else { "" }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader;
+    use rust_decimal_macros::dec;
+
+    const INPUT: &str = "type,client,tx,amount,timestamp
+deposit,42,1,10.0,2024-02-20
+deposit,7,2,3.0,2024-03-01
+withdrawal,42,3,4.0,2024-03-02T09:00:00Z
+dispute,42,1,,2024-03-10
+withdrawal,42,4,50.0,2024-03-11
+deposit,42,5,1.0,2024-04-01
+";
+
+    #[test]
+    fn test_statement() {
+        let rdr = reader::from_reader(INPUT.as_bytes());
+        let txs = reader::transactions_with("in.csv", &reader::Dialect::default(), rdr).map(Result::unwrap);
+        let statement = statement(txs, 42, "2024-03".parse().unwrap(), &Calendar::default());
+        assert_eq!(statement.opening.available, dec!(10.0));
+        let entries: Vec<(u32, Decision)> = statement.entries.iter().map(|e| (e.tx, e.decision)).collect();
+        assert_eq!(entries, vec![(3, Decision::Applied), (1, Decision::Applied), (4, Decision::Ignored)]);
+        assert_eq!(statement.closing.available, dec!(-4.0));
+        assert_eq!(statement.closing.held, dec!(10.0));
+        assert_eq!(statement.closing.total, dec!(6.0));
+        let mut csv = Vec::new();
+        statement.write_csv(&mut csv);
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "timestamp,entry,tx,amount,status,available,held,total,locked");
+        assert!(lines[1].starts_with("2024-03-01 00:00:00,opening,,,,10"));
+        assert!(lines[2].starts_with("2024-03-02 09:00:00,withdrawal,3,4.0,applied,6"));
+        assert_eq!(lines.len(), 6);
+        assert!(statement.to_string().contains("withdrawal"));
+    }
+}
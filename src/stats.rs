@@ -0,0 +1,151 @@
+use crate::amount::{self, Amount};
+use crate::engine::{process_tx, AccountMap, Applied, Transaction};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+/// How the statistics are printed
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StatsFormat {
+    /// every figure as a JSON document
+    #[default]
+    Json,
+    /// the top clients table only
+    Csv,
+}
+
+#[derive(serde::Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ClientStats {
+    pub client: u16,
+    pub deposits: u64,
+    pub deposited: Amount,
+    pub withdrawals: u64,
+    pub withdrawn: Amount,
+    pub disputes: u64,
+    pub chargebacks: u64,
+    /// deposited + withdrawn
+    pub volume: Amount,
+    /// chargebacks per applied deposit
+    pub chargeback_rate: f64,
+}
+
+/// Number of applied deposits and withdrawals whose amount is in `[from, to)`
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Bucket {
+    pub from: Decimal,
+    pub to: Decimal,
+    pub count: u64,
+}
+
+#[derive(serde::Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub transactions: u64,
+    pub clients: usize,
+    pub deposited: Amount,
+    pub withdrawn: Amount,
+    pub chargebacks: u64,
+    /// clients by decreasing volume
+    pub top_clients: Vec<ClientStats>,
+    /// transaction sizes by power of ten
+    pub sizes: Vec<Bucket>,
+}
+
+/// Aggregate figures of the applied transactions, keeping the `top` clients by volume
+pub fn stats(txs: impl Iterator<Item = Transaction>, top: usize) -> Stats {
+    let mut accounts = AccountMap::default();
+    let mut clients: BTreeMap<u16, ClientStats> = BTreeMap::new();
+    // power of ten --> count, amounts below 1 in bucket 0
+    let mut sizes: BTreeMap<u32, u64> = BTreeMap::new();
+    let mut stats = Stats::default();
+    for tx in txs {
+        stats.transactions += 1;
+        let client = tx.client;
+        let res = process_tx(&mut accounts, tx);
+        let entry = clients.entry(client).or_insert_with(|| ClientStats { client, ..Default::default() });
+        let size = match res {
+            Ok(Applied::Deposited(amount)) => {
+                entry.deposits += 1;
+                entry.deposited += amount;
+                Some(amount)
+            }
+            Ok(Applied::Withdrawn(amount)) => {
+                entry.withdrawals += 1;
+                entry.withdrawn += amount;
+                Some(amount)
+            }
+            Ok(Applied::ChargedBack(_)) => {
+                entry.chargebacks += 1;
+                None
+            }
+            Ok(Applied::Held(_)) => {
+                entry.disputes += 1;
+                None
+            }
+            _ => None,
+        };
+        if let Some(size) = size {
+            let size = amount::to_decimal(size).abs();
+            let digits = size.trunc().to_string().trim_start_matches('0').len() as u32;
+            *sizes.entry(digits).or_default() += 1;
+        }
+    }
+    let mut top_clients: Vec<ClientStats> = clients
+        .into_values()
+        .map(|mut client| {
+            client.volume = client.deposited + client.withdrawn;
+            if client.deposits > 0 {
+                client.chargeback_rate = client.chargebacks as f64 / client.deposits as f64;
+            }
+            client
+        })
+        .collect();
+    stats.clients = top_clients.len();
+    for client in &top_clients {
+        stats.deposited += client.deposited;
+        stats.withdrawn += client.withdrawn;
+        stats.chargebacks += client.chargebacks;
+    }
+    // stable sort, ties stay by client id
+    top_clients.sort_by_key(|client| std::cmp::Reverse(client.volume));
+    top_clients.truncate(top);
+    stats.top_clients = top_clients;
+    stats.sizes = sizes
+        .into_iter()
+        .map(|(digits, count)| {
+            let from = if digits == 0 { Decimal::ZERO } else { Decimal::from(10u64.pow(digits - 1)) };
+            Bucket { from, to: Decimal::from(10u64.pow(digits)), count }
+        })
+        .collect();
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TxType;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_stats() {
+        let tx = |client, tx_type, tx, amount| Transaction{client, tx_type, tx, amount, ..Default::default()};
+        let txs = vec![
+            tx(1, TxType::Deposit, 1, Some(dec!(0.5))),
+            tx(1, TxType::Deposit, 2, Some(dec!(150.0))),
+            tx(2, TxType::Deposit, 3, Some(dec!(20.0))),
+            tx(2, TxType::Withdrawal, 4, Some(dec!(5.0))),
+            tx(2, TxType::Withdrawal, 5, Some(dec!(500.0))),
+            tx(1, TxType::Dispute, 1, None),
+            tx(1, TxType::ChargeBack, 1, None),
+        ];
+        let stats = stats(txs.into_iter(), 1);
+        assert_eq!(stats.transactions, 7);
+        assert_eq!(stats.clients, 2);
+        assert_eq!(stats.deposited, dec!(170.5));
+        assert_eq!(stats.withdrawn, dec!(5.0));
+        assert_eq!(stats.top_clients.len(), 1);
+        assert_eq!(stats.top_clients[0].client, 1);
+        assert_eq!(stats.top_clients[0].disputes, 1);
+        assert_eq!(stats.top_clients[0].chargeback_rate, 0.5);
+        let sizes: Vec<(Decimal, u64)> = stats.sizes.iter().map(|bucket| (bucket.from, bucket.count)).collect();
+        assert_eq!(sizes, vec![(dec!(0), 1), (dec!(1), 1), (dec!(10), 1), (dec!(100), 1)]);
+    }
+}
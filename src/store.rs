@@ -0,0 +1,235 @@
+use crate::query;
+use std::fs::File;
+use std::io::{Read, Write};
+
+/// whether `path` names an object, `s3://bucket/key` or `gs://bucket/key`,
+/// rather than a local file
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("s3://") || path.starts_with("gs://")
+}
+
+/// Local input file, object streamed from its store while it is read, or
+/// rows of a database query
+pub enum Input {
+    File(File),
+    #[cfg(feature = "object-store")]
+    Object(object::Reader),
+    Query(query::Rows),
+}
+
+impl Input {
+    /// size in bytes, 0 when unknown
+    pub fn size(&self) -> u64 {
+        match self {
+            Input::File(file) => file.metadata().map_or(0, |meta| meta.len()),
+            #[cfg(feature = "object-store")]
+            Input::Object(object) => object.size,
+            Input::Query(_) => 0,
+        }
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Input::File(file) => file.read(buf),
+            #[cfg(feature = "object-store")]
+            Input::Object(object) => object.read(buf),
+            Input::Query(rows) => rows.read(buf),
+        }
+    }
+}
+
+/// Local output file, or object uploaded as a whole once dropped
+pub enum Output {
+    File(File),
+    #[cfg(feature = "object-store")]
+    Object(object::Writer),
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Output::File(file) => file.write(buf),
+            #[cfg(feature = "object-store")]
+            Output::Object(object) => object.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Output::File(file) => file.flush(),
+            #[cfg(feature = "object-store")]
+            Output::Object(object) => object.flush(),
+        }
+    }
+}
+
+/// file, object or query at `path`, objects need the `object-store` feature
+/// and queries the `sqlite` or `postgres` one
+pub fn open(path: &str) -> std::io::Result<Input> {
+    if query::is_query(path) {
+        return query::open(path).map(Input::Query).map_err(std::io::Error::other);
+    }
+    if is_url(path) {
+        #[cfg(feature = "object-store")]
+        return object::Reader::open(path).map(Input::Object);
+        #[cfg(not(feature = "object-store"))]
+        return Err(unsupported(path));
+    }
+    File::open(path).map(Input::File)
+}
+
+/// file or object written at `path`, replacing what was there
+pub fn create(path: &str) -> std::io::Result<Output> {
+    if is_url(path) {
+        #[cfg(feature = "object-store")]
+        return object::Writer::create(path).map(Output::Object);
+        #[cfg(not(feature = "object-store"))]
+        return Err(unsupported(path));
+    }
+    File::create(path).map(Output::File)
+}
+
+#[cfg(not(feature = "object-store"))]
+fn unsupported(path: &str) -> std::io::Error {
+    std::io::Error::other(format!("{} is an object store url, build with the object-store feature", path))
+}
+
+#[cfg(feature = "object-store")]
+mod object {
+    use bytes::Bytes;
+    use futures_util::stream::{BoxStream, StreamExt};
+    use object_store::aws::AmazonS3Builder;
+    use object_store::gcp::GoogleCloudStorageBuilder;
+    use object_store::path::Path;
+    use object_store::{ObjectStore, ObjectStoreExt, PutPayload};
+    use std::io::{Read, Write};
+    use std::sync::Arc;
+    use tokio::runtime::Runtime;
+
+    /// store of the bucket of `url` and key of the object in it, credentials
+    /// and region come from the environment (`AWS_ACCESS_KEY_ID`,
+    /// `AWS_REGION`, `GOOGLE_SERVICE_ACCOUNT`, ...)
+    fn locate(url: &str) -> std::io::Result<(Arc<dyn ObjectStore>, Path)> {
+        let (scheme, rest) = url.split_once("://").expect("object store url");
+        let (bucket, key) = rest
+            .split_once('/')
+            .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+            .ok_or_else(|| std::io::Error::other(format!("{} names no object, expected {}://bucket/key", url, scheme)))?;
+        let store: Arc<dyn ObjectStore> = match scheme {
+            "s3" => Arc::new(AmazonS3Builder::from_env().with_bucket_name(bucket).build()?),
+            _ => Arc::new(GoogleCloudStorageBuilder::from_env().with_bucket_name(bucket).build()?),
+        };
+        Ok((store, Path::from(key)))
+    }
+
+    /// the store client is async, a reader or writer drives it on its own runtime
+    fn runtime() -> std::io::Result<Runtime> {
+        tokio::runtime::Builder::new_current_thread().enable_all().build()
+    }
+
+    /// Object read chunk by chunk as the download goes on
+    pub struct Reader {
+        runtime: Runtime,
+        stream: BoxStream<'static, object_store::Result<Bytes>>,
+        chunk: Bytes,
+        pub size: u64,
+    }
+
+    impl Reader {
+        pub fn open(url: &str) -> std::io::Result<Reader> {
+            let (store, path) = locate(url)?;
+            Reader::get(store.as_ref(), &path)
+        }
+
+        fn get(store: &dyn ObjectStore, path: &Path) -> std::io::Result<Reader> {
+            let runtime = runtime()?;
+            let res = runtime.block_on(store.get(path))?;
+            Ok(Reader {
+                size: res.meta.size,
+                stream: res.into_stream(),
+                chunk: Bytes::new(),
+                runtime,
+            })
+        }
+    }
+
+    impl Read for Reader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            while self.chunk.is_empty() {
+                match self.runtime.block_on(self.stream.next()) {
+                    Some(chunk) => self.chunk = chunk?,
+                    None => return Ok(0),
+                }
+            }
+            let len = buf.len().min(self.chunk.len());
+            buf[..len].copy_from_slice(&self.chunk.split_to(len));
+            Ok(len)
+        }
+    }
+
+    /// Writer that collects its content and puts the object once dropped,
+    /// so a failed run leaves the previous object in place
+    pub struct Writer {
+        runtime: Runtime,
+        store: Arc<dyn ObjectStore>,
+        path: Path,
+        buf: Vec<u8>,
+    }
+
+    impl Writer {
+        pub fn create(url: &str) -> std::io::Result<Writer> {
+            let (store, path) = locate(url)?;
+            Writer::put(store, path)
+        }
+
+        fn put(store: Arc<dyn ObjectStore>, path: Path) -> std::io::Result<Writer> {
+            Ok(Writer { runtime: runtime()?, store, path, buf: Vec::new() })
+        }
+    }
+
+    impl Write for Writer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.buf.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Drop for Writer {
+        fn drop(&mut self) {
+            let payload = PutPayload::from(std::mem::take(&mut self.buf));
+            let res = self.runtime.block_on(self.store.put(&self.path, payload));
+            assert!(res.is_ok(), "error in uploading {}: {:?}", self.path, res.err());
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use object_store::memory::InMemory;
+
+        #[test]
+        fn test_object_round_trip() {
+            let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+            let path = Path::from("reports/accounts.csv");
+            {
+                let mut wrt = Writer::put(store.clone(), path.clone()).unwrap();
+                wrt.write_all(b"client,available\n").unwrap();
+                wrt.write_all(b"1,2.0\n").unwrap();
+            }
+            let mut rdr = Reader::get(store.as_ref(), &path).unwrap();
+            assert_eq!(rdr.size, 23);
+            let mut content = String::new();
+            rdr.read_to_string(&mut content).unwrap();
+            assert_eq!(content, "client,available\n1,2.0\n");
+            assert!(Reader::get(store.as_ref(), &Path::from("missing.csv")).is_err());
+            assert!(locate("s3://bucket").is_err());
+            assert!(locate("gs:///key.csv").is_err());
+        }
+    }
+}
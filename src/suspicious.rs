@@ -0,0 +1,91 @@
+use crate::amount::{self, Amount};
+use crate::engine::{process_tx, AccountMap, Applied, Transaction};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+/// A client is suspicious above any of these
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Thresholds {
+    /// applied disputes
+    pub max_disputes: u64,
+    /// disputed amount over deposited amount
+    pub max_ratio: Decimal,
+}
+
+/// Client to review, with the transactions it disputed
+#[derive(serde::Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Suspect {
+    pub client: u16,
+    pub disputes: u64,
+    pub disputed: Amount,
+    pub deposited: Amount,
+    pub ratio: Decimal,
+    /// disputed tx ids, space separated in the csv output
+    #[serde(serialize_with = "space_separated")]
+    pub txs: Vec<u32>,
+}
+
+fn space_separated<S: serde::Serializer>(txs: &[u32], s: S) -> Result<S::Ok, S::Error> {
+    let txs: Vec<String> = txs.iter().map(u32::to_string).collect();
+    s.serialize_str(&txs.join(" "))
+}
+
+/// Clients exceeding the dispute thresholds, sorted by client id
+pub fn suspects(txs: impl Iterator<Item = Transaction>, thresholds: &Thresholds) -> Vec<Suspect> {
+    let mut accounts = AccountMap::default();
+    let mut clients: BTreeMap<u16, Suspect> = BTreeMap::new();
+    for tx in txs {
+        let (client, id) = (tx.client, tx.tx);
+        let res = process_tx(&mut accounts, tx);
+        let entry = clients.entry(client).or_insert_with(|| Suspect { client, ..Default::default() });
+        match res {
+            Ok(Applied::Deposited(amount)) => entry.deposited += amount,
+            Ok(Applied::Held(amount)) => {
+                entry.disputes += 1;
+                entry.disputed += amount;
+                entry.txs.push(id);
+            }
+            _ => {}
+        }
+    }
+    clients
+        .into_values()
+        .filter_map(|mut suspect| {
+            let deposited = amount::to_decimal(suspect.deposited);
+            if !deposited.is_zero() {
+                suspect.ratio = (amount::to_decimal(suspect.disputed) / deposited).round_dp(4);
+            }
+            (suspect.disputes > thresholds.max_disputes || suspect.ratio > thresholds.max_ratio).then_some(suspect)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TxType;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_suspects() {
+        let tx = |client, tx_type, tx, amount| Transaction{client, tx_type, tx, amount, ..Default::default()};
+        let mut txs = vec![
+            tx(1, TxType::Deposit, 1, Some(dec!(100.0))),
+            tx(1, TxType::Deposit, 2, Some(dec!(10.0))),
+            tx(1, TxType::Dispute, 2, None),
+            tx(2, TxType::Deposit, 3, Some(dec!(10.0))),
+            tx(2, TxType::Dispute, 3, None),
+            tx(2, TxType::Dispute, 9, None),
+        ];
+        for id in 4..8 {
+            txs.push(tx(3, TxType::Deposit, id, Some(dec!(100.0))));
+            txs.push(tx(3, TxType::Dispute, id, None));
+            txs.push(tx(3, TxType::Resolve, id, None));
+        }
+        let thresholds = Thresholds { max_disputes: 3, max_ratio: dec!(0.5) };
+        let suspects = suspects(txs.into_iter(), &thresholds);
+        let found: Vec<(u16, u64, Decimal)> = suspects.iter().map(|s| (s.client, s.disputes, s.ratio)).collect();
+        assert_eq!(found, vec![(2, 1, dec!(1)), (3, 4, dec!(1))]);
+        assert_eq!(suspects[1].txs, vec![4, 5, 6, 7]);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::amount::Amount;
+use crate::engine::{Account, Applied, Decision, Engine, Rejection, Transaction};
+use std::collections::BTreeMap;
+use std::io::Read;
+
+/// Engines of several business units served by one process. Each tenant has
+/// its own account namespace, so the same client id in two tenants refers to
+/// two unrelated accounts; observers are set per tenant through `engine`.
+#[derive(Default)]
+pub struct Tenants {
+    engines: BTreeMap<String, Engine>,
+}
+
+impl Tenants {
+    pub fn new() -> Tenants {
+        Tenants::default()
+    }
+
+    /// engine of a tenant, created empty on first use
+    pub fn engine(&mut self, tenant: &str) -> &mut Engine {
+        if !self.engines.contains_key(tenant) {
+            self.engines.insert(tenant.to_string(), Engine::new());
+        }
+        self.engines.get_mut(tenant).unwrap()
+    }
+
+    pub fn apply(&mut self, tenant: &str, tx: Transaction) -> Decision {
+        self.engine(tenant).apply(tx)
+    }
+
+    /// `Engine::process` in the engine of the `tenant` column of `tx`
+    pub fn process(&mut self, tx: Transaction) -> Result<Applied, Rejection> {
+        let tenant = tx.tenant.clone().unwrap_or_default();
+        self.engine(&tenant).process(tx)
+    }
+
+    pub fn tenants(&self) -> impl Iterator<Item = &str> {
+        self.engines.keys().map(String::as_str)
+    }
+
+    /// accounts of every tenant, tenants in name order
+    pub fn into_accounts(self) -> impl Iterator<Item = (String, Account)> {
+        self.engines
+            .into_iter()
+            .flat_map(|(tenant, engine)| engine.into_accounts().map(move |account| (tenant.clone(), account)))
+    }
+}
+
+/// a row of the account report of a run with tenants
+#[derive(serde::Deserialize)]
+struct Row {
+    tenant: String,
+    client: u16,
+    available: Amount,
+    held: Amount,
+    total: Amount,
+    locked: bool,
+}
+
+/// Accounts of a report written with `--tenants`, with their tenant, to
+/// start the next run from with `carry_forward` in the engine of each tenant
+pub fn read_report<R: Read>(mut rdr: csv::Reader<R>) -> Vec<(String, Account)> {
+    rdr.deserialize()
+        .map(|res: Result<Row, _>| {
+            assert!(res.is_ok(), "error in parsing a tenant account record: {:?}", res.err());
+            let row = res.unwrap();
+            let mut account = Account::new(row.client);
+            (account.available, account.held, account.total, account.locked) = (row.available, row.held, row.total, row.locked);
+            (row.tenant, account)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TxType;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_tenants_are_isolated() {
+        let tx = |tx_type, tx, amount| Transaction{client: 1, tx_type, tx, amount, ..Default::default()};
+        let mut tenants = Tenants::new();
+        tenants.apply("cards", tx(TxType::Deposit, 1, Some(dec!(5.0))));
+        tenants.apply("loans", tx(TxType::Deposit, 2, Some(dec!(1.0))));
+        // tx 1 belongs to the other tenant
+        assert_eq!(tenants.apply("loans", tx(TxType::Dispute, 1, None)), Decision::Ignored);
+        assert_eq!(tenants.apply("loans", tx(TxType::Withdrawal, 3, Some(dec!(2.0)))), Decision::Ignored);
+        assert_eq!(tenants.tenants().collect::<Vec<_>>(), vec!["cards", "loans"]);
+        let accounts: Vec<(String, u16, bool)> = tenants
+            .into_accounts()
+            .map(|(tenant, account)| (tenant, account.client, account.available == dec!(5.0)))
+            .collect();
+        assert_eq!(accounts, vec![("cards".to_string(), 1, true), ("loans".to_string(), 1, false)]);
+    }
+}
@@ -0,0 +1,100 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::Deserialize;
+
+/// Time of a transaction, in UTC
+pub type Timestamp = NaiveDateTime;
+
+/// Timestamp written as RFC 3339, `YYYY-MM-DD[ HH:MM:SS]` (UTC) or unix seconds
+pub fn parse(value: &str) -> Result<Timestamp, String> {
+    let value = value.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.naive_utc());
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(value, format) {
+            return Ok(time);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_time(Default::default()));
+    }
+    value
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .map(|time| time.naive_utc())
+        .ok_or_else(|| format!("invalid timestamp `{}`", value))
+}
+
+/// optional timestamp column, an empty value is no timestamp
+pub fn deserialize<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<Timestamp>, D::Error> {
+    match Option::<String>::deserialize(deserializer)? {
+        Some(value) if !value.trim().is_empty() => parse(&value).map(Some).map_err(serde::de::Error::custom),
+        _ => Ok(None),
+    }
+}
+
+/// Calendar month written as `YYYY-MM`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Month {
+    /// first day of the month
+    first: NaiveDate,
+}
+
+impl Month {
+    /// start of the month, included
+    pub fn start(&self) -> Timestamp {
+        self.first.and_time(Default::default())
+    }
+
+    /// start of the next month, excluded
+    pub fn end(&self) -> Timestamp {
+        let next = self.first.checked_add_months(chrono::Months::new(1)).expect("month in range");
+        next.and_time(Default::default())
+    }
+
+    pub fn contains(&self, time: &Timestamp) -> bool {
+        (self.start()..self.end()).contains(time)
+    }
+}
+
+impl std::str::FromStr for Month {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NaiveDate::parse_from_str(&format!("{}-01", s.trim()), "%Y-%m-%d")
+            .map(|first| Month { first })
+            .map_err(|_| format!("invalid month `{}`, expected YYYY-MM", s))
+    }
+}
+
+impl std::fmt::Display for Month {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.first.format("%Y-%m"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_formats() {
+        let expected = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap().and_hms_opt(10, 0, 0).unwrap();
+        assert_eq!(parse("2024-03-05T10:00:00Z"), Ok(expected));
+        assert_eq!(parse("2024-03-05T12:00:00+02:00"), Ok(expected));
+        assert_eq!(parse("2024-03-05 10:00:00"), Ok(expected));
+        assert_eq!(parse("1709632800"), Ok(expected));
+        assert_eq!(parse("2024-03-05").unwrap().date(), expected.date());
+        assert!(parse("5 march").is_err());
+    }
+
+    #[test]
+    fn test_month() {
+        let month: Month = "2024-12".parse().unwrap();
+        assert!(month.contains(&parse("2024-12-31T23:59:59Z").unwrap()));
+        assert!(!month.contains(&parse("2025-01-01").unwrap()));
+        assert_eq!(month.to_string(), "2024-12");
+        assert!("2024-13".parse::<Month>().is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use crate::engine::{Engine, TxType};
+use crate::reader::{self, Dialect};
+use crate::rejection::Reason;
+use rust_decimal::Decimal;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// amounts are expected with at most 4 decimal places, without a `[precision]` policy
+pub const MAX_SCALE: u32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// header line or record cannot be parsed into a transaction
+    Schema,
+    /// value of the type column is not a known transaction type
+    UnknownType,
+    /// deposit, withdrawal or escrow without an amount, or move without amount or destination
+    MissingAmount,
+    /// deposit, withdrawal or escrow of a negative amount
+    NegativeAmount,
+    /// deposit, withdrawal or escrow reusing an already seen tx id
+    DuplicateTx,
+    /// dispute, settle, release or reversal referencing an unknown tx, or resolve/chargeback of a tx not under dispute
+    OrphanDispute,
+    /// dispute, resolve, chargeback, settle, release or reversal by a client other than the owner of the tx
+    ForeignTx,
+    /// dispute of a tx already under dispute
+    AlreadyDisputed,
+    /// amount with more than `MAX_SCALE` decimal places, or rejected by the `[precision]` policy
+    Precision,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    /// 1-based line in the input file
+    pub line: u64,
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {:?}: {}", self.line, self.kind, self.message)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub records: u64,
+    pub accounts: usize,
+    pub issues: Vec<Issue>,
+}
+
+/// Parse every record of the `source` file as a process run with the same
+/// `dialect` would, and simulate processing, collecting whatever would make
+/// that run stop, reject or silently drop a row.
+pub fn validate<R: std::io::Read>(source: &str, dialect: &Dialect, input: R) -> Report {
+    let mut report = Report::default();
+    let rdr = match reader::from_reader_with(dialect, input) {
+        Ok(rdr) => rdr,
+        Err(err) => {
+            report.issues.push(Issue { line: 1, kind: IssueKind::Schema, message: err });
+            return report;
+        }
+    };
+    let mut engine = Engine::new();
+    // tx id --> owner client of every deposit/withdrawal
+    let mut seen: HashMap<u32, u16> = HashMap::new();
+    let mut disputed: HashSet<u32> = HashSet::new();
+    let mut charged_back: HashSet<u32> = HashSet::new();
+    for (line, res) in reader::located_with(source, dialect, rdr) {
+        report.records += 1;
+        let line = line.unwrap_or_default();
+        let mut issue = |kind, message: String| report.issues.push(Issue { line, kind, message });
+        let tx = match res {
+            Ok(tx) => tx,
+            Err(rejected) => {
+                let kind = match rejected.reason {
+                    Reason::UnknownType => IssueKind::UnknownType,
+                    Reason::Precision => IssueKind::Precision,
+                    Reason::MissingAmount => IssueKind::MissingAmount,
+                    _ => IssueKind::Schema,
+                };
+                issue(kind, rejected.detail);
+                continue;
+            }
+        };
+        if let Some(amount) = tx.amount.filter(|_| dialect.precision.is_none()) {
+            if amount.scale() > MAX_SCALE {
+                issue(IssueKind::Precision, format!("amount {} has more than {} decimal places", amount, MAX_SCALE));
+                // fixed-point amounts cannot represent it, the engine would abort
+                if cfg!(feature = "fixed-point") {
+                    continue;
+                }
+            }
+        }
+        match tx.tx_type {
+            TxType::Deposit | TxType::Withdrawal | TxType::Escrow => {
+                if tx.amount.is_none() {
+                    issue(IssueKind::MissingAmount, format!("{:?} {} without amount", tx.tx_type, tx.tx));
+                    continue;
+                }
+                if tx.amount.is_some_and(|amount| amount < Decimal::ZERO) {
+                    issue(IssueKind::NegativeAmount, format!("{:?} {} of a negative amount", tx.tx_type, tx.tx));
+                    continue;
+                }
+                if seen.insert(tx.tx, tx.client).is_some() {
+                    issue(IssueKind::DuplicateTx, format!("tx {} already used", tx.tx));
+                }
+            }
+            TxType::Dispute | TxType::Resolve | TxType::ChargeBack | TxType::Settle | TxType::Release | TxType::Reversal
+                if seen.get(&tx.tx).is_some_and(|owner| *owner != tx.client) =>
+            {
+                let owner = seen[&tx.tx];
+                issue(IssueKind::ForeignTx, format!("{:?} of tx {} which belongs to client {}", tx.tx_type, tx.tx, owner));
+                continue;
+            }
+            TxType::Dispute => {
+                if !seen.contains_key(&tx.tx) {
+                    issue(IssueKind::OrphanDispute, format!("dispute of unknown tx {}", tx.tx));
+                } else if charged_back.contains(&tx.tx) {
+                    issue(IssueKind::OrphanDispute, format!("dispute of tx {} which was charged back", tx.tx));
+                } else if !disputed.insert(tx.tx) {
+                    issue(IssueKind::AlreadyDisputed, format!("dispute of tx {} which is already under dispute", tx.tx));
+                }
+            }
+            TxType::Resolve | TxType::ChargeBack => {
+                if !disputed.remove(&tx.tx) {
+                    issue(IssueKind::OrphanDispute, format!("{:?} of tx {} which is not under dispute", tx.tx_type, tx.tx));
+                } else if tx.tx_type == TxType::ChargeBack {
+                    charged_back.insert(tx.tx);
+                }
+            }
+            TxType::Move => {
+                if tx.amount.is_none() || tx.to_wallet.is_none() {
+                    issue(IssueKind::MissingAmount, format!("move {} without amount or to_wallet", tx.tx));
+                    continue;
+                }
+            }
+            TxType::Convert => {
+                if tx.amount.is_none() || tx.to_currency.is_none() {
+                    issue(IssueKind::MissingAmount, format!("convert {} without amount or to_currency", tx.tx));
+                    continue;
+                }
+            }
+            TxType::Settle | TxType::Release | TxType::Reversal => {
+                if !seen.contains_key(&tx.tx) {
+                    issue(IssueKind::OrphanDispute, format!("{:?} of unknown tx {}", tx.tx_type, tx.tx));
+                }
+            }
+            TxType::Unknown | TxType::AutoResolve | TxType::AutoChargeBack | TxType::Merge | TxType::Opening => {
+                issue(IssueKind::UnknownType, format!("unsupported transaction type {:?}", tx.tx_type).to_lowercase());
+                continue;
+            }
+        }
+        engine.apply(tx);
+    }
+    report.accounts = engine.into_accounts().count();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::{AmountFormat, Columns};
+
+    fn run_with(input: &str, dialect: &Dialect) -> Vec<(u64, IssueKind)> {
+        validate("in.csv", dialect, input.as_bytes()).issues.into_iter().map(|issue| (issue.line, issue.kind)).collect()
+    }
+
+    fn run(input: &str) -> Vec<(u64, IssueKind)> {
+        run_with(input, &Dialect::default())
+    }
+
+    #[test]
+    fn test_clean_file() {
+        let input = "type,client,tx,amount\ndeposit,1,1,1.0\ndispute,1,1,\nresolve,1,1,\n";
+        assert_eq!(run(input), vec![]);
+    }
+
+    #[test]
+    fn test_bad_header() {
+        assert_eq!(run("kind,client,tx,amount\ndeposit,1,1,1.0\n"), vec![(1, IssueKind::Schema)]);
+    }
+
+    #[test]
+    fn test_reports_every_issue_with_line() {
+        let input = "type,client,tx,amount
+deposit,1,1,1.0
+deposit,2,1,2.0
+withdrawal,1,2,
+deposit,1,3,0.12345
+dispute,1,9,
+resolve,1,3,
+dispute,3,1,
+refund,1,4,1.0
+deposit,x,5,1.0
+deposit,1,6,-1.0
+deposit,1,7,1.0
+dispute,1,7,
+dispute,1,7,
+";
+        assert_eq!(
+            run(input),
+            vec![
+                (3, IssueKind::DuplicateTx),
+                (4, IssueKind::MissingAmount),
+                (5, IssueKind::Precision),
+                (6, IssueKind::OrphanDispute),
+                (7, IssueKind::OrphanDispute),
+                (8, IssueKind::ForeignTx),
+                (9, IssueKind::UnknownType),
+                (10, IssueKind::Schema),
+                (11, IssueKind::NegativeAmount),
+                (14, IssueKind::AlreadyDisputed),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dialect_of_the_run() {
+        let input = "kind;customer;id;value\ndeposit;1;1;1.234,5\nwithdrawal;1;2;0,5\n";
+        assert_eq!(run(input), vec![(1, IssueKind::Schema)]);
+        let dialect = Dialect {
+            delimiter: b';',
+            columns: Columns {
+                tx_type: Some("kind".to_string()),
+                client: Some("customer".to_string()),
+                tx: Some("id".to_string()),
+                amount: Some("value".to_string()),
+                ..Default::default()
+            },
+            amounts: AmountFormat { decimal_separator: ',', thousands_separator: Some('.') },
+            ..Default::default()
+        };
+        assert_eq!(run_with(input, &dialect), vec![]);
+        let report = validate("in.csv", &dialect, input.as_bytes());
+        assert_eq!((report.records, report.accounts), (2, 1));
+    }
+}
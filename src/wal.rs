@@ -0,0 +1,167 @@
+use crate::diff;
+use crate::engine::{Account, Engine, Transaction, TxType};
+use crate::output::{self, Labels};
+use crate::reader;
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Write-ahead log of the transactions accepted by a long-running engine,
+/// one JSON line each. A transaction is appended and synced to disk before it
+/// is applied, so after a crash `recover` applies again every transaction
+/// accepted since the last snapshot of the accounts.
+pub struct Wal {
+    path: String,
+    snapshot: Option<String>,
+    file: File,
+    // transactions applied again from the log, whose messages may be delivered again
+    recovered: HashSet<(TxType, u16, u32)>,
+    // time the snapshot was last saved
+    checkpointed: Option<SystemTime>,
+}
+
+impl Wal {
+    /// Open the log at `path`, created when missing, and start `engine` from
+    /// the accounts of the `snapshot` report, when there is one yet, and the
+    /// transactions logged since, giving their number. A last line cut short
+    /// by a crash was never applied and is dropped.
+    pub fn recover(path: &str, snapshot: Option<&str>, engine: &mut Engine) -> Result<(Wal, u64), String> {
+        let error = |err: std::io::Error| format!("write-ahead log {}: {}", path, err);
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(path).map_err(error)?;
+        let mut content = Vec::new();
+        file.read_to_end(&mut content).map_err(error)?;
+        if let Some(snapshot) = snapshot {
+            let pending = format!("{}.tmp", snapshot);
+            // a checkpoint stopped by a crash: the new snapshot holds the emptied log when it is empty
+            if Path::new(&pending).exists() {
+                let res = if content.is_empty() { fs::rename(&pending, snapshot) } else { fs::remove_file(&pending) };
+                res.map_err(|err| format!("snapshot {}: {}", pending, err))?;
+            }
+            if Path::new(snapshot).exists() {
+                for account in diff::read_report(reader::open(snapshot)) {
+                    engine.carry_forward(&account);
+                }
+            }
+        }
+        let complete = content.iter().rposition(|byte| *byte == b'\n').map_or(0, |end| end + 1);
+        let mut recovered = HashSet::new();
+        let mut replayed = 0;
+        for (idx, line) in content[..complete].split(|byte| *byte == b'\n').enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let tx: Transaction = serde_json::from_slice(line).map_err(|err| format!("{}:{}: {}", path, idx + 1, err))?;
+            recovered.insert((tx.tx_type, tx.client, tx.tx));
+            let _ = engine.process(tx);
+            replayed += 1;
+        }
+        if complete < content.len() {
+            file.set_len(complete as u64).map_err(error)?;
+        }
+        let checkpointed = snapshot.and_then(|snapshot| fs::metadata(snapshot).and_then(|meta| meta.modified()).ok());
+        let snapshot = snapshot.map(str::to_string);
+        Ok((Wal { path: path.to_string(), snapshot, file, recovered, checkpointed }, replayed))
+    }
+
+    /// Whether `tx` is one applied again by `recover`, true once for each: a
+    /// message logged but not acknowledged before a crash is delivered again
+    /// and must not be applied twice.
+    pub fn recovered(&mut self, tx: &Transaction) -> bool {
+        self.recovered.remove(&(tx.tx_type, tx.client, tx.tx))
+    }
+
+    /// log `tx`, to call before applying it
+    pub fn append(&mut self, tx: &Transaction) -> Result<(), String> {
+        let mut line = serde_json::to_vec(tx).expect("transaction is always serializable");
+        line.push(b'\n');
+        self.file.write_all(&line).and_then(|_| self.file.sync_data()).map_err(|err| self.error(err))
+    }
+
+    /// Save `accounts`, those the logged transactions led to, as the new
+    /// snapshot and start the log over; without a snapshot the log is kept
+    /// whole. The snapshot is written aside and moved in place once the log
+    /// is emptied, so after a crash `recover` starts either from the old
+    /// snapshot and the whole log or from the new one alone.
+    pub fn checkpoint(&mut self, accounts: impl Iterator<Item = Account>) -> Result<(), String> {
+        let Some(snapshot) = self.snapshot.as_deref() else {
+            return Ok(());
+        };
+        let pending = format!("{}.tmp", snapshot);
+        let error = |err: std::io::Error| format!("snapshot {}: {}", pending, err);
+        let mut file = File::create(&pending).map_err(error)?;
+        output::write_csv(accounts, &mut file, &Labels::default());
+        file.sync_all().map_err(error)?;
+        self.file.set_len(0).and_then(|_| self.file.sync_data()).map_err(|err| self.error(err))?;
+        fs::rename(&pending, snapshot).map_err(error)?;
+        self.checkpointed = Some(SystemTime::now());
+        Ok(())
+    }
+
+    /// when the snapshot was last saved, by this log or the run before
+    pub fn checkpointed(&self) -> Option<SystemTime> {
+        self.checkpointed
+    }
+
+    fn error(&self, err: std::io::Error) -> String {
+        format!("write-ahead log {}: {}", self.path, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount;
+    use crate::engine::TxType;
+    use crate::timestamp;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_recover_from_snapshot_and_log() {
+        let dir = std::env::temp_dir().join(format!("wal-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (path, snapshot) = (dir.join("wal.jsonl"), dir.join("snapshot.csv"));
+        let (path, snapshot) = (path.to_str().unwrap(), snapshot.to_str().unwrap());
+        let tx = |tx_type, tx, amount| Transaction { client: 1, tx_type, tx, amount, ..Default::default() };
+        let available = |engine: &Engine| amount::to_decimal(engine.account(1).unwrap().available);
+
+        let mut engine = Engine::new();
+        let (mut wal, replayed) = Wal::recover(path, Some(snapshot), &mut engine).unwrap();
+        assert_eq!((replayed, wal.checkpointed()), (0, None));
+        for tx in [tx(TxType::Deposit, 1, Some(dec!(5.25))), tx(TxType::Deposit, 2, Some(dec!(1.0)))] {
+            wal.append(&tx).unwrap();
+            engine.apply(tx);
+        }
+        wal.checkpoint(engine.into_accounts()).unwrap();
+        assert!(wal.checkpointed().is_some());
+        let timed = Transaction { timestamp: timestamp::parse("2024-03-01 09:00:00").ok(), ..tx(TxType::Withdrawal, 3, Some(dec!(1.0))) };
+        wal.append(&timed).unwrap();
+        drop(wal);
+        // a crash in the middle of the next append
+        let mut file = OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(br#"{"tx_type":"deposit","client":1,"#).unwrap();
+
+        let mut engine = Engine::new();
+        let (mut wal, replayed) = Wal::recover(path, Some(snapshot), &mut engine).unwrap();
+        assert_eq!((replayed, available(&engine)), (1, dec!(5.25)));
+        assert!(wal.recovered(&timed) && !wal.recovered(&timed));
+        wal.append(&tx(TxType::Withdrawal, 4, Some(dec!(0.25)))).unwrap();
+        let mut engine = Engine::new();
+        let (mut wal, replayed) = Wal::recover(path, Some(snapshot), &mut engine).unwrap();
+        assert_eq!((replayed, available(&engine)), (2, dec!(5.0)));
+
+        // a crash during a checkpoint, before the log is emptied, keeps the old snapshot
+        fs::write(format!("{}.tmp", snapshot), "client,available,held,total,locked\n1,100,0,100,false\n").unwrap();
+        let mut engine = Engine::new();
+        Wal::recover(path, Some(snapshot), &mut engine).unwrap();
+        assert_eq!(available(&engine), dec!(5.0));
+        // and after, the new one
+        wal.checkpoint(engine.into_accounts()).unwrap();
+        fs::write(format!("{}.tmp", snapshot), "client,available,held,total,locked\n1,7.5,0,7.5,false\n").unwrap();
+        let mut engine = Engine::new();
+        let (_, replayed) = Wal::recover(path, Some(snapshot), &mut engine).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!((replayed, available(&engine)), (0, dec!(7.5)));
+    }
+}
@@ -0,0 +1,94 @@
+use crate::engine::{Account, Applied, Engine, Rejection, Transaction, TxType};
+use std::collections::{BTreeMap, HashSet};
+
+/// wallet of the transactions without a wallet column
+pub const MAIN: &str = "main";
+
+/// Named sub-balances of the clients, each with its own available and held
+/// funds. A wallet is an engine of its own, so the same tx id can be
+/// disputed in the wallet it was made in only.
+#[derive(Default)]
+pub struct Wallets {
+    engines: BTreeMap<String, Engine>,
+    /// (wallet, tx) of the deposits made by moves into a wallet
+    received: HashSet<(String, u32)>,
+}
+
+impl Wallets {
+    pub fn new() -> Wallets {
+        Wallets::default()
+    }
+
+    fn engine(&mut self, wallet: &str) -> &mut Engine {
+        if !self.engines.contains_key(wallet) {
+            self.engines.insert(wallet.to_string(), Engine::new());
+        }
+        self.engines.get_mut(wallet).unwrap()
+    }
+
+    /// Apply a transaction to its wallet. A move is a withdrawal from `wallet`
+    /// followed by a deposit in `to_wallet`, both with the tx id of the move;
+    /// only the withdrawal can be disputed or reversed.
+    pub fn process(&mut self, tx: Transaction) -> Result<Applied, Rejection> {
+        let wallet = tx.wallet.clone().unwrap_or_else(|| MAIN.to_string());
+        let referencing = matches!(tx.tx_type, TxType::Dispute | TxType::Resolve | TxType::ChargeBack | TxType::Reversal);
+        if referencing && self.received.contains(&(wallet.clone(), tx.tx)) {
+            return Err(Rejection::MoveLeg);
+        }
+        if tx.tx_type != TxType::Move {
+            return self.engine(&wallet).process(tx);
+        }
+        let Some(to_wallet) = tx.to_wallet.clone() else {
+            return Err(Rejection::UnsupportedType);
+        };
+        let withdrawal = Transaction { tx_type: TxType::Withdrawal, ..tx.clone() };
+        let applied = self.engine(&wallet).process(withdrawal)?;
+        let deposit = Transaction { tx_type: TxType::Deposit, ..tx };
+        let id = deposit.tx;
+        self.engine(&to_wallet).process(deposit)?;
+        self.received.insert((to_wallet, id));
+        Ok(applied)
+    }
+
+    /// accounts of every wallet, wallets in name order
+    pub fn into_accounts(self) -> impl Iterator<Item = (String, Account)> {
+        self.engines
+            .into_iter()
+            .flat_map(|(wallet, engine)| engine.into_accounts().map(move |account| (wallet.clone(), account)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_moves_between_wallets() {
+        let tx = |tx_type, tx, amount, wallet: Option<&str>, to_wallet: Option<&str>| Transaction {
+            client: 1,
+            tx_type,
+            tx,
+            amount,
+            wallet: wallet.map(str::to_string),
+            to_wallet: to_wallet.map(str::to_string),
+            ..Default::default()
+        };
+        let mut wallets = Wallets::new();
+        assert!(wallets.process(tx(TxType::Deposit, 1, Some(dec!(5.0)), None, None)).is_ok());
+        assert!(wallets.process(tx(TxType::Move, 2, Some(dec!(2.0)), None, Some("bonus"))).is_ok());
+        let res = wallets.process(tx(TxType::Move, 3, Some(dec!(9.0)), Some("bonus"), Some("main")));
+        assert_eq!(res, Err(Rejection::InsufficientFunds));
+        assert_eq!(wallets.process(tx(TxType::Dispute, 2, None, Some("bonus"), None)), Err(Rejection::MoveLeg));
+        assert!(wallets.process(tx(TxType::Dispute, 2, None, None, None)).is_ok());
+        assert!(wallets.process(tx(TxType::ChargeBack, 2, None, None, None)).is_ok());
+        let accounts: Vec<(String, Account)> = wallets.into_accounts().collect();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].0, "bonus");
+        assert_eq!(accounts[0].1.available, dec!(2.0));
+        assert_eq!(accounts[0].1.held, dec!(0));
+        assert_eq!(accounts[1].0, "main");
+        assert_eq!(accounts[1].1.available, dec!(1.0));
+        assert!(accounts[1].1.locked);
+    }
+}
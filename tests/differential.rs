@@ -0,0 +1,156 @@
+//! The engine checked against a reference model on random workloads of
+//! deposits, withdrawals, disputes, resolves and chargebacks, some of zero or
+//! negative amounts. The model
+//! keeps nothing but the list of applied transactions and recomputes
+//! everything from it at every step: slow, but easy to check by reading.
+
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use transactions::amount;
+use transactions::engine::{Engine, Transaction, TxType};
+
+/// Balances of an account as (available, held, total, locked)
+type State = (Decimal, Decimal, Decimal, bool);
+
+#[derive(Default)]
+struct Reference {
+    applied: Vec<Transaction>,
+}
+
+impl Reference {
+    /// amount of the deposit or withdrawal `tx`, with the client it belongs to
+    fn original(&self, tx: u32) -> Option<(u16, Decimal)> {
+        self.applied
+            .iter()
+            .find(|row| row.tx == tx && matches!(row.tx_type, TxType::Deposit | TxType::Withdrawal))
+            .map(|row| (row.client, row.amount.unwrap()))
+    }
+
+    /// last dispute, resolve or chargeback applied to `tx`
+    fn last_dispute_step(&self, tx: u32) -> Option<TxType> {
+        self.applied
+            .iter()
+            .rev()
+            .find(|row| row.tx == tx && matches!(row.tx_type, TxType::Dispute | TxType::Resolve | TxType::ChargeBack))
+            .map(|row| row.tx_type)
+    }
+
+    fn state(&self, client: u16) -> State {
+        let (mut available, mut held, mut locked) = (Decimal::ZERO, Decimal::ZERO, false);
+        for row in self.applied.iter().filter(|row| row.client == client) {
+            match row.tx_type {
+                TxType::Deposit => available += row.amount.unwrap(),
+                TxType::Withdrawal => available -= row.amount.unwrap(),
+                TxType::Dispute => {
+                    available -= self.original(row.tx).unwrap().1;
+                    held += self.original(row.tx).unwrap().1;
+                }
+                TxType::Resolve => {
+                    available += self.original(row.tx).unwrap().1;
+                    held -= self.original(row.tx).unwrap().1;
+                }
+                TxType::ChargeBack => {
+                    held -= self.original(row.tx).unwrap().1;
+                    locked = true;
+                }
+                _ => unreachable!(),
+            }
+        }
+        (available, held, available + held, locked)
+    }
+
+    /// whether the transaction is applied, recording it if so
+    fn process(&mut self, tx: Transaction) -> bool {
+        let ok = match tx.tx_type {
+            // a negative amount would turn a deposit into a withdrawal and back
+            _ if tx.amount.is_some_and(|amount| amount < Decimal::ZERO) => false,
+            TxType::Deposit => true,
+            TxType::Withdrawal => self.state(tx.client).0 >= tx.amount.unwrap(),
+            _ => {
+                let own = self.original(tx.tx).is_some_and(|(client, _)| client == tx.client);
+                let step = self.last_dispute_step(tx.tx);
+                match tx.tx_type {
+                    // a tx is disputed again only once its dispute is resolved
+                    TxType::Dispute => own && matches!(step, None | Some(TxType::Resolve)),
+                    _ => own && step == Some(TxType::Dispute),
+                }
+            }
+        };
+        if ok {
+            self.applied.push(tx);
+        }
+        ok
+    }
+}
+
+/// xorshift, to keep the workloads reproducible without a dependency
+struct Random(u64);
+
+impl Random {
+    fn below(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % bound
+    }
+}
+
+/// Rows over a few clients; deposits and withdrawals get fresh tx ids, the
+/// other types reference any id seen so far, or one never used.
+fn workload(random: &mut Random, rows: usize) -> Vec<Transaction> {
+    let mut next_tx = 1;
+    (0..rows)
+        .map(|_| {
+            let tx_type = match random.below(10) {
+                0..=3 => TxType::Deposit,
+                4..=5 => TxType::Withdrawal,
+                6..=7 => TxType::Dispute,
+                8 => TxType::Resolve,
+                _ => TxType::ChargeBack,
+            };
+            let (tx, amount) = match tx_type {
+                TxType::Deposit | TxType::Withdrawal => {
+                    next_tx += 1;
+                    let mantissa = match random.below(20) {
+                        0 => 0,
+                        1 => -(random.below(100_000) as i64) - 1,
+                        _ => random.below(100_000) as i64 + 1,
+                    };
+                    (next_tx - 1, Some(Decimal::new(mantissa, random.below(5) as u32)))
+                }
+                _ => (random.below(next_tx as u64 + 1) as u32, None),
+            };
+            let client = random.below(4) as u16 + 1;
+            Transaction { tx_type, client, tx, amount, ..Default::default() }
+        })
+        .collect()
+}
+
+#[test]
+fn test_engine_matches_reference() {
+    let mut random = Random(0x9e37_79b9_7f4a_7c15);
+    for run in 0..300 {
+        let rows = 1 + random.below(150) as usize;
+        let txs = workload(&mut random, rows);
+        let mut engine = Engine::new();
+        let mut reference = Reference::default();
+        for (idx, tx) in txs.iter().enumerate() {
+            let applied = engine.process(tx.clone()).is_ok();
+            assert_eq!(applied, reference.process(tx.clone()), "run {} row {}: {:?}", run, idx, tx);
+        }
+        let accounts: BTreeMap<u16, State> = engine
+            .into_accounts()
+            .map(|account| {
+                let state = (
+                    amount::to_decimal(account.available),
+                    amount::to_decimal(account.held),
+                    amount::to_decimal(account.total),
+                    account.locked,
+                );
+                (account.client, state)
+            })
+            .collect();
+        let expected: BTreeMap<u16, State> = txs.iter().map(|tx| (tx.client, reference.state(tx.client))).collect();
+        assert_eq!(accounts, expected, "run {}: {:?}", run, txs);
+    }
+}
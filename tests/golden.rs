@@ -0,0 +1,299 @@
+//! End-to-end runs of the binary over the cases in `tests/golden`. Each case
+//! is a directory with an `input.csv`, an optional `config.toml` passed with
+//! `--config`, and either the `expected.csv` account report or, for inputs
+//! the binary must refuse, an `expected.err` text found in its stderr.
+//! Report rows are compared in client order, as the output order is not
+//! defined. Expected reports are those of the default decimal amounts, so
+//! the cases are not run with `fixed-point`. Options writing files of their
+//! own have a test each.
+#![cfg(not(feature = "fixed-point"))]
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// header followed by the sorted rows
+fn normalize(report: &str) -> Vec<String> {
+    let mut lines = report.lines().map(str::to_string);
+    let header = lines.next().unwrap_or_default();
+    let mut rows: Vec<String> = lines.filter(|line| !line.is_empty()).collect();
+    rows.sort();
+    rows.insert(0, header);
+    rows
+}
+
+fn run(case: &Path) -> Result<(), String> {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_transactions"));
+    let config = case.join("config.toml");
+    if config.exists() {
+        command.arg("--config").arg(config);
+    }
+    let output = command.arg(case.join("input.csv")).output().map_err(|err| err.to_string())?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if let Ok(expected) = fs::read_to_string(case.join("expected.err")) {
+        if output.status.success() || !stderr.contains(expected.trim()) {
+            return Err(format!("expected a failure with `{}`, got {}\n{}", expected.trim(), output.status, stderr));
+        }
+        return Ok(());
+    }
+    if !output.status.success() {
+        return Err(format!("exited with {}\n{}", output.status, stderr));
+    }
+    let expected = fs::read_to_string(case.join("expected.csv")).map_err(|err| err.to_string())?;
+    if normalize(&stdout) != normalize(&expected) {
+        return Err(format!("report differs, expected\n{}\ngot\n{}", expected, stdout));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_golden_cases() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let mut cases: Vec<_> = fs::read_dir(&root).unwrap().map(|entry| entry.unwrap().path()).collect();
+    cases.sort();
+    assert!(!cases.is_empty(), "no cases in {}", root.display());
+    let failures: Vec<String> = cases
+        .iter()
+        .filter_map(|case| run(case).err().map(|err| format!("{}: {}", case.display(), err)))
+        .collect();
+    assert!(failures.is_empty(), "{} of {} golden cases failed\n{}", failures.len(), cases.len(), failures.join("\n\n"));
+}
+
+#[test]
+fn test_scheduled_transactions_applied_when_due() {
+    let dir = std::env::temp_dir().join(format!("golden-scheduled-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("input.csv");
+    let scheduled = dir.join("scheduled.csv");
+    fs::write(
+        &input,
+        "type,client,tx,amount,timestamp,due\n\
+         deposit,1,1,5.0,2024-03-01,\n\
+         withdrawal,1,2,4.0,2024-03-01,2024-03-04\n\
+         deposit,1,3,1.5,2024-03-02,2024-03-10\n\
+         deposit,1,4,0.5,2024-03-05,\n",
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_transactions"))
+        .arg("--scheduled")
+        .arg(&scheduled)
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    // the withdrawal is applied once the last row moves the clock past its due time
+    let report = normalize(&String::from_utf8_lossy(&output.stdout));
+    assert_eq!(report, ["client,available,held,total,locked", "1,1.5,0,1.5,false"]);
+    let held = fs::read_to_string(&scheduled).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+    assert_eq!(held.lines().skip(1).collect::<Vec<_>>(), ["deposit,1,3,1.5,2024-03-02T00:00:00,2024-03-10T00:00:00"]);
+}
+
+#[test]
+fn test_forget_client() {
+    let dir = std::env::temp_dir().join(format!("golden-forget-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (input, journal, ids, snapshot) = (dir.join("input.csv"), dir.join("audit.ndjson"), dir.join("ids.csv"), dir.join("snapshot.csv"));
+    fs::write(
+        &input,
+        "type,client,tx,amount\n\
+         deposit,2f6c2b0e-aaaa,1,5.0\n\
+         deposit,91d0c4a2-bbbb,2,2.0\n\
+         withdrawal,2f6c2b0e-aaaa,3,1.5\n",
+    )
+    .unwrap();
+    let run = |args: &[&std::ffi::OsStr]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_transactions")).args(args).output().unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    run(&["--audit".as_ref(), journal.as_ref(), "--client-ids".as_ref(), ids.as_ref(), input.as_ref()]);
+    fs::write(&snapshot, "client,available,held,total,locked\n1,3.5,0,3.5,false\n2,2.0,0,2.0,false\n").unwrap();
+    run(&[
+        "forget-client".as_ref(),
+        "--client".as_ref(),
+        "1".as_ref(),
+        "--audit".as_ref(),
+        journal.as_ref(),
+        "--client-ids".as_ref(),
+        ids.as_ref(),
+        "--snapshot".as_ref(),
+        snapshot.as_ref(),
+    ]);
+    let erased = fs::read_to_string(&journal).unwrap();
+    assert!(run(&["verify-audit".as_ref(), journal.as_ref()]).contains("4 entries"));
+    // the balances of the forgotten client are kept, its transactions are not
+    let replayed = normalize(&run(&["replay".as_ref(), journal.as_ref()]));
+    let (ids, snapshot) = (fs::read_to_string(&ids).unwrap(), fs::read_to_string(&snapshot).unwrap());
+    let _ = fs::remove_dir_all(&dir);
+    assert_eq!(erased.matches("\"type\":\"erased\"").count(), 2);
+    assert!(!erased.contains("\"5.0\"") && !erased.contains("\"1.5\""));
+    assert_eq!(replayed, ["client,available,held,total,locked", "1,3.5,0,3.5,false", "2,2.0,0,2.0,false"]);
+    assert!(!ids.contains("2f6c2b0e-aaaa") && ids.contains("91d0c4a2-bbbb,2"));
+    assert_eq!(normalize(&snapshot)[1], "1,3.5,0,3.5,true");
+}
+
+#[test]
+fn test_encrypted_journal() {
+    let dir = std::env::temp_dir().join(format!("golden-encrypted-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (input, journal, key) = (dir.join("in.csv"), dir.join("audit.jsonl"), dir.join("key.hex"));
+    fs::write(&input, "type,client,tx,amount\ndeposit,1,1,5.0\ndeposit,2,2,2.0\nwithdrawal,1,3,1.5\n").unwrap();
+    fs::write(&key, "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff\n").unwrap();
+    let run = |args: &[&std::ffi::OsStr]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_transactions")).args(args).output().unwrap();
+        let (stdout, stderr) = (String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+        (output.status.success(), stdout.into_owned(), stderr.into_owned())
+    };
+    run(&["--audit".as_ref(), journal.as_ref(), "--encrypt-key".as_ref(), key.as_ref(), input.as_ref()]);
+    let plain = run(&["verify-audit".as_ref(), journal.as_ref()]);
+    let verified = run(&["verify-audit".as_ref(), "--encrypt-key".as_ref(), key.as_ref(), journal.as_ref()]);
+    let replayed = run(&["replay".as_ref(), "--encrypt-key".as_ref(), key.as_ref(), journal.as_ref()]);
+    let _ = fs::remove_dir_all(&dir);
+    assert!(!plain.0 && plain.1.is_empty() && plain.2.contains("audit.jsonl"), "{:?}", plain);
+    assert!(verified.0 && verified.1.contains("3 entries, chain intact"), "{:?}", verified);
+    assert!(replayed.0, "{:?}", replayed);
+    assert_eq!(normalize(&replayed.1), ["client,available,held,total,locked", "1,3.5,0,3.5,false", "2,2.0,0,2.0,false"]);
+}
+
+#[test]
+fn test_tenants_kept_apart() {
+    let dir = std::env::temp_dir().join(format!("golden-tenants-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (first, second, report, rejections) = (dir.join("first.csv"), dir.join("second.csv"), dir.join("report.csv"), dir.join("rejected.csv"));
+    // the same client and tx ids in both tenants are unrelated accounts and transactions
+    fs::write(
+        &first,
+        "type,client,tx,amount,tenant\n\
+         deposit,1,1,5.0,cards\n\
+         deposit,1,1,2.0,loans\n\
+         dispute,1,1,,loans\n\
+         withdrawal,1,2,1.0,cards\n\
+         deposit,2,3,1.0,\n",
+    )
+    .unwrap();
+    fs::write(&second, "type,client,tx,amount,tenant\nwithdrawal,1,4,3.0,cards\ndeposit,1,5,1.0,loans\n").unwrap();
+    let run = |args: &[&std::ffi::OsStr]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_transactions")).args(args).output().unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    let closing = run(&["--tenants".as_ref(), "--rejections".as_ref(), rejections.as_ref(), first.as_ref()]);
+    fs::write(&report, &closing).unwrap();
+    // the next run starts each tenant from its own rows of the report
+    let next = run(&["--tenants".as_ref(), "--opening-balances".as_ref(), report.as_ref(), second.as_ref()]);
+    let rejected = fs::read_to_string(&rejections).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+    assert_eq!(
+        normalize(&closing),
+        ["tenant,client,available,held,total,locked", "cards,1,4.0,0,4.0,false", "loans,1,0.0,2.0,2.0,false"]
+    );
+    assert!(rejected.contains("malformed,2,3,row has no tenant"), "{}", rejected);
+    assert_eq!(normalize(&next), ["tenant,client,available,held,total,locked", "cards,1,1.0,0,1.0,false", "loans,1,1.0,2,3.0,false"]);
+}
+
+#[test]
+fn test_conversions_between_currencies() {
+    let dir = std::env::temp_dir().join(format!("golden-currencies-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (input, config, rates) = (dir.join("input.csv"), dir.join("config.toml"), dir.join("rates.csv"));
+    let (audit, rejections) = (dir.join("audit.ndjson"), dir.join("rejected.csv"));
+    fs::write(&config, "[fx]\nbase = \"EUR\"\nspread = 0.01\n[fx.rates]\n\"EUR/USD\" = 1.1\n").unwrap();
+    // the rates file replaces the rate of the config
+    fs::write(&rates, "from,to,rate\nEUR,USD,1.2\n").unwrap();
+    fs::write(
+        &input,
+        "type,client,tx,amount,currency,to_currency\n\
+         deposit,1,1,100.0,,\n\
+         convert,1,2,10.0,EUR,USD\n\
+         deposit,1,3,5.0,GBP,\n\
+         convert,1,4,1.0,GBP,USD\n",
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_transactions"))
+        .args(["--currencies".as_ref(), "--config".as_ref(), config.as_os_str(), "--rates".as_ref(), rates.as_ref()])
+        .args(["--audit".as_ref(), audit.as_os_str(), "--rejections".as_ref(), rejections.as_ref(), input.as_ref()])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let (journal, rejected) = (fs::read_to_string(&audit).unwrap(), fs::read_to_string(&rejections).unwrap());
+    let _ = fs::remove_dir_all(&dir);
+    assert_eq!(
+        normalize(&String::from_utf8_lossy(&output.stdout)),
+        ["client,available,held,total,locked,currency", "1,11.8800,0,11.8800,false,USD", "1,5.0,0,5.0,false,GBP", "1,90.0,0,90.0,false,EUR"]
+    );
+    assert!(rejected.contains("no_rate,1,4,"), "{}", rejected);
+    let legs: Vec<&str> = journal.lines().filter(|line| line.contains(r#""type":"convert""#)).collect();
+    assert_eq!(legs.len(), 3, "{}", journal);
+    assert!(legs[1].contains(r#""currency":"USD""#) && legs[1].contains(r#""rate":"1.2""#) && legs[1].contains(r#""spread":"0.01""#), "{}", legs[1]);
+}
+
+#[test]
+fn test_chaos_input_is_rejected_row_by_row() {
+    let dir = std::env::temp_dir().join(format!("golden-chaos-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (config, input, rejections) = (dir.join("config.toml"), dir.join("input.csv"), dir.join("rejected.csv"));
+    let simulation = "[simulation]\nclients = 20\ndays = 30\ndeposit = 0.5\nwithdrawal = 0.3\ndispute = 0.05\nchargeback = 0.5\nmax_amount = 100\n";
+    fs::write(&config, simulation).unwrap();
+    let generated = Command::new(env!("CARGO_BIN_EXE_transactions")).args(["simulate", "--chaos", "0.3", "--config"]).arg(&config).output().unwrap();
+    assert!(generated.status.success(), "{}", String::from_utf8_lossy(&generated.stderr));
+    fs::write(&input, &generated.stdout).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_transactions")).arg("--rejections").arg(&rejections).arg(&input).output().unwrap();
+    let rejected = fs::read_to_string(&rejections).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(rejected.lines().count() > 10 && rejected.contains(",malformed,"), "{}", rejected);
+}
+
+#[test]
+fn test_subcommands_stop_on_bad_input() {
+    let dir = std::env::temp_dir().join(format!("golden-subcommands-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (bad, renamed, ledger) = (dir.join("bad.csv"), dir.join("renamed.csv"), dir.join("ledger.csv"));
+    fs::write(&bad, "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,x,2,1.0\ndeposit,1,3,zz\n").unwrap();
+    fs::write(&renamed, "kind,client,tx,amount\ndeposit,1,1,1.0\n").unwrap();
+    fs::write(&ledger, "client,expected_total\n1,1.0\n").unwrap();
+    let run = |args: &[&std::ffi::OsStr]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_transactions")).args(args).output().unwrap();
+        (output.status.success(), String::from_utf8_lossy(&output.stderr).into_owned())
+    };
+    let reconciled = run(&["reconcile".as_ref(), "--ledger".as_ref(), ledger.as_ref(), bad.as_ref()]);
+    let balanced = run(&["trial-balance".as_ref(), bad.as_ref()]);
+    let stats = run(&["stats".as_ref(), renamed.as_ref()]);
+    let _ = fs::remove_dir_all(&dir);
+    for (success, stderr) in [&reconciled, &balanced] {
+        assert!(!success, "{}", stderr);
+        assert!(stderr.contains("bad.csv:3: error in parsing") && stderr.contains("bad.csv:4:"), "{}", stderr);
+        assert!(stderr.contains("2 rows rejected"), "{}", stderr);
+    }
+    assert!(!stats.0 && stats.1.contains("unexpected header line: missing columns: type"), "{}", stats.1);
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn test_source_query() {
+    let path = std::env::temp_dir().join(format!("golden-source-{}.db", std::process::id()));
+    let connection = rusqlite::Connection::open(&path).unwrap();
+    connection
+        .execute_batch(
+            "CREATE TABLE txs (id INTEGER, type TEXT, client INTEGER, tx INTEGER, amount TEXT);
+             INSERT INTO txs VALUES (1, 'deposit', 1, 1, '5.0'), (3, 'withdrawal', 1, 3, '1.5'), (2, 'deposit', 2, 2, '2.0'), (4, 'refund', 2, 4, '1.0');",
+        )
+        .unwrap();
+    let rejections = path.with_extension("rejections.csv");
+    let source = format!("sqlite://{}?query=SELECT type, client, tx, amount FROM txs ORDER BY id", path.display());
+    let output = Command::new(env!("CARGO_BIN_EXE_transactions"))
+        .args(["--source", &source, "--rejections"])
+        .arg(&rejections)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let report = normalize(&String::from_utf8_lossy(&output.stdout));
+    assert_eq!(report, ["client,available,held,total,locked", "1,3.5,0,3.5,false", "2,2.0,0,2.0,false"]);
+    let rejected = fs::read_to_string(&rejections).unwrap();
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(&rejections);
+    // rows are numbered as the lines of a csv file with its header line
+    assert!(rejected.contains(&format!("sqlite://{},5,unknown_type", path.display())), "{}", rejected);
+}